@@ -11,6 +11,21 @@ CREATE TABLE users (
     username VARCHAR(255) UNIQUE NOT NULL,
     password VARCHAR(255) NOT NULL,
     name VARCHAR(255) NOT NULL,
+    email VARCHAR(255) UNIQUE, -- Optional; needed for password reset
+    -- Set once the user opened a link sent to email; accounts with an unverified email get restricted tokens
+    email_verified_at TIMESTAMP WITH TIME ZONE,
+    role VARCHAR(20) NOT NULL DEFAULT 'member' CHECK (role IN ('member', 'admin')),
+    -- Tokens issued before this instant are rejected (set on password change and logout-all)
+    tokens_valid_after TIMESTAMP WITH TIME ZONE,
+    -- Bumped when the role, email verification or team memberships change; tokens carrying an older value must be refreshed
+    claims_version INTEGER NOT NULL DEFAULT 1,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE, -- Cleared when the user is offboarded or deleted
+    availability_status VARCHAR(20) NOT NULL DEFAULT 'available'
+        CHECK (availability_status IN ('available', 'out_of_office')),
+    away_from TIMESTAMP WITH TIME ZONE, -- Absence range; NULL bounds are open-ended
+    away_until TIMESTAMP WITH TIME ZONE,
+    delegate_id INTEGER REFERENCES users(id) ON DELETE SET NULL, -- Suggested assignee while away
+    locale VARCHAR(10) NOT NULL DEFAULT 'en', -- Language of emails sent to the user
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
     updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
 );
@@ -35,9 +50,11 @@ CREATE TABLE tasks (
     description TEXT,
     status VARCHAR(20) NOT NULL CHECK (status IN ('TO_DO', 'DOING', 'DONE')),
     external_link TEXT, -- For Google Docs/Forms URLs
+    client_id UUID UNIQUE, -- Optional client-generated id for offline-created tasks
     created_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
-    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    status_changed_at TIMESTAMP WITH TIME ZONE DEFAULT NOW() -- When the task entered its current status
 );
 
 -- 4. Task-Teams junction table (many-to-many relationship)
@@ -60,9 +77,358 @@ CREATE TABLE task_attachments (
     cloudinary_url TEXT NOT NULL, -- Full Cloudinary URL
     cloudinary_secure_url TEXT NOT NULL, -- HTTPS Cloudinary URL
     uploaded_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    processing_status VARCHAR(20) NOT NULL DEFAULT 'uploaded'
+        CHECK (processing_status IN ('uploaded', 'scanning', 'ready', 'infected', 'failed', 'flagged', 'rejected')),
+    preview_path TEXT, -- First-page PNG rendered after the scan, for documents
+    content_tsv TSVECTOR, -- Extracted document text, for search inside attachments
+    sha256 CHAR(64), -- Hex SHA-256 of the uploaded file, checked against the client's on upload
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 6. Event outbox (events written in the same transaction as the mutation,
+-- deleted once every subscriber accepted them)
+CREATE TABLE event_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    aggregate_type VARCHAR(50) NOT NULL,
+    aggregate_id INTEGER NOT NULL,
+    event_type VARCHAR(100) NOT NULL,
+    payload JSONB NOT NULL,
+    delivered_to TEXT[] NOT NULL DEFAULT '{}', -- Subscribers that already accepted the event
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 7. Task events (append-only history; tasks/task_teams are projections of it)
+CREATE TABLE task_events (
+    id BIGSERIAL PRIMARY KEY,
+    task_id INTEGER NOT NULL, -- No FK: events outlive deleted tasks
+    version INTEGER NOT NULL,
+    event_type VARCHAR(50) NOT NULL,
+    actor_id INTEGER NOT NULL REFERENCES users(id),
+    data JSONB NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    UNIQUE(task_id, version)
+);
+
+-- 8. Long-running operations (exports, imports, archives) polled by clients
+CREATE TABLE operations (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    kind VARCHAR(50) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'running', 'succeeded', 'failed')),
+    progress INTEGER NOT NULL DEFAULT 0 CHECK (progress BETWEEN 0 AND 100),
+    result JSONB,
+    error TEXT,
+    created_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    completed_at TIMESTAMP WITH TIME ZONE
+);
+
+-- 9. Attachment download audit log (kept when confidential mode is on)
+CREATE TABLE attachment_downloads (
+    id BIGSERIAL PRIMARY KEY,
+    attachment_id INTEGER NOT NULL, -- No FK: the log outlives deleted attachments
+    task_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users(id),
+    ip_address VARCHAR(45),
+    user_agent TEXT,
+    downloaded_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 10. Dead letters: background jobs parked after exhausting their retries
+CREATE TABLE dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(50) NOT NULL, -- Which job type, decides how a requeue is replayed
+    payload JSONB NOT NULL, -- Everything needed to run the job again
+    attempts INTEGER NOT NULL,
+    last_error TEXT,
+    first_attempted_at TIMESTAMP WITH TIME ZONE,
+    dead_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 11. Password reset tokens; only an HMAC of the emailed token is stored
+CREATE TABLE password_reset_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 12. Revoked tokens: JWT ids invalidated by logout until they expire anyway
+CREATE TABLE revoked_tokens (
+    jti VARCHAR(64) PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 13. API keys: long-lived credentials for bots and scripts, sent as X-Api-Key
+CREATE TABLE api_keys (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    prefix VARCHAR(16) NOT NULL,
+    key_hash VARCHAR(64) NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL DEFAULT '{read}',
+    expires_at TIMESTAMP WITH TIME ZONE,
+    last_used_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
 );
 
+-- 14. User identities: accounts at OAuth providers linked to local users
+CREATE TABLE user_identities (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR(20) NOT NULL,
+    provider_user_id VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    UNIQUE (provider, provider_user_id)
+);
+
+-- 15. API usage: requests per user and API key (NULL for login tokens) per UTC day
+CREATE TABLE api_usage (
+    day DATE NOT NULL,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    api_key_id INTEGER REFERENCES api_keys(id) ON DELETE CASCADE,
+    requests BIGINT NOT NULL DEFAULT 0,
+    client_errors BIGINT NOT NULL DEFAULT 0,
+    server_errors BIGINT NOT NULL DEFAULT 0
+);
+CREATE UNIQUE INDEX idx_api_usage_bucket ON api_usage(day, user_id, COALESCE(api_key_id, 0));
+
+-- 16. Team members: which teams each user belongs to
+CREATE TABLE team_members (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    team_id INTEGER NOT NULL REFERENCES teams(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (user_id, team_id)
+);
+
+-- 17. Invitations: emailed signup links; only an HMAC of the token is stored
+CREATE TABLE invitations (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    team_ids INTEGER[] NOT NULL, -- Teams joined on acceptance
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    invited_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    revoked_at TIMESTAMP WITH TIME ZONE, -- Set when a newer invitation replaces it
+    locale VARCHAR(10) NOT NULL DEFAULT 'en', -- Language of the email, passed on to the account
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 18. Scripts: admin-managed Rhai scripts run on task events
+CREATE TABLE scripts (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) UNIQUE NOT NULL,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('validate', 'automate')),
+    event VARCHAR(50) NOT NULL, -- task.created, task.updated or task.status_changed
+    source TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE, -- Automation updates are made as this user
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 19. Email templates: admin overrides of the built-in email templates
+CREATE TABLE email_templates (
+    name VARCHAR(50) NOT NULL, -- invitation, password_reset, magic_link
+    locale VARCHAR(10) NOT NULL DEFAULT 'en',
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (name, locale)
+);
+
+-- 20. Login links: emailed single-use passwordless logins; only an HMAC of the token is stored
+CREATE TABLE login_links (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 21. Task links: tasks mentioned by key (e.g. KAN-12) in another task's description
+CREATE TABLE task_links (
+    source_task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    target_task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (source_task_id, target_task_id)
+);
+
+-- 22. Board settings: one row of defaults for tasks created without teams or a description
+CREATE TABLE board_settings (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    default_team_ids INTEGER[] NOT NULL DEFAULT '{}',
+    default_description TEXT,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 23. Task drafts: unfinished tasks visible only to their author, unvalidated until published
+CREATE TABLE task_drafts (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(255),
+    description TEXT,
+    status VARCHAR(20),
+    external_link TEXT,
+    teams TEXT[],
+    created_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 24. Task votes: one upvote per user and task, for prioritizing by stakeholder interest
+CREATE TABLE task_votes (
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (task_id, user_id)
+);
+
+-- 25. Feedback submissions: contact details of whoever filed a triage task through the public form
+CREATE TABLE feedback_submissions (
+    task_id INTEGER PRIMARY KEY REFERENCES tasks(id) ON DELETE CASCADE,
+    contact_name VARCHAR(100),
+    contact_email VARCHAR(255),
+    ip_address VARCHAR(45),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 26. Email verifications: links proving the user receives mail at an address; only an HMAC of the token is stored
+CREATE TABLE email_verifications (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL, -- Address the link was sent to; it verifies nothing once the user's email changes
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 27. Column policies: who a task is assigned to when it moves into a status column
+CREATE TABLE column_policies (
+    status VARCHAR(20) PRIMARY KEY CHECK (status IN ('TO_DO', 'DOING', 'DONE')),
+    assign_to INTEGER REFERENCES users(id) ON DELETE CASCADE, -- A fixed user, or
+    team_id INTEGER REFERENCES teams(id) ON DELETE CASCADE, -- round-robin among the team's active members
+    last_assigned_user_id INTEGER REFERENCES users(id) ON DELETE SET NULL, -- Where the round-robin continues from
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CHECK ((assign_to IS NULL) <> (team_id IS NULL))
+);
+
+-- 28. SLA rules: how long tasks (optionally only those of a team) may stay in a status column
+CREATE TABLE sla_rules (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    status VARCHAR(20) NOT NULL CHECK (status IN ('TO_DO', 'DOING', 'DONE')),
+    team_id INTEGER REFERENCES teams(id) ON DELETE CASCADE, -- NULL applies the rule to every task
+    max_hours INTEGER NOT NULL CHECK (max_hours > 0),
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 29. SLA breaches: tasks that overstayed a rule's column, resolved once they leave it
+CREATE TABLE sla_breaches (
+    id SERIAL PRIMARY KEY,
+    rule_id INTEGER NOT NULL REFERENCES sla_rules(id) ON DELETE CASCADE,
+    task_id INTEGER NOT NULL REFERENCES tasks(id) ON DELETE CASCADE,
+    entered_at TIMESTAMP WITH TIME ZONE NOT NULL, -- When the task entered the column; one breach per stay
+    breached_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    resolved_at TIMESTAMP WITH TIME ZONE,
+    UNIQUE(rule_id, task_id, entered_at)
+);
+
+-- 30. Escalation chain: who is notified as an SLA breach stays open, by hours since the breach
+CREATE TABLE escalation_steps (
+    after_hours INTEGER PRIMARY KEY CHECK (after_hours > 0),
+    notify VARCHAR(20) NOT NULL CHECK (notify IN ('owner', 'user', 'admins')),
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE, -- The user to notify when notify = 'user', e.g. a team lead
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CHECK ((notify = 'user') = (user_id IS NOT NULL))
+);
+
+-- 31. SLA escalations: the chain's steps scheduled for a breach; cancelled when the breach resolves first
+CREATE TABLE sla_escalations (
+    id SERIAL PRIMARY KEY,
+    breach_id INTEGER NOT NULL REFERENCES sla_breaches(id) ON DELETE CASCADE,
+    after_hours INTEGER NOT NULL,
+    notify VARCHAR(20) NOT NULL, -- Copied from the step so later chain changes keep the history intact
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    due_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    escalated_at TIMESTAMP WITH TIME ZONE,
+    cancelled_at TIMESTAMP WITH TIME ZONE,
+    UNIQUE(breach_id, after_hours)
+);
+
+-- 32. Business calendar: one row of working hours that SLA timers count; every hour counts without it
+CREATE TABLE business_calendar (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    utc_offset_minutes INTEGER NOT NULL DEFAULT 0 CHECK (utc_offset_minutes BETWEEN -720 AND 840),
+    work_days INTEGER[] NOT NULL, -- ISO weekdays, 1 = Monday
+    day_start TIME NOT NULL,
+    day_end TIME NOT NULL CHECK (day_end > day_start),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- 33. Holidays: local dates that are not working days, whatever their weekday
+CREATE TABLE holidays (
+    day DATE PRIMARY KEY,
+    name VARCHAR(100) NOT NULL
+);
+
+-- 34. Moderation reviews: uploads the moderation service held back for an admin
+CREATE TABLE moderation_reviews (
+    id SERIAL PRIMARY KEY,
+    attachment_id INTEGER NOT NULL REFERENCES task_attachments(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL, -- Why it was held, as given by the moderation service
+    flagged_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    decision VARCHAR(10) CHECK (decision IN ('approved', 'rejected')), -- NULL while pending
+    decided_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    decided_at TIMESTAMP WITH TIME ZONE
+);
+
+-- 35. Audit logs: every mutating request that reached a route, with the diff its handler recorded
+CREATE TABLE audit_logs (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL, -- NULL before signing in
+    method VARCHAR(10) NOT NULL,
+    route VARCHAR(255) NOT NULL, -- Route template, e.g. /api/tasks/{id}
+    path TEXT NOT NULL,
+    entity_type VARCHAR(50),
+    entity_id VARCHAR(100),
+    status SMALLINT NOT NULL,
+    changes JSONB, -- {field: {before, after}}
+    request_id VARCHAR(64) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- 36. Weekly digests: one row per scheduled send, claimed before the emails go out so each week is sent once
+CREATE TABLE weekly_digests (
+    scheduled_at TIMESTAMP WITH TIME ZONE PRIMARY KEY,
+    recipients INTEGER NOT NULL DEFAULT 0,
+    sent_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+-- 37. Board cards: each task's teams, attachments, links, vote count and SLA state, kept current by triggers so the board loads in one query
+CREATE TABLE board_cards (
+    task_id INTEGER PRIMARY KEY REFERENCES tasks(id) ON DELETE CASCADE,
+    teams TEXT[] NOT NULL DEFAULT '{}',
+    attachments JSONB NOT NULL DEFAULT '[]', -- [{name, url}] of attachments not held by moderation
+    links_to JSONB NOT NULL DEFAULT '[]', -- [{id, name, status}] of tasks this one mentions
+    linked_from JSONB NOT NULL DEFAULT '[]', -- [{id, name, status}] of tasks mentioning this one
+    votes BIGINT NOT NULL DEFAULT 0,
+    sla_breached BOOLEAN NOT NULL DEFAULT FALSE
+);
+
 -- Create indexes for better query performance
 CREATE INDEX idx_users_username ON users(username);
 CREATE INDEX idx_tasks_created_by ON tasks(created_by);
@@ -72,12 +438,34 @@ CREATE INDEX idx_task_teams_task_id ON task_teams(task_id);
 CREATE INDEX idx_task_teams_team_id ON task_teams(team_id);
 CREATE INDEX idx_task_attachments_task_id ON task_attachments(task_id);
 CREATE INDEX idx_task_attachments_cloudinary_public_id ON task_attachments(cloudinary_public_id);
+CREATE INDEX idx_task_attachments_content_tsv ON task_attachments USING GIN (content_tsv);
+CREATE INDEX idx_task_events_created_at ON task_events(created_at);
+CREATE INDEX idx_operations_created_by ON operations(created_by);
+CREATE INDEX idx_attachment_downloads_attachment_id ON attachment_downloads(attachment_id);
+CREATE INDEX idx_dead_letters_kind ON dead_letters(kind);
+CREATE INDEX idx_task_links_target_task_id ON task_links(target_task_id);
+CREATE INDEX idx_task_drafts_created_by ON task_drafts(created_by);
+CREATE INDEX idx_revoked_tokens_expires_at ON revoked_tokens(expires_at);
+CREATE INDEX idx_api_keys_user_id ON api_keys(user_id);
+CREATE INDEX idx_user_identities_user_id ON user_identities(user_id);
+CREATE INDEX idx_team_members_team_id ON team_members(team_id);
+CREATE INDEX idx_invitations_email ON invitations(LOWER(email));
+CREATE INDEX idx_sla_breaches_open ON sla_breaches(task_id) WHERE resolved_at IS NULL;
+CREATE INDEX idx_sla_escalations_pending ON sla_escalations(due_at) WHERE escalated_at IS NULL AND cancelled_at IS NULL;
+CREATE INDEX idx_moderation_reviews_pending ON moderation_reviews(flagged_at) WHERE decision IS NULL;
+CREATE INDEX idx_audit_logs_created_at ON audit_logs(created_at);
+CREATE INDEX idx_audit_logs_user_id ON audit_logs(user_id, created_at);
+CREATE INDEX idx_audit_logs_entity ON audit_logs(entity_type, entity_id, created_at);
 
 -- Function to automatically update the updated_at column
+-- Statements that set updated_at themselves, like a projection rebuilt from
+-- the event log, keep their value
 CREATE OR REPLACE FUNCTION update_updated_at_column()
 RETURNS TRIGGER AS $$
 BEGIN
-    NEW.updated_at = NOW();
+    IF NEW.updated_at IS NOT DISTINCT FROM OLD.updated_at THEN
+        NEW.updated_at = NOW();
+    END IF;
     RETURN NEW;
 END;
 $$ language 'plpgsql';
@@ -88,10 +476,220 @@ CREATE TRIGGER update_tasks_updated_at
     FOR EACH ROW 
     EXECUTE FUNCTION update_updated_at_column();
 
+-- Record when a task moves to another status, for SLA rules
+CREATE OR REPLACE FUNCTION touch_task_status_changed_at()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.status IS DISTINCT FROM OLD.status THEN
+        NEW.status_changed_at = NOW();
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER tasks_status_changed_at
+    BEFORE UPDATE OF status ON tasks
+    FOR EACH ROW
+    EXECUTE FUNCTION touch_task_status_changed_at();
+
+-- Tokens carry the user's role, whether their email is verified and their
+-- teams; bump the user's claims version when any of these changes so older
+-- tokens are refused until refreshed
+CREATE OR REPLACE FUNCTION bump_user_claims_version()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.role IS DISTINCT FROM OLD.role
+        OR NEW.email IS DISTINCT FROM OLD.email
+        OR NEW.email_verified_at IS DISTINCT FROM OLD.email_verified_at THEN
+        NEW.claims_version = OLD.claims_version + 1;
+    END IF;
+    RETURN NEW;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER users_claims_version
+    BEFORE UPDATE OF role, email, email_verified_at ON users
+    FOR EACH ROW
+    EXECUTE FUNCTION bump_user_claims_version();
+
+CREATE OR REPLACE FUNCTION bump_team_claims_version()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE users SET claims_version = claims_version + 1
+    WHERE id IN (OLD.user_id, NEW.user_id);
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER team_members_claims_version
+    AFTER INSERT OR UPDATE OR DELETE ON team_members
+    FOR EACH ROW
+    EXECUTE FUNCTION bump_team_claims_version();
+
+-- Recompute a task's board card. The card row is locked first so that a
+-- concurrent change to the same task, which waits here, recomputes it again
+-- once the first commits instead of writing back what it saw before.
+CREATE OR REPLACE FUNCTION refresh_board_card(card_task_id INTEGER)
+RETURNS VOID AS $$
+BEGIN
+    INSERT INTO board_cards (task_id) SELECT id FROM tasks WHERE id = card_task_id
+    ON CONFLICT (task_id) DO NOTHING;
+    PERFORM 1 FROM board_cards WHERE task_id = card_task_id FOR UPDATE;
+
+    UPDATE board_cards SET
+        teams = ARRAY(SELECT t.name FROM teams t JOIN task_teams tt ON t.id = tt.team_id
+                      WHERE tt.task_id = card_task_id ORDER BY tt.id),
+        attachments = COALESCE((SELECT jsonb_agg(jsonb_build_object('name', a.file_name, 'url', a.cloudinary_secure_url) ORDER BY a.id)
+                                FROM task_attachments a
+                                WHERE a.task_id = card_task_id AND a.processing_status NOT IN ('flagged', 'rejected')), '[]'),
+        links_to = COALESCE((SELECT jsonb_agg(jsonb_build_object('id', t.id, 'name', t.name, 'status', t.status) ORDER BY t.id)
+                             FROM task_links l JOIN tasks t ON t.id = l.target_task_id
+                             WHERE l.source_task_id = card_task_id), '[]'),
+        linked_from = COALESCE((SELECT jsonb_agg(jsonb_build_object('id', t.id, 'name', t.name, 'status', t.status) ORDER BY t.id)
+                                FROM task_links l JOIN tasks t ON t.id = l.source_task_id
+                                WHERE l.target_task_id = card_task_id), '[]'),
+        votes = (SELECT COUNT(*) FROM task_votes v WHERE v.task_id = card_task_id),
+        sla_breached = EXISTS(SELECT 1 FROM sla_breaches b WHERE b.task_id = card_task_id AND b.resolved_at IS NULL)
+    WHERE task_id = card_task_id;
+END;
+$$ language 'plpgsql';
+
+-- Refresh the cards of the tasks named by the trigger's arguments, which
+-- are columns of the changed row holding task ids
+CREATE OR REPLACE FUNCTION refresh_board_cards_of_row()
+RETURNS TRIGGER AS $$
+DECLARE
+    task_column TEXT;
+BEGIN
+    FOREACH task_column IN ARRAY TG_ARGV LOOP
+        IF TG_OP <> 'INSERT' THEN
+            PERFORM refresh_board_card((to_jsonb(OLD) ->> task_column)::INTEGER);
+        END IF;
+        IF TG_OP <> 'DELETE' THEN
+            PERFORM refresh_board_card((to_jsonb(NEW) ->> task_column)::INTEGER);
+        END IF;
+    END LOOP;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER tasks_board_card
+    AFTER INSERT ON tasks
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_board_cards_of_row('id');
+
+CREATE TRIGGER task_teams_board_card
+    AFTER INSERT OR UPDATE OR DELETE ON task_teams
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_board_cards_of_row('task_id');
+
+CREATE TRIGGER task_attachments_board_card
+    AFTER INSERT OR UPDATE OF task_id, file_name, cloudinary_secure_url, processing_status OR DELETE ON task_attachments
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_board_cards_of_row('task_id');
+
+CREATE TRIGGER task_links_board_card
+    AFTER INSERT OR UPDATE OR DELETE ON task_links
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_board_cards_of_row('source_task_id', 'target_task_id');
+
+CREATE TRIGGER task_votes_board_card
+    AFTER INSERT OR UPDATE OR DELETE ON task_votes
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_board_cards_of_row('task_id');
+
+CREATE TRIGGER sla_breaches_board_card
+    AFTER INSERT OR UPDATE OR DELETE ON sla_breaches
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_board_cards_of_row('task_id');
+
+-- Cards show the name and status of linked tasks and the names of teams
+CREATE OR REPLACE FUNCTION refresh_linked_board_cards()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.name IS DISTINCT FROM OLD.name OR NEW.status IS DISTINCT FROM OLD.status THEN
+        PERFORM refresh_board_card(linked.task_id)
+        FROM (SELECT source_task_id AS task_id FROM task_links WHERE target_task_id = NEW.id
+              UNION SELECT target_task_id FROM task_links WHERE source_task_id = NEW.id) linked;
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER tasks_linked_board_cards
+    AFTER UPDATE OF name, status ON tasks
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_linked_board_cards();
+
+CREATE OR REPLACE FUNCTION refresh_team_board_cards()
+RETURNS TRIGGER AS $$
+BEGIN
+    IF NEW.name IS DISTINCT FROM OLD.name THEN
+        PERFORM refresh_board_card(task_id) FROM task_teams WHERE team_id = NEW.id;
+    END IF;
+    RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER teams_board_cards
+    AFTER UPDATE OF name ON teams
+    FOR EACH ROW
+    EXECUTE FUNCTION refresh_team_board_cards();
+
+-- Build the cards of tasks created before board_cards existed
+SELECT refresh_board_card(id) FROM tasks;
+
+-- Keep the task event log append-only
+CREATE OR REPLACE FUNCTION reject_task_event_changes()
+RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'task_events is append-only';
+END;
+$$ language 'plpgsql';
+
+CREATE TRIGGER task_events_append_only
+    BEFORE UPDATE OR DELETE ON task_events
+    FOR EACH ROW
+    EXECUTE FUNCTION reject_task_event_changes();
+
+-- Row-level security: with ROW_LEVEL_SECURITY=true the service sets app.user_id
+-- to the signed-in user on each connection a request uses. Rows the service
+-- only ever shows to their owner (or, for API keys, to admins in the usage
+-- report) are then hidden from other users by Postgres too. Work done on
+-- nobody's behalf (background jobs, API key lookup, or every connection when
+-- row-level security is off) sets app.system instead. A connection with
+-- neither sees and writes none of these rows.
+CREATE OR REPLACE FUNCTION app_user_id()
+RETURNS INTEGER AS $$
+    SELECT NULLIF(current_setting('app.user_id', true), '')::INTEGER;
+$$ language 'sql' STABLE;
+
+CREATE OR REPLACE FUNCTION app_system()
+RETURNS BOOLEAN AS $$
+    SELECT COALESCE(current_setting('app.system', true), '') = 'on';
+$$ language 'sql' STABLE;
+
+CREATE OR REPLACE FUNCTION app_user_is_admin()
+RETURNS BOOLEAN AS $$
+    SELECT EXISTS (SELECT 1 FROM users WHERE id = app_user_id() AND role = 'admin');
+$$ language 'sql' STABLE;
+
+ALTER TABLE task_drafts ENABLE ROW LEVEL SECURITY;
+ALTER TABLE task_drafts FORCE ROW LEVEL SECURITY;
+CREATE POLICY task_drafts_author ON task_drafts
+    USING (app_system() OR created_by = app_user_id())
+    WITH CHECK (app_system() OR created_by = app_user_id());
+
+ALTER TABLE api_keys ENABLE ROW LEVEL SECURITY;
+ALTER TABLE api_keys FORCE ROW LEVEL SECURITY;
+CREATE POLICY api_keys_owner ON api_keys
+    USING (app_system() OR user_id = app_user_id() OR app_user_is_admin())
+    WITH CHECK (app_system() OR user_id = app_user_id());
+
 -- Insert a default admin user for testing (password: 'admin123')
 -- Note: This is a bcrypt hash of 'admin123' - change this in production!
-INSERT INTO users (username, password, name) VALUES 
-    ('admin', '$2b$12$LQv3c1yqBWVHxkd0LHAkCOYz6TtxMQJqhN8/LewdBPj8LhQnE.K6W', 'Administrator');
+INSERT INTO users (username, password, name, role) VALUES 
+    ('admin', '$2b$12$LQv3c1yqBWVHxkd0LHAkCOYz6TtxMQJqhN8/LewdBPj8LhQnE.K6W', 'Administrator', 'admin');
 
 -- Insert some sample tasks for testing (optional - remove in production)
 -- Uncomment the following lines if you want sample data:
@@ -1,12 +1,215 @@
 use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::cookie::SameSite;
+use chrono::{NaiveTime, Weekday};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+
+use crate::utils::password::{CharacterClass, PasswordPolicy};
 
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub database_url: String,
     pub port: u16,
+    /// Where /api/admin/* and /metrics are served instead of `port`; None
+    /// serves everything on `port`
+    pub admin_bind_address: Option<SocketAddr>,
     pub jwt_secret: String,
+    /// Key of the HMAC stored in place of API keys and emailed tokens, and
+    /// of OAuth state signatures; kept apart from `jwt_secret` so rotating
+    /// that does not invalidate every API key
+    pub token_hash_key: String,
+    pub jwt_algorithm: String,
+    pub jwt_private_key_file: Option<String>,
+    pub jwt_public_key_file: Option<String>,
+    pub jwt_key_id: Option<String>,
+    pub jwt_previous_public_keys: Vec<(String, String)>,
+    pub token_ttl_hours: i64,
+    /// Lifetime of tokens from logins with `remember_me` set
+    pub remember_me_ttl_hours: i64,
     pub environment: String,
     pub frontend_urls: Vec<String>,
+    pub outbox_poll_interval_secs: u64,
+    pub outbox_max_attempts: i32,
+    pub usage_flush_interval_secs: u64,
+    pub sla_check_interval_secs: u64,
+    /// When the weekly digest is emailed to admins, in UTC; None sends none
+    pub weekly_digest_at: Option<(Weekday, NaiveTime)>,
+    pub realtime_queue_capacity: usize,
+    pub cdn_base_url: Option<String>,
+    pub cdn_signing_key: Option<String>,
+    pub cdn_url_ttl_secs: u64,
+    pub load_shed_max_in_flight: usize,
+    pub max_json_body_bytes: usize,
+    pub max_upload_bytes: usize,
+    pub load_shed_max_pool_wait_ms: u64,
+    pub load_shed_retry_after_secs: u64,
+    /// Pool waits over this are logged and alerted as exhaustion
+    pub pool_wait_alert_ms: u64,
+    /// Receives a POST when pool exhaustion starts and ends
+    pub pool_alert_webhook_url: Option<String>,
+    pub rate_limit_requests: u32,
+    pub rate_limit_ip_requests: u32,
+    pub rate_limit_window_secs: u64,
+    /// Proxies whose X-Forwarded-For entries are believed when working out
+    /// the client IP
+    pub trusted_proxies: Vec<IpAddr>,
+    pub login_max_attempts_per_ip: u32,
+    pub login_max_attempts_per_username: u32,
+    pub login_attempt_window_secs: u64,
+    pub worker_threads: Option<usize>,
+    pub request_timeout_secs: u64,
+    pub long_request_timeout_secs: u64,
+    /// Requests taking at least this long are logged as slow
+    pub slow_request_ms: u64,
+    pub virus_scan_command: Option<String>,
+    pub confidential_board: bool,
+    /// Encrypt task descriptions and attachment files at rest
+    pub sensitive_board: bool,
+    /// Org key wrapping the per-item data keys; required on a sensitive board
+    pub encryption_key: Option<[u8; 32]>,
+    pub watermark_command: Option<String>,
+    pub preview_command: Option<String>,
+    pub pdf_text_command: Option<String>,
+    /// Service reviewing uploaded images; None unless MODERATION_URL is set
+    pub moderation: Option<ModerationService>,
+    pub readiness_file: Option<String>,
+    pub sql_context_tagging: bool,
+    pub row_level_security: bool,
+    pub mail_command: Option<String>,
+    pub mail_from: String,
+    pub email_template_dir: Option<String>,
+    pub password_reset_url: String,
+    pub password_reset_ttl_minutes: i64,
+    pub invite_url: String,
+    pub invite_ttl_hours: i64,
+    pub email_verify_url: String,
+    pub email_verification_ttl_hours: i64,
+    /// Frontend page for emailed login links; None unless MAGIC_LINK_ENABLED
+    pub magic_link_url: Option<String>,
+    pub magic_link_ttl_minutes: i64,
+    pub password_policy: PasswordPolicy,
+    pub google_oauth: Option<OAuthCredentials>,
+    pub github_oauth: Option<OAuthCredentials>,
+    pub oauth_redirect_base_url: String,
+    pub auth_transport: AuthTransport,
+    pub auth_cookie_name: String,
+    pub auth_cookie_same_site: SameSite,
+    pub api_docs: ApiDocsAccess,
+    pub log_format: LogFormat,
+    /// Whether every response carries HSTS, CSP and the other security headers
+    pub security_headers: bool,
+    pub content_security_policy: String,
+    /// Whether text responses are gzip/brotli/zstd encoded when the client
+    /// accepts it
+    pub compression: bool,
+    pub compression_min_bytes: usize,
+    /// Where to send anonymous usage stats; None unless TELEMETRY_ENABLED
+    pub telemetry_endpoint: Option<String>,
+    pub telemetry_interval_secs: u64,
+    /// Names of the compiled-in plugins to enable, in order
+    pub plugins: Vec<String>,
+    /// Captcha checks on public endpoints; None unless CAPTCHA_SECRET is set
+    pub captcha: Option<CaptchaSettings>,
+    /// Public feedback form; None unless FEEDBACK_ENABLED
+    pub feedback: Option<FeedbackIntake>,
+}
+
+/// Service that checks captcha response tokens. All of them take the same
+/// form post and answer `{"success": bool}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptchaProvider {
+    HCaptcha,
+    ReCaptcha,
+    Turnstile,
+}
+
+impl CaptchaProvider {
+    pub fn verify_url(self) -> &'static str {
+        match self {
+            CaptchaProvider::HCaptcha => "https://api.hcaptcha.com/siteverify",
+            CaptchaProvider::ReCaptcha => "https://www.google.com/recaptcha/api/siteverify",
+            CaptchaProvider::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaptchaSettings {
+    pub provider: CaptchaProvider,
+    pub secret: String,
+    /// Failed logins for a username before its next login needs a captcha;
+    /// 0 requires one on every login
+    pub after_failed_logins: u32,
+}
+
+/// HTTP service that reviews uploaded images before they are shown. It is
+/// POSTed the file as JSON and answers whether to hold it for an admin.
+#[derive(Debug, Clone)]
+pub struct ModerationService {
+    pub url: String,
+    /// Sent as a bearer token when set
+    pub token: Option<String>,
+}
+
+/// Settings of the public feedback form, which files triage tasks without a
+/// login
+#[derive(Debug, Clone)]
+pub struct FeedbackIntake {
+    /// Secret in the form's URL that identifies the board
+    pub token: String,
+    /// Username the triage tasks are created as
+    pub owner: String,
+    /// Column (task status) triage tasks start in
+    pub status: String,
+    pub max_per_ip: u32,
+    pub window_secs: u64,
+}
+
+/// Who may open Swagger UI and the raw OpenAPI document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiDocsAccess {
+    Public,
+    /// Only browsers holding a docs session cookie, issued to administrators
+    Admin,
+    /// Not served at all; both routes answer 404
+    Disabled,
+}
+
+/// How log lines are written to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// env_logger's plain text, for people reading a terminal
+    Text,
+    /// One JSON object per line, for log shippers such as Loki or Logstash
+    Json,
+}
+
+/// How login tokens reach the client and come back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthTransport {
+    /// Token in the response body, sent back as `Authorization: Bearer`
+    Header,
+    /// Token in a Secure, HttpOnly cookie set on login; bearer headers are
+    /// still accepted for non-browser clients
+    Cookie,
+}
+
+/// Client registration with an OAuth2 provider
+#[derive(Debug, Clone)]
+pub struct OAuthCredentials {
+    pub client_id: String,
+    pub client_secret: String,
+}
+
+impl OAuthCredentials {
+    // A provider is enabled only when both halves of its credentials are set
+    fn from_env(id_var: &str, secret_var: &str) -> Option<Self> {
+        let client_id = env::var(id_var).ok().filter(|s| !s.trim().is_empty())?;
+        let client_secret = env::var(secret_var).ok().filter(|s| !s.trim().is_empty())?;
+        Some(OAuthCredentials { client_id, client_secret })
+    }
 }
 
 #[derive(Debug)]
@@ -35,13 +238,73 @@ impl AppConfig {
         
         let jwt_secret = env::var("JWT_SECRET")
             .map_err(|_| ConfigError::MissingVariable("JWT_SECRET".to_string()))?;
+
+        // Defaults to JWT_SECRET, which is what hashes were keyed with before
+        // it existed; pin it to that value before rotating JWT_SECRET
+        let token_hash_key = env::var("TOKEN_HASH_KEY")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| jwt_secret.clone());
         
+        // Token signing: HS256 with JWT_SECRET, or RS256/ES256 with a PEM key
+        // pair. Keys rotated out stay valid for verification when listed in
+        // JWT_PREVIOUS_PUBLIC_KEYS as comma separated `kid=path` pairs.
+        let jwt_algorithm = env::var("JWT_ALGORITHM")
+            .unwrap_or_else(|_| "HS256".to_string())
+            .trim()
+            .to_uppercase();
+        if !["HS256", "RS256", "ES256"].contains(&jwt_algorithm.as_str()) {
+            return Err(ConfigError::InvalidFormat("JWT_ALGORITHM must be HS256, RS256 or ES256".to_string()));
+        }
+        let jwt_private_key_file = env::var("JWT_PRIVATE_KEY_FILE").ok().filter(|s| !s.trim().is_empty());
+        let jwt_public_key_file = env::var("JWT_PUBLIC_KEY_FILE").ok().filter(|s| !s.trim().is_empty());
+        let jwt_key_id = env::var("JWT_KEY_ID").ok().filter(|s| !s.trim().is_empty());
+        let jwt_previous_public_keys = env::var("JWT_PREVIOUS_PUBLIC_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| match entry.split_once('=') {
+                Some((key_id, path)) if !key_id.trim().is_empty() && !path.trim().is_empty() => {
+                    Ok((key_id.trim().to_string(), path.trim().to_string()))
+                }
+                _ => Err(ConfigError::InvalidFormat("JWT_PREVIOUS_PUBLIC_KEYS entries must look like kid=/path/to/key.pem".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Lifetime of issued access tokens and their cookie; "remember me"
+        // logins get the longer one
+        let token_ttl_hours = env::var("TOKEN_TTL_HOURS")
+            .unwrap_or_else(|_| "24".to_string())
+            .parse::<i64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("TOKEN_TTL_HOURS must be a positive number of hours".to_string()))?;
+
+        let remember_me_ttl_hours = env::var("REMEMBER_ME_TTL_HOURS")
+            .unwrap_or_else(|_| "720".to_string())
+            .parse::<i64>()
+            .ok()
+            .filter(|n| *n >= token_ttl_hours)
+            .ok_or_else(|| ConfigError::InvalidFormat("REMEMBER_ME_TTL_HOURS must be a number of hours no shorter than TOKEN_TTL_HOURS".to_string()))?;
+
         let environment = env::var("ENVIRONMENT").unwrap_or_else(|_| "development".to_string());
         
         let port = env::var("SERVER_PORT")
             .unwrap_or_else(|_| "8080".to_string())
             .parse::<u16>()
             .map_err(|_| ConfigError::InvalidFormat("SERVER_PORT must be a valid port number".to_string()))?;
+
+        // A separate listener for the administrative surface, so it can be
+        // kept off the public load balancer; its port must differ from
+        // SERVER_PORT since requests are told apart by the port they hit
+        let admin_bind_address = env::var("ADMIN_BIND_ADDRESS").ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.parse::<SocketAddr>()
+                .ok()
+                .filter(|addr| addr.port() != 0 && addr.port() != port)
+                .ok_or_else(|| ConfigError::InvalidFormat("ADMIN_BIND_ADDRESS must be an address:port whose port differs from SERVER_PORT".to_string())))
+            .transpose()?;
         
         // Parse allowed origins
         let frontend_urls = env::var("FRONTEND_URLS")
@@ -50,16 +313,601 @@ impl AppConfig {
             .map(|s| s.trim().to_string())
             .collect();
         
+        let outbox_poll_interval_secs = env::var("OUTBOX_POLL_INTERVAL_SECS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("OUTBOX_POLL_INTERVAL_SECS must be a positive number of seconds".to_string()))?;
+
+        let outbox_max_attempts = env::var("OUTBOX_MAX_ATTEMPTS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse::<i32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("OUTBOX_MAX_ATTEMPTS must be a positive number".to_string()))?;
+
+        // Request counts are kept in memory and written out on this interval
+        let usage_flush_interval_secs = env::var("USAGE_FLUSH_INTERVAL_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("USAGE_FLUSH_INTERVAL_SECS must be a positive number of seconds".to_string()))?;
+
+        // How often tasks are checked against the SLA rules
+        let sla_check_interval_secs = env::var("SLA_CHECK_INTERVAL_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("SLA_CHECK_INTERVAL_SECS must be a positive number of seconds".to_string()))?;
+
+        // Weekly digest schedule as a weekday and UTC time, e.g. "Mon 08:00"
+        let weekly_digest_at = match env::var("WEEKLY_DIGEST_AT").ok().filter(|s| !s.trim().is_empty()) {
+            Some(schedule) => {
+                let invalid = || ConfigError::InvalidFormat("WEEKLY_DIGEST_AT must be a weekday and time, e.g. Mon 08:00".to_string());
+                let (day, time) = schedule.trim().split_once(' ').ok_or_else(invalid)?;
+                let day = day.parse::<Weekday>().map_err(|_| invalid())?;
+                let time = NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| invalid())?;
+                Some((day, time))
+            }
+            None => None,
+        };
+
+        let realtime_queue_capacity = env::var("REALTIME_QUEUE_CAPACITY")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("REALTIME_QUEUE_CAPACITY must be a positive number".to_string()))?;
+
+        // Optional CDN in front of attachment downloads
+        let cdn_base_url = env::var("CDN_BASE_URL").ok().filter(|s| !s.trim().is_empty());
+        let cdn_signing_key = env::var("CDN_SIGNING_KEY").ok().filter(|s| !s.trim().is_empty());
+
+        let cdn_url_ttl_secs = env::var("CDN_URL_TTL_SECS")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse::<u64>()
+            .map_err(|_| ConfigError::InvalidFormat("CDN_URL_TTL_SECS must be a number of seconds".to_string()))?;
+
+        // Largest JSON request body and largest attachment accepted; bigger
+        // ones are answered with 413
+        let max_json_body_bytes = env::var("MAX_JSON_BODY_BYTES")
+            .unwrap_or_else(|_| "2097152".to_string())
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("MAX_JSON_BODY_BYTES must be a positive number of bytes".to_string()))?;
+
+        let max_upload_bytes = env::var("MAX_UPLOAD_BYTES")
+            .unwrap_or_else(|_| "10485760".to_string())
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("MAX_UPLOAD_BYTES must be a positive number of bytes".to_string()))?;
+
+        // Saturation thresholds past which low-priority requests are shed
+        let load_shed_max_in_flight = env::var("LOAD_SHED_MAX_IN_FLIGHT")
+            .unwrap_or_else(|_| "256".to_string())
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("LOAD_SHED_MAX_IN_FLIGHT must be a positive number".to_string()))?;
+
+        let load_shed_max_pool_wait_ms = env::var("LOAD_SHED_MAX_POOL_WAIT_MS")
+            .unwrap_or_else(|_| "250".to_string())
+            .parse::<u64>()
+            .map_err(|_| ConfigError::InvalidFormat("LOAD_SHED_MAX_POOL_WAIT_MS must be a number of milliseconds".to_string()))?;
+
+        let load_shed_retry_after_secs = env::var("LOAD_SHED_RETRY_AFTER_SECS")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u64>()
+            .map_err(|_| ConfigError::InvalidFormat("LOAD_SHED_RETRY_AFTER_SECS must be a number of seconds".to_string()))?;
+
+        // Waits for a pool connection past which the pool counts as exhausted
+        let pool_wait_alert_ms = env::var("POOL_WAIT_ALERT_MS")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|ms| *ms > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("POOL_WAIT_ALERT_MS must be a positive number of milliseconds".to_string()))?;
+        let pool_alert_webhook_url = env::var("POOL_ALERT_WEBHOOK_URL").ok().filter(|s| !s.trim().is_empty());
+
+        // Requests allowed per window to each signed-in user, and to each
+        // client IP for requests without valid credentials
+        let rate_limit_requests = env::var("RATE_LIMIT_REQUESTS")
+            .unwrap_or_else(|_| "600".to_string())
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("RATE_LIMIT_REQUESTS must be a positive number".to_string()))?;
+
+        let rate_limit_ip_requests = env::var("RATE_LIMIT_IP_REQUESTS")
+            .unwrap_or_else(|_| "120".to_string())
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("RATE_LIMIT_IP_REQUESTS must be a positive number".to_string()))?;
+
+        let rate_limit_window_secs = env::var("RATE_LIMIT_WINDOW_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("RATE_LIMIT_WINDOW_SECS must be a positive number of seconds".to_string()))?;
+
+        // Reverse proxies in front of the service, by address; only the
+        // X-Forwarded-For entries they append count towards the client IP.
+        // The default trusts a proxy on the same host, like nginx.conf.
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .unwrap_or_else(|_| "127.0.0.1,::1".to_string())
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| entry.parse::<IpAddr>()
+                .map_err(|_| ConfigError::InvalidFormat("TRUSTED_PROXIES must be comma separated IP addresses".to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Login attempts allowed per client IP and per username; spent
+        // attempts come back gradually over the window
+        let login_max_attempts_per_ip = env::var("LOGIN_MAX_ATTEMPTS_PER_IP")
+            .unwrap_or_else(|_| "20".to_string())
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("LOGIN_MAX_ATTEMPTS_PER_IP must be a positive number".to_string()))?;
+
+        let login_max_attempts_per_username = env::var("LOGIN_MAX_ATTEMPTS_PER_USERNAME")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<u32>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("LOGIN_MAX_ATTEMPTS_PER_USERNAME must be a positive number".to_string()))?;
+
+        let login_attempt_window_secs = env::var("LOGIN_ATTEMPT_WINDOW_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("LOGIN_ATTEMPT_WINDOW_SECS must be a positive number of seconds".to_string()))?;
+
+        // Actix defaults to one worker per CPU core when unset
+        let worker_threads = match env::var("WORKER_THREADS") {
+            Ok(value) if !value.trim().is_empty() => Some(
+                value.trim().parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| ConfigError::InvalidFormat("WORKER_THREADS must be a positive number".to_string()))?
+            ),
+            _ => None,
+        };
+
+        let request_timeout_secs = env::var("REQUEST_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("REQUEST_TIMEOUT_SECS must be a positive number of seconds".to_string()))?;
+
+        let long_request_timeout_secs = env::var("LONG_REQUEST_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("LONG_REQUEST_TIMEOUT_SECS must be a positive number of seconds".to_string()))?;
+
+        // Requests at or above this many milliseconds get a warning log line
+        let slow_request_ms = env::var("SLOW_REQUEST_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|ms| *ms > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("SLOW_REQUEST_MS must be a positive number of milliseconds".to_string()))?;
+
+        // Command run against each upload; exit 0 = clean, 1 = infected
+        let virus_scan_command = env::var("VIRUS_SCAN_COMMAND").ok().filter(|s| !s.trim().is_empty());
+
+        // Confidential boards keep an audit log of every attachment download
+        let confidential_board = env::var("CONFIDENTIAL_BOARD")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("CONFIDENTIAL_BOARD must be true or false".to_string()))?;
+
+        // Sensitive boards encrypt task descriptions and attachment files with
+        // per-item data keys wrapped by this org key. The key is 32 bytes in
+        // base64, given inline or as a file mounted by a secret manager/KMS.
+        let sensitive_board = env::var("SENSITIVE_BOARD")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("SENSITIVE_BOARD must be true or false".to_string()))?;
+
+        let encryption_key = match env::var("ENCRYPTION_KEY_FILE").ok().filter(|s| !s.trim().is_empty()) {
+            Some(path) => Some(std::fs::read_to_string(&path)
+                .map_err(|e| ConfigError::InvalidFormat(format!("Failed to read ENCRYPTION_KEY_FILE {}: {}", path, e)))?),
+            None => env::var("ENCRYPTION_KEY").ok(),
+        }
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+            .map(|key| BASE64.decode(key).ok()
+                .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                .ok_or_else(|| ConfigError::InvalidFormat("ENCRYPTION_KEY must be 32 bytes in base64".to_string())))
+            .transpose()?;
+        if sensitive_board && encryption_key.is_none() {
+            return Err(ConfigError::MissingVariable("ENCRYPTION_KEY".to_string()));
+        }
+
+        // Command that stamps confidential PDF/image downloads; it is called
+        // with the input path, output path and watermark text
+        let watermark_command = env::var("WATERMARK_COMMAND").ok().filter(|s| !s.trim().is_empty());
+
+        // Command that renders the first page of a document as PNG; it is
+        // called with the input path and output path
+        let preview_command = env::var("PREVIEW_COMMAND").ok().filter(|s| !s.trim().is_empty());
+
+        // Command that prints the text of a PDF for attachment search; it is
+        // called with the input path and `-`, like pdftotext
+        let pdf_text_command = env::var("PDF_TEXT_COMMAND").ok().filter(|s| !s.trim().is_empty());
+
+        // Moderation callout for uploaded images; off unless a URL is set
+        let moderation = env::var("MODERATION_URL").ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .map(|url| ModerationService {
+                url,
+                token: env::var("MODERATION_TOKEN").ok().map(|t| t.trim().to_string()).filter(|t| !t.is_empty()),
+            });
+
+        // Where the boot report is written once the server is listening, for
+        // orchestration readiness hooks; an empty value disables the file
+        let readiness_file = Some(env::var("READINESS_FILE").unwrap_or_else(|_| "/tmp/kanban-be.ready".to_string()))
+            .filter(|s| !s.trim().is_empty());
+
+        // Tag each request's transactions with the request and user id via
+        // application_name, so they show up in pg_stat_activity and DB logs
+        let sql_context_tagging = env::var("SQL_CONTEXT_TAGGING")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("SQL_CONTEXT_TAGGING must be true or false".to_string()))?;
+
+        // Hand each request's user to Postgres as app.user_id so the
+        // row-level security policies in kanban_db.sql apply
+        let row_level_security = env::var("ROW_LEVEL_SECURITY")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("ROW_LEVEL_SECURITY must be true or false".to_string()))?;
+
+        // Mail is piped to a sendmail-compatible command; without one,
+        // messages are only logged
+        let mail_command = env::var("MAIL_COMMAND").ok().filter(|s| !s.trim().is_empty());
+        let mail_from = env::var("MAIL_FROM").unwrap_or_else(|_| "Kanban <no-reply@kanban.local>".to_string());
+
+        // Directory with `<name>.<locale>.subject.txt` / `.body.txt` files
+        // replacing the built-in email templates
+        let email_template_dir = env::var("EMAIL_TEMPLATE_DIR").ok().filter(|s| !s.trim().is_empty());
+
+        // Frontend page that takes the reset token from its `token` query parameter
+        let password_reset_url = env::var("PASSWORD_RESET_URL")
+            .unwrap_or_else(|_| "http://localhost:3000/reset-password".to_string());
+
+        let password_reset_ttl_minutes = env::var("PASSWORD_RESET_TTL_MINUTES")
+            .unwrap_or_else(|_| "30".to_string())
+            .parse::<i64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("PASSWORD_RESET_TTL_MINUTES must be a positive number of minutes".to_string()))?;
+
+        // Frontend page that takes the invite token from its `token` query parameter
+        let invite_url = env::var("INVITE_URL")
+            .unwrap_or_else(|_| "http://localhost:3000/accept-invite".to_string());
+
+        let invite_ttl_hours = env::var("INVITE_TTL_HOURS")
+            .unwrap_or_else(|_| "72".to_string())
+            .parse::<i64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("INVITE_TTL_HOURS must be a positive number of hours".to_string()))?;
+
+        // Frontend page that takes the email verification token from its `token` query parameter
+        let email_verify_url = env::var("EMAIL_VERIFY_URL")
+            .unwrap_or_else(|_| "http://localhost:3000/verify-email".to_string());
+
+        let email_verification_ttl_hours = env::var("EMAIL_VERIFICATION_TTL_HOURS")
+            .unwrap_or_else(|_| "48".to_string())
+            .parse::<i64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("EMAIL_VERIFICATION_TTL_HOURS must be a positive number of hours".to_string()))?;
+
+        // Passwordless login by emailed link, e.g. for demo deployments; the
+        // page takes the token from its `token` query parameter
+        let magic_link_enabled = env::var("MAGIC_LINK_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("MAGIC_LINK_ENABLED must be true or false".to_string()))?;
+        let magic_link_url = magic_link_enabled.then(|| env::var("MAGIC_LINK_URL")
+            .unwrap_or_else(|_| "http://localhost:3000/magic-link".to_string()));
+
+        let magic_link_ttl_minutes = env::var("MAGIC_LINK_TTL_MINUTES")
+            .unwrap_or_else(|_| "15".to_string())
+            .parse::<i64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("MAGIC_LINK_TTL_MINUTES must be a positive number of minutes".to_string()))?;
+
+        let defaults = PasswordPolicy::default();
+        let min_length = match env::var("PASSWORD_MIN_LENGTH") {
+            Ok(value) if !value.trim().is_empty() => value.trim().parse::<usize>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| ConfigError::InvalidFormat("PASSWORD_MIN_LENGTH must be a positive number".to_string()))?,
+            _ => defaults.min_length,
+        };
+        // Comma separated: letter, upper, lower, digit, symbol; empty for none
+        let required_classes = match env::var("PASSWORD_REQUIRED_CLASSES") {
+            Ok(value) => value.split(',')
+                .filter(|name| !name.trim().is_empty())
+                .map(|name| CharacterClass::parse(name).ok_or_else(|| {
+                    ConfigError::InvalidFormat(format!("Unknown password character class '{}'", name.trim()))
+                }))
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(_) => defaults.required_classes,
+        };
+        let deny_common = env::var("PASSWORD_DENY_COMMON")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("PASSWORD_DENY_COMMON must be true or false".to_string()))?;
+        let password_policy = PasswordPolicy { min_length, required_classes, deny_common };
+
+        // Social login; the callback URL registered with each provider is
+        // {OAUTH_REDIRECT_BASE_URL}/{provider}/callback
+        let google_oauth = OAuthCredentials::from_env("GOOGLE_CLIENT_ID", "GOOGLE_CLIENT_SECRET");
+        let github_oauth = OAuthCredentials::from_env("GITHUB_CLIENT_ID", "GITHUB_CLIENT_SECRET");
+        let oauth_redirect_base_url = env::var("OAUTH_REDIRECT_BASE_URL")
+            .unwrap_or_else(|_| format!("http://localhost:{}/api/auth/oauth", port))
+            .trim_end_matches('/')
+            .to_string();
+
+        // Cookie mode keeps the token out of reach of page scripts. Use
+        // SameSite=None only when the frontend is served from another site.
+        let auth_transport = match env::var("AUTH_TRANSPORT").unwrap_or_else(|_| "header".to_string()).trim().to_lowercase().as_str() {
+            "header" => AuthTransport::Header,
+            "cookie" => AuthTransport::Cookie,
+            _ => return Err(ConfigError::InvalidFormat("AUTH_TRANSPORT must be header or cookie".to_string())),
+        };
+        let auth_cookie_name = env::var("AUTH_COOKIE_NAME")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| "kanban_token".to_string());
+        let auth_cookie_same_site = match env::var("AUTH_COOKIE_SAME_SITE").unwrap_or_else(|_| "lax".to_string()).trim().to_lowercase().as_str() {
+            "strict" => SameSite::Strict,
+            "lax" => SameSite::Lax,
+            "none" => SameSite::None,
+            _ => return Err(ConfigError::InvalidFormat("AUTH_COOKIE_SAME_SITE must be strict, lax or none".to_string())),
+        };
+
+        // API docs are open outside production and off in production unless
+        // API_DOCS says otherwise
+        let api_docs = match env::var("API_DOCS").ok().map(|s| s.trim().to_lowercase()).as_deref() {
+            Some("public") => ApiDocsAccess::Public,
+            Some("admin") => ApiDocsAccess::Admin,
+            Some("disabled") => ApiDocsAccess::Disabled,
+            None | Some("") if environment == "production" => ApiDocsAccess::Disabled,
+            None | Some("") => ApiDocsAccess::Public,
+            Some(_) => return Err(ConfigError::InvalidFormat("API_DOCS must be public, admin or disabled".to_string())),
+        };
+
+        // Security headers are on in production unless SECURITY_HEADERS says
+        // otherwise. The default policy still lets Swagger UI load its own
+        // scripts, styles and inline images.
+        let security_headers = match env::var("SECURITY_HEADERS").ok().map(|s| s.trim().to_lowercase()).as_deref() {
+            None | Some("") => environment == "production",
+            Some(value) => value.parse::<bool>()
+                .map_err(|_| ConfigError::InvalidFormat("SECURITY_HEADERS must be true or false".to_string()))?,
+        };
+        let content_security_policy = env::var("CONTENT_SECURITY_POLICY").ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "default-src 'self'; img-src 'self' data:; style-src 'self' 'unsafe-inline'; frame-ancestors 'none'".to_string());
+        if content_security_policy.chars().any(|c| c.is_control()) {
+            return Err(ConfigError::InvalidFormat("CONTENT_SECURITY_POLICY must be a single line".to_string()));
+        }
+
+        // Text responses are compressed once they reach the minimum size;
+        // smaller ones cost more to encode than they save
+        let compression = env::var("COMPRESSION")
+            .unwrap_or_else(|_| "true".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("COMPRESSION must be true or false".to_string()))?;
+        let compression_min_bytes = env::var("COMPRESSION_MIN_BYTES")
+            .unwrap_or_else(|_| "1024".to_string())
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("COMPRESSION_MIN_BYTES must be a positive number of bytes".to_string()))?;
+
+        // Plain text logs unless a log shipper asks for JSON
+        let log_format = match env::var("LOG_FORMAT").unwrap_or_default().trim().to_lowercase().as_str() {
+            "" | "text" => LogFormat::Text,
+            "json" => LogFormat::Json,
+            _ => return Err(ConfigError::InvalidFormat("LOG_FORMAT must be text or json".to_string())),
+        };
+
+        // Anonymous aggregate stats for the maintainers; off unless opted in
+        let telemetry_enabled = env::var("TELEMETRY_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("TELEMETRY_ENABLED must be true or false".to_string()))?;
+        let telemetry_endpoint = match env::var("TELEMETRY_ENDPOINT").ok().filter(|s| !s.trim().is_empty()) {
+            Some(endpoint) if telemetry_enabled => Some(endpoint),
+            None if telemetry_enabled => return Err(ConfigError::MissingVariable("TELEMETRY_ENDPOINT".to_string())),
+            _ => None,
+        };
+        let telemetry_interval_secs = env::var("TELEMETRY_INTERVAL_SECS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse::<u64>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| ConfigError::InvalidFormat("TELEMETRY_INTERVAL_SECS must be a positive number of seconds".to_string()))?;
+
+        // Captcha checks on account registration and on logins after repeated
+        // failures; off unless a secret is set
+        let captcha = match env::var("CAPTCHA_SECRET").ok().filter(|s| !s.trim().is_empty()) {
+            Some(secret) => {
+                let provider = match env::var("CAPTCHA_PROVIDER").unwrap_or_else(|_| "hcaptcha".to_string()).trim().to_lowercase().as_str() {
+                    "hcaptcha" => CaptchaProvider::HCaptcha,
+                    "recaptcha" => CaptchaProvider::ReCaptcha,
+                    "turnstile" => CaptchaProvider::Turnstile,
+                    _ => return Err(ConfigError::InvalidFormat("CAPTCHA_PROVIDER must be hcaptcha, recaptcha or turnstile".to_string())),
+                };
+                let after_failed_logins = env::var("CAPTCHA_AFTER_FAILED_LOGINS")
+                    .unwrap_or_else(|_| "3".to_string())
+                    .parse::<u32>()
+                    .map_err(|_| ConfigError::InvalidFormat("CAPTCHA_AFTER_FAILED_LOGINS must be a number".to_string()))?;
+                Some(CaptchaSettings { provider, secret: secret.trim().to_string(), after_failed_logins })
+            }
+            None => None,
+        };
+
+        // Public feedback form for people without an account; off unless
+        // enabled, and then it needs a token, an owner and captcha checks
+        let feedback_enabled = env::var("FEEDBACK_ENABLED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse::<bool>()
+            .map_err(|_| ConfigError::InvalidFormat("FEEDBACK_ENABLED must be true or false".to_string()))?;
+        let feedback = if feedback_enabled {
+            let required = |name: &str| env::var(name).ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| ConfigError::MissingVariable(name.to_string()));
+            if captcha.is_none() {
+                return Err(ConfigError::MissingVariable("CAPTCHA_SECRET".to_string()));
+            }
+            let token = required("FEEDBACK_TOKEN")?;
+            if token.len() < 16 {
+                return Err(ConfigError::InvalidFormat("FEEDBACK_TOKEN must be at least 16 characters".to_string()));
+            }
+            let status = env::var("FEEDBACK_STATUS").unwrap_or_else(|_| "TO_DO".to_string());
+            if !["TO_DO", "DOING", "DONE"].contains(&status.as_str()) {
+                return Err(ConfigError::InvalidFormat("FEEDBACK_STATUS must be TO_DO, DOING or DONE".to_string()));
+            }
+            let max_per_ip = env::var("FEEDBACK_MAX_PER_IP")
+                .unwrap_or_else(|_| "5".to_string())
+                .parse::<u32>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| ConfigError::InvalidFormat("FEEDBACK_MAX_PER_IP must be a positive number".to_string()))?;
+            let window_secs = env::var("FEEDBACK_WINDOW_SECS")
+                .unwrap_or_else(|_| "3600".to_string())
+                .parse::<u64>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| ConfigError::InvalidFormat("FEEDBACK_WINDOW_SECS must be a positive number of seconds".to_string()))?;
+            Some(FeedbackIntake {
+                token,
+                owner: required("FEEDBACK_OWNER")?,
+                status,
+                max_per_ip,
+                window_secs,
+            })
+        } else {
+            None
+        };
+
+        // Compiled-in plugins to enable, e.g. PLUGINS=audit_log
+        let plugins = env::var("PLUGINS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+
         Ok(AppConfig {
             database_url,
             jwt_secret,
+            token_hash_key,
+            jwt_algorithm,
+            jwt_private_key_file,
+            jwt_public_key_file,
+            jwt_key_id,
+            jwt_previous_public_keys,
+            token_ttl_hours,
+            remember_me_ttl_hours,
             environment,
             port,
+            admin_bind_address,
             frontend_urls,
+            outbox_poll_interval_secs,
+            outbox_max_attempts,
+            usage_flush_interval_secs,
+            sla_check_interval_secs,
+            weekly_digest_at,
+            realtime_queue_capacity,
+            cdn_base_url,
+            cdn_signing_key,
+            cdn_url_ttl_secs,
+            load_shed_max_in_flight,
+            max_json_body_bytes,
+            max_upload_bytes,
+            load_shed_max_pool_wait_ms,
+            load_shed_retry_after_secs,
+            pool_wait_alert_ms,
+            pool_alert_webhook_url,
+            rate_limit_requests,
+            rate_limit_ip_requests,
+            rate_limit_window_secs,
+            trusted_proxies,
+            login_max_attempts_per_ip,
+            login_max_attempts_per_username,
+            login_attempt_window_secs,
+            worker_threads,
+            request_timeout_secs,
+            long_request_timeout_secs,
+            slow_request_ms,
+            virus_scan_command,
+            confidential_board,
+            sensitive_board,
+            encryption_key,
+            watermark_command,
+            preview_command,
+            pdf_text_command,
+            moderation,
+            readiness_file,
+            sql_context_tagging,
+            row_level_security,
+            mail_command,
+            mail_from,
+            email_template_dir,
+            password_reset_url,
+            password_reset_ttl_minutes,
+            invite_url,
+            invite_ttl_hours,
+            email_verify_url,
+            email_verification_ttl_hours,
+            magic_link_url,
+            magic_link_ttl_minutes,
+            password_policy,
+            google_oauth,
+            github_oauth,
+            oauth_redirect_base_url,
+            auth_transport,
+            auth_cookie_name,
+            auth_cookie_same_site,
+            api_docs,
+            log_format,
+            security_headers,
+            content_security_policy,
+            compression,
+            compression_min_bytes,
+            telemetry_endpoint,
+            telemetry_interval_secs,
+            plugins,
+            captcha,
+            feedback,
         })
     }
 
-    pub fn is_development(&self) -> bool {
-        self.environment == "development"
+    pub fn is_production(&self) -> bool {
+        self.environment == "production"
     }
 }
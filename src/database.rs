@@ -1,21 +1,76 @@
-use sqlx::{PgPool, Row};
+use std::str::FromStr;
+
+use sqlx::{PgConnection, PgPool, Postgres, Row, Transaction};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
 use anyhow::{Result, Context};
 
+use crate::middleware::request_context;
+
 pub struct Database {
     pub pool: PgPool,
+    tag_transactions: bool,
 }
 
 impl Database {
-    pub async fn new(database_url: &str) -> Result<Self> {
+    /// Connect to the database. With `row_level_security`, every connection
+    /// handed out while handling a request carries the request's user as
+    /// `app.user_id` for the policies in kanban_db.sql, and connections
+    /// taken outside a request act as the service (`app.system`).
+    pub async fn new(database_url: &str, row_level_security: bool) -> Result<Self> {
         log::info!("🔗 Connecting to database...");
-        
-        let pool = PgPool::connect(database_url)
+
+        let mut options = PgPoolOptions::new();
+        if row_level_security {
+            // New connections go through after_connect, pooled ones through
+            // before_acquire; both run on the task that asked for one
+            options = options
+                .after_connect(|conn, _| Box::pin(set_request_user(conn)))
+                .before_acquire(|conn, _| Box::pin(async move {
+                    set_request_user(conn).await?;
+                    Ok(true)
+                }));
+        }
+        let mut connect_options = PgConnectOptions::from_str(database_url)
+            .context("Invalid database URL")?;
+        if !row_level_security {
+            // The policies deny a connection that says nothing about whom
+            // it acts for, so without row-level security all act as the
+            // service
+            connect_options = connect_options.options([("app.system", "on")]);
+        }
+
+        let pool = options.connect_with(connect_options)
             .await
             .context("Failed to connect to the database")?;
 
         log::info!("✅ Database connection established");
 
-        Ok(Database { pool })
+        Ok(Database { pool, tag_transactions: false })
+    }
+
+    /// Label transactions begun while handling a request with its request
+    /// and user id (see `begin`)
+    pub fn with_transaction_tagging(mut self, enabled: bool) -> Self {
+        self.tag_transactions = enabled;
+        self
+    }
+
+    /// Begin a transaction. With tagging enabled, a transaction begun inside
+    /// a request sets its application_name to the request context so
+    /// pg_stat_activity and the server log show who initiated it.
+    pub async fn begin(&self) -> std::result::Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+
+        if self.tag_transactions {
+            if let Some(ctx) = request_context::current() {
+                sqlx::query("SELECT set_config('application_name', $1, true)")
+                    .bind(format!("kanban-be {}", ctx))
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        Ok(tx)
     }
 
     pub async fn health_check(&self) -> Result<()> {
@@ -36,7 +91,9 @@ impl Database {
         }
     }
 
-    pub async fn check_tables(&self) -> Result<()> {
+    /// Check the schema from kanban_db.sql has been applied; returns the
+    /// names of any expected tables that are missing
+    pub async fn check_tables(&self) -> Result<Vec<String>> {
         log::info!("📋 Checking database tables...");
 
         let tables = sqlx::query(
@@ -44,7 +101,7 @@ impl Database {
             SELECT table_name 
             FROM information_schema.tables 
             WHERE table_schema = 'public' 
-            AND table_name IN ('users', 'teams', 'tasks', 'task_teams', 'task_attachments')
+            AND table_name IN ('users', 'teams', 'tasks', 'task_teams', 'task_attachments', 'event_outbox', 'task_events', 'operations', 'attachment_downloads', 'dead_letters', 'password_reset_tokens', 'revoked_tokens', 'api_keys', 'user_identities', 'api_usage', 'team_members', 'invitations', 'scripts', 'email_templates', 'login_links', 'task_links', 'board_settings', 'task_drafts', 'task_votes', 'feedback_submissions', 'email_verifications', 'column_policies', 'sla_rules', 'sla_breaches', 'escalation_steps', 'sla_escalations', 'business_calendar', 'holidays', 'moderation_reviews', 'audit_logs', 'weekly_digests', 'board_cards')
             ORDER BY table_name
             "#
         )
@@ -52,7 +109,7 @@ impl Database {
         .await
         .context("Failed to check database tables")?;
 
-        let expected_tables = vec!["task_attachments", "task_teams", "tasks", "teams", "users"];
+        let expected_tables = vec!["api_keys", "api_usage", "attachment_downloads", "audit_logs", "board_cards", "board_settings", "business_calendar", "column_policies", "dead_letters", "email_templates", "email_verifications", "escalation_steps", "event_outbox", "feedback_submissions", "holidays", "invitations", "login_links", "moderation_reviews", "operations", "password_reset_tokens", "revoked_tokens", "scripts", "sla_breaches", "sla_escalations", "sla_rules", "task_attachments", "task_drafts", "task_events", "task_links", "task_teams", "task_votes", "tasks", "team_members", "teams", "user_identities", "users", "weekly_digests"];
         let found_tables: Vec<String> = tables
             .iter()
             .map(|row| row.get::<String, _>("table_name"))
@@ -60,14 +117,20 @@ impl Database {
 
         log::info!("📊 Found tables: {:?}", found_tables);
 
-        if found_tables.len() == expected_tables.len() {
+        let missing_tables: Vec<String> = expected_tables
+            .iter()
+            .filter(|table| !found_tables.iter().any(|found| found == *table))
+            .map(|table| table.to_string())
+            .collect();
+
+        if missing_tables.is_empty() {
             log::info!("✅ All required tables exist");
         } else {
-            log::warn!("⚠️  Some tables may be missing. Expected: {:?}", expected_tables);
+            log::warn!("⚠️  Missing tables: {:?}", missing_tables);
             log::warn!("   Run the kanban_db.sql script in your Neon database if tables are missing");
         }
 
-        Ok(())
+        Ok(missing_tables)
     }
 
     pub async fn get_stats(&self) -> Result<DatabaseStats> {
@@ -93,6 +156,32 @@ impl Database {
     }
 }
 
+// Publish the user of the request being handled as app.user_id. Outside a
+// request the connection acts as the service; a request that is not signed
+// in (yet) gets neither, which the policies treat as nobody
+async fn set_request_user(conn: &mut PgConnection) -> std::result::Result<(), sqlx::Error> {
+    let ctx = request_context::current();
+    let user_id = ctx.as_ref()
+        .and_then(|ctx| ctx.user_id())
+        .map(|id| id.to_string())
+        .unwrap_or_default();
+    sqlx::query("SELECT set_config('app.user_id', $1, false), set_config('app.system', $2, false)")
+        .bind(user_id)
+        .bind(if ctx.is_none() { "on" } else { "off" })
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// Let the rest of a transaction past the row-level policies, for work done
+/// on nobody's behalf such as resolving an API key before its owner is known
+pub async fn act_as_service(tx: &mut PgConnection) -> std::result::Result<(), sqlx::Error> {
+    sqlx::query("SELECT set_config('app.system', 'on', true)")
+        .execute(tx)
+        .await?;
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct DatabaseStats {
     pub users: i64,
@@ -110,3 +199,120 @@ impl DatabaseStats {
         log::info!("   📎 Attachments: {}", self.attachments);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+    use actix_web::http::StatusCode;
+    use actix_web::{web, HttpResponse};
+    use sqlx::Connection;
+
+    use super::*;
+    use crate::handlers::task::{create_draft, delete_draft, list_drafts, update_draft};
+    use crate::middleware::request_context::run_as;
+    use crate::models::task::SaveTaskDraftRequest;
+    use crate::utils::errors::ServiceError;
+    use crate::utils::test_db::{signed_in, TestDb};
+
+    fn draft(name: &str) -> web::Json<SaveTaskDraftRequest> {
+        web::Json(SaveTaskDraftRequest {
+            name: Some(name.to_string()),
+            description: None,
+            status: None,
+            external_link: None,
+            teams: None,
+        })
+    }
+
+    async fn json(response: Result<HttpResponse, ServiceError>) -> serde_json::Value {
+        let response = response.expect("handler succeeds");
+        let body = to_bytes(response.into_body()).await.expect("body");
+        serde_json::from_slice(&body).expect("JSON body")
+    }
+
+    async fn drafts_seen(test: &TestDb) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM task_drafts")
+            .fetch_one(&test.db.pool)
+            .await
+            .expect("count drafts")
+    }
+
+    #[actix_web::test]
+    async fn draft_handlers_work_under_row_level_security() {
+        let Some(test) = TestDb::create_with(true).await else { return };
+        let alice = test.insert_user("alice", "member").await;
+        let bob = test.insert_user("bob", "member").await;
+        let as_alice = || signed_in(alice, "member");
+        let as_bob = || signed_in(bob, "member");
+
+        let created = json(run_as(Some(alice), create_draft(as_alice(), test.db.clone(), draft("mine"))).await).await;
+        let id = created["data"]["id"].as_i64().expect("draft id") as i32;
+        let updated = json(run_as(Some(alice), update_draft(as_alice(), test.db.clone(), web::Path::from(id), draft("still mine"))).await).await;
+        assert_eq!(updated["data"]["name"], "still mine");
+        let listed = json(run_as(Some(alice), list_drafts(as_alice(), test.db.clone())).await).await;
+        assert_eq!(listed["data"].as_array().map(Vec::len), Some(1));
+
+        let listed = json(run_as(Some(bob), list_drafts(as_bob(), test.db.clone())).await).await;
+        assert_eq!(listed["data"].as_array().map(Vec::len), Some(0));
+        let error = run_as(Some(bob), update_draft(as_bob(), test.db.clone(), web::Path::from(id), draft("taken"))).await
+            .expect_err("bob cannot update alice's draft");
+        assert_eq!(actix_web::ResponseError::status_code(&error), StatusCode::NOT_FOUND);
+
+        // Postgres hides the row from bob even without the handlers' filters,
+        // and refuses rows he writes for someone else
+        assert_eq!(run_as(Some(bob), drafts_seen(&test)).await, 0);
+        let forged = run_as(Some(bob), sqlx::query("INSERT INTO task_drafts (name, created_by) VALUES ('forged', $1)")
+            .bind(alice)
+            .execute(&test.db.pool)).await;
+        assert!(forged.is_err());
+        // Background jobs act as the service and see every draft
+        assert_eq!(drafts_seen(&test).await, 1);
+
+        json(run_as(Some(alice), delete_draft(as_alice(), test.db.clone(), web::Path::from(id))).await).await;
+        assert_eq!(drafts_seen(&test).await, 0);
+        test.drop().await;
+    }
+
+    #[actix_web::test]
+    async fn policies_fail_closed_without_the_session_setting() {
+        let Some(test) = TestDb::create_with(true).await else { return };
+        let alice = test.insert_user("alice", "member").await;
+        sqlx::query("INSERT INTO task_drafts (name, created_by) VALUES ('draft', $1)")
+            .bind(alice)
+            .execute(&test.db.pool)
+            .await
+            .expect("insert draft");
+        sqlx::query("INSERT INTO api_keys (user_id, name, prefix, key_hash) VALUES ($1, 'key', 'kbk_', 'hash')")
+            .bind(alice)
+            .execute(&test.db.pool)
+            .await
+            .expect("insert key");
+
+        // A request that has not signed in is nobody
+        assert_eq!(run_as(Some(alice), drafts_seen(&test)).await, 1);
+        assert_eq!(run_as(None, drafts_seen(&test)).await, 0);
+
+        // So is a connection that never set app.user_id or app.system
+        let mut conn = PgConnection::connect(&test.app_url).await.expect("connect");
+        for table in ["task_drafts", "api_keys"] {
+            let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+                .fetch_one(&mut conn)
+                .await
+                .expect("count rows");
+            assert_eq!(count, 0, "{} is visible without the session setting", table);
+        }
+        let inserted = sqlx::query("INSERT INTO task_drafts (name, created_by) VALUES ('draft', $1)")
+            .bind(alice)
+            .execute(&mut conn)
+            .await;
+        assert!(inserted.is_err());
+        conn.close().await.ok();
+
+        // Without row-level security the pool acts as the service
+        let open = Database::new(&test.app_url, false).await.expect("connect");
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM task_drafts").fetch_one(&open.pool).await.expect("count");
+        assert_eq!(count, 1);
+        open.pool.close().await;
+        test.drop().await;
+    }
+}
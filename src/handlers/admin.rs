@@ -0,0 +1,1020 @@
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::config::{ApiDocsAccess, AppConfig};
+use crate::Database;
+use crate::middleware::{AuthenticatedUser, Permission};
+use crate::models::auth::ApiResponse;
+use crate::models::audit::{AuditLogEntry, AuditLogQuery};
+use crate::models::admin::{DeactivationReport, IndexAdvice, OffboardReport, OffboardUserRequest, PermissionPolicy};
+use crate::models::dead_letter::{DeadLetter, DeadLetterQuery};
+use crate::models::email_template::{EmailTemplate, EmailTemplateQuery, PreviewEmailTemplateRequest, RenderedEmail, UpdateEmailTemplateRequest};
+use crate::models::ids::{TaskId, UserId};
+use crate::models::list::ListParams;
+use crate::models::moderation::{ModerationQuery, ModerationReview};
+use crate::models::params::EmailTemplatePath;
+use crate::models::script::{CreateScriptRequest, Script, UpdateScriptRequest};
+use crate::models::usage::{UsageEntry, UsageQuery};
+use crate::services::{audit, dead_letters, index_advisor, moderation, outbox, scripts, task_events, usage};
+use crate::services::email_templates::EmailTemplates;
+use crate::services::task_response::TaskResponseAssembler;
+use crate::utils::docs_session;
+use crate::utils::errors::ServiceError;
+
+/// List dead-lettered background jobs, newest first
+#[utoipa::path(
+    get,
+    path = "/api/admin/dead-letters",
+    operation_id = "listDeadLetters",
+    tag = "dead-letters",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(DeadLetterQuery),
+    responses(
+        (status = 200, description = "Dead letters retrieved successfully", body = ApiResponse<Vec<DeadLetter>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_dead_letters(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<DeadLetterQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/dead-letters");
+
+    user.requires(Permission::DeadLetterManage)?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let entries = dead_letters::list(&db, query.kind.as_deref(), limit).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Dead letters retrieved successfully", entries)))
+}
+
+/// Get a single dead letter including its payload
+#[utoipa::path(
+    get,
+    path = "/api/admin/dead-letters/{id}",
+    operation_id = "getDeadLetter",
+    tag = "dead-letters",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i64, Path, description = "Dead letter ID")
+    ),
+    responses(
+        (status = 200, description = "Dead letter retrieved successfully", body = ApiResponse<DeadLetter>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Dead letter not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_dead_letter(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("GET /api/admin/dead-letters/{}", id);
+
+    user.requires(Permission::DeadLetterManage)?;
+
+    let entry = dead_letters::get(&db, id).await?
+        .ok_or_else(|| ServiceError::NotFound("Dead letter not found".to_string()).with_code("DEAD_LETTER_NOT_FOUND"))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Dead letter retrieved successfully", entry)))
+}
+
+/// Put a dead letter back on its original queue with a fresh retry budget
+#[utoipa::path(
+    post,
+    path = "/api/admin/dead-letters/{id}/requeue",
+    operation_id = "requeueDeadLetter",
+    tag = "dead-letters",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i64, Path, description = "Dead letter ID")
+    ),
+    responses(
+        (status = 200, description = "Dead letter requeued successfully", body = ApiResponse<bool>),
+        (status = 400, description = "Dead letter cannot be requeued", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Dead letter not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn requeue_dead_letter(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("POST /api/admin/dead-letters/{}/requeue", id);
+
+    user.requires(Permission::DeadLetterManage)?;
+    let user_id = user.id;
+
+    if !dead_letters::requeue(&db, id).await? {
+        return Err(ServiceError::NotFound("Dead letter not found".to_string()).with_code("DEAD_LETTER_NOT_FOUND"));
+    }
+
+    log::info!("Dead letter {} requeued by user {}", id, user_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Dead letter requeued successfully", true)))
+}
+
+/// Permanently delete a single dead letter
+#[utoipa::path(
+    delete,
+    path = "/api/admin/dead-letters/{id}",
+    operation_id = "deleteDeadLetter",
+    tag = "dead-letters",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i64, Path, description = "Dead letter ID")
+    ),
+    responses(
+        (status = 200, description = "Dead letter deleted successfully", body = ApiResponse<bool>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Dead letter not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn delete_dead_letter(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i64>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("DELETE /api/admin/dead-letters/{}", id);
+
+    user.requires(Permission::DeadLetterManage)?;
+    let user_id = user.id;
+
+    if dead_letters::purge(&db, Some(id), None).await? == 0 {
+        return Err(ServiceError::NotFound("Dead letter not found".to_string()).with_code("DEAD_LETTER_NOT_FOUND"));
+    }
+
+    log::info!("Dead letter {} deleted by user {}", id, user_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Dead letter deleted successfully", true)))
+}
+
+/// Purge all dead letters, or only those of the given kind
+#[utoipa::path(
+    delete,
+    path = "/api/admin/dead-letters",
+    operation_id = "purgeDeadLetters",
+    tag = "dead-letters",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(DeadLetterQuery),
+    responses(
+        (status = 200, description = "Number of dead letters purged", body = ApiResponse<u64>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn purge_dead_letters(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<DeadLetterQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("DELETE /api/admin/dead-letters");
+
+    user.requires(Permission::DeadLetterManage)?;
+    let user_id = user.id;
+
+    let purged = dead_letters::purge(&db, None, query.kind.as_deref()).await?;
+
+    log::info!("{} dead letters purged by user {}", purged, user_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Dead letters purged successfully", purged)))
+}
+
+/// Offboard a user: hand their open tasks to another user and deactivate
+/// the account, all in one transaction
+#[utoipa::path(
+    post,
+    path = "/api/admin/users/{id}/offboard",
+    operation_id = "offboardUser",
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    request_body = OffboardUserRequest,
+    responses(
+        (status = 200, description = "User offboarded", body = ApiResponse<OffboardReport>),
+        (status = 400, description = "Invalid reassignment target", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "User not found", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "User is already deactivated", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn offboard_user(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<UserId>,
+    offboard_req: web::Json<OffboardUserRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let leaver_id = path.into_inner();
+    let reassign_to = offboard_req.reassign_to;
+    log::info!("POST /api/admin/users/{}/offboard", leaver_id);
+
+    user.requires(Permission::UserOffboard)?;
+    let admin_id = user.id;
+
+    if reassign_to == leaver_id {
+        return Err(ServiceError::ValidationError("Tasks cannot be reassigned to the user being offboarded".to_string())
+            .with_code("INVALID_REASSIGNMENT"));
+    }
+
+    // Begin transaction
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let leaver_active: bool = sqlx::query_scalar("SELECT is_active FROM users WHERE id = $1 FOR UPDATE")
+        .bind(leaver_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking user: {}", e);
+            ServiceError::DatabaseError("Failed to check user".to_string())
+        })?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()).with_code("USER_NOT_FOUND"))?;
+
+    if !leaver_active {
+        return Err(ServiceError::Conflict("User is already deactivated".to_string()).with_code("USER_ALREADY_OFFBOARDED"));
+    }
+
+    let target_active: Option<bool> = sqlx::query_scalar("SELECT is_active FROM users WHERE id = $1")
+        .bind(reassign_to)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking user: {}", e);
+            ServiceError::DatabaseError("Failed to check user".to_string())
+        })?;
+
+    if target_active != Some(true) {
+        return Err(ServiceError::ValidationError("Tasks must be reassigned to an active user".to_string())
+            .with_code("INVALID_REASSIGNMENT"));
+    }
+
+    let task_rows = sqlx::query(
+        "UPDATE tasks SET created_by = $2, updated_at = NOW()
+         WHERE created_by = $1 AND status <> 'DONE'
+         RETURNING id, name, description, status, external_link, client_id, created_by, created_at, updated_at"
+    )
+    .bind(leaver_id)
+    .bind(reassign_to)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error reassigning tasks: {}", e);
+        ServiceError::DatabaseError("Failed to reassign tasks".to_string())
+    })?;
+
+    let task_ids: Vec<TaskId> = task_rows.iter().map(|row| row.get("id")).collect();
+    let task_responses = TaskResponseAssembler::preload(&db, &task_ids).await?
+        .assemble_all(&task_rows);
+
+    for task_response in &task_responses {
+        task_events::append(
+            &mut tx,
+            task_response.id,
+            task_events::TASK_TRANSFERRED,
+            admin_id,
+            &serde_json::json!({ "created_by": reassign_to, "previous_owner": leaver_id, "reason": "offboarding" }),
+        ).await?;
+        outbox::enqueue(&mut tx, "task", task_response.id.0, "task.updated", task_response).await?;
+    }
+
+    // Deactivating also revokes every token the user still holds
+    let deactivated_at: DateTime<Utc> = sqlx::query_scalar(
+        "UPDATE users SET is_active = FALSE, tokens_valid_after = date_trunc('second', NOW()), updated_at = NOW()
+         WHERE id = $1
+         RETURNING updated_at"
+    )
+    .bind(leaver_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error deactivating user: {}", e);
+        ServiceError::DatabaseError("Failed to deactivate user".to_string())
+    })?;
+
+    // Commit transaction
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    log::info!(
+        "User {} offboarded by user {}: {} open tasks reassigned to user {}",
+        leaver_id, admin_id, task_ids.len(), reassign_to
+    );
+    let report = OffboardReport {
+        user_id: leaver_id,
+        reassigned_to: reassign_to,
+        reassigned_tasks: task_ids,
+        deactivated_at,
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success("User offboarded successfully", report)))
+}
+
+/// Soft-delete a user: the account is deactivated and every token and API
+/// key it holds stops working, while its tasks, attachments and history
+/// keep pointing at it
+#[utoipa::path(
+    delete,
+    path = "/api/admin/users/{id}",
+    operation_id = "deactivateUser",
+    tag = "users",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User deactivated", body = ApiResponse<DeactivationReport>),
+        (status = 400, description = "Cannot deactivate yourself", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "User not found", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "User is already deactivated", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn deactivate_user(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<UserId>,
+) -> Result<HttpResponse, ServiceError> {
+    let target_id = path.into_inner();
+    log::info!("DELETE /api/admin/users/{}", target_id);
+
+    user.requires(Permission::UserOffboard)?;
+    let admin_id = user.id;
+
+    if target_id == admin_id {
+        return Err(ServiceError::ValidationError("You cannot deactivate your own account".to_string())
+            .with_code("CANNOT_DEACTIVATE_SELF"));
+    }
+
+    // Deactivating also revokes every token the user still holds; API keys
+    // are refused for inactive owners
+    let row = sqlx::query(
+        "UPDATE users u SET is_active = FALSE, tokens_valid_after = date_trunc('second', NOW()), updated_at = NOW()
+         FROM (SELECT id, is_active FROM users WHERE id = $1 FOR UPDATE) before
+         WHERE u.id = before.id
+         RETURNING before.is_active AS was_active, u.updated_at"
+    )
+    .bind(target_id)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error deactivating user: {}", e);
+        ServiceError::DatabaseError("Failed to deactivate user".to_string())
+    })?
+    .ok_or_else(|| ServiceError::NotFound("User not found".to_string()).with_code("USER_NOT_FOUND"))?;
+
+    if !row.get::<bool, _>("was_active") {
+        return Err(ServiceError::Conflict("User is already deactivated".to_string()).with_code("USER_ALREADY_OFFBOARDED"));
+    }
+
+    log::info!("User {} deactivated by user {}", target_id, admin_id);
+    let report = DeactivationReport {
+        user_id: target_id,
+        deactivated_at: row.get("updated_at"),
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success("User deactivated successfully", report)))
+}
+
+/// Let this browser open Swagger UI for an hour when the API docs are
+/// admin-only, by setting a signed session cookie
+#[utoipa::path(
+    post,
+    path = "/api/admin/docs-session",
+    operation_id = "startDocsSession",
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Docs session cookie set", body = ApiResponse<bool>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "API docs are disabled", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn start_docs_session(
+    user: AuthenticatedUser,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/admin/docs-session");
+    user.requires(Permission::ApiDocs)?;
+    let admin_id = user.id;
+
+    if config.api_docs == ApiDocsAccess::Disabled {
+        return Err(ServiceError::NotFound("API docs are disabled".to_string()).with_code("API_DOCS_DISABLED"));
+    }
+
+    log::info!("Docs session started by user {}", admin_id);
+    Ok(HttpResponse::Ok()
+        .cookie(docs_session::issue(&config.jwt_secret))
+        .json(ApiResponse::success("Docs session started", true)))
+}
+
+/// List every permission with the roles that hold it and the endpoints that
+/// require it
+#[utoipa::path(
+    get,
+    path = "/api/admin/permissions",
+    operation_id = "listPermissions",
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Authorization policy", body = ApiResponse<Vec<PermissionPolicy>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_permissions(user: AuthenticatedUser) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/permissions");
+    user.requires(Permission::PolicyRead)?;
+
+    let policies: Vec<PermissionPolicy> = Permission::ALL.into_iter().map(PermissionPolicy::from).collect();
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Permissions retrieved successfully", policies)))
+}
+
+/// Request counts and error rates per user and API key, busiest first
+#[utoipa::path(
+    get,
+    path = "/api/admin/usage",
+    operation_id = "listApiUsage",
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(UsageQuery),
+    responses(
+        (status = 200, description = "Usage retrieved", body = ApiResponse<Vec<UsageEntry>>),
+        (status = 400, description = "Invalid number of days", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_usage(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<UsageQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/usage");
+    user.requires(Permission::UsageRead)?;
+
+    let entries = usage::summary(&db, query.days()?, None).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Usage retrieved successfully", entries)))
+}
+
+/// List every validation and automation script
+#[utoipa::path(
+    get,
+    path = "/api/admin/scripts",
+    operation_id = "listScripts",
+    tag = "scripts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Scripts retrieved successfully", body = ApiResponse<Vec<Script>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_scripts(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/scripts");
+    user.requires(Permission::ScriptManage)?;
+
+    let scripts = scripts::list(&db).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Scripts retrieved successfully", scripts)))
+}
+
+/// Add a Rhai script run on task events. Scripts run with operation, time
+/// and size limits and cannot import modules.
+#[utoipa::path(
+    post,
+    path = "/api/admin/scripts",
+    operation_id = "createScript",
+    tag = "scripts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = CreateScriptRequest,
+    responses(
+        (status = 201, description = "Script created", body = ApiResponse<Script>),
+        (status = 400, description = "Invalid kind, event or source", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Script name already taken", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn create_script(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    script_req: web::Json<CreateScriptRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/admin/scripts");
+    user.requires(Permission::ScriptManage)?;
+
+    let mut script_req = script_req.into_inner();
+    script_req.normalize()?;
+
+    let script = scripts::create(&db, user.id, &script_req).await?;
+
+    log::info!("Script {} ({}) created by user {}", script.id, script.name, user.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success("Script created successfully", script)))
+}
+
+/// Change a script's name, event, source or whether it is enabled
+#[utoipa::path(
+    put,
+    path = "/api/admin/scripts/{id}",
+    operation_id = "updateScript",
+    tag = "scripts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "Script ID")
+    ),
+    request_body = UpdateScriptRequest,
+    responses(
+        (status = 200, description = "Script updated", body = ApiResponse<Script>),
+        (status = 400, description = "Invalid event or source", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Script not found", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Script name already taken", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn update_script(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+    script_req: web::Json<UpdateScriptRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("PUT /api/admin/scripts/{}", id);
+    user.requires(Permission::ScriptManage)?;
+
+    let script = scripts::update(&db, id, script_req.into_inner()).await?
+        .ok_or_else(|| ServiceError::NotFound("Script not found".to_string()).with_code("SCRIPT_NOT_FOUND"))?;
+
+    log::info!("Script {} updated by user {}", id, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Script updated successfully", script)))
+}
+
+/// Delete a script
+#[utoipa::path(
+    delete,
+    path = "/api/admin/scripts/{id}",
+    operation_id = "deleteScript",
+    tag = "scripts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "Script ID")
+    ),
+    responses(
+        (status = 200, description = "Script deleted successfully", body = ApiResponse<bool>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Script not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn delete_script(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("DELETE /api/admin/scripts/{}", id);
+    user.requires(Permission::ScriptManage)?;
+
+    if !scripts::delete(&db, id).await? {
+        return Err(ServiceError::NotFound("Script not found".to_string()).with_code("SCRIPT_NOT_FOUND"));
+    }
+
+    log::info!("Script {} deleted by user {}", id, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Script deleted successfully", true)))
+}
+
+/// List the email templates in effect in every locale, with where each
+/// comes from and the variables it can use
+#[utoipa::path(
+    get,
+    path = "/api/admin/email-templates",
+    operation_id = "listEmailTemplates",
+    tag = "email-templates",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Email templates retrieved successfully", body = ApiResponse<Vec<EmailTemplate>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_email_templates(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    templates: web::Data<EmailTemplates>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/email-templates");
+    user.requires(Permission::EmailTemplateManage)?;
+
+    let templates = templates.list(&db).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Email templates retrieved successfully", templates)))
+}
+
+/// Replace an email template. It must render against the template's sample
+/// data.
+#[utoipa::path(
+    put,
+    path = "/api/admin/email-templates/{name}",
+    operation_id = "updateEmailTemplate",
+    tag = "email-templates",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        EmailTemplatePath,
+        EmailTemplateQuery
+    ),
+    request_body = UpdateEmailTemplateRequest,
+    responses(
+        (status = 200, description = "Email template saved", body = ApiResponse<EmailTemplate>),
+        (status = 400, description = "Template does not render, or unsupported locale", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Email template not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn update_email_template(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    templates: web::Data<EmailTemplates>,
+    path: web::Path<EmailTemplatePath>,
+    query: web::Query<EmailTemplateQuery>,
+    template_req: web::Json<UpdateEmailTemplateRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let name = path.into_inner().name;
+    log::info!("PUT /api/admin/email-templates/{}", name);
+    user.requires(Permission::EmailTemplateManage)?;
+
+    let mut template_req = template_req.into_inner();
+    template_req.normalize()?;
+
+    let template = templates.save(&db, &name, query.locale()?, user.id, &template_req).await?;
+
+    log::info!("Email template {} saved by user {}", name, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Email template saved successfully", template)))
+}
+
+/// Drop a saved email template, going back to the file or built-in one
+#[utoipa::path(
+    delete,
+    path = "/api/admin/email-templates/{name}",
+    operation_id = "resetEmailTemplate",
+    tag = "email-templates",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        EmailTemplatePath,
+        EmailTemplateQuery
+    ),
+    responses(
+        (status = 200, description = "Email template reset", body = ApiResponse<EmailTemplate>),
+        (status = 400, description = "Unsupported locale", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Email template not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn reset_email_template(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    templates: web::Data<EmailTemplates>,
+    path: web::Path<EmailTemplatePath>,
+    query: web::Query<EmailTemplateQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    let name = path.into_inner().name;
+    log::info!("DELETE /api/admin/email-templates/{}", name);
+    user.requires(Permission::EmailTemplateManage)?;
+
+    let template = templates.reset(&db, &name, query.locale()?).await?;
+
+    log::info!("Email template {} reset by user {}", name, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Email template reset successfully", template)))
+}
+
+/// Render a draft, or the template in effect, against sample data
+#[utoipa::path(
+    post,
+    path = "/api/admin/email-templates/{name}/preview",
+    operation_id = "previewEmailTemplate",
+    tag = "email-templates",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        EmailTemplatePath,
+        EmailTemplateQuery
+    ),
+    request_body = PreviewEmailTemplateRequest,
+    responses(
+        (status = 200, description = "Email rendered", body = ApiResponse<RenderedEmail>),
+        (status = 400, description = "Template does not render, or unsupported locale", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Email template not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn preview_email_template(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    templates: web::Data<EmailTemplates>,
+    path: web::Path<EmailTemplatePath>,
+    query: web::Query<EmailTemplateQuery>,
+    preview_req: web::Json<PreviewEmailTemplateRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let name = path.into_inner().name;
+    log::info!("POST /api/admin/email-templates/{}/preview", name);
+    user.requires(Permission::EmailTemplateManage)?;
+
+    let rendered = templates.preview(&db, &name, query.locale()?, preview_req.into_inner()).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Email rendered successfully", rendered)))
+}
+
+/// List uploads held by the moderation service: pending ones oldest first,
+/// or decided ones most recent first, one page at a time
+#[utoipa::path(
+    get,
+    path = "/api/admin/moderation",
+    operation_id = "listModerationReviews",
+    tag = "moderation",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(ModerationQuery, ListParams),
+    responses(
+        (status = 200, description = "Moderation queue retrieved successfully; X-Total-Count holds the unpaged total", body = ApiResponse<Vec<ModerationReview>>),
+        (status = 400, description = "Invalid list parameters", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_moderation_reviews(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<ModerationQuery>,
+    params: ListParams,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/moderation");
+    user.requires(Permission::ContentModerate)?;
+
+    let (reviews, total) = moderation::list(&db, query.pending.unwrap_or(true), params.per_page(), params.offset()).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(ApiResponse::success("Moderation queue retrieved successfully", reviews)))
+}
+
+/// Approve a held upload so it can be listed and downloaded
+#[utoipa::path(
+    post,
+    path = "/api/admin/moderation/{id}/approve",
+    operation_id = "approveModerationReview",
+    tag = "moderation",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "Moderation review ID")
+    ),
+    responses(
+        (status = 200, description = "Upload approved", body = ApiResponse<ModerationReview>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Moderation review not found", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Review was already decided", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn approve_moderation_review(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("POST /api/admin/moderation/{}/approve", id);
+    user.requires(Permission::ContentModerate)?;
+
+    let review = moderation::decide(&db, id, true, user.id).await?;
+    log::info!("Attachment {} approved by user {}", review.attachment_id, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Upload approved", review)))
+}
+
+/// Reject a held upload; it stays hidden and its file is removed
+#[utoipa::path(
+    post,
+    path = "/api/admin/moderation/{id}/reject",
+    operation_id = "rejectModerationReview",
+    tag = "moderation",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "Moderation review ID")
+    ),
+    responses(
+        (status = 200, description = "Upload rejected", body = ApiResponse<ModerationReview>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Moderation review not found", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Review was already decided", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn reject_moderation_review(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("POST /api/admin/moderation/{}/reject", id);
+    user.requires(Permission::ContentModerate)?;
+
+    let review = moderation::decide(&db, id, false, user.id).await?;
+    log::info!("Attachment {} rejected by user {}", review.attachment_id, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Upload rejected", review)))
+}
+
+/// List recorded mutating requests, most recent first, optionally only
+/// those of one user, on one entity or within a time range
+#[utoipa::path(
+    get,
+    path = "/api/admin/audit-logs",
+    operation_id = "listAuditLogs",
+    tag = "audit",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(AuditLogQuery, ListParams),
+    responses(
+        (status = 200, description = "Audit logs retrieved successfully; X-Total-Count holds the unpaged total", body = ApiResponse<Vec<AuditLogEntry>>),
+        (status = 400, description = "Invalid filter or list parameters", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_audit_logs(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<AuditLogQuery>,
+    params: ListParams,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/audit-logs");
+    user.requires(Permission::AuditRead)?;
+
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from >= to {
+            return Err(ServiceError::ValidationError("from must be before to".to_string()).with_code("INVALID_DATE_RANGE"));
+        }
+    }
+
+    let (entries, total) = audit::list(&db, &query, params.per_page(), params.offset()).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(ApiResponse::success("Audit logs retrieved successfully", entries)))
+}
+
+/// Indexes the database is missing on foreign keys and frequently filtered
+/// columns, busiest tables first
+#[utoipa::path(
+    get,
+    path = "/api/admin/index-advice",
+    operation_id = "listIndexAdvice",
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Index advice retrieved", body = ApiResponse<Vec<IndexAdvice>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_index_advice(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/admin/index-advice");
+    user.requires(Permission::DatabaseManage)?;
+
+    let advice = index_advisor::advise(&db).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Index advice retrieved", advice)))
+}
+
+/// Create every index the advisor recommends, without blocking writes.
+/// Returns the indexes created; large tables may take a while.
+#[utoipa::path(
+    post,
+    path = "/api/admin/index-advice/apply",
+    operation_id = "applyIndexAdvice",
+    tag = "admin",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Recommended indexes created", body = ApiResponse<Vec<IndexAdvice>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 500, description = "An index could not be created", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn apply_index_advice(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/admin/index-advice/apply");
+    user.requires(Permission::DatabaseManage)?;
+
+    let applied = index_advisor::apply(&db).await?;
+    log::info!("{} recommended indexes created by user {}", applied.len(), user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Recommended indexes created", applied)))
+}
+
+pub fn admin_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/admin/dead-letters")
+            .route("", web::get().to(list_dead_letters))
+            .route("", web::delete().to(purge_dead_letters))
+            .route("/{id}", web::get().to(get_dead_letter))
+            .route("/{id}", web::delete().to(delete_dead_letter))
+            .route("/{id}/requeue", web::post().to(requeue_dead_letter))
+    )
+    .service(
+        web::scope("/api/admin/users")
+            .route("/{id}", web::delete().to(deactivate_user))
+            .route("/{id}/offboard", web::post().to(offboard_user))
+    )
+    .service(
+        web::scope("/api/admin/scripts")
+            .route("", web::get().to(list_scripts))
+            .route("", web::post().to(create_script))
+            .route("/{id}", web::put().to(update_script))
+            .route("/{id}", web::delete().to(delete_script))
+    )
+    .service(
+        web::scope("/api/admin/email-templates")
+            .route("", web::get().to(list_email_templates))
+            .route("/{name}", web::put().to(update_email_template))
+            .route("/{name}", web::delete().to(reset_email_template))
+            .route("/{name}/preview", web::post().to(preview_email_template))
+    )
+    .service(
+        web::scope("/api/admin/moderation")
+            .route("", web::get().to(list_moderation_reviews))
+            .route("/{id}/approve", web::post().to(approve_moderation_review))
+            .route("/{id}/reject", web::post().to(reject_moderation_review))
+    )
+    .route("/api/admin/docs-session", web::post().to(start_docs_session))
+    .route("/api/admin/permissions", web::get().to(list_permissions))
+    .route("/api/admin/usage", web::get().to(list_usage))
+    .route("/api/admin/audit-logs", web::get().to(list_audit_logs))
+    .route("/api/admin/index-advice", web::get().to(list_index_advice))
+    .route("/api/admin/index-advice/apply", web::post().to(apply_index_advice));
+}
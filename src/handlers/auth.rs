@@ -1,42 +1,153 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::cookie::{time, Cookie};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, HttpResponseBuilder, Result};
+use uuid::Uuid;
 use sqlx::Row;
-use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, decode, Header, EncodingKey, DecodingKey, Validation};
-use bcrypt::verify;
-use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Duration, Utc};
+use bcrypt::{hash, verify, DEFAULT_COST};
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, AuthTransport};
 use crate::Database;
-use crate::models::auth::{LoginRequest, LoginResponseData, UserResponse, ApiResponse};
+use crate::middleware::auth::{AuthenticatedUser, Claims};
+use crate::middleware::Permission;
+use crate::models::auth::{LoginRequest, LoginResponseData, UserResponse, ApiResponse, ChangePasswordRequest, ChangePasswordResponse, ForgotPasswordRequest, ResetPasswordRequest, OAuthCallbackQuery, UpdateProfileRequest, AcceptInviteRequest, LocaleSetting, MagicLinkRequest, MagicLinkLoginRequest, EmailSetting, UpdateEmailRequest, VerifyEmailRequest};
+use crate::models::availability::{Availability, SetAvailabilityRequest};
+use crate::models::usage::{UsageEntry, UsageQuery};
+use crate::models::api_key::{CreateApiKeyRequest, CreatedApiKey, SCOPES};
+use crate::models::ids::UserId;
+use crate::services::{api_keys, availability, captcha, oauth, usage};
+use crate::services::email_templates::{self, EmailTemplates};
+use crate::services::mailer::{Email, Mailer};
+use crate::services::{email_verification, invitations, magic_links, password_reset};
+use crate::services::login_limiter::LoginLimiter;
+use crate::utils::client_ip::client_ip;
 use crate::utils::errors::ServiceError;
+use crate::utils::jwt::JwtKeys;
+use crate::utils::{locale, text};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String, // Subject (user id)
-    pub username: String,
-    pub name: String,
-    pub exp: usize, // Expiration time (Unix timestamp)
-    pub iat: usize, // Issued at (Unix timestamp)
+// Helper function to sign a JWT for a user, valid for `ttl`. The user's role,
+// teams and claims version are read fresh, so the token carries them as
+// they are now.
+async fn issue_token(
+    db: &Database,
+    keys: &JwtKeys,
+    user_id: UserId,
+    username: &str,
+    name: String,
+    ttl: Duration,
+) -> Result<String, ServiceError> {
+    let account = sqlx::query(
+        "SELECT role, claims_version, email IS NOT NULL AND email_verified_at IS NULL AS email_unverified,
+                ARRAY(SELECT team_id FROM team_members WHERE user_id = u.id ORDER BY team_id) AS team_ids
+         FROM users u WHERE u.id = $1"
+    )
+    .bind(user_id)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading token claims: {}", e);
+        ServiceError::DatabaseError("Failed to generate token".to_string())
+    })?;
+
+    let now = Utc::now();
+    let exp = now
+        .checked_add_signed(ttl)
+        .expect("valid timestamp")
+        .timestamp() as usize;
+    let iat = now.timestamp() as usize;
+
+    let claims = Claims {
+        sub: user_id.to_string(),
+        username: username.to_string(),
+        name,
+        exp,
+        iat,
+        jti: Uuid::new_v4().to_string(),
+        role: account.get("role"),
+        team_ids: account.get("team_ids"),
+        ver: account.get("claims_version"),
+        email_unverified: account.get("email_unverified"),
+    };
+
+    keys.sign(&claims).map_err(|e| {
+        log::error!("JWT encoding error: {}", e);
+        ServiceError::AuthenticationError("Failed to generate token".to_string())
+    })
+}
+
+// Lifetime of a token replacing the caller's own, e.g. after a profile
+// change: as long as the session it replaces, so a "remember me" login
+// stays remembered
+fn replacement_ttl(config: &AppConfig, user: &AuthenticatedUser) -> Duration {
+    match user.claims.exp.checked_sub(user.claims.iat) {
+        Some(secs) if secs > 0 => Duration::seconds(secs as i64),
+        _ => Duration::hours(config.token_ttl_hours),
+    }
+}
+
+// Hand a freshly issued token to the client: as a Secure, HttpOnly cookie in
+// cookie auth mode, where the body then leaves it out, or in the body. The
+// cookie lives as long as the token, `ttl`.
+fn deliver_token(config: &AppConfig, response: &mut HttpResponseBuilder, token: String, ttl: Duration) -> Option<String> {
+    if config.auth_transport == AuthTransport::Header {
+        return Some(token);
+    }
+    response.cookie(
+        Cookie::build(config.auth_cookie_name.clone(), token)
+            .path("/")
+            .secure(true)
+            .http_only(true)
+            .same_site(config.auth_cookie_same_site)
+            .max_age(time::Duration::seconds(ttl.num_seconds()))
+            .finish(),
+    );
+    None
+}
+
+// In cookie mode, tell the browser to forget the token cookie
+fn clear_token_cookie(config: &AppConfig, response: &mut HttpResponseBuilder) {
+    if config.auth_transport == AuthTransport::Cookie {
+        let mut cookie = Cookie::build(config.auth_cookie_name.clone(), "")
+            .path("/")
+            .secure(true)
+            .http_only(true)
+            .same_site(config.auth_cookie_same_site)
+            .finish();
+        cookie.make_removal();
+        response.cookie(cookie);
+    }
 }
 
 /// User login endpoint
 #[utoipa::path(
     post,
     path = "/api/auth/login",
+    operation_id = "login",
     tag = "auth",
     request_body = LoginRequest,
     responses(
         (status = 200, description = "Login successful", body = ApiResponse<LoginResponseData>),
-        (status = 401, description = "Invalid credentials", body = crate::utils::errors::ServiceError)
+        (status = 400, description = "Captcha required after repeated failed logins, or failed", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Invalid credentials", body = crate::utils::errors::ServiceError),
+        (status = 429, description = "Too many attempts, retry after the Retry-After delay", body = crate::utils::errors::ServiceError),
+        (status = 503, description = "Captcha verification unavailable", body = crate::utils::errors::ServiceError)
     )
 )]
 pub async fn login(
+    req: HttpRequest,
     db: web::Data<Database>,
     config: web::Data<AppConfig>,
+    keys: web::Data<JwtKeys>,
+    limiter: web::Data<LoginLimiter>,
     login_req: web::Json<LoginRequest>,
 ) -> Result<HttpResponse, ServiceError> {
     log::info!("POST /api/auth/login - Login attempt for: {}", login_req.username);
 
+    let ip = client_ip(&req, &config.trusted_proxies).map(|ip| ip.to_string());
+    limiter.check(ip.as_deref(), &login_req.username)?;
+    if limiter.captcha_required(&login_req.username) {
+        captcha::verify(&config, login_req.captcha_token.as_deref(), ip.as_deref()).await?;
+    }
+
     // Validate input
     if login_req.username.trim().is_empty() {
         return Err(ServiceError::ValidationError("Username is required".to_string()));
@@ -48,7 +159,7 @@ pub async fn login(
 
     // Query user from database
     let user_row = sqlx::query(
-        "SELECT id, username, name, password, created_at, updated_at FROM users WHERE username = $1"
+        "SELECT id, username, name, password, created_at, updated_at FROM users WHERE username = $1 AND is_active"
     )
     .bind(&login_req.username)
     .fetch_optional(&db.pool)
@@ -62,6 +173,7 @@ pub async fn login(
         Some(row) => row,
         None => {
             log::warn!("Login failed: User not found - {}", login_req.username);
+            limiter.failed(&login_req.username);
             return Err(ServiceError::Unauthorized("Invalid credentials".to_string()));
         }
     };
@@ -76,38 +188,18 @@ pub async fn login(
 
     if !password_valid {
         log::warn!("Login failed: Invalid password for user - {}", login_req.username);
+        limiter.failed(&login_req.username);
         return Err(ServiceError::Unauthorized("Invalid credentials".to_string()));
     }
 
     // Create JWT token
-    let user_id: i32 = user_row.get("id");
-    let now = Utc::now();
-    let exp = now
-        .checked_add_signed(Duration::hours(24))
-        .expect("valid timestamp")
-        .timestamp() as usize;
-    let iat = now.timestamp() as usize;
-
-    let claims = Claims {
-        sub: user_id.to_string(),
-        username: login_req.username.clone(),
-        name: user_row.get("name"),
-        exp,
-        iat,
-    };
-
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(config.jwt_secret.as_ref()),
-    )
-    .map_err(|e| {
-        log::error!("JWT encoding error: {}", e);
-        ServiceError::AuthenticationError("Failed to generate token".to_string())
-    })?;
+    let user_id: UserId = user_row.get("id");
+    let ttl = Duration::hours(if login_req.remember_me { config.remember_me_ttl_hours } else { config.token_ttl_hours });
+    let token = issue_token(&db, &keys, user_id, &login_req.username, user_row.get("name"), ttl).await?;
 
+    let mut response = HttpResponse::Ok();
     let response_data = LoginResponseData {
-        token,
+        token: deliver_token(&config, &mut response, token, ttl),
         user: UserResponse {
             id: user_id,
             username: user_row.get("username"),
@@ -117,14 +209,38 @@ pub async fn login(
         },
     };
 
+    limiter.succeeded(&login_req.username);
     log::info!("Login successful for user: {}", login_req.username);
-    Ok(HttpResponse::Ok().json(ApiResponse::success("Login successful", response_data)))
+    Ok(response.json(ApiResponse::success("Login successful", response_data)))
+}
+
+/// Revoke the token a request was made with until it would have expired.
+/// Tokens without a jti cannot be revoked one by one.
+async fn revoke_token<'e>(executor: impl sqlx::PgExecutor<'e>, user: &AuthenticatedUser) -> Result<(), sqlx::Error> {
+    if user.claims.jti.is_empty() {
+        return Ok(());
+    }
+    let expires_at = DateTime::<Utc>::from_timestamp(user.claims.exp as i64, 0).unwrap_or_else(Utc::now);
+
+    // Entries are only needed until the token would have expired anyway
+    sqlx::query(
+        "WITH pruned AS (DELETE FROM revoked_tokens WHERE expires_at < NOW())
+         INSERT INTO revoked_tokens (jti, user_id, expires_at) VALUES ($1, $2, $3)
+         ON CONFLICT (jti) DO NOTHING"
+    )
+    .bind(&user.claims.jti)
+    .bind(user.id)
+    .bind(expires_at)
+    .execute(executor)
+    .await?;
+    Ok(())
 }
 
 /// User logout endpoint
 #[utoipa::path(
     post,
     path = "/api/auth/logout",
+    operation_id = "logout",
     tag = "auth",
     security(
         ("bearer_auth" = [])
@@ -134,70 +250,170 @@ pub async fn login(
         (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
     )
 )]
-pub async fn logout(req: HttpRequest) -> Result<HttpResponse, ServiceError> {
+pub async fn logout(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, ServiceError> {
     log::info!("POST /api/auth/logout");
 
-    // Extract token from Authorization header
-    let auth_header = req.headers().get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "));
+    // Drop the session cookie whether or not the token can be revoked
+    let mut response = HttpResponse::Ok();
+    clear_token_cookie(&config, &mut response);
 
-    if auth_header.is_none() {
-        log::warn!("Logout attempt without valid authentication");
-        return Err(ServiceError::Unauthorized("Authentication required".to_string()));
+    // Tokens minted before token ids existed cannot be revoked individually;
+    // they simply run out within a day
+    if user.claims.jti.is_empty() {
+        log::warn!("Logout of user {} with a token that has no jti", user.id);
+        return Ok(response.json(ApiResponse::success("Successfully logout from the system", true)));
     }
 
-    // For logout, we just return success since we're stateless
-    // In a real app, you might want to maintain a blacklist of tokens
-    log::info!("User logout successful");
-    Ok(HttpResponse::Ok().json(ApiResponse::success("Successfully logout from the system", true)))
+    revoke_token(&db.pool, &user).await
+        .map_err(|e| {
+            log::error!("Database error revoking token: {}", e);
+            ServiceError::DatabaseError("Failed to log out".to_string())
+        })?;
+
+    log::info!("User {} logged out", user.id);
+    Ok(response.json(ApiResponse::success("Successfully logout from the system", true)))
 }
 
-/// Get current user information
+/// Log out from all devices: every token issued to the user before the
+/// current second stops working, and so does the one used for this request.
+/// API keys are not affected; revoke them separately.
 #[utoipa::path(
-    get,
-    path = "/api/auth/me",
+    post,
+    path = "/api/auth/logout-all",
+    operation_id = "logoutAll",
     tag = "auth",
     security(
         ("bearer_auth" = [])
     ),
     responses(
-        (status = 200, description = "User information retrieved", body = ApiResponse<UserResponse>),
+        (status = 200, description = "All sessions logged out", body = ApiResponse<bool>),
         (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
     )
 )]
-pub async fn get_me(
+pub async fn logout_all(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/logout-all - user {}", user.id);
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Same cutoff as a password change: tokens issued within the current
+    // second stay valid, so a login straight after works. The token of this
+    // request may be one of them and is revoked by id.
+    sqlx::query("UPDATE users SET tokens_valid_after = date_trunc('second', NOW()), updated_at = NOW() WHERE id = $1")
+        .bind(user.id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error revoking tokens: {}", e);
+            ServiceError::DatabaseError("Failed to log out".to_string())
+        })?;
+    revoke_token(&mut *tx, &user).await
+        .map_err(|e| {
+            log::error!("Database error revoking token: {}", e);
+            ServiceError::DatabaseError("Failed to log out".to_string())
+        })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let mut response = HttpResponse::Ok();
+    clear_token_cookie(&config, &mut response);
+
+    log::info!("User {} logged out from all devices", user.id);
+    Ok(response.json(ApiResponse::success("Successfully logged out from all devices", true)))
+}
+
+/// Replace the caller's token with one carrying their current role and
+/// teams. Answer TOKEN_STALE errors with this; the new token expires when
+/// the old one would have.
+#[utoipa::path(
+    post,
+    path = "/api/auth/refresh",
+    operation_id = "refreshToken",
+    tag = "auth",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Token refreshed", body = ApiResponse<LoginResponseData>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn refresh_token(
     req: HttpRequest,
     db: web::Data<Database>,
     config: web::Data<AppConfig>,
+    keys: web::Data<JwtKeys>,
 ) -> Result<HttpResponse, ServiceError> {
-    log::info!("GET /api/auth/me");
+    log::info!("POST /api/auth/refresh");
+    let user = AuthenticatedUser::allowing_stale(&req).await?;
 
-    // Extract token from Authorization header
-    let auth_header = req.headers().get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "));
+    let user_row = sqlx::query("SELECT id, username, name, created_at, updated_at FROM users WHERE id = $1")
+        .bind(user.id)
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching user: {}", e);
+            ServiceError::DatabaseError("Failed to query user".to_string())
+        })?;
 
-    let token = auth_header.ok_or_else(|| {
-        log::warn!("Get me attempt without valid authentication");
-        ServiceError::Unauthorized("Authentication required".to_string())
-    })?;
+    let user_response = UserResponse {
+        id: user_row.get("id"),
+        username: user_row.get("username"),
+        name: user_row.get("name"),
+        created_at: user_row.get("created_at"),
+        updated_at: user_row.get("updated_at"),
+    };
+    let ttl = Duration::seconds(user.claims.exp as i64 - Utc::now().timestamp()).max(Duration::seconds(1));
+    let token = issue_token(&db, &keys, user.id, &user_response.username, user_response.name.clone(), ttl).await?;
+
+    let mut response = HttpResponse::Ok();
+    let response_data = LoginResponseData {
+        token: deliver_token(&config, &mut response, token, ttl),
+        user: user_response,
+    };
+
+    log::info!("Token refreshed for user {}", user.id);
+    Ok(response.json(ApiResponse::success("Token refreshed successfully", response_data)))
+}
 
-    // Validate the token
-    let claims = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(config.jwt_secret.as_ref()),
-        &Validation::default(),
+/// Get current user information
+#[utoipa::path(
+    get,
+    path = "/api/auth/me",
+    operation_id = "getMe",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "User information retrieved", body = ApiResponse<UserResponse>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
     )
-    .map_err(|e| {
-        log::warn!("JWT validation error: {}", e);
-        ServiceError::Unauthorized("Invalid token".to_string())
-    })?;
+)]
+pub async fn get_me(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/auth/me - {}", user.claims.username);
 
     // Query user from database
-    let user_id: i32 = claims.claims.sub.parse()
-        .map_err(|_| ServiceError::Unauthorized("Invalid user ID in token".to_string()))?;
-    
+    let user_id = user.id;
+
     let user_row = sqlx::query(
         "SELECT id, username, name, created_at, updated_at FROM users WHERE id = $1"
     )
@@ -229,11 +445,1171 @@ pub async fn get_me(
     Ok(HttpResponse::Ok().json(ApiResponse::success("Successfully retrieved user data", user_response)))
 }
 
+/// Change the current user's display name and/or username. Tokens embed
+/// both, so a fresh token is issued with the updated profile.
+#[utoipa::path(
+    put,
+    path = "/api/auth/me",
+    operation_id = "updateMe",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = UpdateProfileRequest,
+    responses(
+        (status = 200, description = "Profile updated", body = ApiResponse<LoginResponseData>),
+        (status = 400, description = "Invalid name or username", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Username is taken", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn update_me(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<JwtKeys>,
+    profile_req: web::Json<UpdateProfileRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/auth/me - {}", user.claims.username);
+
+    let mut profile_req = profile_req.into_inner();
+    profile_req.normalize()?;
+
+    if let Some(username) = &profile_req.username {
+        // Usernames differing only in case would be confusing to log in with
+        let taken = sqlx::query("SELECT 1 FROM users WHERE LOWER(username) = LOWER($1) AND id <> $2")
+            .bind(username)
+            .bind(user.id)
+            .fetch_optional(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error checking username: {}", e);
+                ServiceError::DatabaseError("Failed to update profile".to_string())
+            })?
+            .is_some();
+        if taken {
+            return Err(ServiceError::Conflict("Username is already taken".to_string()).with_code("USERNAME_TAKEN"));
+        }
+    }
+
+    let user_row = sqlx::query(
+        "UPDATE users SET name = COALESCE($2, name), username = COALESCE($3, username), updated_at = NOW()
+         WHERE id = $1
+         RETURNING id, username, name, created_at, updated_at"
+    )
+    .bind(user.id)
+    .bind(&profile_req.name)
+    .bind(&profile_req.username)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| match e {
+        // Another user took the name between the check and the update
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            ServiceError::Conflict("Username is already taken".to_string()).with_code("USERNAME_TAKEN")
+        }
+        _ => {
+            log::error!("Database error updating profile: {}", e);
+            ServiceError::DatabaseError("Failed to update profile".to_string())
+        }
+    })?
+    .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+    let user_response = UserResponse {
+        id: user_row.get("id"),
+        username: user_row.get("username"),
+        name: user_row.get("name"),
+        created_at: user_row.get("created_at"),
+        updated_at: user_row.get("updated_at"),
+    };
+    let ttl = replacement_ttl(&config, &user);
+    let token = issue_token(&db, &keys, user.id, &user_response.username, user_response.name.clone(), ttl).await?;
+
+    let mut response = HttpResponse::Ok();
+    let response_data = LoginResponseData {
+        token: deliver_token(&config, &mut response, token, ttl),
+        user: user_response,
+    };
+
+    log::info!("Profile updated for user {}", user.id);
+    Ok(response.json(ApiResponse::success("Profile updated successfully", response_data)))
+}
+
+/// Get the current user's availability
+#[utoipa::path(
+    get,
+    path = "/api/auth/me/availability",
+    operation_id = "getMyAvailability",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Availability retrieved", body = ApiResponse<Availability>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_my_availability(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/auth/me/availability - user {}", user.id);
+
+    let availability = availability::load(&db, user.id).await?
+        .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Availability retrieved successfully", availability)))
+}
+
+/// Set the current user's availability, e.g. out of office for a date range
+/// with a delegate suggested to anyone assigning them work meanwhile
+#[utoipa::path(
+    put,
+    path = "/api/auth/me/availability",
+    operation_id = "setMyAvailability",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = SetAvailabilityRequest,
+    responses(
+        (status = 200, description = "Availability updated", body = ApiResponse<Availability>),
+        (status = 400, description = "Invalid status, range or delegate", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn set_my_availability(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    availability_req: web::Json<SetAvailabilityRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/auth/me/availability - user {}", user.id);
+
+    let availability = availability::set(&db, user.id, &availability_req).await?;
+
+    log::info!("User {} is now {}", user.id, availability.status);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Availability updated successfully", availability)))
+}
+
+/// Get the language the current user's emails are sent in
+#[utoipa::path(
+    get,
+    path = "/api/auth/me/locale",
+    operation_id = "getMyLocale",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Locale retrieved", body = ApiResponse<LocaleSetting>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_my_locale(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/auth/me/locale - user {}", user.id);
+
+    let locale: String = sqlx::query_scalar("SELECT locale FROM users WHERE id = $1")
+        .bind(user.id)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading locale: {}", e);
+            ServiceError::DatabaseError("Failed to load locale".to_string())
+        })?
+        .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Locale retrieved successfully", LocaleSetting { locale })))
+}
+
+/// Set the language the current user's emails are sent in. Unsupported
+/// languages are rejected rather than silently sent in English.
+#[utoipa::path(
+    put,
+    path = "/api/auth/me/locale",
+    operation_id = "setMyLocale",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = LocaleSetting,
+    responses(
+        (status = 200, description = "Locale updated", body = ApiResponse<LocaleSetting>),
+        (status = 400, description = "Unsupported locale", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn set_my_locale(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    locale_req: web::Json<LocaleSetting>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/auth/me/locale - user {}", user.id);
+
+    let locale = locale::parse(&locale_req.locale)?;
+    sqlx::query("UPDATE users SET locale = $2, updated_at = NOW() WHERE id = $1")
+        .bind(user.id)
+        .bind(locale)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error updating locale: {}", e);
+            ServiceError::DatabaseError("Failed to update locale".to_string())
+        })?;
+
+    log::info!("User {} locale set to {}", user.id, locale);
+    let setting = LocaleSetting { locale: locale.to_string() };
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Locale updated successfully", setting)))
+}
+
+/// Get the current user's email address and whether it is verified
+#[utoipa::path(
+    get,
+    path = "/api/auth/me/email",
+    operation_id = "getMyEmail",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Email retrieved", body = ApiResponse<EmailSetting>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_my_email(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/auth/me/email - user {}", user.id);
+
+    let row = sqlx::query("SELECT email, email_verified_at IS NOT NULL AS verified FROM users WHERE id = $1")
+        .bind(user.id)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading email: {}", e);
+            ServiceError::DatabaseError("Failed to load email".to_string())
+        })?
+        .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+    let setting = EmailSetting { email: row.get("email"), verified: row.get("verified") };
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Email retrieved successfully", setting)))
+}
+
+/// Set the current user's email address and send a verification link to
+/// it. Until the link is opened the account can only read the board, and
+/// its tokens must be refreshed (`POST /api/auth/refresh`) to pick up the
+/// change. Setting the current unverified address again resends the link.
+#[utoipa::path(
+    put,
+    path = "/api/auth/me/email",
+    operation_id = "setMyEmail",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = UpdateEmailRequest,
+    responses(
+        (status = 200, description = "Email updated; verification link sent unless already verified", body = ApiResponse<EmailSetting>),
+        (status = 400, description = "Invalid email", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Email belongs to another account", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn set_my_email(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
+    templates: web::Data<EmailTemplates>,
+    email_req: web::Json<UpdateEmailRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/auth/me/email - user {}", user.id);
+
+    let email = text::email(&email_req.email)?;
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Changing only the case of a verified address keeps it verified
+    let user_row = sqlx::query(
+        "UPDATE users SET email = $2, updated_at = NOW(),
+             email_verified_at = CASE WHEN LOWER(email) = LOWER($2) THEN email_verified_at END
+         WHERE id = $1
+         RETURNING email_verified_at IS NOT NULL AS verified, locale"
+    )
+    .bind(user.id)
+    .bind(&email)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            ServiceError::Conflict("Email is already used by another account".to_string()).with_code("EMAIL_TAKEN")
+        }
+        _ => {
+            log::error!("Database error updating email: {}", e);
+            ServiceError::DatabaseError("Failed to update email".to_string())
+        }
+    })?
+    .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+    let verified: bool = user_row.get("verified");
+    let token = if verified {
+        None
+    } else {
+        Some(email_verification::issue(&mut tx, &config, user.id, &email).await?)
+    };
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    if let Some(token) = token {
+        let separator = if config.email_verify_url.contains('?') { '&' } else { '?' };
+        let recipient_locale: String = user_row.get("locale");
+        let expires_at = Utc::now() + Duration::hours(config.email_verification_ttl_hours);
+        let context = serde_json::json!({
+            "ttl_hours": config.email_verification_ttl_hours,
+            "expires_at": locale::format_datetime(expires_at, &recipient_locale),
+            "link": format!("{}{}token={}", config.email_verify_url, separator, token),
+        });
+        let to = email.clone();
+        let user_id = user.id;
+
+        let db = db.into_inner();
+        let templates = templates.into_inner();
+        let mailer = mailer.into_inner();
+        tokio::spawn(async move {
+            let result = match templates.render(&db, email_templates::EMAIL_VERIFICATION, &recipient_locale, &context).await {
+                Ok(rendered) => mailer.send(&Email { to, subject: rendered.subject, body: rendered.body }).await,
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = result {
+                log::error!("Failed to send verification email for user {}: {}", user_id, e);
+            }
+        });
+        log::info!("Verification email requested for user {}", user.id);
+    }
+
+    let message = if verified { "Email is already verified" } else { "Verification email sent" };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(message, EmailSetting { email: Some(email), verified })))
+}
+
+/// Verify an email address with the token from the emailed link. Like login
+/// links, the link should open a frontend page that posts the token here.
+/// Tokens issued before verification stay restricted until refreshed.
+#[utoipa::path(
+    post,
+    path = "/api/auth/verify-email",
+    operation_id = "verifyEmail",
+    tag = "auth",
+    request_body = VerifyEmailRequest,
+    responses(
+        (status = 200, description = "Email verified", body = ApiResponse<bool>),
+        (status = 400, description = "Invalid, expired or used verification link", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn verify_email(
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    verify_req: web::Json<VerifyEmailRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/verify-email");
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let user_id = email_verification::consume(&mut tx, &config, verify_req.token.trim()).await?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    log::info!("Email verified for user {}", user_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Email verified successfully", true)))
+}
+
+/// Get the current user's request counts and error rates, per API key and
+/// for login tokens
+#[utoipa::path(
+    get,
+    path = "/api/auth/me/usage",
+    operation_id = "getMyUsage",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(UsageQuery),
+    responses(
+        (status = 200, description = "Usage retrieved", body = ApiResponse<Vec<UsageEntry>>),
+        (status = 400, description = "Invalid number of days", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_my_usage(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<UsageQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/auth/me/usage - user {}", user.id);
+
+    let entries = usage::summary(&db, query.days()?, Some(user.id)).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Usage retrieved successfully", entries)))
+}
+
+/// Change the current user's password
+#[utoipa::path(
+    put,
+    path = "/api/auth/password",
+    operation_id = "changePassword",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed", body = ApiResponse<ChangePasswordResponse>),
+        (status = 400, description = "New password does not meet the policy", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized or wrong current password", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn change_password(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<JwtKeys>,
+    password_req: web::Json<ChangePasswordRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/auth/password");
+
+    let user_row = sqlx::query("SELECT username, name, password FROM users WHERE id = $1")
+        .bind(user.id)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error during password change: {}", e);
+            ServiceError::DatabaseError("Failed to query user".to_string())
+        })?
+        .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+    let stored_hash: String = user_row.get("password");
+    let password_valid = verify(&password_req.current_password, &stored_hash)
+        .map_err(|e| {
+            log::error!("Password verification error: {}", e);
+            ServiceError::AuthenticationError("Password verification failed".to_string())
+        })?;
+
+    if !password_valid {
+        log::warn!("Password change failed: wrong current password for user {}", user.id);
+        return Err(ServiceError::Unauthorized("Current password is incorrect".to_string())
+            .with_code("WRONG_PASSWORD"));
+    }
+
+    let username: String = user_row.get("username");
+    config.password_policy.validate(&password_req.new_password, &username)?;
+    if password_req.new_password == password_req.current_password {
+        return Err(ServiceError::ValidationError("New password must differ from the current one".to_string())
+            .with_code("WEAK_PASSWORD"));
+    }
+
+    let new_hash = hash(&password_req.new_password, DEFAULT_COST)?;
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Tokens carry whole-second iat values, so the cut-off is truncated to
+    // the second to keep the replacement token below valid; the token of
+    // this request may be from the same second and is revoked by id
+    sqlx::query(
+        "UPDATE users SET password = $2, updated_at = NOW(),
+            tokens_valid_after = CASE WHEN $3 THEN date_trunc('second', NOW()) ELSE tokens_valid_after END
+         WHERE id = $1"
+    )
+    .bind(user.id)
+    .bind(&new_hash)
+    .bind(password_req.invalidate_tokens)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error updating password: {}", e);
+        ServiceError::DatabaseError("Failed to update password".to_string())
+    })?;
+
+    if password_req.invalidate_tokens {
+        revoke_token(&mut *tx, &user).await
+            .map_err(|e| {
+                log::error!("Database error revoking token: {}", e);
+                ServiceError::DatabaseError("Failed to update password".to_string())
+            })?;
+    }
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let mut response = HttpResponse::Ok();
+    let token = if password_req.invalidate_tokens {
+        let ttl = replacement_ttl(&config, &user);
+        let token = issue_token(&db, &keys, user.id, &username, user_row.get("name"), ttl).await?;
+        deliver_token(&config, &mut response, token, ttl)
+    } else {
+        None
+    };
+
+    log::info!("Password changed for user {} (tokens invalidated: {})", user.id, password_req.invalidate_tokens);
+    Ok(response.json(ApiResponse::success("Password changed successfully", ChangePasswordResponse { token })))
+}
+
+/// Request a password reset link by email
+#[utoipa::path(
+    post,
+    path = "/api/auth/forgot-password",
+    operation_id = "forgotPassword",
+    tag = "auth",
+    request_body = ForgotPasswordRequest,
+    responses(
+        (status = 200, description = "Reset link sent if the email belongs to an account", body = ApiResponse<bool>)
+    )
+)]
+pub async fn forgot_password(
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
+    templates: web::Data<EmailTemplates>,
+    forgot_req: web::Json<ForgotPasswordRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/forgot-password");
+
+    let user_row = sqlx::query("SELECT id, email, locale FROM users WHERE LOWER(email) = LOWER($1) AND is_active")
+        .bind(forgot_req.email.trim())
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error during forgot password: {}", e);
+            ServiceError::DatabaseError("Failed to query user".to_string())
+        })?;
+
+    // The response is the same either way so the endpoint cannot be used to
+    // find out which emails have accounts
+    if let Some(user_row) = user_row {
+        let user_id: UserId = user_row.get("id");
+        let token = password_reset::issue(&db, &config, user_id).await?;
+
+        let separator = if config.password_reset_url.contains('?') { '&' } else { '?' };
+        let recipient_locale: String = user_row.get("locale");
+        let expires_at = Utc::now() + Duration::minutes(config.password_reset_ttl_minutes);
+        let context = serde_json::json!({
+            "ttl_minutes": config.password_reset_ttl_minutes,
+            "expires_at": locale::format_datetime(expires_at, &recipient_locale),
+            "link": format!("{}{}token={}", config.password_reset_url, separator, token),
+        });
+        let to: String = user_row.get("email");
+
+        // Rendered and sent in the background so response time does not
+        // reveal a match
+        let db = db.into_inner();
+        let templates = templates.into_inner();
+        let mailer = mailer.into_inner();
+        tokio::spawn(async move {
+            let result = match templates.render(&db, email_templates::PASSWORD_RESET, &recipient_locale, &context).await {
+                Ok(rendered) => mailer.send(&Email { to, subject: rendered.subject, body: rendered.body }).await,
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = result {
+                log::error!("Failed to send password reset email for user {}: {}", user_id, e);
+            }
+        });
+        log::info!("Password reset requested for user {}", user_id);
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "If an account exists for that email, a reset link has been sent",
+        true,
+    )))
+}
+
+/// Set a new password using an emailed reset token
+#[utoipa::path(
+    post,
+    path = "/api/auth/reset-password",
+    operation_id = "resetPassword",
+    tag = "auth",
+    request_body = ResetPasswordRequest,
+    responses(
+        (status = 200, description = "Password reset", body = ApiResponse<bool>),
+        (status = 400, description = "Invalid or expired token, or weak password", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn reset_password(
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    reset_req: web::Json<ResetPasswordRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/reset-password");
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // A rejected password rolls the transaction back, leaving the token usable
+    let user_id = password_reset::consume(&mut tx, &config, reset_req.token.trim()).await?;
+
+    let username: String = sqlx::query_scalar("SELECT username FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error during password reset: {}", e);
+            ServiceError::DatabaseError("Failed to query user".to_string())
+        })?;
+
+    config.password_policy.validate(&reset_req.new_password, &username)?;
+    let new_hash = hash(&reset_req.new_password, DEFAULT_COST)?;
+
+    // Whoever had the old password may still hold a token, so sign out everywhere
+    sqlx::query(
+        "UPDATE users SET password = $2, updated_at = NOW(), tokens_valid_after = date_trunc('second', NOW())
+         WHERE id = $1"
+    )
+    .bind(user_id)
+    .bind(&new_hash)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error updating password: {}", e);
+        ServiceError::DatabaseError("Failed to update password".to_string())
+    })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    log::info!("Password reset for user {}", user_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Password reset successfully", true)))
+}
+
+/// Request a single-use login link by email, as an alternative to the
+/// password. Only available when MAGIC_LINK_ENABLED is set.
+#[utoipa::path(
+    post,
+    path = "/api/auth/magic-link",
+    operation_id = "requestMagicLink",
+    tag = "auth",
+    request_body = MagicLinkRequest,
+    responses(
+        (status = 200, description = "Login link sent if the email belongs to an account", body = ApiResponse<bool>),
+        (status = 404, description = "Login links are disabled", body = crate::utils::errors::ServiceError),
+        (status = 429, description = "Too many requests, retry after the Retry-After delay", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn request_magic_link(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
+    templates: web::Data<EmailTemplates>,
+    limiter: web::Data<LoginLimiter>,
+    magic_req: web::Json<MagicLinkRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/magic-link");
+
+    let Some(ref magic_link_url) = config.magic_link_url else {
+        return Err(ServiceError::NotFound("Login links are disabled".to_string()).with_code("MAGIC_LINK_DISABLED"));
+    };
+    // Each link request counts as a login attempt, so the endpoint cannot be
+    // used to flood a mailbox
+    let ip = client_ip(&req, &config.trusted_proxies).map(|ip| ip.to_string());
+    limiter.check(ip.as_deref(), &magic_req.email)?;
+
+    let user_row = sqlx::query("SELECT id, email, locale FROM users WHERE LOWER(email) = LOWER($1) AND is_active")
+        .bind(magic_req.email.trim())
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error during login link request: {}", e);
+            ServiceError::DatabaseError("Failed to query user".to_string())
+        })?;
+
+    // The response is the same either way, as for password resets
+    if let Some(user_row) = user_row {
+        let user_id: UserId = user_row.get("id");
+        let token = magic_links::issue(&db, &config, user_id).await?;
+
+        let separator = if magic_link_url.contains('?') { '&' } else { '?' };
+        let recipient_locale: String = user_row.get("locale");
+        let expires_at = Utc::now() + Duration::minutes(config.magic_link_ttl_minutes);
+        let context = serde_json::json!({
+            "ttl_minutes": config.magic_link_ttl_minutes,
+            "expires_at": locale::format_datetime(expires_at, &recipient_locale),
+            "link": format!("{}{}token={}", magic_link_url, separator, token),
+        });
+        let to: String = user_row.get("email");
+
+        let db = db.into_inner();
+        let templates = templates.into_inner();
+        let mailer = mailer.into_inner();
+        tokio::spawn(async move {
+            let result = match templates.render(&db, email_templates::MAGIC_LINK, &recipient_locale, &context).await {
+                Ok(rendered) => mailer.send(&Email { to, subject: rendered.subject, body: rendered.body }).await,
+                Err(e) => Err(e.to_string()),
+            };
+            if let Err(e) = result {
+                log::error!("Failed to send login link for user {}: {}", user_id, e);
+            }
+        });
+        log::info!("Login link requested for user {}", user_id);
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "If an account exists for that email, a login link has been sent",
+        true,
+    )))
+}
+
+/// Exchange the token from an emailed login link for a session. The link
+/// should open a frontend page that posts the token here, so mail scanners
+/// that prefetch links cannot use it up.
+#[utoipa::path(
+    post,
+    path = "/api/auth/magic-link/verify",
+    operation_id = "verifyMagicLink",
+    tag = "auth",
+    request_body = MagicLinkLoginRequest,
+    responses(
+        (status = 200, description = "Login successful", body = ApiResponse<LoginResponseData>),
+        (status = 401, description = "Invalid, expired or used login link", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Login links are disabled", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn verify_magic_link(
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<JwtKeys>,
+    login_req: web::Json<MagicLinkLoginRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/magic-link/verify");
+
+    if config.magic_link_url.is_none() {
+        return Err(ServiceError::NotFound("Login links are disabled".to_string()).with_code("MAGIC_LINK_DISABLED"));
+    }
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let user_id = magic_links::consume(&mut tx, &config, login_req.token.trim()).await?;
+
+    // Users deactivated since the link was sent cannot log in with it
+    let user_row = sqlx::query("SELECT id, username, name, created_at, updated_at FROM users WHERE id = $1 AND is_active")
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error during login link login: {}", e);
+            ServiceError::DatabaseError("Failed to query user".to_string())
+        })?
+        .ok_or_else(|| ServiceError::Unauthorized("Login link is invalid or has expired".to_string())
+            .with_code("INVALID_LOGIN_LINK"))?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let username: String = user_row.get("username");
+    let ttl = Duration::hours(config.token_ttl_hours);
+    let token = issue_token(&db, &keys, user_id, &username, user_row.get("name"), ttl).await?;
+
+    let mut response = HttpResponse::Ok();
+    let response_data = LoginResponseData {
+        token: deliver_token(&config, &mut response, token, ttl),
+        user: UserResponse {
+            id: user_id,
+            username,
+            name: user_row.get("name"),
+            created_at: user_row.get("created_at"),
+            updated_at: user_row.get("updated_at"),
+        },
+    };
+
+    log::info!("Login link used by user {}", user_id);
+    Ok(response.json(ApiResponse::success("Login successful", response_data)))
+}
+
+/// Create an account from an emailed invitation and log in. The account
+/// gets the invitation's email and joins its teams.
+#[utoipa::path(
+    post,
+    path = "/api/auth/accept-invite",
+    operation_id = "acceptInvite",
+    tag = "auth",
+    request_body = AcceptInviteRequest,
+    responses(
+        (status = 201, description = "Account created", body = ApiResponse<LoginResponseData>),
+        (status = 400, description = "Invalid or expired invitation, invalid account details or failed captcha", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Username or email already taken", body = crate::utils::errors::ServiceError),
+        (status = 503, description = "Captcha verification unavailable", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn accept_invite(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<JwtKeys>,
+    invite_req: web::Json<AcceptInviteRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/accept-invite");
+
+    let mut invite_req = invite_req.into_inner();
+    invite_req.normalize()?;
+    config.password_policy.validate(&invite_req.password, &invite_req.username)?;
+    let ip = client_ip(&req, &config.trusted_proxies).map(|ip| ip.to_string());
+    captcha::verify(&config, invite_req.captcha_token.as_deref(), ip.as_deref()).await?;
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Any error below rolls the transaction back, leaving the invitation usable
+    let (email, team_ids, locale) = invitations::consume(&mut tx, &config, invite_req.token.trim()).await?;
+
+    // Usernames differing only in case would be confusing to log in with
+    let taken = sqlx::query("SELECT 1 FROM users WHERE LOWER(username) = LOWER($1)")
+        .bind(&invite_req.username)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking username: {}", e);
+            ServiceError::DatabaseError("Failed to create account".to_string())
+        })?
+        .is_some();
+    if taken {
+        return Err(ServiceError::Conflict("Username is already taken".to_string()).with_code("USERNAME_TAKEN"));
+    }
+
+    // The invitation was delivered to the email, so it needs no further
+    // verification
+    let password_hash = hash(&invite_req.password, DEFAULT_COST)?;
+    let user_row = sqlx::query(
+        "INSERT INTO users (username, password, name, email, email_verified_at, locale) VALUES ($1, $2, $3, $4, NOW(), $5)
+         RETURNING id, username, name, created_at, updated_at"
+    )
+    .bind(&invite_req.username)
+    .bind(&password_hash)
+    .bind(&invite_req.name)
+    .bind(&email)
+    .bind(&locale)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            ServiceError::Conflict("Username or email is already taken".to_string()).with_code("USERNAME_TAKEN")
+        }
+        _ => {
+            log::error!("Database error creating invited user: {}", e);
+            ServiceError::DatabaseError("Failed to create account".to_string())
+        }
+    })?;
+    let user_id: UserId = user_row.get("id");
+
+    // Teams deleted since the invitation was sent are skipped
+    sqlx::query(
+        "INSERT INTO team_members (user_id, team_id)
+         SELECT $1, id FROM teams WHERE id = ANY($2)"
+    )
+    .bind(user_id)
+    .bind(&team_ids)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error adding team memberships: {}", e);
+        ServiceError::DatabaseError("Failed to create account".to_string())
+    })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let user_response = UserResponse {
+        id: user_id,
+        username: user_row.get("username"),
+        name: user_row.get("name"),
+        created_at: user_row.get("created_at"),
+        updated_at: user_row.get("updated_at"),
+    };
+    let ttl = Duration::hours(config.token_ttl_hours);
+    let token = issue_token(&db, &keys, user_id, &user_response.username, user_response.name.clone(), ttl).await?;
+
+    let mut response = HttpResponse::Created();
+    let response_data = LoginResponseData {
+        token: deliver_token(&config, &mut response, token, ttl),
+        user: user_response,
+    };
+
+    log::info!("Invitation accepted: user {} created in {} teams", user_id, team_ids.len());
+    Ok(response.json(ApiResponse::success("Account created successfully", response_data)))
+}
+
+/// Create an API key for the current user
+#[utoipa::path(
+    post,
+    path = "/api/auth/api-keys",
+    operation_id = "createApiKey",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 201, description = "API key created", body = ApiResponse<CreatedApiKey>),
+        (status = 400, description = "Invalid name, scope or expiry", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Request was made with an API key", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn create_api_key(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    key_req: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/auth/api-keys - user {}", user.id);
+    user.requires(Permission::ApiKeyManage)?;
+
+    let key_req = key_req.into_inner();
+    let name = key_req.name.trim();
+    if name.is_empty() || name.len() > 100 {
+        return Err(ServiceError::ValidationError("Name must be between 1 and 100 characters".to_string()));
+    }
+    if key_req.scopes.is_empty() {
+        return Err(ServiceError::ValidationError("At least one scope is required".to_string()).with_code("INVALID_SCOPE"));
+    }
+    if let Some(scope) = key_req.scopes.iter().find(|scope| !SCOPES.contains(&scope.as_str())) {
+        return Err(ServiceError::ValidationError(format!("Unknown scope '{}'; expected one of: {}", scope, SCOPES.join(", ")))
+            .with_code("INVALID_SCOPE"));
+    }
+    if key_req.expires_in_days.is_some_and(|days| !(1..=3650).contains(&days)) {
+        return Err(ServiceError::ValidationError("expires_in_days must be between 1 and 3650".to_string()));
+    }
+
+    let (key, api_key) = api_keys::mint(&db, &config, user.id, name, &key_req.scopes, key_req.expires_in_days).await?;
+
+    log::info!("API key {} created for user {}", api_key.id, user.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success("API key created successfully", CreatedApiKey { key, api_key })))
+}
+
+/// Revoke one of the current user's API keys
+#[utoipa::path(
+    delete,
+    path = "/api/auth/api-keys/{id}",
+    operation_id = "revokeApiKey",
+    tag = "account",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "API key revoked", body = ApiResponse<bool>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Request was made with an API key", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "API key not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn revoke_api_key(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let key_id = path.into_inner();
+    log::info!("DELETE /api/auth/api-keys/{} - user {}", key_id, user.id);
+    user.requires(Permission::ApiKeyManage)?;
+
+    if !api_keys::revoke(&db, user.id, key_id).await? {
+        return Err(ServiceError::NotFound("API key not found".to_string()));
+    }
+
+    log::info!("API key {} revoked by user {}", key_id, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("API key revoked successfully", true)))
+}
+
+/// Start logging in with an OAuth provider by redirecting to its consent page
+#[utoipa::path(
+    get,
+    path = "/api/auth/oauth/{provider}/authorize",
+    operation_id = "oauthAuthorize",
+    tag = "auth",
+    params(
+        ("provider" = String, Path, description = "`google` or `github`")
+    ),
+    responses(
+        (status = 302, description = "Redirect to the provider, setting the nonce cookie the callback checks"),
+        (status = 404, description = "Unknown or disabled provider", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn oauth_authorize(
+    config: web::Data<AppConfig>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/auth/oauth/{}/authorize", path);
+
+    let provider = oauth::Provider::parse(&path)?;
+    let (url, nonce_cookie) = oauth::authorize_url(&config, provider)?;
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .cookie(nonce_cookie)
+        .finish())
+}
+
+/// Finish an OAuth login: link or create the local user and issue the same
+/// token as the password login
+#[utoipa::path(
+    get,
+    path = "/api/auth/oauth/{provider}/callback",
+    operation_id = "oauthCallback",
+    tag = "auth",
+    params(
+        ("provider" = String, Path, description = "`google` or `github`"),
+        OAuthCallbackQuery
+    ),
+    responses(
+        (status = 200, description = "Login successful", body = ApiResponse<LoginResponseData>),
+        (status = 400, description = "Missing code, or a state that is invalid or was not issued to this browser", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Provider rejected the login or account is deactivated", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Unknown or disabled provider", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Email belongs to an account that has not verified it", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn oauth_callback(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    keys: web::Data<JwtKeys>,
+    path: web::Path<String>,
+    query: web::Query<OAuthCallbackQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/auth/oauth/{}/callback", path);
+
+    let provider = oauth::Provider::parse(&path)?;
+    if let Some(error) = &query.error {
+        log::warn!("OAuth login with {} was not completed: {}", provider.name(), error);
+        return Err(ServiceError::Unauthorized(format!("Login with {} was cancelled", provider.name())).with_code("OAUTH_FAILED"));
+    }
+    let (code, state) = match (&query.code, &query.state) {
+        (Some(code), Some(state)) => (code, state),
+        _ => return Err(ServiceError::ValidationError("code and state are required".to_string())),
+    };
+
+    let nonce = req.cookie(oauth::NONCE_COOKIE).map(|cookie| cookie.value().to_string());
+    let profile = oauth::exchange(&config, provider, code, state, nonce.as_deref()).await?;
+    let linked = oauth::link_or_create_user(&db, provider, &profile).await?;
+
+    let user_row = sqlx::query("SELECT created_at, updated_at FROM users WHERE id = $1")
+        .bind(linked.id)
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading OAuth user: {}", e);
+            ServiceError::DatabaseError("Failed to query user".to_string())
+        })?;
+
+    let ttl = Duration::hours(config.token_ttl_hours);
+    let token = issue_token(&db, &keys, linked.id, &linked.username, linked.name.clone(), ttl).await?;
+    let mut response = HttpResponse::Ok();
+    response.cookie(oauth::clear_nonce_cookie());
+    let response_data = LoginResponseData {
+        token: deliver_token(&config, &mut response, token, ttl),
+        user: UserResponse {
+            id: linked.id,
+            username: linked.username,
+            name: linked.name,
+            created_at: user_row.get("created_at"),
+            updated_at: user_row.get("updated_at"),
+        },
+    };
+
+    log::info!("Login with {} successful for user {}", provider.name(), linked.id);
+    Ok(response.json(ApiResponse::success("Login successful", response_data)))
+}
+
+/// Public keys tokens are signed with, as a JSON Web Key Set, so other
+/// services can verify tokens without sharing a secret. Keys that were
+/// rotated out stay listed while their tokens may still be in use; the set
+/// is empty when tokens are signed with HS256.
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    operation_id = "getJwks",
+    tag = "auth",
+    responses(
+        (status = 200, description = "JSON Web Key Set", body = Object)
+    )
+)]
+pub async fn jwks(keys: web::Data<JwtKeys>) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /.well-known/jwks.json");
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(keys.jwks()))
+}
+
 pub fn auth_config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/.well-known/jwks.json", web::get().to(jwks));
     cfg.service(
         web::scope("/api/auth")
             .route("/login", web::post().to(login))
             .route("/logout", web::post().to(logout))
+            .route("/logout-all", web::post().to(logout_all))
+            .route("/refresh", web::post().to(refresh_token))
             .route("/me", web::get().to(get_me))
+            .route("/me", web::put().to(update_me))
+            .route("/me/availability", web::get().to(get_my_availability))
+            .route("/me/availability", web::put().to(set_my_availability))
+            .route("/me/locale", web::get().to(get_my_locale))
+            .route("/me/locale", web::put().to(set_my_locale))
+            .route("/me/email", web::get().to(get_my_email))
+            .route("/me/email", web::put().to(set_my_email))
+            .route("/me/usage", web::get().to(get_my_usage))
+            .route("/password", web::put().to(change_password))
+            .route("/forgot-password", web::post().to(forgot_password))
+            .route("/reset-password", web::post().to(reset_password))
+            .route("/magic-link", web::post().to(request_magic_link))
+            .route("/magic-link/verify", web::post().to(verify_magic_link))
+            .route("/accept-invite", web::post().to(accept_invite))
+            .route("/verify-email", web::post().to(verify_email))
+            .route("/api-keys", web::post().to(create_api_key))
+            .route("/api-keys/{id}", web::delete().to(revoke_api_key))
+            .route("/oauth/{provider}/authorize", web::get().to(oauth_authorize))
+            .route("/oauth/{provider}/callback", web::get().to(oauth_callback))
     );
 }
@@ -0,0 +1,293 @@
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{self, CloseCode, CloseReason, Codec, Frame, Message};
+use actix_web::http::header::SEC_WEBSOCKET_ACCEPT;
+use actix_web::web::{Bytes, BytesMut};
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use chrono::Utc;
+use futures_util::{stream, FutureExt, StreamExt};
+use tokio::sync::mpsc;
+
+use crate::Database;
+use crate::middleware::{AuthenticatedUser, Permission};
+use crate::models::ids::UserId;
+use crate::services::realtime::Broker;
+use crate::utils::errors::ServiceError;
+
+// Largest frame read from a client; clients only send pings and close frames
+const MAX_CLIENT_FRAME_BYTES: usize = 4096;
+
+// How often an open stream checks that its subscriber may still see updates
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Resolves once the subscriber's credentials lapse: when the token expires,
+// or when a periodic check finds the account deactivated, the token revoked
+// or logged out, or the API key revoked. Streams end then rather than
+// outliving the credentials they were opened with.
+async fn credentials_lapse(user: AuthenticatedUser, db: web::Data<Database>) {
+    loop {
+        let mut wait = RECHECK_INTERVAL;
+        if !user.is_api_key() {
+            let left = (user.claims.exp as i64 - Utc::now().timestamp()).max(0) as u64;
+            wait = wait.min(Duration::from_secs(left));
+        }
+        tokio::time::sleep(wait).await;
+
+        match user.recheck(&db).await {
+            Ok(()) => {}
+            // Keep streaming through a database hiccup; the next check decides
+            Err(ServiceError::DatabaseError(_)) => {}
+            Err(e) => {
+                log::info!("Ending event stream of user {}: {}", user.id, e);
+                return;
+            }
+        }
+    }
+}
+
+/// Subscribe to realtime board updates as server-sent events
+#[utoipa::path(
+    get,
+    path = "/api/events/stream",
+    operation_id = "streamEvents",
+    tag = "events",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Event stream", content_type = "text/event-stream"),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn stream_events(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    broker: web::Data<Broker>,
+) -> Result<HttpResponse, ServiceError> {
+    let user_id = user.id;
+    log::info!("GET /api/events/stream - user {}", user_id);
+    user.requires(Permission::TaskRead)?;
+
+    let events = broker.into_inner().subscribe().take_until(Box::pin(credentials_lapse(user, db)));
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events))
+}
+
+/// Subscribe to realtime board updates over a WebSocket
+///
+/// Each update is a text frame with the same JSON as an event of
+/// `/api/events/stream`. Client frames other than ping and close are
+/// ignored. A client too slow to keep up is sent a close frame with code
+/// 1013 (try again later) and should reconnect and reload the board. Once
+/// the credentials the socket was opened with expire or are revoked, it is
+/// closed with code 1008 (policy violation).
+#[utoipa::path(
+    get,
+    path = "/api/events/ws",
+    operation_id = "websocketEvents",
+    tag = "events",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 101, description = "Switched to the WebSocket protocol"),
+        (status = 400, description = "Not a WebSocket handshake", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn websocket_events(
+    user: AuthenticatedUser,
+    req: HttpRequest,
+    payload: web::Payload,
+    db: web::Data<Database>,
+    broker: web::Data<Broker>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/events/ws - user {}", user.id);
+    user.requires(Permission::TaskRead)?;
+
+    let user_id = user.id;
+    websocket(user_id, req, payload, broker, credentials_lapse(user, db))
+}
+
+// Upgrade to a WebSocket carrying the broker's updates until `lapse`
+// resolves
+fn websocket(
+    user_id: UserId,
+    req: HttpRequest,
+    mut payload: web::Payload,
+    broker: web::Data<Broker>,
+    lapse: impl Future<Output = ()> + 'static,
+) -> Result<HttpResponse, ServiceError> {
+    ws::verify_handshake(req.head()).map_err(|e| {
+        ServiceError::ValidationError(format!("Invalid WebSocket handshake: {}", e)).with_code("WEBSOCKET_HANDSHAKE_FAILED")
+    })?;
+    let accept = req.headers().get("Sec-WebSocket-Key")
+        .map(|key| ws::hash_key(key.as_bytes()))
+        .ok_or_else(|| ServiceError::ValidationError("Missing Sec-WebSocket-Key".to_string()))?;
+
+    // Answers to the client's frames, written between the updates
+    let (replies, replies_rx) = mpsc::unbounded_channel::<Message>();
+    actix_web::rt::spawn(async move {
+        let mut codec = Codec::new().max_size(MAX_CLIENT_FRAME_BYTES);
+        let mut buffer = BytesMut::new();
+        while let Some(Ok(chunk)) = payload.next().await {
+            buffer.extend_from_slice(&chunk);
+            loop {
+                match codec.decode(&mut buffer) {
+                    Ok(Some(Frame::Ping(data))) => {
+                        let _ = replies.send(Message::Pong(data));
+                    }
+                    Ok(Some(Frame::Close(reason))) => {
+                        let _ = replies.send(Message::Close(reason));
+                        return;
+                    }
+                    Ok(Some(_)) => {}
+                    Ok(None) => break,
+                    Err(e) => {
+                        log::warn!("Closing WebSocket of user {} on a protocol error: {}", user_id, e);
+                        let _ = replies.send(Message::Close(Some(CloseReason::from(CloseCode::Protocol))));
+                        return;
+                    }
+                }
+            }
+        }
+    });
+
+    let updates = broker.into_inner().messages()
+        .map(|body| Message::Text(body.into()))
+        .chain(stream::once(async {
+            Message::Close(Some(CloseReason {
+                code: CloseCode::Again,
+                description: Some("Too slow to keep up with updates".to_string()),
+            }))
+        }));
+    let replies = stream::unfold(replies_rx, |mut replies_rx| async move {
+        replies_rx.recv().await.map(|message| (message, replies_rx))
+    });
+    let lapsed = lapse.map(|()| {
+        Message::Close(Some(CloseReason {
+            code: CloseCode::Policy,
+            description: Some("Credentials are no longer valid".to_string()),
+        }))
+    }).into_stream();
+
+    // The response ends with the first close frame, whichever side closed
+    // first, rather than waiting for the client to hang up
+    let messages = Box::pin(stream::select(stream::select(updates, replies), lapsed));
+    let frames = stream::unfold((messages, false), |(mut messages, closed)| async move {
+        if closed {
+            return None;
+        }
+        let message = messages.next().await?;
+        let closed = matches!(message, Message::Close(_));
+        let mut frame = BytesMut::new();
+        let encoded = Codec::new().encode(message, &mut frame)
+            .map(|()| frame.freeze())
+            .map_err(|e| {
+                log::error!("Failed to encode WebSocket frame: {}", e);
+                actix_web::error::ErrorInternalServerError("WebSocket encoding failed")
+            });
+        Some((encoded, (messages, closed)))
+    });
+
+    Ok(HttpResponse::SwitchingProtocols()
+        .upgrade("websocket")
+        .insert_header((SEC_WEBSOCKET_ACCEPT, Bytes::from(accept.to_vec())))
+        .streaming(frames))
+}
+
+pub fn events_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/events")
+            .route("/stream", web::get().to(stream_events))
+            .route("/ws", web::get().to(websocket_events))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::Pin;
+
+    use actix_web::body::{BoxBody, MessageBody};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use actix_web::FromRequest;
+
+    use super::*;
+
+    async fn connect(broker: &web::Data<Broker>) -> HttpResponse {
+        connect_until(broker, std::future::pending()).await
+    }
+
+    async fn connect_until(broker: &web::Data<Broker>, lapse: impl Future<Output = ()> + 'static) -> HttpResponse {
+        let (req, mut payload) = TestRequest::get()
+            .uri("/api/events/ws")
+            .insert_header(("Upgrade", "websocket"))
+            .insert_header(("Connection", "Upgrade"))
+            .insert_header(("Sec-WebSocket-Version", "13"))
+            .insert_header(("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_http_parts();
+        let payload = web::Payload::from_request(&req, &mut payload).await.expect("payload");
+        websocket(UserId(1), req, payload, broker.clone(), lapse).expect("handshake")
+    }
+
+    // Read the next frame the server sends, as the client decodes it
+    async fn next_frame(body: &mut Pin<Box<BoxBody>>) -> Frame {
+        let chunk = std::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await
+            .expect("a frame")
+            .expect("frame bytes");
+        let mut buffer = BytesMut::from(&chunk[..]);
+        Codec::new().client_mode().decode(&mut buffer).expect("valid frame").expect("whole frame")
+    }
+
+    #[actix_web::test]
+    async fn websocket_clients_receive_updates() {
+        let broker = web::Data::new(Broker::new(8));
+        let response = connect(&broker).await;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        // The accept key for the sample nonce of RFC 6455
+        assert_eq!(response.headers().get(SEC_WEBSOCKET_ACCEPT).unwrap(), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        let mut body = Box::pin(response.into_body());
+        broker.broadcast("task:1", r#"{"id":1}"#);
+        assert_eq!(next_frame(&mut body).await, Frame::Text(Bytes::from_static(br#"{"id":1}"#)));
+    }
+
+    #[actix_web::test]
+    async fn slow_websocket_clients_are_closed() {
+        let broker = web::Data::new(Broker::new(1));
+        let mut body = Box::pin(connect(&broker).await.into_body());
+
+        broker.broadcast("task:1", "first");
+        broker.broadcast("task:2", "overflow");
+        assert_eq!(broker.client_count(), 0);
+
+        assert_eq!(next_frame(&mut body).await, Frame::Text(Bytes::from_static(b"first")));
+        match next_frame(&mut body).await {
+            Frame::Close(Some(reason)) => assert_eq!(reason.code, CloseCode::Again),
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+    }
+
+    #[actix_web::test]
+    async fn websockets_close_when_credentials_lapse() {
+        let broker = web::Data::new(Broker::new(8));
+        let (lapse, lapsed) = tokio::sync::oneshot::channel::<()>();
+        let mut body = Box::pin(connect_until(&broker, async { let _ = lapsed.await; }).await.into_body());
+
+        broker.broadcast("task:1", "first");
+        assert_eq!(next_frame(&mut body).await, Frame::Text(Bytes::from_static(b"first")));
+
+        lapse.send(()).expect("socket is open");
+        match next_frame(&mut body).await {
+            Frame::Close(Some(reason)) => assert_eq!(reason.code, CloseCode::Policy),
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+        assert!(std::future::poll_fn(|cx| body.as_mut().poll_next(cx)).await.is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::middleware::{AuthenticatedUser, Permission};
+use crate::models::auth::ApiResponse;
+use crate::models::feedback::{FeedbackReceipt, FeedbackSubmission, SubmitFeedbackRequest};
+use crate::models::params::TaskPath;
+use crate::models::task::task_key;
+use crate::services::captcha;
+use crate::services::feedback::{self, FeedbackLimiter};
+use crate::utils::client_ip::client_ip;
+use crate::utils::errors::ServiceError;
+
+/// Submit feedback or a bug report without an account. It is filed as a
+/// triage task; the contact details are only visible to administrators.
+#[utoipa::path(
+    post,
+    path = "/api/public/feedback/{token}",
+    operation_id = "submitFeedback",
+    tag = "feedback",
+    params(
+        ("token" = String, Path, description = "The board's feedback form token")
+    ),
+    request_body = SubmitFeedbackRequest,
+    responses(
+        (status = 201, description = "Feedback received", body = ApiResponse<FeedbackReceipt>),
+        (status = 400, description = "Invalid feedback or failed captcha", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "No feedback form with this token", body = crate::utils::errors::ServiceError),
+        (status = 429, description = "Too much feedback from this address", body = crate::utils::errors::ServiceError),
+        (status = 503, description = "Captcha verification unavailable", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn submit_feedback(
+    req: HttpRequest,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    limiter: web::Data<FeedbackLimiter>,
+    path: web::Path<String>,
+    feedback_req: web::Json<SubmitFeedbackRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/public/feedback");
+    let intake = feedback::intake(&config, &path)?;
+
+    let ip = client_ip(&req, &config.trusted_proxies).map(|ip| ip.to_string());
+    let ip = ip.as_deref();
+    limiter.check(ip)?;
+
+    let mut feedback_req = feedback_req.into_inner();
+    feedback_req.normalize()?;
+    captcha::verify(&config, Some(&feedback_req.captcha_token), ip).await?;
+
+    let task = feedback::submit(&db, intake, feedback_req, ip).await?;
+
+    log::info!("Feedback filed as task {}", task.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success(
+        "Feedback received",
+        FeedbackReceipt { reference: task_key(task.id) },
+    )))
+}
+
+/// Get the contact details left by whoever filed a task through the
+/// feedback form
+#[utoipa::path(
+    get,
+    path = "/api/admin/feedback/{id}",
+    operation_id = "getFeedbackSubmission",
+    tag = "feedback",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        TaskPath
+    ),
+    responses(
+        (status = 200, description = "Submitter found", body = ApiResponse<FeedbackSubmission>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Task was not filed through the feedback form", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_feedback_submission(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<TaskPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let task_id = path.into_inner().id;
+    log::info!("GET /api/admin/feedback/{}", task_id);
+    user.requires(Permission::FeedbackRead)?;
+
+    let submission = feedback::get_submission(&db, task_id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Feedback submission retrieved", submission)))
+}
+
+pub fn feedback_config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/api/public/feedback/{token}", web::post().to(submit_feedback))
+        .route("/api/admin/feedback/{id}", web::get().to(get_feedback_submission));
+}
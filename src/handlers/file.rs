@@ -1,50 +1,33 @@
 use actix_multipart::Multipart;
 use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::{
+    CacheControl, CacheDirective, ETag, CONTENT_LENGTH, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, SubsecRound, Utc};
 use futures_util::TryStreamExt;
+use sha2::{Digest, Sha256};
 use sqlx::Row;
 use std::io::Write;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use uuid::Uuid;
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use serde::{Serialize, Deserialize};
 
 use crate::config::AppConfig;
 use crate::Database;
+use crate::middleware::{AuthenticatedUser, Permission};
 use crate::models::auth::ApiResponse;
-use crate::models::file::{AttachmentResponse, UploadResponse, UploadFileRequest};
+use crate::models::file::{AttachmentDownload, AttachmentResponse, DeleteAttachmentsRequest, DeletedAttachments, UploadResponse, UploadFileRequest, UploadQuery, StorageQuery, StorageReport, StoredFile, ATTACHMENT_FAILED, ATTACHMENT_FLAGGED, ATTACHMENT_INFECTED, ATTACHMENT_READY, ATTACHMENT_REJECTED};
+use crate::models::ids::{AttachmentId, TaskId, UserId};
+use crate::models::list::ListParams;
+use crate::models::params::{AttachmentPath, TaskAttachmentsPath};
+use crate::services::{attachment_scan, encryption, watermark};
+use crate::utils::cdn;
+use crate::utils::client_ip::client_ip;
 use crate::utils::errors::ServiceError;
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String, // Subject (user id)
-    pub username: String,
-    pub name: String,
-    pub exp: usize, // Expiration time (Unix timestamp)
-    pub iat: usize, // Issued at (Unix timestamp)
-}
-
-// Helper function to extract user ID from JWT token
-async fn get_user_from_token(req: &HttpRequest, config: &AppConfig) -> Result<i32, ServiceError> {
-    let auth_header = req.headers().get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "));
-
-    let token = auth_header.ok_or_else(|| {
-        ServiceError::Unauthorized("Authentication required".to_string())
-    })?;
-
-    let claims = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(config.jwt_secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|_| ServiceError::Unauthorized("Invalid token".to_string()))?;
-
-    let user_id: i32 = claims.claims.sub.parse()
-        .map_err(|_| ServiceError::Unauthorized("Invalid user ID in token".to_string()))?;
-
-    Ok(user_id)
-}
+use crate::utils::sql::{Select, Sort};
+use crate::utils::text;
 
 // Helper function to ensure upload directory exists
 fn ensure_upload_dir() -> Result<PathBuf, ServiceError> {
@@ -59,17 +42,16 @@ fn ensure_upload_dir() -> Result<PathBuf, ServiceError> {
     Ok(upload_dir.to_path_buf())
 }
 
-// Helper function to validate file type and size
-fn validate_file(file_name: &str, file_size: usize) -> Result<String, ServiceError> {
-    // Max file size: 10MB
-    const MAX_FILE_SIZE: usize = 10 * 1024 * 1024;
-    
-    if file_size > MAX_FILE_SIZE {
-        return Err(ServiceError::ValidationError(
-            "File size exceeds 10MB limit".to_string()
-        ));
-    }
+// Room for the multipart boundaries and part headers around the file
+const MULTIPART_OVERHEAD_BYTES: usize = 64 * 1024;
 
+fn file_too_large(limit: usize) -> ServiceError {
+    ServiceError::PayloadTooLarge(format!("File size exceeds {} limit", text::byte_size(limit)))
+        .with_code("FILE_TOO_LARGE")
+}
+
+// Helper function to validate file type
+fn validate_file(file_name: &str) -> Result<String, ServiceError> {
     // Allowed file extensions
     let allowed_extensions = [
         "jpg", "jpeg", "png", "gif", "pdf", "doc", "docx", 
@@ -109,16 +91,101 @@ fn validate_file(file_name: &str, file_size: usize) -> Result<String, ServiceErr
     Ok(mime_type.to_string())
 }
 
+// Downloads require a bearer token, so only the browser may cache them.
+// Confidential boards must see every download, so nothing is cached there.
+fn attachment_cache_control(config: &AppConfig) -> CacheControl {
+    if config.confidential_board {
+        return CacheControl(vec![CacheDirective::Private, CacheDirective::NoStore]);
+    }
+    CacheControl(vec![
+        CacheDirective::Private,
+        CacheDirective::MaxAge(31_536_000),
+        CacheDirective::Extension("immutable".to_string(), None),
+    ])
+}
+
+// Only clean, approved uploads are served, whether as the file or its preview
+fn ensure_servable(processing_status: &str) -> Result<(), ServiceError> {
+    match processing_status {
+        ATTACHMENT_READY => Ok(()),
+        ATTACHMENT_INFECTED => Err(ServiceError::Forbidden("Attachment failed the virus scan".to_string())
+            .with_code("ATTACHMENT_INFECTED")),
+        ATTACHMENT_FAILED => Err(ServiceError::Conflict("Attachment could not be processed".to_string())
+            .with_code("ATTACHMENT_PROCESSING_FAILED")),
+        ATTACHMENT_FLAGGED => Err(ServiceError::Forbidden("Attachment is waiting for moderation".to_string())
+            .with_code("ATTACHMENT_UNDER_REVIEW")),
+        ATTACHMENT_REJECTED => Err(ServiceError::Forbidden("Attachment was rejected by a moderator".to_string())
+            .with_code("ATTACHMENT_REJECTED")),
+        _ => Err(ServiceError::Conflict("Attachment is still being processed".to_string())
+            .with_code("ATTACHMENT_NOT_READY")),
+    }
+}
+
+// Helper function to append a download to the confidential audit log. A
+// failure to record blocks the download rather than letting it go unlogged.
+async fn record_download(
+    db: &Database,
+    config: &AppConfig,
+    req: &HttpRequest,
+    task_id: TaskId,
+    attachment_id: AttachmentId,
+    user_id: UserId,
+) -> Result<(), ServiceError> {
+    let ip_address = client_ip(req, &config.trusted_proxies).map(|ip| ip.to_string());
+    let user_agent = req.headers().get("User-Agent")
+        .and_then(|h| h.to_str().ok())
+        .map(|h| h.to_string());
+
+    sqlx::query(
+        "INSERT INTO attachment_downloads (attachment_id, task_id, user_id, ip_address, user_agent)
+         VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(attachment_id)
+    .bind(task_id)
+    .bind(user_id)
+    .bind(ip_address)
+    .bind(user_agent)
+    .execute(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error recording attachment download: {}", e);
+        ServiceError::DatabaseError("Failed to record download".to_string())
+    })?;
+
+    Ok(())
+}
+
+// Helper function to evaluate conditional request headers
+fn is_not_modified(req: &HttpRequest, etag: &EntityTag, modified: DateTime<Utc>) -> bool {
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => return true,
+        Ok(IfNoneMatch::Items(tags)) if !tags.is_empty() => {
+            return tags.iter().any(|tag| tag.weak_eq(etag));
+        }
+        _ => {}
+    }
+
+    // If-Modified-Since is only considered when no If-None-Match was sent
+    if let Ok(IfModifiedSince(since)) = IfModifiedSince::parse(req) {
+        let since: SystemTime = since.into();
+        return SystemTime::from(modified.trunc_subsecs(0)) <= since;
+    }
+
+    false
+}
+
 /// Upload a file attachment to a task
 #[utoipa::path(
     post,
     path = "/api/tasks/{task_id}/attachments",
+    operation_id = "uploadAttachment",
     tag = "attachments",
     security(
         ("bearer_auth" = [])
     ),
     params(
-        ("task_id" = i32, Path, description = "Task ID to attach file to")
+        TaskAttachmentsPath,
+        UploadQuery
     ),
     request_body(
         content = inline(UploadFileRequest),
@@ -127,22 +194,36 @@ fn validate_file(file_name: &str, file_size: usize) -> Result<String, ServiceErr
     ),
     responses(
         (status = 201, description = "File uploaded successfully", body = ApiResponse<UploadResponse>),
-        (status = 400, description = "Validation error", body = crate::utils::errors::ServiceError),
+        (status = 400, description = "Validation error or checksum mismatch", body = crate::utils::errors::ServiceError),
+        (status = 413, description = "File too large", body = crate::utils::errors::ServiceError),
         (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
         (status = 404, description = "Task not found", body = crate::utils::errors::ServiceError)
     )
 )]
 pub async fn upload_file(
+    user: AuthenticatedUser,
     req: HttpRequest,
     db: web::Data<Database>,
     config: web::Data<AppConfig>,
-    path: web::Path<i32>,
+    path: web::Path<TaskAttachmentsPath>,
+    query: web::Query<UploadQuery>,
     mut payload: Multipart,
 ) -> Result<HttpResponse, ServiceError> {
-    let task_id = path.into_inner();
+    let task_id = path.into_inner().task_id;
     log::info!("POST /api/tasks/{}/attachments - Uploading file", task_id);
+    user.requires(Permission::AttachmentWrite)?;
+    let expected_sha256 = query.expected_sha256()?;
 
-    let user_id = get_user_from_token(&req, &config).await?;
+    // Refuse bodies that announce more than the file limit allows up front,
+    // before reading any of them
+    let announced = req.headers().get(CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|len| len.parse::<usize>().ok());
+    if announced.is_some_and(|len| len > config.max_upload_bytes + MULTIPART_OVERHEAD_BYTES) {
+        return Err(file_too_large(config.max_upload_bytes));
+    }
+
+    let user_id = user.id;
 
     // Check if task exists
     let task_exists = sqlx::query("SELECT id FROM tasks WHERE id = $1")
@@ -155,7 +236,7 @@ pub async fn upload_file(
         })?;
 
     if task_exists.is_none() {
-        return Err(ServiceError::NotFound("Task not found".to_string()));
+        return Err(ServiceError::NotFound("Task not found".to_string()).with_code("TASK_NOT_FOUND"));
     }
 
     let upload_dir = ensure_upload_dir()?;
@@ -190,17 +271,23 @@ pub async fn upload_file(
             })? {
                 file_data.extend_from_slice(&chunk);
                 // Check size during upload to prevent memory issues
-                if file_data.len() > 10 * 1024 * 1024 {
-                    return Err(ServiceError::ValidationError(
-                        "File size exceeds 10MB limit".to_string()
-                    ));
+                if file_data.len() > config.max_upload_bytes {
+                    return Err(file_too_large(config.max_upload_bytes));
                 }
             }
 
             let file_size = file_data.len();
-            let mime_type = validate_file(&file_name, file_size)?;
+            let mime_type = validate_file(&file_name)?;
+
+            let sha256 = hex::encode(Sha256::digest(&file_data));
+            if expected_sha256.as_ref().is_some_and(|expected| *expected != sha256) {
+                log::warn!("Checksum mismatch uploading {} to task {}", file_name, task_id);
+                return Err(ServiceError::ValidationError("File does not match the given sha256".to_string())
+                    .with_code("CHECKSUM_MISMATCH"));
+            }
 
-            // Write file to disk
+            // Write file to disk, sealed on a sensitive board
+            let file_data = encryption::seal(file_data)?;
             let mut file = std::fs::File::create(&file_path)
                 .map_err(|e| {
                     log::error!("Failed to create file: {}", e);
@@ -215,9 +302,9 @@ pub async fn upload_file(
 
             // Save file info to database
             let attachment_row = sqlx::query(
-                "INSERT INTO task_attachments (task_id, file_name, original_name, file_path, file_size, mime_type, uploaded_by) 
-                 VALUES ($1, $2, $3, $4, $5, $6, $7) 
-                 RETURNING id, task_id, file_name, original_name, file_path, file_size, mime_type, uploaded_by, created_at"
+                "INSERT INTO task_attachments (task_id, file_name, original_name, file_path, file_size, mime_type, uploaded_by, sha256) 
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8) 
+                 RETURNING id, task_id, file_name, original_name, file_path, file_size, mime_type, uploaded_by, processing_status, sha256, created_at"
             )
             .bind(task_id)
             .bind(&stored_file_name)
@@ -226,6 +313,7 @@ pub async fn upload_file(
             .bind(file_size as i64)
             .bind(&mime_type)
             .bind(user_id)
+            .bind(&sha256)
             .fetch_one(&db.pool)
             .await
             .map_err(|e| {
@@ -243,10 +331,24 @@ pub async fn upload_file(
                 file_size: attachment_row.get("file_size"),
                 mime_type: attachment_row.get("mime_type"),
                 uploaded_by: attachment_row.get("uploaded_by"),
-                download_url: format!("/api/tasks/{}/attachments/{}/download", task_id, attachment_row.get::<i32, _>("id")),
+                processing_status: attachment_row.get("processing_status"),
+                download_url: cdn::attachment_download_url(&config, task_id, attachment_row.get("id")),
+                preview_url: None,
+                sha256: attachment_row.get("sha256"),
+                content_match: false,
                 created_at: attachment_row.get("created_at"),
             };
 
+            // The file is not downloadable until the scan marks it ready
+            attachment_scan::spawn_scan(
+                db.clone().into_inner(),
+                &config,
+                attachment_response.id,
+                file_path.clone(),
+                file_name.clone(),
+                mime_type.clone(),
+            );
+
             let upload_response = UploadResponse {
                 attachment: attachment_response,
                 message: "File uploaded successfully".to_string(),
@@ -260,33 +362,54 @@ pub async fn upload_file(
     Err(ServiceError::ValidationError("No file found in request".to_string()))
 }
 
-/// Get all attachments for a task
+/// Get the attachments of a task, one page at a time
 #[utoipa::path(
     get,
     path = "/api/tasks/{task_id}/attachments",
+    operation_id = "listAttachments",
     tag = "attachments",
     security(
         ("bearer_auth" = [])
     ),
     params(
-        ("task_id" = i32, Path, description = "Task ID")
+        TaskAttachmentsPath,
+        ListParams
     ),
     responses(
-        (status = 200, description = "Attachments retrieved successfully", body = ApiResponse<Vec<AttachmentResponse>>),
+        (status = 200, description = "Attachments retrieved successfully; X-Total-Count holds the unpaged total", body = ApiResponse<Vec<AttachmentResponse>>),
+        (status = 400, description = "Invalid list parameters", body = crate::utils::errors::ServiceError),
         (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
         (status = 404, description = "Task not found", body = crate::utils::errors::ServiceError)
     )
 )]
 pub async fn get_task_attachments(
-    req: HttpRequest,
+    user: AuthenticatedUser,
     db: web::Data<Database>,
     config: web::Data<AppConfig>,
-    path: web::Path<i32>,
+    path: web::Path<TaskAttachmentsPath>,
+    params: ListParams,
 ) -> Result<HttpResponse, ServiceError> {
-    let task_id = path.into_inner();
+    let task_id = path.into_inner().task_id;
     log::info!("GET /api/tasks/{}/attachments", task_id);
+    user.requires(Permission::AttachmentRead)?;
 
-    let _user_id = get_user_from_token(&req, &config).await?;
+    let mut select = Select::new(
+        "task_attachments",
+        "id, task_id, file_name, original_name, file_size, mime_type, uploaded_by, processing_status, preview_path, sha256, created_at",
+    )
+    .eq("task_id", task_id);
+    // Uploads held or refused by moderation are only listed for moderators
+    if !user.can(Permission::ContentModerate) {
+        select = select.ne("processing_status", ATTACHMENT_FLAGGED).ne("processing_status", ATTACHMENT_REJECTED);
+    }
+    let select = select
+    .ilike_or_matches("original_name", params.search_pattern(), "content_tsv", params.search_text())
+    .order_by(params.sort(
+        &[("name", "original_name"), ("size", "file_size"), ("created_at", "created_at")],
+        "-created_at",
+    )?)
+    .order_by(Sort { column: "id", descending: false })
+    .paginate(params.per_page(), params.offset());
 
     // Check if task exists
     let task_exists = sqlx::query("SELECT id FROM tasks WHERE id = $1")
@@ -299,20 +422,46 @@ pub async fn get_task_attachments(
         })?;
 
     if task_exists.is_none() {
-        return Err(ServiceError::NotFound("Task not found".to_string()));
+        return Err(ServiceError::NotFound("Task not found".to_string()).with_code("TASK_NOT_FOUND"));
     }
 
-    let attachment_rows = sqlx::query(
-        "SELECT id, task_id, file_name, original_name, file_size, mime_type, uploaded_by, created_at 
-         FROM task_attachments WHERE task_id = $1 ORDER BY created_at DESC"
-    )
-    .bind(task_id)
-    .fetch_all(&db.pool)
-    .await
-    .map_err(|e| {
-        log::error!("Database error fetching attachments: {}", e);
-        ServiceError::DatabaseError("Failed to fetch attachments".to_string())
-    })?;
+    let total: i64 = select.build_count()
+        .build_query_scalar()
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error counting attachments: {}", e);
+            ServiceError::DatabaseError("Failed to fetch attachments".to_string())
+        })?;
+
+    let attachment_rows = select.build()
+        .build()
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching attachments: {}", e);
+            ServiceError::DatabaseError("Failed to fetch attachments".to_string())
+        })?;
+
+    // Which of the returned files matched the search inside their text
+    let content_matches: Vec<AttachmentId> = match params.search_text() {
+        Some(query) => {
+            let ids: Vec<AttachmentId> = attachment_rows.iter().map(|row| row.get("id")).collect();
+            sqlx::query_scalar(
+                "SELECT id FROM task_attachments
+                 WHERE id = ANY($1) AND content_tsv @@ plainto_tsquery('simple', $2)"
+            )
+            .bind(&ids)
+            .bind(query)
+            .fetch_all(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error matching attachment content: {}", e);
+                ServiceError::DatabaseError("Failed to fetch attachments".to_string())
+            })?
+        }
+        None => Vec::new(),
+    };
 
     let attachments: Vec<AttachmentResponse> = attachment_rows.iter().map(|row| {
         AttachmentResponse {
@@ -323,47 +472,59 @@ pub async fn get_task_attachments(
             file_size: row.get("file_size"),
             mime_type: row.get("mime_type"),
             uploaded_by: row.get("uploaded_by"),
-            download_url: format!("/api/tasks/{}/attachments/{}/download", task_id, row.get::<i32, _>("id")),
+            processing_status: row.get("processing_status"),
+            download_url: cdn::attachment_download_url(&config, task_id, row.get("id")),
+            preview_url: row.get::<Option<String>, _>("preview_path")
+                .map(|_| cdn::attachment_preview_url(&config, task_id, row.get("id"))),
+            sha256: row.get("sha256"),
+            content_match: content_matches.contains(&row.get("id")),
             created_at: row.get("created_at"),
         }
     }).collect();
 
-    log::info!("Retrieved {} attachments for task {}", attachments.len(), task_id);
-    Ok(HttpResponse::Ok().json(ApiResponse::success("Attachments retrieved successfully", attachments)))
+    log::info!("Retrieved {} of {} attachments for task {}", attachments.len(), total, task_id);
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(ApiResponse::success("Attachments retrieved successfully", attachments)))
 }
 
 /// Download a file attachment
 #[utoipa::path(
     get,
     path = "/api/tasks/{task_id}/attachments/{attachment_id}/download",
+    operation_id = "downloadAttachment",
     tag = "attachments",
     security(
         ("bearer_auth" = [])
     ),
     params(
-        ("task_id" = i32, Path, description = "Task ID"),
-        ("attachment_id" = i32, Path, description = "Attachment ID")
+        AttachmentPath
     ),
     responses(
-        (status = 200, description = "File download", content_type = "application/octet-stream"),
+        (status = 200, description = "File download; Content-Digest holds the SHA-256 of the bytes sent", content_type = "application/octet-stream",
+            headers(("Content-Digest" = String, description = "sha-256 digest of the body, RFC 9530"))),
         (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "File is infected, waiting for moderation or rejected", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "File is still being processed or processing failed", body = crate::utils::errors::ServiceError),
         (status = 404, description = "File not found", body = crate::utils::errors::ServiceError)
     )
 )]
 pub async fn download_file(
+    user: AuthenticatedUser,
     req: HttpRequest,
     db: web::Data<Database>,
     config: web::Data<AppConfig>,
-    path: web::Path<(i32, i32)>,
+    path: web::Path<AttachmentPath>,
 ) -> Result<HttpResponse, ServiceError> {
-    let (task_id, attachment_id) = path.into_inner();
+    let AttachmentPath { task_id, attachment_id } = path.into_inner();
     log::info!("GET /api/tasks/{}/attachments/{}/download", task_id, attachment_id);
+    user.requires(Permission::AttachmentRead)?;
 
-    let _user_id = get_user_from_token(&req, &config).await?;
+    let user_id = user.id;
 
     // Get attachment info
     let attachment_row = sqlx::query(
-        "SELECT file_path, original_name, mime_type 
+        "SELECT file_path, original_name, mime_type, file_size, processing_status, sha256, created_at 
          FROM task_attachments 
          WHERE id = $1 AND task_id = $2"
     )
@@ -380,46 +541,161 @@ pub async fn download_file(
         Some(row) => row,
         None => {
             log::warn!("Attachment not found: {} for task {}", attachment_id, task_id);
-            return Err(ServiceError::NotFound("Attachment not found".to_string()));
+            return Err(ServiceError::NotFound("Attachment not found".to_string()).with_code("ATTACHMENT_NOT_FOUND"));
         }
     };
 
+    ensure_servable(attachment_row.get::<&str, _>("processing_status"))?;
+
     let file_path: String = attachment_row.get("file_path");
     let original_name: String = attachment_row.get("original_name");
     let mime_type: String = attachment_row.get("mime_type");
+    let file_size: i64 = attachment_row.get("file_size");
+    let stored_sha256: Option<String> = attachment_row.get("sha256");
+    let created_at: DateTime<Utc> = attachment_row.get("created_at");
+
+    // Stored files are never rewritten (each upload gets a fresh UUID name),
+    // so the validators below stay valid for the lifetime of the attachment
+    let etag = EntityTag::new_strong(format!("{}-{}-{}", attachment_id, file_size, created_at.timestamp()));
+    let last_modified = HttpDate::from(SystemTime::from(created_at));
+
+    if !config.confidential_board && is_not_modified(&req, &etag, created_at) {
+        log::info!("Attachment {} not modified, returning 304", attachment_id);
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(LastModified(last_modified))
+            .insert_header(attachment_cache_control(&config))
+            .finish());
+    }
 
     // Check if file exists on disk
     if !Path::new(&file_path).exists() {
         log::error!("File not found on disk: {}", file_path);
-        return Err(ServiceError::NotFound("File not found on disk".to_string()));
+        return Err(ServiceError::NotFound("File not found on disk".to_string()).with_code("ATTACHMENT_FILE_MISSING"));
     }
 
     // Read file
-    let file_data = std::fs::read(&file_path)
+    let mut file_data = std::fs::read(&file_path)
         .map_err(|e| {
             log::error!("Failed to read file {}: {}", file_path, e);
             ServiceError::InternalError("Failed to read file".to_string())
         })?;
+    file_data = encryption::open(file_data)?;
+
+    let mut watermarked = false;
+    if config.confidential_board {
+        record_download(&db, &config, &req, task_id, attachment_id, user_id).await?;
+
+        if let Some(ref command) = config.watermark_command {
+            if watermark::supports(&mime_type) {
+                let username: String = sqlx::query("SELECT username FROM users WHERE id = $1")
+                    .bind(user_id)
+                    .fetch_one(&db.pool)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Database error fetching downloader: {}", e);
+                        ServiceError::DatabaseError("Failed to fetch user".to_string())
+                    })?
+                    .get("username");
+
+                let text = format!("{} {}", username, Utc::now().format("%Y-%m-%d %H:%M UTC"));
+                let extension = Path::new(&file_path).extension().and_then(|ext| ext.to_str()).unwrap_or("bin");
+                file_data = watermark::apply(command, &file_data, extension, &text).await?;
+                watermarked = true;
+            }
+        }
+    }
+
+    // The digest covers the bytes sent, which differ from the stored hash
+    // when the file was watermarked for this download
+    let digest = Sha256::digest(&file_data);
+    if !watermarked && stored_sha256.as_ref().is_some_and(|stored| *stored != hex::encode(digest)) {
+        log::error!("Attachment {} on disk no longer matches its stored checksum", attachment_id);
+        return Err(ServiceError::InternalError("Stored file is corrupted".to_string()).with_code("ATTACHMENT_CORRUPTED"));
+    }
 
     log::info!("File downloaded: {} ({} bytes)", original_name, file_data.len());
 
     Ok(HttpResponse::Ok()
         .content_type(mime_type.as_str())
         .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", original_name)))
+        .insert_header(("Content-Digest", format!("sha-256=:{}:", BASE64.encode(digest))))
+        .insert_header(ETag(etag))
+        .insert_header(LastModified(last_modified))
+        .insert_header(attachment_cache_control(&config))
         .body(file_data))
 }
 
+/// Get the rendered first-page preview of a document attachment
+#[utoipa::path(
+    get,
+    path = "/api/tasks/{task_id}/attachments/{attachment_id}/preview",
+    operation_id = "getAttachmentPreview",
+    tag = "attachments",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        AttachmentPath
+    ),
+    responses(
+        (status = 200, description = "Preview image", content_type = "image/png"),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "File is infected, waiting for moderation or rejected", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "File is still being processed or processing failed", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Attachment not found or it has no preview", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_attachment_preview(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    path: web::Path<AttachmentPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let AttachmentPath { task_id, attachment_id } = path.into_inner();
+    log::info!("GET /api/tasks/{}/attachments/{}/preview", task_id, attachment_id);
+    user.requires(Permission::AttachmentRead)?;
+
+    let attachment_row = sqlx::query(
+        "SELECT preview_path, processing_status FROM task_attachments WHERE id = $1 AND task_id = $2"
+    )
+    .bind(attachment_id)
+    .bind(task_id)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching attachment: {}", e);
+        ServiceError::DatabaseError("Failed to fetch attachment".to_string())
+    })?
+    .ok_or_else(|| ServiceError::NotFound("Attachment not found".to_string()).with_code("ATTACHMENT_NOT_FOUND"))?;
+    ensure_servable(attachment_row.get::<&str, _>("processing_status"))?;
+
+    let preview_path: String = attachment_row.get::<Option<String>, _>("preview_path")
+        .ok_or_else(|| ServiceError::NotFound("Attachment has no preview".to_string()).with_code("PREVIEW_NOT_AVAILABLE"))?;
+
+    let data = tokio::fs::read(&preview_path).await.map_err(|e| {
+        log::error!("Failed to read preview {}: {}", preview_path, e);
+        ServiceError::NotFound("Attachment has no preview".to_string()).with_code("PREVIEW_NOT_AVAILABLE")
+    })?;
+    let data = encryption::open(data)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header(attachment_cache_control(&config))
+        .body(data))
+}
+
 /// Delete a file attachment
 #[utoipa::path(
     delete,
     path = "/api/tasks/{task_id}/attachments/{attachment_id}",
+    operation_id = "deleteAttachment",
     tag = "attachments",
     security(
         ("bearer_auth" = [])
     ),
     params(
-        ("task_id" = i32, Path, description = "Task ID"),
-        ("attachment_id" = i32, Path, description = "Attachment ID")
+        AttachmentPath
     ),
     responses(
         (status = 200, description = "Attachment deleted successfully", body = ApiResponse<bool>),
@@ -428,19 +704,17 @@ pub async fn download_file(
     )
 )]
 pub async fn delete_attachment(
-    req: HttpRequest,
+    user: AuthenticatedUser,
     db: web::Data<Database>,
-    config: web::Data<AppConfig>,
-    path: web::Path<(i32, i32)>,
+    path: web::Path<AttachmentPath>,
 ) -> Result<HttpResponse, ServiceError> {
-    let (task_id, attachment_id) = path.into_inner();
+    let AttachmentPath { task_id, attachment_id } = path.into_inner();
     log::info!("DELETE /api/tasks/{}/attachments/{}", task_id, attachment_id);
-
-    let _user_id = get_user_from_token(&req, &config).await?;
+    user.requires(Permission::AttachmentWrite)?;
 
     // Get attachment info before deletion (to clean up file)
     let attachment_row = sqlx::query(
-        "SELECT file_path FROM task_attachments WHERE id = $1 AND task_id = $2"
+        "SELECT file_path, preview_path FROM task_attachments WHERE id = $1 AND task_id = $2"
     )
     .bind(attachment_id)
     .bind(task_id)
@@ -451,10 +725,10 @@ pub async fn delete_attachment(
         ServiceError::DatabaseError("Failed to fetch attachment".to_string())
     })?;
 
-    let file_path = match attachment_row {
-        Some(row) => row.get::<String, _>("file_path"),
+    let (file_path, preview_path) = match attachment_row {
+        Some(row) => (row.get::<String, _>("file_path"), row.get::<Option<String>, _>("preview_path")),
         None => {
-            return Err(ServiceError::NotFound("Attachment not found".to_string()));
+            return Err(ServiceError::NotFound("Attachment not found".to_string()).with_code("ATTACHMENT_NOT_FOUND"));
         }
     };
 
@@ -470,30 +744,284 @@ pub async fn delete_attachment(
         })?;
 
     if result.rows_affected() == 0 {
-        return Err(ServiceError::NotFound("Attachment not found".to_string()));
+        return Err(ServiceError::NotFound("Attachment not found".to_string()).with_code("ATTACHMENT_NOT_FOUND"));
     }
 
-    // Clean up file from disk
-    if Path::new(&file_path).exists() {
-        if let Err(e) = std::fs::remove_file(&file_path) {
+    remove_stored_files(&file_path, preview_path.as_deref());
+
+    log::info!("Attachment deleted successfully: {}", attachment_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Attachment deleted successfully", true)))
+}
+
+// Clean up an attachment's file and preview from disk once its row is gone.
+// Failures are only logged so the request does not fail over a stray file.
+fn remove_stored_files(file_path: &str, preview_path: Option<&str>) {
+    if Path::new(file_path).exists() {
+        if let Err(e) = std::fs::remove_file(file_path) {
             log::warn!("Failed to delete file {}: {}", file_path, e);
-            // Don't fail the request if file cleanup fails
         }
     }
+    if let Some(preview_path) = preview_path {
+        let _ = std::fs::remove_file(preview_path);
+    }
+}
 
-    log::info!("Attachment deleted successfully: {}", attachment_id);
-    Ok(HttpResponse::Ok().json(ApiResponse::success("Attachment deleted successfully", true)))
+/// Delete several attachments of a task at once, or all of them. Either
+/// every listed attachment is deleted or none is.
+#[utoipa::path(
+    delete,
+    path = "/api/tasks/{task_id}/attachments",
+    operation_id = "deleteAttachments",
+    tag = "attachments",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        TaskAttachmentsPath
+    ),
+    request_body = DeleteAttachmentsRequest,
+    responses(
+        (status = 200, description = "Attachments deleted successfully", body = ApiResponse<DeletedAttachments>),
+        (status = 400, description = "Neither or both of attachment_ids and all set", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Task or one of the attachments not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn delete_attachments(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<TaskAttachmentsPath>,
+    body: web::Json<DeleteAttachmentsRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let task_id = path.into_inner().task_id;
+    log::info!("DELETE /api/tasks/{}/attachments", task_id);
+    user.requires(Permission::AttachmentWrite)?;
+    body.validate()?;
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Lock the task so uploads to it wait until the deletion is decided
+    let task_exists = sqlx::query("SELECT id FROM tasks WHERE id = $1 FOR UPDATE")
+        .bind(task_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking task: {}", e);
+            ServiceError::DatabaseError("Failed to check task".to_string())
+        })?;
+    if task_exists.is_none() {
+        return Err(ServiceError::NotFound("Task not found".to_string()).with_code("TASK_NOT_FOUND"));
+    }
+
+    let rows = sqlx::query(
+        "DELETE FROM task_attachments
+         WHERE task_id = $1 AND ($2 OR id = ANY($3))
+         RETURNING id, file_path, preview_path"
+    )
+    .bind(task_id)
+    .bind(body.all)
+    .bind(&body.attachment_ids)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error deleting attachments: {}", e);
+        ServiceError::DatabaseError("Failed to delete attachments".to_string())
+    })?;
+
+    if !body.all {
+        let mut requested = body.attachment_ids.clone();
+        requested.sort();
+        requested.dedup();
+        if rows.len() != requested.len() {
+            // Dropping the transaction rolls the deletion back
+            return Err(ServiceError::NotFound("Attachment not found".to_string()).with_code("ATTACHMENT_NOT_FOUND"));
+        }
+    }
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Files go only once the rows are committed, so a rollback never leaves
+    // attachments pointing at missing files
+    let mut deleted = Vec::with_capacity(rows.len());
+    for row in &rows {
+        remove_stored_files(&row.get::<String, _>("file_path"), row.get::<Option<String>, _>("preview_path").as_deref());
+        deleted.push(row.get::<AttachmentId, _>("id"));
+    }
+    deleted.sort();
+
+    log::info!("Deleted {} attachments of task {}", deleted.len(), task_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Attachments deleted successfully",
+        DeletedAttachments { deleted },
+    )))
+}
+
+/// Download audit log for an attachment, newest first
+#[utoipa::path(
+    get,
+    path = "/api/tasks/{task_id}/attachments/{attachment_id}/downloads",
+    operation_id = "listAttachmentDownloads",
+    tag = "attachments",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        AttachmentPath
+    ),
+    responses(
+        (status = 200, description = "Download log retrieved successfully", body = ApiResponse<Vec<AttachmentDownload>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_attachment_downloads(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<AttachmentPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let AttachmentPath { task_id, attachment_id } = path.into_inner();
+    log::info!("GET /api/tasks/{}/attachments/{}/downloads", task_id, attachment_id);
+    user.requires(Permission::AttachmentRead)?;
+
+    let download_rows = sqlx::query(
+        "SELECT d.id, d.attachment_id, d.user_id, u.username, d.ip_address, d.user_agent, d.downloaded_at
+         FROM attachment_downloads d
+         JOIN users u ON u.id = d.user_id
+         WHERE d.attachment_id = $1 AND d.task_id = $2
+         ORDER BY d.downloaded_at DESC, d.id DESC"
+    )
+    .bind(attachment_id)
+    .bind(task_id)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching attachment downloads: {}", e);
+        ServiceError::DatabaseError("Failed to fetch download log".to_string())
+    })?;
+
+    let downloads: Vec<AttachmentDownload> = download_rows.iter().map(|row| AttachmentDownload {
+        id: row.get("id"),
+        attachment_id: row.get("attachment_id"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        ip_address: row.get("ip_address"),
+        user_agent: row.get("user_agent"),
+        downloaded_at: row.get("downloaded_at"),
+    }).collect();
+
+    log::info!("Retrieved {} downloads for attachment {}", downloads.len(), attachment_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Download log retrieved successfully", downloads)))
+}
+
+/// Storage usage summary and the largest attachments, to find what to clean up
+#[utoipa::path(
+    get,
+    path = "/api/storage",
+    operation_id = "getStorageReport",
+    tag = "attachments",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(StorageQuery),
+    responses(
+        (status = 200, description = "Storage report", body = ApiResponse<StorageReport>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_storage_report(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<StorageQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/storage");
+    user.requires(Permission::AttachmentRead)?;
+
+    let limit = query.limit.unwrap_or(10).clamp(1, 100);
+
+    let totals = sqlx::query(
+        "SELECT COUNT(*) AS attachment_count, COALESCE(SUM(file_size), 0)::BIGINT AS total_bytes
+         FROM task_attachments"
+    )
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error summarizing storage: {}", e);
+        ServiceError::DatabaseError("Failed to summarize storage".to_string())
+    })?;
+
+    let largest_rows = sqlx::query(
+        "SELECT a.id, a.task_id, t.name AS task_name, a.original_name, a.file_size, a.mime_type, a.uploaded_by, a.created_at
+         FROM task_attachments a
+         JOIN tasks t ON t.id = a.task_id
+         ORDER BY a.file_size DESC, a.id
+         LIMIT $1"
+    )
+    .bind(limit)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching largest attachments: {}", e);
+        ServiceError::DatabaseError("Failed to summarize storage".to_string())
+    })?;
+
+    let report = StorageReport {
+        attachment_count: totals.get("attachment_count"),
+        total_bytes: totals.get("total_bytes"),
+        largest_files: largest_rows.iter().map(|row| StoredFile {
+            id: row.get("id"),
+            task_id: row.get("task_id"),
+            task_name: row.get("task_name"),
+            original_name: row.get("original_name"),
+            file_size: row.get("file_size"),
+            mime_type: row.get("mime_type"),
+            uploaded_by: row.get("uploaded_by"),
+            created_at: row.get("created_at"),
+        }).collect(),
+    };
+
+    log::info!("Storage report: {} attachments, {} bytes", report.attachment_count, report.total_bytes);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Storage report retrieved successfully", report)))
 }
 
 pub fn file_config(cfg: &mut web::ServiceConfig) {
     cfg.service(
-        web::scope("/api")
-            .service(
-                web::scope("/tasks/{task_id}/attachments")
-                    .route("", web::post().to(upload_file))
-                    .route("", web::get().to(get_task_attachments))
-                    .route("/{attachment_id}/download", web::get().to(download_file))
-                    .route("/{attachment_id}", web::delete().to(delete_attachment))
-            )
+        web::scope("/api/tasks/{task_id}/attachments")
+            .route("", web::post().to(upload_file))
+            .route("", web::get().to(get_task_attachments))
+            .route("", web::delete().to(delete_attachments))
+            .route("/{attachment_id}/download", web::get().to(download_file))
+            .route("/{attachment_id}/preview", web::get().to(get_attachment_preview))
+            .route("/{attachment_id}/downloads", web::get().to(get_attachment_downloads))
+            .route("/{attachment_id}", web::delete().to(delete_attachment))
+    )
+    .service(
+        web::scope("/api/storage")
+            .route("", web::get().to(get_storage_report))
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(processing_status: &str) -> Option<&'static str> {
+        ensure_servable(processing_status).err().map(|e| e.error_code())
+    }
+
+    #[test]
+    fn only_ready_attachments_are_servable() {
+        assert!(ensure_servable(ATTACHMENT_READY).is_ok());
+        assert_eq!(code(ATTACHMENT_FLAGGED), Some("ATTACHMENT_UNDER_REVIEW"));
+        assert_eq!(code(ATTACHMENT_REJECTED), Some("ATTACHMENT_REJECTED"));
+        assert_eq!(code(ATTACHMENT_INFECTED), Some("ATTACHMENT_INFECTED"));
+        assert_eq!(code(ATTACHMENT_FAILED), Some("ATTACHMENT_PROCESSING_FAILED"));
+        assert_eq!(code("scanning"), Some("ATTACHMENT_NOT_READY"));
+    }
+}
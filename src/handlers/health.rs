@@ -3,11 +3,13 @@ use serde_json::json;
 
 use crate::models::auth::ApiResponse;
 use crate::database::{Database, DatabaseStats};
+use crate::services::metrics::METRICS;
+use crate::services::pool_monitor::PoolMonitor;
 
 pub async fn health_check(db: web::Data<Database>) -> Result<HttpResponse> {
     match db.health_check().await {
         Ok(_) => {
-            let stats = db.get_stats().await.unwrap_or_else(|_| DatabaseStats {
+            let stats = db.get_stats().await.unwrap_or(DatabaseStats {
                 users: 0,
                 teams: 0,
                 tasks: 0,
@@ -24,7 +26,8 @@ pub async fn health_check(db: web::Data<Database>) -> Result<HttpResponse> {
                         "teams": stats.teams,
                         "tasks": stats.tasks,
                         "attachments": stats.attachments
-                    }
+                    },
+                    "metrics": METRICS.snapshot()
                 })
             )))
         }
@@ -39,6 +42,40 @@ pub async fn health_check(db: web::Data<Database>) -> Result<HttpResponse> {
     }
 }
 
+/// Readiness for load balancers: 503 while the database is unreachable.
+/// Reports the connection pool so exhaustion shows up before requests fail.
+pub async fn readiness_check(db: web::Data<Database>, pool: web::Data<PoolMonitor>) -> Result<HttpResponse> {
+    let stats = pool.stats();
+    match db.health_check().await {
+        Ok(_) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+            "Kanban Backend API is ready",
+            json!({
+                "status": if stats.exhausted { "degraded" } else { "ready" },
+                "database": "connected",
+                "pool": stats
+            })
+        ))),
+        Err(e) => {
+            log::error!("Database readiness check failed: {}", e);
+            Ok(HttpResponse::ServiceUnavailable().json(json!({
+                "status": "error",
+                "message": "Database connection failed",
+                "pool": stats
+            })))
+        }
+    }
+}
+
+/// Process counters for Prometheus scrapes. Served on ADMIN_BIND_ADDRESS
+/// when one is set.
+pub async fn metrics() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(METRICS.render_prometheus())
+}
+
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.route("/health", web::get().to(health_check));
+    cfg.route("/health", web::get().to(health_check))
+        .route("/health/ready", web::get().to(readiness_check))
+        .route("/metrics", web::get().to(metrics));
 }
@@ -0,0 +1,78 @@
+use actix_web::{web, HttpResponse, Result};
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::middleware::{AuthenticatedUser, Permission};
+use crate::models::auth::ApiResponse;
+use crate::models::invitation::{CreateInvitationRequest, Invitation};
+use crate::services::email_templates::{self, EmailTemplates};
+use crate::services::invitations;
+use crate::services::mailer::{Email, Mailer};
+use crate::utils::errors::ServiceError;
+use crate::utils::locale;
+
+/// Invite someone by email. They get a link to create their account, which
+/// joins the given teams on creation.
+#[utoipa::path(
+    post,
+    path = "/api/invitations",
+    operation_id = "createInvitation",
+    tag = "invitations",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = CreateInvitationRequest,
+    responses(
+        (status = 201, description = "Invitation sent", body = ApiResponse<Invitation>),
+        (status = 400, description = "Invalid email or teams", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 409, description = "Email already has an account", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn create_invitation(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    config: web::Data<AppConfig>,
+    mailer: web::Data<dyn Mailer>,
+    templates: web::Data<EmailTemplates>,
+    invitation_req: web::Json<CreateInvitationRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/invitations");
+    user.requires(Permission::UserInvite)?;
+
+    let mut invitation_req = invitation_req.into_inner();
+    invitation_req.normalize()?;
+
+    let (invitation, token) = invitations::issue(&db, &config, user.id, &invitation_req).await?;
+
+    let separator = if config.invite_url.contains('?') { '&' } else { '?' };
+    let context = serde_json::json!({
+        "inviter_name": user.claims.name,
+        "ttl_hours": config.invite_ttl_hours,
+        "expires_at": locale::format_datetime(invitation.expires_at, &invitation.locale),
+        "link": format!("{}{}token={}", config.invite_url, separator, token),
+    });
+    let to = invitation.email.clone();
+    let recipient_locale = invitation.locale.clone();
+    let invitation_id = invitation.id;
+    let db = db.into_inner();
+    let templates = templates.into_inner();
+    let mailer = mailer.into_inner();
+    tokio::spawn(async move {
+        let result = match templates.render(&db, email_templates::INVITATION, &recipient_locale, &context).await {
+            Ok(rendered) => mailer.send(&Email { to, subject: rendered.subject, body: rendered.body }).await,
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to send invitation {}: {}", invitation_id, e);
+        }
+    });
+
+    log::info!("Invitation {} created by user {}", invitation.id, user.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success("Invitation sent successfully", invitation)))
+}
+
+pub fn invitation_config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/api/invitations", web::post().to(create_invitation));
+}
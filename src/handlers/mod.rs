@@ -2,7 +2,21 @@ pub mod auth;
 pub mod task;
 pub mod file;
 pub mod health;
+pub mod events;
+pub mod sync;
+pub mod operations;
+pub mod admin;
+pub mod invitation;
+pub mod feedback;
+pub mod report;
 
 pub use auth::auth_config;
 pub use task::task_config;
 pub use file::file_config;
+pub use events::events_config;
+pub use sync::sync_config;
+pub use operations::operations_config;
+pub use admin::admin_config;
+pub use invitation::invitation_config;
+pub use feedback::feedback_config;
+pub use report::report_config;
@@ -0,0 +1,107 @@
+use actix_web::{web, HttpResponse, Result};
+use uuid::Uuid;
+
+use crate::Database;
+use crate::middleware::{AuthenticatedUser, Permission};
+use crate::models::auth::ApiResponse;
+use crate::models::operation::{Operation, OPERATION_SUCCEEDED};
+use crate::services::operations;
+use crate::utils::errors::ServiceError;
+
+/// Get the status of a long-running operation
+#[utoipa::path(
+    get,
+    path = "/api/operations/{id}",
+    operation_id = "getOperation",
+    tag = "operations",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = Uuid, Path, description = "Operation ID")
+    ),
+    responses(
+        (status = 200, description = "Operation status", body = ApiResponse<Operation>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Operation not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_operation(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let operation_id = path.into_inner();
+    log::info!("GET /api/operations/{}", operation_id);
+    user.requires(Permission::TaskRead)?;
+
+    let user_id = user.id;
+
+    let operation = operations::find_for_user(&db, operation_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Operation not found".to_string()).with_code("OPERATION_NOT_FOUND"))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Operation retrieved successfully", operation)))
+}
+
+/// Download the file produced by a finished operation
+#[utoipa::path(
+    get,
+    path = "/api/operations/{id}/download",
+    operation_id = "downloadOperationResult",
+    tag = "operations",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = Uuid, Path, description = "Operation ID")
+    ),
+    responses(
+        (status = 200, description = "Operation result file", content_type = "application/octet-stream"),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Operation or result not found", body = crate::utils::errors::ServiceError),
+        (status = 412, description = "Operation has not succeeded", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn download_operation_result(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, ServiceError> {
+    let operation_id = path.into_inner();
+    log::info!("GET /api/operations/{}/download", operation_id);
+    user.requires(Permission::TaskRead)?;
+
+    let user_id = user.id;
+
+    let operation = operations::find_for_user(&db, operation_id, user_id).await?
+        .ok_or_else(|| ServiceError::NotFound("Operation not found".to_string()).with_code("OPERATION_NOT_FOUND"))?;
+
+    if operation.status != OPERATION_SUCCEEDED {
+        return Err(ServiceError::PreconditionFailed(format!("Operation is {}", operation.status))
+            .with_code("OPERATION_NOT_COMPLETE"));
+    }
+
+    let result = operation.result.unwrap_or_default();
+    let file_name = result.get("file_name").and_then(|v| v.as_str()).unwrap_or("result");
+    let content_type = result.get("content_type").and_then(|v| v.as_str()).unwrap_or("application/octet-stream");
+
+    let file_data = tokio::fs::read(operations::result_path(operation_id)).await
+        .map_err(|e| {
+            log::error!("Failed to read result of operation {}: {}", operation_id, e);
+            ServiceError::NotFound("Operation result is no longer available".to_string())
+                .with_code("OPERATION_RESULT_MISSING")
+        })?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", file_name)))
+        .body(file_data))
+}
+
+pub fn operations_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/operations")
+            .route("/{id}", web::get().to(get_operation))
+            .route("/{id}/download", web::get().to(download_operation_result))
+    );
+}
@@ -0,0 +1,346 @@
+use std::collections::HashSet;
+
+use actix_web::{web, HttpResponse, Result};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::Row;
+
+use crate::Database;
+use crate::middleware::{AuthenticatedUser, Permission};
+use crate::models::auth::ApiResponse;
+use crate::models::sync::{SyncQuery, SyncResponse, SyncPushRequest, TaskChange, TaskChangeResult, FieldConflict};
+use crate::models::task::{TaskResponse, Team};
+use crate::models::ids::{TaskId, TeamId, UserId};
+use crate::services::{encryption, outbox, scripts, task_events, task_writes};
+use crate::services::task_response::TaskResponseAssembler;
+use crate::utils::errors::ServiceError;
+
+// Helper function to load full task responses for a set of ids
+async fn load_tasks(db: &Database, task_ids: &[TaskId]) -> Result<Vec<TaskResponse>, ServiceError> {
+    let task_rows = sqlx::query(
+        "SELECT id, name, description, status, external_link, client_id, created_by, created_at, updated_at 
+         FROM tasks WHERE id = ANY($1) ORDER BY id"
+    )
+    .bind(task_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching tasks for sync: {}", e);
+        ServiceError::DatabaseError("Failed to fetch tasks".to_string())
+    })?;
+
+    let loaded_ids: Vec<TaskId> = task_rows.iter().map(|row| row.get("id")).collect();
+    Ok(TaskResponseAssembler::preload(db, &loaded_ids).await?.assemble_all(&task_rows))
+}
+
+/// Fetch changes since a sync cursor
+#[utoipa::path(
+    get,
+    path = "/api/sync",
+    operation_id = "pullChanges",
+    tag = "sync",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(SyncQuery),
+    responses(
+        (status = 200, description = "Changes retrieved successfully", body = ApiResponse<SyncResponse>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_changes(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<SyncQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/sync - since {:?}", query.since);
+    user.requires(Permission::TaskRead)?;
+
+    let since = query.since.unwrap_or(0);
+
+    // Latest event per task after the cursor
+    let change_rows = sqlx::query(
+        "SELECT task_id, MAX(id) AS last_event_id,
+                BOOL_OR(event_type = 'task.deleted') AS deleted
+         FROM task_events WHERE id > $1
+         GROUP BY task_id"
+    )
+    .bind(since)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching task changes: {}", e);
+        ServiceError::DatabaseError("Failed to fetch changes".to_string())
+    })?;
+
+    let mut cursor = since;
+    let mut changed_ids = Vec::new();
+    for row in &change_rows {
+        cursor = cursor.max(row.get::<i64, _>("last_event_id"));
+        changed_ids.push(row.get::<TaskId, _>("task_id"));
+    }
+
+    let tasks = if query.since.is_some() {
+        load_tasks(&db, &changed_ids).await?
+    } else {
+        // Full snapshot: every live task, including ones predating the event log
+        let all_ids: Vec<TaskId> = sqlx::query("SELECT id FROM tasks")
+            .fetch_all(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error fetching task ids: {}", e);
+                ServiceError::DatabaseError("Failed to fetch tasks".to_string())
+            })?
+            .iter()
+            .map(|row| row.get("id"))
+            .collect();
+        load_tasks(&db, &all_ids).await?
+    };
+
+    // Anything that changed but no longer exists was deleted
+    let deleted_task_ids: Vec<TaskId> = if query.since.is_some() {
+        change_rows.iter()
+            .filter(|row| row.get::<bool, _>("deleted"))
+            .map(|row| row.get::<TaskId, _>("task_id"))
+            .filter(|id| !tasks.iter().any(|task| task.id == *id))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    // Teams are append-only, so only the ones created after the cursor are new
+    let since_time: Option<DateTime<Utc>> = if query.since.is_some() {
+        sqlx::query("SELECT created_at FROM task_events WHERE id <= $1 ORDER BY id DESC LIMIT 1")
+            .bind(since)
+            .fetch_optional(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error resolving sync cursor: {}", e);
+                ServiceError::DatabaseError("Failed to fetch changes".to_string())
+            })?
+            .map(|row| row.get("created_at"))
+    } else {
+        None
+    };
+
+    let team_rows = sqlx::query(
+        "SELECT id, name, created_at FROM teams WHERE $1::timestamptz IS NULL OR created_at > $1 ORDER BY name"
+    )
+    .bind(since_time)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching teams for sync: {}", e);
+        ServiceError::DatabaseError("Failed to fetch teams".to_string())
+    })?;
+
+    let teams: Vec<Team> = team_rows.iter().map(|row| Team {
+        id: row.get("id"),
+        name: row.get("name"),
+        created_at: row.get("created_at"),
+    }).collect();
+
+    let response = SyncResponse {
+        cursor,
+        tasks,
+        deleted_task_ids,
+        teams,
+    };
+
+    log::info!(
+        "Sync returned {} tasks, {} tombstones, {} teams (cursor {})",
+        response.tasks.len(), response.deleted_task_ids.len(), response.teams.len(), response.cursor
+    );
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Changes retrieved successfully", response)))
+}
+
+// Helper function to apply one client change set, detecting per-field conflicts
+async fn apply_change(db: &Database, user_id: UserId, change: &TaskChange) -> Result<TaskChangeResult, ServiceError> {
+    let task_id = change.task_id;
+    let fields = &change.fields;
+
+    if let Some(ref status) = fields.status {
+        let valid_statuses = ["TO_DO", "DOING", "DONE"];
+        if !valid_statuses.contains(&status.as_str()) {
+            return Err(ServiceError::ValidationError(format!("Invalid task status for task {}", task_id)));
+        }
+    }
+
+    let team_ids = match fields.teams {
+        Some(ref team_names) => {
+            let team_rows = sqlx::query("SELECT id, name FROM teams WHERE name = ANY($1)")
+                .bind(team_names)
+                .fetch_all(&db.pool)
+                .await
+                .map_err(|e| {
+                    log::error!("Database error getting teams: {}", e);
+                    ServiceError::DatabaseError("Failed to query team".to_string())
+                })?;
+            if let Some(missing) = team_names.iter().find(|name| !team_rows.iter().any(|row| row.get::<String, _>("name") == **name)) {
+                return Err(ServiceError::ValidationError(format!("Team '{}' not found", missing)).with_code("TEAM_NOT_FOUND"));
+            }
+            Some(team_rows.iter().map(|row| row.get::<TeamId, _>("id")).collect::<Vec<TeamId>>())
+        }
+        None => None,
+    };
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Lock the task so the comparison and the write see the same state
+    let task_row = sqlx::query(
+        "SELECT name, description, status, external_link,
+                ARRAY(SELECT t.name FROM teams t JOIN task_teams tt ON t.id = tt.team_id
+                      WHERE tt.task_id = tasks.id ORDER BY t.name) AS teams
+         FROM tasks WHERE id = $1 FOR UPDATE"
+    )
+    .bind(task_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error locking task: {}", e);
+        ServiceError::DatabaseError("Failed to fetch task".to_string())
+    })?;
+
+    let task_row = match task_row {
+        Some(row) => row,
+        None => {
+            return Ok(TaskChangeResult {
+                task_id,
+                status: "deleted".to_string(),
+                task: None,
+                conflicts: Vec::new(),
+            });
+        }
+    };
+
+    // Fields the server changed after the client's base version
+    let event_rows = sqlx::query(
+        "SELECT data FROM task_events WHERE task_id = $1 AND created_at > $2"
+    )
+    .bind(task_id)
+    .bind(change.base_updated_at)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading task events: {}", e);
+        ServiceError::DatabaseError("Failed to load task events".to_string())
+    })?;
+
+    let mut touched: HashSet<String> = HashSet::new();
+    for row in &event_rows {
+        let data: serde_json::Value = row.get("data");
+        if let Some(object) = data.as_object() {
+            touched.extend(object.keys().cloned());
+        }
+    }
+
+    let mut server_teams: Vec<String> = task_row.get("teams");
+    server_teams.sort();
+    let client_teams = fields.teams.clone().map(|mut teams| {
+        teams.sort();
+        teams
+    });
+
+    let candidates = [
+        ("name", json!(task_row.get::<String, _>("name")), fields.name.as_ref().map(|v| json!(v))),
+        ("description", json!(encryption::open_text(task_row.get("description"))), fields.description.as_ref().map(|v| json!(v))),
+        ("status", json!(task_row.get::<String, _>("status")), fields.status.as_ref().map(|v| json!(v))),
+        ("external_link", json!(task_row.get::<Option<String>, _>("external_link")), fields.external_link.as_ref().map(|v| json!(v))),
+        ("teams", json!(server_teams), client_teams.map(|v| json!(v))),
+    ];
+
+    let conflicts: Vec<FieldConflict> = candidates.into_iter()
+        .filter_map(|(field, server_value, client_value)| {
+            let client_value = client_value?;
+            if touched.contains(field) && server_value != client_value {
+                Some(FieldConflict { field: field.to_string(), server_value, client_value })
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    if !conflicts.is_empty() {
+        log::info!("Sync change for task {} has {} conflicting fields", task_id, conflicts.len());
+        return Ok(TaskChangeResult {
+            task_id,
+            status: "conflict".to_string(),
+            task: load_tasks(db, &[task_id]).await?.pop(),
+            conflicts,
+        });
+    }
+
+    task_writes::apply_update(&mut tx, task_id, user_id, fields, team_ids.as_deref(), None).await?;
+
+    // Read the result back through the transaction so the event can be
+    // written atomically with the update
+    let task = task_writes::fetch(&mut tx, task_id).await?;
+    scripts::validate(&mut tx, task_events::TASK_UPDATED, &task, Some(&task_writes::changed_fields(fields))).await?;
+
+    outbox::enqueue(&mut tx, "task", task_id.0, "task.updated", &task).await?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(TaskChangeResult {
+        task_id,
+        status: "applied".to_string(),
+        task: Some(task),
+        conflicts: Vec::new(),
+    })
+}
+
+/// Push offline edits with per-field conflict detection
+#[utoipa::path(
+    post,
+    path = "/api/sync",
+    operation_id = "pushChanges",
+    tag = "sync",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = SyncPushRequest,
+    responses(
+        (status = 200, description = "Changes processed", body = ApiResponse<Vec<TaskChangeResult>>),
+        (status = 400, description = "Validation error", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn push_changes(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    push_req: web::Json<SyncPushRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/sync - {} change sets", push_req.changes.len());
+    user.requires(Permission::TaskWrite)?;
+
+    let user_id = user.id;
+    let mut push_req = push_req.into_inner();
+    for change in &mut push_req.changes {
+        change.fields.normalize()?;
+    }
+
+    let mut results = Vec::new();
+    for change in &push_req.changes {
+        results.push(apply_change(&db, user_id, change).await?);
+    }
+
+    let conflicts = results.iter().filter(|r| r.status == "conflict").count();
+    log::info!("Processed {} change sets, {} with conflicts", results.len(), conflicts);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Changes processed", results)))
+}
+
+pub fn sync_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/sync")
+            .route("", web::get().to(get_changes))
+            .route("", web::post().to(push_changes))
+    );
+}
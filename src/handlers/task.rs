@@ -1,122 +1,50 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse, Result};
+use chrono::Utc;
+use actix_web::web::Bytes;
+use futures_util::{stream, TryStreamExt};
+use tokio::io::AsyncWriteExt;
 use sqlx::Row;
-use jsonwebtoken::{decode, DecodingKey, Validation};
-use serde::{Serialize, Deserialize};
 
-use crate::config::AppConfig;
 use crate::Database;
+use crate::middleware::{request_context, AuthenticatedUser, Permission};
 use crate::models::auth::ApiResponse;
-use crate::models::task::{TaskResponse, CreateTaskRequest, UpdateTaskRequest, Team};
-use crate::models::file::TaskAttachmentSimple;
+use crate::models::availability::Availability;
+use crate::models::list::ListParams;
+use crate::models::operation::Operation;
+use crate::models::params::{ColumnPath, TaskPath};
+use crate::models::report::{WeeklyDigest, WeeklyDigestQuery};
+use crate::models::task::{TaskResponse, BoardDiff, BoardDiffQuery, CreateTaskRequest, TaskDefaults, UpdateTaskDefaultsRequest, ColumnPolicy, SetColumnPolicyRequest, SlaRule, CreateSlaRuleRequest, SlaBreach, SlaBreachQuery, EscalationStep, SetEscalationChainRequest, Escalation, BusinessCalendar, UpdateBusinessCalendarRequest, TaskDraft, SaveTaskDraftRequest, TaskListQuery, UpdateTaskRequest, TransferTaskRequest, Team, TaskEvent, ExportQuery, ImportQuery, ImportReport, ImportRowError};
+use crate::models::ids::{TaskId, TeamId, UserId};
+use crate::services::{audit, availability, board_diff, business_hours, column_policies, encryption, escalations, operations, outbox, scripts, sla, task_defaults, task_drafts, task_events, task_links, task_writes, weekly_digest};
+use crate::services::email_templates::EmailTemplates;
+use crate::services::task_response::TaskResponseAssembler;
 use crate::utils::errors::ServiceError;
+use crate::utils::locale;
+use crate::utils::sql::{Select, Sort};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String, // Subject (user id)
-    pub username: String,
-    pub name: String,
-    pub exp: usize, // Expiration time (Unix timestamp)
-    pub iat: usize, // Issued at (Unix timestamp)
-}
-
-// Helper function to extract user ID from JWT token
-async fn get_user_from_token(req: &HttpRequest, config: &AppConfig) -> Result<i32, ServiceError> {
-    let auth_header = req.headers().get("Authorization")
-        .and_then(|h| h.to_str().ok())
-        .and_then(|h| h.strip_prefix("Bearer "));
-
-    let token = auth_header.ok_or_else(|| {
-        ServiceError::Unauthorized("Authentication required".to_string())
-    })?;
-
-    let claims = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(config.jwt_secret.as_ref()),
-        &Validation::default(),
-    )
-    .map_err(|_| ServiceError::Unauthorized("Invalid token".to_string()))?;
-
-    let user_id: i32 = claims.claims.sub.parse()
-        .map_err(|_| ServiceError::Unauthorized("Invalid user ID in token".to_string()))?;
-
-    Ok(user_id)
-}
-
-// Helper function to get team IDs from team names
-async fn get_team_ids_from_names(db: &Database, team_names: &[String]) -> Result<Vec<i32>, ServiceError> {
-    let mut team_ids = Vec::new();
-    
-    for team_name in team_names {
-        let team_row = sqlx::query(
-            "SELECT id FROM teams WHERE name = $1"
-        )
-        .bind(team_name)
-        .fetch_optional(&db.pool)
-        .await
-        .map_err(|e| {
-            log::error!("Database error getting team: {}", e);
-            ServiceError::DatabaseError("Failed to query team".to_string())
-        })?;
-
-        if let Some(row) = team_row {
-            team_ids.push(row.get("id"));
-        } else {
-            return Err(ServiceError::ValidationError(format!("Team '{}' not found", team_name)));
-        }
+// Checks a new task must pass beyond normalization, for created tasks and
+// published drafts alike
+fn validate_new_task(task_req: &CreateTaskRequest) -> Result<(), ServiceError> {
+    // Validate input
+    if task_req.name.trim().is_empty() {
+        return Err(ServiceError::ValidationError("Task name is required".to_string()));
     }
 
-    Ok(team_ids)
-}
-
-// Helper function to get teams for a task
-async fn get_task_teams(db: &Database, task_id: i32) -> Result<Vec<String>, ServiceError> {
-    let team_rows = sqlx::query(
-        "SELECT t.name FROM teams t 
-         JOIN task_teams tt ON t.id = tt.team_id 
-         WHERE tt.task_id = $1"
-    )
-    .bind(task_id)
-    .fetch_all(&db.pool)
-    .await
-    .map_err(|e| {
-        log::error!("Database error getting task teams: {}", e);
-        ServiceError::DatabaseError("Failed to query task teams".to_string())
-    })?;
-
-    Ok(team_rows.iter().map(|row| row.get("name")).collect())
-}
-
-// Helper function to get attachments for a task
-async fn get_task_attachments(db: &Database, task_id: i32) -> Result<Vec<TaskAttachmentSimple>, ServiceError> {
-    let attachment_rows = sqlx::query(
-        "SELECT file_name, cloudinary_secure_url FROM task_attachments WHERE task_id = $1"
-    )
-    .bind(task_id)
-    .fetch_all(&db.pool)
-    .await
-    .map_err(|e| {
-        log::error!("Database error getting task attachments: {}", e);
-        ServiceError::DatabaseError("Failed to query task attachments".to_string())
-    })?;
-
-    let mut attachments = Vec::new();
-    for row in attachment_rows {
-        let file_name: String = row.get("file_name");
-        let cloudinary_url: String = row.get("cloudinary_secure_url");
-        
-        attachments.push(TaskAttachmentSimple {
-            name: file_name,
-            url: cloudinary_url,
-        });
+    // Validate status
+    let valid_statuses = ["TO_DO", "DOING", "DONE"];
+    if !valid_statuses.contains(&task_req.status.as_str()) {
+        return Err(ServiceError::ValidationError("Invalid task status".to_string()));
     }
-
-    Ok(attachments)
+    Ok(())
 }
 
 /// Create a new task
 #[utoipa::path(
     post,
     path = "/api/tasks",
+    operation_id = "createTask",
     tag = "tasks",
     security(
         ("bearer_auth" = [])
@@ -124,80 +52,58 @@ async fn get_task_attachments(db: &Database, task_id: i32) -> Result<Vec<TaskAtt
     request_body = CreateTaskRequest,
     responses(
         (status = 201, description = "Task created successfully", body = ApiResponse<TaskResponse>),
+        (status = 200, description = "Task with this client_id already exists", body = ApiResponse<TaskResponse>),
         (status = 400, description = "Validation error", body = crate::utils::errors::ServiceError),
         (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
     )
 )]
 pub async fn create_task(
-    req: HttpRequest,
+    user: AuthenticatedUser,
     db: web::Data<Database>,
-    config: web::Data<AppConfig>,
     task_req: web::Json<CreateTaskRequest>,
 ) -> Result<HttpResponse, ServiceError> {
     log::info!("POST /api/tasks - Creating new task: {}", task_req.name);
+    user.requires(Permission::TaskWrite)?;
 
-    let user_id = get_user_from_token(&req, &config).await?;
-
-    // Validate input
-    if task_req.name.trim().is_empty() {
-        return Err(ServiceError::ValidationError("Task name is required".to_string()));
-    }
-
-    // Validate status
-    let valid_statuses = ["TO_DO", "DOING", "DONE"];
-    if !valid_statuses.contains(&task_req.status.as_str()) {
-        return Err(ServiceError::ValidationError("Invalid task status".to_string()));
-    }
+    let user_id = user.id;
+    let mut task_req = task_req.into_inner();
+    task_req.normalize()?;
+    task_defaults::apply(&db, &mut task_req).await?;
+    validate_new_task(&task_req)?;
 
     // Begin transaction
-    let mut tx = db.pool.begin().await
+    let mut tx = db.begin().await
         .map_err(|e| {
             log::error!("Failed to begin transaction: {}", e);
             ServiceError::DatabaseError("Transaction failed".to_string())
         })?;
 
-    // Create task
-    let task_row = sqlx::query(
-        "INSERT INTO tasks (name, description, status, external_link, created_by) 
-         VALUES ($1, $2, $3, $4, $5) 
-         RETURNING id, name, description, status, external_link, created_by, created_at, updated_at"
-    )
-    .bind(&task_req.name)
-    .bind(&task_req.description)
-    .bind(&task_req.status)
-    .bind(&task_req.external_link)
-    .bind(user_id)
-    .fetch_one(&mut *tx)
-    .await
-    .map_err(|e| {
-        log::error!("Database error creating task: {}", e);
-        ServiceError::DatabaseError("Failed to create task".to_string())
-    })?;
+    let team_ids = task_writes::team_ids(&mut tx, task_req.teams.as_deref().unwrap_or_default()).await?;
+
+    // A repeated client_id means the client is retrying a create that
+    // already went through, so hand back the existing task instead
+    let Some(task_response) = task_writes::insert_once(&mut tx, &task_req, user_id, user_id, &team_ids).await? else {
+        drop(tx);
+        let existing = sqlx::query(
+            "SELECT id, name, description, status, external_link, client_id, created_by, created_at, updated_at
+             FROM tasks WHERE client_id = $1"
+        )
+        .bind(task_req.client_id)
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching task by client id: {}", e);
+            ServiceError::DatabaseError("Failed to fetch task".to_string())
+        })?;
 
-    let task_id: i32 = task_row.get("id");
+        let task_id: TaskId = existing.get("id");
+        let task_response = TaskResponseAssembler::preload(&db, &[task_id]).await?
+            .assemble(&existing);
 
-    // Assign teams if provided
-    let mut teams = Vec::new();
-    if let Some(ref team_names) = task_req.teams {
-        if !team_names.is_empty() {
-            let team_ids = get_team_ids_from_names(&db, team_names).await?;
-            
-            for team_id in team_ids {
-                sqlx::query(
-                    "INSERT INTO task_teams (task_id, team_id) VALUES ($1, $2)"
-                )
-                .bind(task_id)
-                .bind(team_id)
-                .execute(&mut *tx)
-                .await
-                .map_err(|e| {
-                    log::error!("Database error assigning team: {}", e);
-                    ServiceError::DatabaseError("Failed to assign team".to_string())
-                })?;
-            }
-            teams = team_names.clone();
-        }
-    }
+        log::info!("Task with client id {:?} already exists as {}", task_req.client_id, task_id);
+        return Ok(HttpResponse::Ok().json(ApiResponse::success("Task already exists", task_response)));
+    };
+    let task_id = task_response.id;
 
     // Commit transaction
     tx.commit().await
@@ -206,19 +112,6 @@ pub async fn create_task(
             ServiceError::DatabaseError("Transaction failed".to_string())
         })?;
 
-    let task_response = TaskResponse {
-        id: task_id,
-        name: task_row.get("name"),
-        description: task_row.get("description"),
-        status: task_row.get("status"),
-        external_link: task_row.get("external_link"),
-        created_by: task_row.get("created_by"),
-        teams,
-        attachments: Vec::new(), // New task has no attachments
-        created_at: task_row.get("created_at"),
-        updated_at: task_row.get("updated_at"),
-    };
-
     log::info!("Task created successfully with ID: {}", task_id);
     Ok(HttpResponse::Created().json(ApiResponse::success("Task created successfully", task_response)))
 }
@@ -227,28 +120,38 @@ pub async fn create_task(
 #[utoipa::path(
     get,
     path = "/api/tasks",
+    operation_id = "listTasks",
     tag = "tasks",
     security(
         ("bearer_auth" = [])
     ),
+    params(TaskListQuery),
     responses(
         (status = 200, description = "Tasks retrieved successfully", body = ApiResponse<Vec<TaskResponse>>),
+        (status = 400, description = "Invalid sort", body = crate::utils::errors::ServiceError),
         (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
     )
 )]
 pub async fn get_tasks(
-    req: HttpRequest,
+    user: AuthenticatedUser,
     db: web::Data<Database>,
-    config: web::Data<AppConfig>,
+    query: web::Query<TaskListQuery>,
 ) -> Result<HttpResponse, ServiceError> {
     log::info!("GET /api/tasks");
-
-    let _user_id = get_user_from_token(&req, &config).await?;
-
-    let task_rows = sqlx::query(
-        "SELECT id, name, description, status, external_link, created_by, created_at, updated_at 
-         FROM tasks ORDER BY created_at DESC"
-    )
+    user.requires(Permission::TaskRead)?;
+
+    let sort = ListParams { sort: query.sort.clone(), ..ListParams::default() }
+        .sort(&[("created_at", "tk.created_at"), ("votes", "c.votes")], "-created_at")?;
+    // Cards carry each task's relations, kept current by triggers, so the
+    // whole board is one query plus the per-request away owners and votes
+    let task_rows = sqlx::query(&format!(
+        "SELECT tk.id, tk.name, tk.description, tk.status, tk.external_link, tk.client_id, tk.created_by,
+                tk.created_at, tk.updated_at,
+                c.teams, c.attachments, c.links_to, c.linked_from, c.votes, c.sla_breached
+         FROM tasks tk JOIN board_cards c ON c.task_id = tk.id
+         ORDER BY {} {}, tk.id DESC",
+        sort.column, if sort.descending { "DESC" } else { "ASC" }
+    ))
     .fetch_all(&db.pool)
     .await
     .map_err(|e| {
@@ -256,40 +159,439 @@ pub async fn get_tasks(
         ServiceError::DatabaseError("Failed to fetch tasks".to_string())
     })?;
 
-    let mut tasks = Vec::new();
-    for row in task_rows {
-        let task_id: i32 = row.get("id");
-        let teams = get_task_teams(&db, task_id).await?;
-        let attachments = get_task_attachments(&db, task_id).await?;
-
-        tasks.push(TaskResponse {
-            id: task_id,
-            name: row.get("name"),
-            description: row.get("description"),
-            status: row.get("status"),
-            external_link: row.get("external_link"),
-            created_by: row.get("created_by"),
-            teams,
-            attachments,
-            created_at: row.get("created_at"),
-            updated_at: row.get("updated_at"),
-        });
-    }
+    let task_ids: Vec<TaskId> = task_rows.iter().map(|row| row.get("id")).collect();
+    let mut assembler = TaskResponseAssembler::for_cards(&db, &task_ids, user.id).await?;
+    let tasks = task_rows.iter()
+        .map(|row| assembler.assemble_card(row))
+        .collect::<Result<Vec<TaskResponse>, ServiceError>>()?;
 
     log::info!("Retrieved {} tasks", tasks.len());
     Ok(HttpResponse::Ok().json(ApiResponse::success("Tasks retrieved successfully", tasks)))
 }
 
+// Every task with its teams and attachments aggregated into the row, so an
+// export is a single streaming query
+const EXPORT_QUERY: &str =
+    "SELECT tk.id, tk.name, tk.description, tk.status, tk.external_link, tk.client_id, tk.created_by,
+            tk.created_at, tk.updated_at,
+            ARRAY(SELECT t.name FROM teams t JOIN task_teams tt ON t.id = tt.team_id
+                  WHERE tt.task_id = tk.id) AS teams,
+            COALESCE((SELECT json_agg(json_build_object('name', a.file_name, 'url', a.cloudinary_secure_url))
+                      FROM task_attachments a
+                      WHERE a.task_id = tk.id AND a.processing_status NOT IN ('flagged', 'rejected')), '[]'::json) AS attachments,
+            COALESCE((SELECT json_agg(json_build_object('id', t.id, 'name', t.name, 'status', t.status) ORDER BY t.id)
+                      FROM task_links l JOIN tasks t ON t.id = l.target_task_id
+                      WHERE l.source_task_id = tk.id), '[]'::json) AS links_to,
+            COALESCE((SELECT json_agg(json_build_object('id', t.id, 'name', t.name, 'status', t.status) ORDER BY t.id)
+                      FROM task_links l JOIN tasks t ON t.id = l.source_task_id
+                      WHERE l.target_task_id = tk.id), '[]'::json) AS linked_from,
+            (SELECT COUNT(*) FROM task_votes v WHERE v.task_id = tk.id) AS votes,
+            EXISTS(SELECT 1 FROM sla_breaches b WHERE b.task_id = tk.id AND b.resolved_at IS NULL) AS sla_breached
+     FROM tasks tk ORDER BY tk.id";
+
+// How many exported rows between progress updates of a background export
+const EXPORT_PROGRESS_INTERVAL: i64 = 100;
+
+// Encode one export row as a newline-terminated JSON line
+fn export_line(row: &sqlx::postgres::PgRow) -> Result<Vec<u8>, ServiceError> {
+    let task = TaskResponseAssembler::assemble_aggregated(row)?;
+    let mut line = serde_json::to_vec(&task).map_err(|e| {
+        log::error!("Failed to serialize exported task: {}", e);
+        ServiceError::InternalError("Export failed".to_string())
+    })?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// Export all tasks as newline-delimited JSON
+#[utoipa::path(
+    get,
+    path = "/api/tasks/export",
+    operation_id = "exportTasks",
+    tag = "tasks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "One task per line", body = TaskResponse, content_type = "application/x-ndjson"),
+        (status = 400, description = "Unsupported format", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 503, description = "Server saturated, retry after the Retry-After delay", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn export_tasks(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/tasks/export");
+    user.requires(Permission::TaskRead)?;
+
+    let format = query.format.as_deref().unwrap_or("ndjson");
+    if format != "ndjson" {
+        return Err(ServiceError::ValidationError(format!("Unsupported export format '{}'", format)));
+    }
+
+    // Rows are read from the database as the client consumes them; the bounded
+    // channel keeps at most a handful of encoded lines in memory at a time
+    let (sender, receiver) = tokio::sync::mpsc::channel::<Result<Bytes, ServiceError>>(32);
+    let pool = db.pool.clone();
+
+    tokio::spawn(async move {
+        let mut rows = sqlx::query(EXPORT_QUERY).fetch(&pool);
+
+        let mut exported = 0;
+        loop {
+            let row = match rows.try_next().await {
+                Ok(Some(row)) => row,
+                Ok(None) => break,
+                Err(e) => {
+                    log::error!("Database error streaming export: {}", e);
+                    let _ = sender.send(Err(ServiceError::DatabaseError("Export failed".to_string()))).await;
+                    return;
+                }
+            };
+
+            let line = match export_line(&row) {
+                Ok(line) => line,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+
+            // Client went away, stop reading from the database
+            if sender.send(Ok(Bytes::from(line))).await.is_err() {
+                log::warn!("Export aborted by client after {} tasks", exported);
+                return;
+            }
+            exported += 1;
+        }
+
+        log::info!("Exported {} tasks", exported);
+    });
+
+    let body = stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|item| (item, receiver))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .insert_header(("Content-Disposition", "attachment; filename=\"tasks.ndjson\""))
+        .streaming(body))
+}
+
+/// Start a background export of all tasks; poll the returned operation for
+/// progress and download the NDJSON file from its result link when done
+#[utoipa::path(
+    post,
+    path = "/api/tasks/export",
+    operation_id = "startTaskExport",
+    tag = "tasks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(ExportQuery),
+    responses(
+        (status = 202, description = "Export started", body = ApiResponse<Operation>),
+        (status = 400, description = "Unsupported format", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 503, description = "Server saturated, retry after the Retry-After delay", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn start_export(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/tasks/export");
+    user.requires(Permission::TaskRead)?;
+
+    let user_id = user.id;
+
+    let format = query.format.as_deref().unwrap_or("ndjson");
+    if format != "ndjson" {
+        return Err(ServiceError::ValidationError(format!("Unsupported export format '{}'", format)));
+    }
+
+    let operation = operations::create(&db, "task_export", user_id).await?;
+    let operation_id = operation.id;
+
+    let pool = db.pool.clone();
+    operations::spawn(db.clone().into_inner(), &operation, move |handle| async move {
+        let total: i64 = sqlx::query("SELECT COUNT(*) AS total FROM tasks")
+            .fetch_one(&pool)
+            .await
+            .map(|row| row.get("total"))
+            .map_err(|e| {
+                log::error!("Database error counting tasks for export: {}", e);
+                ServiceError::DatabaseError("Export failed".to_string())
+            })?;
+
+        let path = operations::result_path(handle.id);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await.map_err(|e| {
+                log::error!("Failed to create export directory: {}", e);
+                ServiceError::InternalError("Export failed".to_string())
+            })?;
+        }
+        let mut file = tokio::fs::File::create(&path).await.map_err(|e| {
+            log::error!("Failed to create export file: {}", e);
+            ServiceError::InternalError("Export failed".to_string())
+        })?;
+
+        let mut rows = sqlx::query(EXPORT_QUERY).fetch(&pool);
+        let mut exported: i64 = 0;
+        while let Some(row) = rows.try_next().await.map_err(|e| {
+            log::error!("Database error during background export: {}", e);
+            ServiceError::DatabaseError("Export failed".to_string())
+        })? {
+            file.write_all(&export_line(&row)?).await.map_err(|e| {
+                log::error!("Failed to write export file: {}", e);
+                ServiceError::InternalError("Export failed".to_string())
+            })?;
+            exported += 1;
+            if exported % EXPORT_PROGRESS_INTERVAL == 0 && total > 0 {
+                handle.progress((exported * 100 / total) as i32).await;
+            }
+        }
+        file.flush().await.map_err(|e| {
+            log::error!("Failed to write export file: {}", e);
+            ServiceError::InternalError("Export failed".to_string())
+        })?;
+
+        log::info!("Background export {} wrote {} tasks", handle.id, exported);
+        Ok(serde_json::json!({
+            "count": exported,
+            "file_name": "tasks.ndjson",
+            "content_type": "application/x-ndjson",
+            "download_url": format!("/api/operations/{}/download", handle.id),
+        }))
+    });
+
+    Ok(HttpResponse::Accepted()
+        .insert_header(("Location", format!("/api/operations/{}", operation_id)))
+        .json(ApiResponse::success("Export started", operation)))
+}
+
+// Largest CSV accepted by the import endpoint
+const MAX_IMPORT_BYTES: usize = 5 * 1024 * 1024;
+const MAX_IMPORT_ROWS: usize = 5000;
+
+// Resolve the CSV column for a mapped field: an explicit mapping must name
+// an existing header, otherwise a header equal to the field name is used
+fn import_column(headers: &[String], field: &str, mapped: Option<&String>) -> Result<Option<usize>, ServiceError> {
+    let find = |name: &str| headers.iter().position(|header| header.trim().eq_ignore_ascii_case(name.trim()));
+    match mapped {
+        Some(name) => find(name).map(Some).ok_or_else(|| {
+            ServiceError::ValidationError(format!("Column '{}' mapped to {} is not in the CSV header", name, field))
+                .with_code("UNKNOWN_COLUMN")
+        }),
+        None => Ok(find(field)),
+    }
+}
+
+/// Import tasks from a CSV file sent as the request body. Rows that fail
+/// validation are reported and skipped; the valid rows are created in a
+/// single transaction.
+#[utoipa::path(
+    post,
+    path = "/api/tasks/import",
+    operation_id = "importTasks",
+    tag = "tasks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(ImportQuery),
+    request_body(content = String, content_type = "text/csv"),
+    responses(
+        (status = 201, description = "Valid rows imported", body = ApiResponse<ImportReport>),
+        (status = 200, description = "Dry run report", body = ApiResponse<ImportReport>),
+        (status = 400, description = "Unreadable CSV or invalid column mapping", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 413, description = "CSV too large", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn import_tasks(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<ImportQuery>,
+    body: Bytes,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/tasks/import - dry run: {}", query.dry_run);
+    user.requires(Permission::TaskWrite)?;
+
+    if query.due_date.is_some() {
+        return Err(ServiceError::ValidationError("Tasks have no due date; remove the due_date mapping".to_string())
+            .with_code("UNKNOWN_COLUMN"));
+    }
+
+    let text = std::str::from_utf8(&body)
+        .map_err(|_| ServiceError::ValidationError("CSV must be UTF-8 encoded".to_string()).with_code("INVALID_CSV"))?;
+    let mut records = crate::utils::csv::parse(text)
+        .map_err(|e| ServiceError::ValidationError(e).with_code("INVALID_CSV"))?
+        .into_iter();
+    let headers = records.next()
+        .ok_or_else(|| ServiceError::ValidationError("CSV is empty".to_string()).with_code("INVALID_CSV"))?;
+    let rows: Vec<Vec<String>> = records.collect();
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(ServiceError::PayloadTooLarge(format!("CSV has more than {} rows", MAX_IMPORT_ROWS)));
+    }
+
+    let name_column = import_column(&headers, "name", query.name.as_ref())?.ok_or_else(|| {
+        ServiceError::ValidationError("No column is mapped to name".to_string()).with_code("UNKNOWN_COLUMN")
+    })?;
+    let status_column = import_column(&headers, "status", query.status.as_ref())?;
+    let description_column = import_column(&headers, "description", query.description.as_ref())?;
+    let link_column = import_column(&headers, "external_link", query.external_link.as_ref())?;
+    let teams_column = import_column(&headers, "teams", query.teams.as_ref())?;
+    let assignee_column = import_column(&headers, "assignee", query.assignee.as_ref())?;
+
+    // Look up every team and assignee the file mentions in one query each
+    let known_teams: HashMap<String, TeamId> = sqlx::query("SELECT id, name FROM teams")
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading teams for import: {}", e);
+            ServiceError::DatabaseError("Failed to load teams".to_string())
+        })?
+        .iter()
+        .map(|row| (row.get("name"), row.get("id")))
+        .collect();
+
+    let usernames: Vec<String> = match assignee_column {
+        Some(column) => rows.iter()
+            .filter_map(|row| row.get(column))
+            .map(|cell| cell.trim().to_string())
+            .filter(|cell| !cell.is_empty())
+            .collect(),
+        None => Vec::new(),
+    };
+    let known_users: HashMap<String, UserId> = sqlx::query("SELECT id, username FROM users WHERE username = ANY($1) AND is_active")
+        .bind(&usernames)
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading assignees for import: {}", e);
+            ServiceError::DatabaseError("Failed to load users".to_string())
+        })?
+        .iter()
+        .map(|row| (row.get("username"), row.get("id")))
+        .collect();
+
+    let column_name = |column: usize| Some(headers[column].trim().to_string());
+    let cell = |row: &[String], column: Option<usize>| {
+        column.and_then(|column| row.get(column))
+            .map(|cell| cell.trim().to_string())
+            .filter(|cell| !cell.is_empty())
+    };
+
+    let mut errors = Vec::new();
+    let mut valid = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        let row_number = index + 2;
+        let mut row_error = |column: Option<String>, message: String| {
+            errors.push(ImportRowError { row: row_number, column, message });
+        };
+
+        if row.len() != headers.len() {
+            row_error(None, format!("Expected {} columns, found {}", headers.len(), row.len()));
+            continue;
+        }
+
+        let status = cell(row, status_column)
+            .map(|status| status.to_uppercase().replace([' ', '-'], "_"))
+            .unwrap_or_else(|| "TO_DO".to_string());
+        if !["TO_DO", "DOING", "DONE"].contains(&status.as_str()) {
+            row_error(status_column.and_then(column_name), format!("Invalid status '{}'", status));
+            continue;
+        }
+
+        let team_names: Vec<String> = cell(row, teams_column)
+            .map(|teams| teams.split(';').map(|t| t.trim().to_string()).filter(|t| !t.is_empty()).collect())
+            .unwrap_or_default();
+        if let Some(unknown) = team_names.iter().find(|team| !known_teams.contains_key(*team)) {
+            row_error(teams_column.and_then(column_name), format!("Team '{}' not found", unknown));
+            continue;
+        }
+        let team_ids: Vec<TeamId> = team_names.iter().map(|team| known_teams[team]).collect();
+
+        let owner = match cell(row, assignee_column) {
+            Some(username) => match known_users.get(&username) {
+                Some(owner) => *owner,
+                None => {
+                    row_error(assignee_column.and_then(column_name), format!("User '{}' not found", username));
+                    continue;
+                }
+            },
+            None => user.id,
+        };
+
+        let mut task = CreateTaskRequest {
+            name: cell(row, Some(name_column)).unwrap_or_default(),
+            description: cell(row, description_column),
+            status,
+            external_link: cell(row, link_column),
+            teams: Some(team_names),
+            client_id: None,
+        };
+        if let Err(e) = task.normalize() {
+            row_error(None, e.public_message());
+            continue;
+        }
+        if task.name.is_empty() {
+            row_error(column_name(name_column), "Name is required".to_string());
+            continue;
+        }
+        valid.push((task, owner, team_ids));
+    }
+
+    let mut report = ImportReport {
+        dry_run: query.dry_run,
+        total_rows: rows.len(),
+        valid_rows: valid.len(),
+        created: Vec::new(),
+        errors,
+    };
+
+    if query.dry_run {
+        log::info!("Import dry run: {} of {} rows valid", report.valid_rows, report.total_rows);
+        return Ok(HttpResponse::Ok().json(ApiResponse::success("Import validated", report)));
+    }
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    for (task, owner, team_ids) in &valid {
+        let created = task_writes::insert(&mut tx, task, *owner, user.id, team_ids).await?;
+        report.created.push(created.id);
+    }
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    log::info!("Imported {} of {} rows for user {}", report.created.len(), report.total_rows, user.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success("Tasks imported successfully", report)))
+}
+
 /// Get a specific task by ID
 #[utoipa::path(
     get,
     path = "/api/tasks/{id}",
+    operation_id = "getTask",
     tag = "tasks",
     security(
         ("bearer_auth" = [])
     ),
     params(
-        ("id" = i32, Path, description = "Task ID")
+        TaskPath
     ),
     responses(
         (status = 200, description = "Task retrieved successfully", body = ApiResponse<TaskResponse>),
@@ -298,18 +600,16 @@ pub async fn get_tasks(
     )
 )]
 pub async fn get_task(
-    req: HttpRequest,
+    user: AuthenticatedUser,
     db: web::Data<Database>,
-    config: web::Data<AppConfig>,
-    path: web::Path<i32>,
+    path: web::Path<TaskPath>,
 ) -> Result<HttpResponse, ServiceError> {
-    let task_id = path.into_inner();
+    let task_id = path.into_inner().id;
     log::info!("GET /api/tasks/{}", task_id);
-
-    let _user_id = get_user_from_token(&req, &config).await?;
+    user.requires(Permission::TaskRead)?;
 
     let task_row = sqlx::query(
-        "SELECT id, name, description, status, external_link, created_by, created_at, updated_at 
+        "SELECT id, name, description, status, external_link, client_id, created_by, created_at, updated_at 
          FROM tasks WHERE id = $1"
     )
     .bind(task_id)
@@ -328,21 +628,9 @@ pub async fn get_task(
         }
     };
 
-    let teams = get_task_teams(&db, task_id).await?;
-    let attachments = get_task_attachments(&db, task_id).await?;
-
-    let task_response = TaskResponse {
-        id: task_row.get("id"),
-        name: task_row.get("name"),
-        description: task_row.get("description"),
-        status: task_row.get("status"),
-        external_link: task_row.get("external_link"),
-        created_by: task_row.get("created_by"),
-        teams,
-        attachments,
-        created_at: task_row.get("created_at"),
-        updated_at: task_row.get("updated_at"),
-    };
+    let task_response = TaskResponseAssembler::preload(&db, &[task_id]).await?
+        .with_votes_of(&db, &[task_id], user.id).await?
+        .assemble(&task_row);
 
     log::info!("Task retrieved: {}", task_id);
     Ok(HttpResponse::Ok().json(ApiResponse::success("Task retrieved successfully", task_response)))
@@ -352,12 +640,13 @@ pub async fn get_task(
 #[utoipa::path(
     put,
     path = "/api/tasks/{id}",
+    operation_id = "updateTask",
     tag = "tasks",
     security(
         ("bearer_auth" = [])
     ),
     params(
-        ("id" = i32, Path, description = "Task ID")
+        TaskPath
     ),
     request_body = UpdateTaskRequest,
     responses(
@@ -367,20 +656,25 @@ pub async fn get_task(
     )
 )]
 pub async fn update_task(
-    req: HttpRequest,
+    user: AuthenticatedUser,
     db: web::Data<Database>,
-    config: web::Data<AppConfig>,
-    path: web::Path<i32>,
+    path: web::Path<TaskPath>,
     update_req: web::Json<UpdateTaskRequest>,
 ) -> Result<HttpResponse, ServiceError> {
-    let task_id = path.into_inner();
+    let task_id = path.into_inner().id;
     log::info!("PUT /api/tasks/{}", task_id);
+    user.requires(Permission::TaskWrite)?;
 
-    let _user_id = get_user_from_token(&req, &config).await?;
+    let user_id = user.id;
+    let mut update_req = update_req.into_inner();
+    update_req.normalize()?;
 
-    // Check if task exists
+    // Check if task exists, keeping its fields for the audit log diff
     let existing_task = sqlx::query(
-        "SELECT id FROM tasks WHERE id = $1"
+        "SELECT name, description, status, external_link,
+                ARRAY(SELECT t.name FROM teams t JOIN task_teams tt ON t.id = tt.team_id
+                      WHERE tt.task_id = tasks.id ORDER BY t.name) AS teams
+         FROM tasks WHERE id = $1"
     )
     .bind(task_id)
     .fetch_optional(&db.pool)
@@ -388,11 +682,16 @@ pub async fn update_task(
     .map_err(|e| {
         log::error!("Database error checking task: {}", e);
         ServiceError::DatabaseError("Failed to check task".to_string())
-    })?;
+    })?
+    .ok_or_else(|| ServiceError::NotFound("Task not found".to_string()).with_code("TASK_NOT_FOUND"))?;
 
-    if existing_task.is_none() {
-        return Err(ServiceError::NotFound("Task not found".to_string()));
-    }
+    let before = serde_json::json!({
+        "name": existing_task.get::<String, _>("name"),
+        "description": encryption::open_text(existing_task.get("description")),
+        "status": existing_task.get::<String, _>("status"),
+        "external_link": existing_task.get::<Option<String>, _>("external_link"),
+        "teams": existing_task.get::<Vec<String>, _>("teams"),
+    });
 
     // Validate status if provided
     if let Some(ref status) = update_req.status {
@@ -403,64 +702,23 @@ pub async fn update_task(
     }
 
     // Begin transaction
-    let mut tx = db.pool.begin().await
+    let mut tx = db.begin().await
         .map_err(|e| {
             log::error!("Failed to begin transaction: {}", e);
             ServiceError::DatabaseError("Transaction failed".to_string())
         })?;
 
-    // Build dynamic update query
-    let mut has_updates = false;
-    let mut query = "UPDATE tasks SET updated_at = NOW()".to_string();
-    let mut bind_index = 1;
-
-    if update_req.name.is_some() {
-        query.push_str(&format!(", name = ${}", bind_index));
-        bind_index += 1;
-        has_updates = true;
-    }
-
-    if update_req.description.is_some() {
-        query.push_str(&format!(", description = ${}", bind_index));
-        bind_index += 1;
-        has_updates = true;
-    }
-
-    if update_req.status.is_some() {
-        query.push_str(&format!(", status = ${}", bind_index));
-        bind_index += 1;
-        has_updates = true;
-    }
-
-    if update_req.external_link.is_some() {
-        query.push_str(&format!(", external_link = ${}", bind_index));
-        bind_index += 1;
-        has_updates = true;
-    }
-
-    query.push_str(&format!(" WHERE id = ${} RETURNING id, name, description, status, external_link, created_by, created_at, updated_at", bind_index));
+    let status_before = task_writes::status_before_update(&mut tx, task_id, &update_req).await?;
 
-    // Execute the update query using QueryBuilder for better type safety
-    let updated_task = if has_updates {
-        let mut query_builder = sqlx::QueryBuilder::new("UPDATE tasks SET updated_at = NOW()");
-        
-        if let Some(ref name) = update_req.name {
-            query_builder.push(", name = ").push_bind(name);
-        }
-        if let Some(ref description) = update_req.description {
-            query_builder.push(", description = ").push_bind(description);
-        }
-        if let Some(ref status) = update_req.status {
-            query_builder.push(", status = ").push_bind(status);
-        }
-        if let Some(ref external_link) = update_req.external_link {
-            query_builder.push(", external_link = ").push_bind(external_link);
-        }
-        
-        query_builder.push(" WHERE id = ").push_bind(task_id);
-        query_builder.push(" RETURNING id, name, description, status, external_link, created_by, created_at, updated_at");
+    // Only the fields present in the request are written
+    let stored_description = encryption::seal_text(update_req.description.as_deref())?;
+    let patch = task_writes::patch(&update_req, stored_description.as_ref());
+    let query = patch.has_changes()
+        .then(|| patch.build("id", task_id, Some("id, name, description, status, external_link, client_id, created_by, created_at, updated_at")))
+        .flatten();
 
-        query_builder.build()
+    let updated_task = if let Some(mut query) = query {
+        query.build()
             .fetch_one(&mut *tx)
             .await
             .map_err(|e| {
@@ -470,7 +728,7 @@ pub async fn update_task(
     } else {
         // No task fields to update, just get current task
         sqlx::query(
-            "SELECT id, name, description, status, external_link, created_by, created_at, updated_at 
+            "SELECT id, name, description, status, external_link, client_id, created_by, created_at, updated_at 
              FROM tasks WHERE id = $1"
         )
         .bind(task_id)
@@ -482,8 +740,10 @@ pub async fn update_task(
         })?
     };
 
+    let mut assembler = TaskResponseAssembler::preload(&db, &[task_id]).await?;
+
     // Update teams if provided
-    let teams = if let Some(ref team_names) = update_req.teams {
+    if let Some(ref team_names) = update_req.teams {
         // Remove existing team assignments
         sqlx::query("DELETE FROM task_teams WHERE task_id = $1")
             .bind(task_id)
@@ -496,7 +756,7 @@ pub async fn update_task(
 
         // Add new team assignments
         if !team_names.is_empty() {
-            let team_ids = get_team_ids_from_names(&db, team_names).await?;
+            let team_ids = task_writes::team_ids(&mut tx, team_names).await?;
             
             for team_id in team_ids {
                 sqlx::query(
@@ -513,11 +773,25 @@ pub async fn update_task(
             }
         }
 
-        team_names.clone()
-    } else {
-        // Keep existing teams
-        get_task_teams(&db, task_id).await?
-    };
+        assembler = assembler.with_task_teams(task_id, team_names.clone());
+    }
+
+    let mut task_response = assembler.assemble(&updated_task);
+    if update_req.description.is_some() {
+        task_response.references = task_links::replace(&mut tx, task_id, task_response.description.as_deref()).await?;
+    }
+
+    // Record only the fields this request touched
+    let changes = task_writes::changed_fields(&update_req);
+    scripts::validate(&mut tx, task_events::TASK_UPDATED, &task_response, Some(&changes)).await?;
+    task_events::append(&mut tx, task_id, task_events::TASK_UPDATED, user_id, &changes).await?;
+    if let Some(assignee) = column_policies::apply(&mut tx, task_id, user_id, status_before.as_deref(), &update_req).await? {
+        task_response.created_by = assignee;
+        task_response.owner_away = availability::load(&db, assignee).await?
+            .filter(|availability| availability.away_now);
+    }
+    outbox::enqueue(&mut tx, "task", task_id.0, "task.updated", &task_response).await?;
+    task_writes::enqueue_status_change(&mut tx, task_id, status_before, &update_req, None).await?;
 
     // Commit transaction
     tx.commit().await
@@ -526,18 +800,9 @@ pub async fn update_task(
             ServiceError::DatabaseError("Transaction failed".to_string())
         })?;
 
-    let task_response = TaskResponse {
-        id: updated_task.get("id"),
-        name: updated_task.get("name"),
-        description: updated_task.get("description"),
-        status: updated_task.get("status"),
-        external_link: updated_task.get("external_link"),
-        created_by: updated_task.get("created_by"),
-        teams,
-        attachments: get_task_attachments(&db, task_id).await?,
-        created_at: updated_task.get("created_at"),
-        updated_at: updated_task.get("updated_at"),
-    };
+    if let Some(diff) = audit::diff(&before, &changes) {
+        request_context::record_changes(diff);
+    }
 
     log::info!("Task updated successfully: {}", task_id);
     Ok(HttpResponse::Ok().json(ApiResponse::success("Task updated successfully", task_response)))
@@ -547,12 +812,13 @@ pub async fn update_task(
 #[utoipa::path(
     delete,
     path = "/api/tasks/{id}",
+    operation_id = "deleteTask",
     tag = "tasks",
     security(
         ("bearer_auth" = [])
     ),
     params(
-        ("id" = i32, Path, description = "Task ID")
+        TaskPath
     ),
     responses(
         (status = 200, description = "Task deleted successfully", body = ApiResponse<bool>),
@@ -561,19 +827,26 @@ pub async fn update_task(
     )
 )]
 pub async fn delete_task(
-    req: HttpRequest,
+    user: AuthenticatedUser,
     db: web::Data<Database>,
-    config: web::Data<AppConfig>,
-    path: web::Path<i32>,
+    path: web::Path<TaskPath>,
 ) -> Result<HttpResponse, ServiceError> {
-    let task_id = path.into_inner();
+    let task_id = path.into_inner().id;
     log::info!("DELETE /api/tasks/{}", task_id);
+    user.requires(Permission::TaskDelete)?;
+
+    let user_id = user.id;
 
-    let _user_id = get_user_from_token(&req, &config).await?;
+    // Begin transaction
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
 
     let result = sqlx::query("DELETE FROM tasks WHERE id = $1")
         .bind(task_id)
-        .execute(&db.pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| {
             log::error!("Database error deleting task: {}", e);
@@ -581,69 +854,1306 @@ pub async fn delete_task(
         })?;
 
     if result.rows_affected() == 0 {
-        return Err(ServiceError::NotFound("Task not found".to_string()));
+        return Err(ServiceError::NotFound("Task not found".to_string()).with_code("TASK_NOT_FOUND"));
     }
 
+    task_events::append(&mut tx, task_id, task_events::TASK_DELETED, user_id, &serde_json::json!({})).await?;
+    outbox::enqueue(&mut tx, "task", task_id.0, "task.deleted", &serde_json::json!({ "id": task_id })).await?;
+
+    // Commit transaction
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
     log::info!("Task deleted successfully: {}", task_id);
     Ok(HttpResponse::Ok().json(ApiResponse::success("Task deleted successfully", true)))
 }
 
-/// Get all teams
+/// Transfer ownership of a task to another user
 #[utoipa::path(
-    get,
-    path = "/api/teams",
-    tag = "teams",
-    security(
+    post,
+    path = "/api/tasks/{id}/transfer",
+    operation_id = "transferTask",
+    tag = "tasks",
+    security(
         ("bearer_auth" = [])
     ),
+    params(
+        TaskPath
+    ),
+    request_body = TransferTaskRequest,
     responses(
-        (status = 200, description = "Teams retrieved successfully", body = ApiResponse<Vec<Team>>),
-        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+        (status = 200, description = "Task transferred successfully; the message warns when the new owner is out of office", body = ApiResponse<TaskResponse>),
+        (status = 400, description = "New owner does not exist", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Only the owner or an administrator may transfer the task", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Task not found", body = crate::utils::errors::ServiceError)
     )
 )]
-pub async fn get_teams(
-    req: HttpRequest,
+pub async fn transfer_task(
+    user: AuthenticatedUser,
     db: web::Data<Database>,
-    config: web::Data<AppConfig>,
+    path: web::Path<TaskPath>,
+    transfer_req: web::Json<TransferTaskRequest>,
 ) -> Result<HttpResponse, ServiceError> {
-    log::info!("GET /api/teams");
+    let task_id = path.into_inner().id;
+    let new_owner_id = transfer_req.new_owner_id;
+    log::info!("POST /api/tasks/{}/transfer", task_id);
+    user.requires(Permission::TaskWrite)?;
+
+    let owner_exists = sqlx::query("SELECT id FROM users WHERE id = $1")
+        .bind(new_owner_id)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking user: {}", e);
+            ServiceError::DatabaseError("Failed to check user".to_string())
+        })?;
+
+    if owner_exists.is_none() {
+        return Err(ServiceError::ValidationError("New owner does not exist".to_string()).with_code("USER_NOT_FOUND"));
+    }
+
+    // Begin transaction
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Lock the task so a concurrent transfer cannot slip in after the check
+    let previous_owner: UserId = sqlx::query_scalar("SELECT created_by FROM tasks WHERE id = $1 FOR UPDATE")
+        .bind(task_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking task: {}", e);
+            ServiceError::DatabaseError("Failed to check task".to_string())
+        })?
+        .ok_or_else(|| ServiceError::NotFound("Task not found".to_string()).with_code("TASK_NOT_FOUND"))?;
 
-    let _user_id = get_user_from_token(&req, &config).await?;
+    if previous_owner != user.id && !user.can(Permission::TaskTransferAny) {
+        return Err(ServiceError::Forbidden("Only the task owner or an administrator can transfer it".to_string())
+            .with_code("TASK_TRANSFER_FORBIDDEN"));
+    }
 
-    let team_rows = sqlx::query(
-        "SELECT id, name, created_at FROM teams ORDER BY name"
+    let updated_task = sqlx::query(
+        "UPDATE tasks SET created_by = $2, updated_at = NOW() WHERE id = $1
+         RETURNING id, name, description, status, external_link, client_id, created_by, created_at, updated_at"
     )
-    .fetch_all(&db.pool)
+    .bind(task_id)
+    .bind(new_owner_id)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| {
-        log::error!("Database error fetching teams: {}", e);
-        ServiceError::DatabaseError("Failed to fetch teams".to_string())
+        log::error!("Database error transferring task: {}", e);
+        ServiceError::DatabaseError("Failed to transfer task".to_string())
     })?;
 
+    let mut task_response = TaskResponseAssembler::preload(&db, &[task_id]).await?
+        .assemble(&updated_task);
+    // The preload cannot see the uncommitted owner change
+    task_response.owner_away = availability::load(&db, new_owner_id).await?
+        .filter(|availability| availability.away_now);
+
+    task_events::append(
+        &mut tx,
+        task_id,
+        task_events::TASK_TRANSFERRED,
+        user.id,
+        &serde_json::json!({ "created_by": new_owner_id, "previous_owner": previous_owner }),
+    ).await?;
+    outbox::enqueue(&mut tx, "task", task_id.0, "task.updated", &task_response).await?;
+
+    // Commit transaction
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    log::info!("Task {} transferred from user {} to user {} by user {}", task_id, previous_owner, new_owner_id, user.id);
+
+    // Still transferred, but warn that the new owner is away and point at
+    // the delegate they chose
+    let message = match &task_response.owner_away {
+        Some(Availability { delegate_id: Some(delegate_id), .. }) => format!(
+            "Task transferred successfully; the new owner is out of office and suggests user {} instead", delegate_id
+        ),
+        Some(_) => "Task transferred successfully; the new owner is out of office".to_string(),
+        None => "Task transferred successfully".to_string(),
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(&message, task_response)))
+}
+
+/// Get the event history of a task
+#[utoipa::path(
+    get,
+    path = "/api/tasks/{id}/events",
+    operation_id = "listTaskEvents",
+    tag = "tasks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        TaskPath
+    ),
+    responses(
+        (status = 200, description = "Task events retrieved successfully", body = ApiResponse<Vec<TaskEvent>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_task_events(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<TaskPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let task_id = path.into_inner().id;
+    log::info!("GET /api/tasks/{}/events", task_id);
+    user.requires(Permission::TaskRead)?;
+
+    let events = task_events::load_events(&db, task_id).await?;
+
+    log::info!("Retrieved {} events for task {}", events.len(), task_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Task events retrieved successfully", events)))
+}
+
+/// Rebuild a task from its event history
+#[utoipa::path(
+    post,
+    path = "/api/tasks/{id}/events/replay",
+    operation_id = "replayTaskEvents",
+    tag = "tasks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        TaskPath
+    ),
+    responses(
+        (status = 200, description = "Task rebuilt successfully", body = ApiResponse<TaskResponse>),
+        (status = 404, description = "Task has no events", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn replay_task_events(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<TaskPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let task_id = path.into_inner().id;
+    log::info!("POST /api/tasks/{}/events/replay", task_id);
+    user.requires(Permission::TaskReplay)?;
+
+    let state = task_events::rebuild_projection(&db, task_id).await?
+        .ok_or_else(|| ServiceError::NotFound("No events recorded for task".to_string()).with_code("TASK_EVENTS_NOT_FOUND"))?;
+
+    if state.deleted {
+        log::info!("Task {} replayed as deleted", task_id);
+        return Ok(HttpResponse::Ok().json(ApiResponse::success("Task not found", None::<TaskResponse>)));
+    }
+
+    let task_response = TaskResponseAssembler::preload(&db, &[task_id]).await?
+        .assemble_state(task_id, state);
+
+    log::info!("Task {} rebuilt from events", task_id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Task rebuilt successfully", task_response)))
+}
+
+/// Get teams, one page at a time
+#[utoipa::path(
+    get,
+    path = "/api/teams",
+    operation_id = "listTeams",
+    tag = "teams",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(ListParams),
+    responses(
+        (status = 200, description = "Teams retrieved successfully; X-Total-Count holds the unpaged total", body = ApiResponse<Vec<Team>>),
+        (status = 400, description = "Invalid list parameters", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_teams(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    params: ListParams,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/teams");
+    user.requires(Permission::TaskRead)?;
+
+    let select = Select::new("teams", "id, name, created_at")
+        .ilike("name", params.search_pattern())
+        .order_by(params.sort(&[("name", "name"), ("created_at", "created_at")], "name")?)
+        .order_by(Sort { column: "id", descending: false })
+        .paginate(params.per_page(), params.offset());
+
+    let total: i64 = select.build_count()
+        .build_query_scalar()
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error counting teams: {}", e);
+            ServiceError::DatabaseError("Failed to fetch teams".to_string())
+        })?;
+
+    let team_rows = select.build()
+        .build()
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching teams: {}", e);
+            ServiceError::DatabaseError("Failed to fetch teams".to_string())
+        })?;
+
     let teams: Vec<Team> = team_rows.iter().map(|row| Team {
         id: row.get("id"),
         name: row.get("name"),
         created_at: row.get("created_at"),
     }).collect();
 
-    log::info!("Retrieved {} teams", teams.len());
-    Ok(HttpResponse::Ok().json(ApiResponse::success("Teams retrieved successfully", teams)))
+    log::info!("Retrieved {} of {} teams", teams.len(), total);
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(ApiResponse::success("Teams retrieved successfully", teams)))
 }
 
-pub fn task_config(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api")
-            .service(
-                web::scope("/tasks")
-                    .route("", web::post().to(create_task))
-                    .route("", web::get().to(get_tasks))
-                    .route("/{id}", web::get().to(get_task))
-                    .route("/{id}", web::put().to(update_task))
-                    .route("/{id}", web::delete().to(delete_task))
-            )
-            .service(
-                web::scope("/teams")
-                    .route("", web::get().to(get_teams))
-            )
-    );
+// Add or withdraw the caller's vote for a task and return the task with its
+// new count
+async fn set_vote(
+    user: &AuthenticatedUser,
+    db: &Database,
+    task_id: TaskId,
+    voted: bool,
+) -> Result<TaskResponse, ServiceError> {
+    let task_row = sqlx::query(
+        "SELECT id, name, description, status, external_link, client_id, created_by, created_at, updated_at
+         FROM tasks WHERE id = $1"
+    )
+    .bind(task_id)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching task: {}", e);
+        ServiceError::DatabaseError("Failed to fetch task".to_string())
+    })?
+    .ok_or_else(|| ServiceError::NotFound("Task not found".to_string()).with_code("TASK_NOT_FOUND"))?;
+
+    let query = if voted {
+        "INSERT INTO task_votes (task_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+    } else {
+        "DELETE FROM task_votes WHERE task_id = $1 AND user_id = $2"
+    };
+    sqlx::query(query)
+        .bind(task_id)
+        .bind(user.id)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error saving vote: {}", e);
+            ServiceError::DatabaseError("Failed to save vote".to_string())
+        })?;
+
+    Ok(TaskResponseAssembler::preload(db, &[task_id]).await?
+        .with_votes_of(db, &[task_id], user.id).await?
+        .assemble(&task_row))
+}
+
+/// Upvote a task. Each user has one vote per task; voting again changes
+/// nothing.
+#[utoipa::path(
+    post,
+    path = "/api/tasks/{id}/vote",
+    operation_id = "voteTask",
+    tag = "tasks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        TaskPath
+    ),
+    responses(
+        (status = 200, description = "Vote recorded", body = ApiResponse<TaskResponse>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Task not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn vote_task(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<TaskPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let task_id = path.into_inner().id;
+    log::info!("POST /api/tasks/{}/vote", task_id);
+    user.requires(Permission::TaskWrite)?;
+
+    let task_response = set_vote(&user, &db, task_id, true).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Vote recorded successfully", task_response)))
+}
+
+/// Withdraw the caller's vote for a task
+#[utoipa::path(
+    delete,
+    path = "/api/tasks/{id}/vote",
+    operation_id = "unvoteTask",
+    tag = "tasks",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        TaskPath
+    ),
+    responses(
+        (status = 200, description = "Vote withdrawn", body = ApiResponse<TaskResponse>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Task not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn unvote_task(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<TaskPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let task_id = path.into_inner().id;
+    log::info!("DELETE /api/tasks/{}/vote", task_id);
+    user.requires(Permission::TaskWrite)?;
+
+    let task_response = set_vote(&user, &db, task_id, false).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Vote withdrawn successfully", task_response)))
+}
+
+/// List the caller's task drafts
+#[utoipa::path(
+    get,
+    path = "/api/tasks/drafts",
+    operation_id = "listDrafts",
+    tag = "drafts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Drafts retrieved successfully", body = ApiResponse<Vec<TaskDraft>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_drafts(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/tasks/drafts");
+    user.requires(Permission::TaskWrite)?;
+
+    let drafts = task_drafts::list(&db, user.id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Drafts retrieved successfully", drafts)))
+}
+
+/// Save an unfinished task as a draft only the caller can see. Any field
+/// may be left out or invalid until the draft is published.
+#[utoipa::path(
+    post,
+    path = "/api/tasks/drafts",
+    operation_id = "createDraft",
+    tag = "drafts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = SaveTaskDraftRequest,
+    responses(
+        (status = 201, description = "Draft saved successfully", body = ApiResponse<TaskDraft>),
+        (status = 400, description = "Field too long", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn create_draft(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    draft_req: web::Json<SaveTaskDraftRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/tasks/drafts");
+    user.requires(Permission::TaskWrite)?;
+
+    let mut draft_req = draft_req.into_inner();
+    draft_req.normalize()?;
+
+    let draft = task_drafts::create(&db, user.id, &draft_req).await?;
+    log::info!("Draft {} saved by user {}", draft.id, user.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success("Draft saved successfully", draft)))
+}
+
+/// Replace the contents of one of the caller's drafts
+#[utoipa::path(
+    put,
+    path = "/api/tasks/drafts/{id}",
+    operation_id = "updateDraft",
+    tag = "drafts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "Draft ID")
+    ),
+    request_body = SaveTaskDraftRequest,
+    responses(
+        (status = 200, description = "Draft saved successfully", body = ApiResponse<TaskDraft>),
+        (status = 400, description = "Field too long", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Draft not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn update_draft(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+    draft_req: web::Json<SaveTaskDraftRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("PUT /api/tasks/drafts/{}", id);
+    user.requires(Permission::TaskWrite)?;
+
+    let mut draft_req = draft_req.into_inner();
+    draft_req.normalize()?;
+
+    let draft = task_drafts::update(&db, user.id, id, &draft_req).await?
+        .ok_or_else(|| ServiceError::NotFound("Draft not found".to_string()).with_code("DRAFT_NOT_FOUND"))?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Draft saved successfully", draft)))
+}
+
+/// Discard one of the caller's drafts
+#[utoipa::path(
+    delete,
+    path = "/api/tasks/drafts/{id}",
+    operation_id = "deleteDraft",
+    tag = "drafts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "Draft ID")
+    ),
+    responses(
+        (status = 200, description = "Draft deleted successfully", body = ApiResponse<bool>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Draft not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn delete_draft(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("DELETE /api/tasks/drafts/{}", id);
+    user.requires(Permission::TaskWrite)?;
+
+    if !task_drafts::delete(&db, user.id, id).await? {
+        return Err(ServiceError::NotFound("Draft not found".to_string()).with_code("DRAFT_NOT_FOUND"));
+    }
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Draft deleted successfully", true)))
+}
+
+/// Publish one of the caller's drafts as a task. The draft gets the same
+/// board defaults and validation as `POST /api/tasks`; if it fails, the
+/// draft is kept unchanged.
+#[utoipa::path(
+    post,
+    path = "/api/tasks/drafts/{id}/publish",
+    operation_id = "publishDraft",
+    tag = "drafts",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "Draft ID")
+    ),
+    responses(
+        (status = 201, description = "Draft published as a task", body = ApiResponse<TaskResponse>),
+        (status = 400, description = "Draft is not a valid task yet", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Draft not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn publish_draft(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("POST /api/tasks/drafts/{}/publish", id);
+    user.requires(Permission::TaskWrite)?;
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    // Taking the draft in the same transaction as the insert publishes it
+    // at most once; any error below rolls back and keeps the draft
+    let draft = task_drafts::take(&mut tx, user.id, id).await?
+        .ok_or_else(|| ServiceError::NotFound("Draft not found".to_string()).with_code("DRAFT_NOT_FOUND"))?;
+
+    let mut task_req = draft.into_create_request();
+    task_req.normalize()?;
+    task_defaults::apply(&db, &mut task_req).await?;
+    validate_new_task(&task_req)?;
+    let team_ids = task_writes::team_ids(&mut tx, task_req.teams.as_deref().unwrap_or_default()).await?;
+
+    let task_response = task_writes::insert(&mut tx, &task_req, user.id, user.id, &team_ids).await?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    log::info!("Draft {} published as task {} by user {}", id, task_response.id, user.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success("Draft published successfully", task_response)))
+}
+
+/// Get the board's defaults for new tasks
+#[utoipa::path(
+    get,
+    path = "/api/board/settings",
+    operation_id = "getBoardSettings",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Task defaults retrieved successfully", body = ApiResponse<TaskDefaults>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_board_settings(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/settings");
+    user.requires(Permission::TaskRead)?;
+
+    let defaults = task_defaults::get(&db).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Task defaults retrieved successfully", defaults)))
+}
+
+/// Replace the board's defaults for new tasks; they apply to tasks created
+/// through `POST /api/tasks` that omit `teams` or `description`
+#[utoipa::path(
+    put,
+    path = "/api/board/settings",
+    operation_id = "updateBoardSettings",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = UpdateTaskDefaultsRequest,
+    responses(
+        (status = 200, description = "Task defaults updated successfully", body = ApiResponse<TaskDefaults>),
+        (status = 400, description = "Validation error or unknown team", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn update_board_settings(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    settings_req: web::Json<UpdateTaskDefaultsRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/board/settings");
+    user.requires(Permission::BoardManage)?;
+
+    let mut settings_req = settings_req.into_inner();
+    settings_req.normalize()?;
+
+    let defaults = task_defaults::save(&db, &settings_req).await?;
+    log::info!("Task defaults updated by user {}", user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Task defaults updated successfully", defaults)))
+}
+
+/// Get the auto-assignment policies of the board's columns
+#[utoipa::path(
+    get,
+    path = "/api/board/column-policies",
+    operation_id = "listColumnPolicies",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Column policies retrieved successfully", body = ApiResponse<Vec<ColumnPolicy>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_column_policies(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/column-policies");
+    user.requires(Permission::TaskRead)?;
+
+    let policies = column_policies::list(&db).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Column policies retrieved successfully", policies)))
+}
+
+/// Set who tasks are assigned to when they move into a column: a fixed user
+/// or, in turn, each active member of a team. The assignment shows in the
+/// task's history as a transfer by whoever moved it, naming the column.
+#[utoipa::path(
+    put,
+    path = "/api/board/column-policies/{status}",
+    operation_id = "setColumnPolicy",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ColumnPath
+    ),
+    request_body = SetColumnPolicyRequest,
+    responses(
+        (status = 200, description = "Column policy saved successfully", body = ApiResponse<ColumnPolicy>),
+        (status = 400, description = "Invalid column, user or team", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn set_column_policy(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<ColumnPath>,
+    policy_req: web::Json<SetColumnPolicyRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    let status = path.into_inner().status;
+    log::info!("PUT /api/board/column-policies/{}", status);
+    user.requires(Permission::BoardManage)?;
+
+    let policy = column_policies::set(&db, &status, &policy_req, user.id).await?;
+    log::info!("Column policy for {} set by user {}", status, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Column policy saved successfully", policy)))
+}
+
+/// Stop auto-assigning tasks that move into a column
+#[utoipa::path(
+    delete,
+    path = "/api/board/column-policies/{status}",
+    operation_id = "deleteColumnPolicy",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ColumnPath
+    ),
+    responses(
+        (status = 200, description = "Column policy removed successfully", body = ApiResponse<bool>),
+        (status = 400, description = "Invalid column", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "Column has no policy", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn delete_column_policy(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<ColumnPath>,
+) -> Result<HttpResponse, ServiceError> {
+    let status = path.into_inner().status;
+    log::info!("DELETE /api/board/column-policies/{}", status);
+    user.requires(Permission::BoardManage)?;
+
+    if !column_policies::remove(&db, &status).await? {
+        return Err(ServiceError::NotFound("Column has no policy".to_string()).with_code("COLUMN_POLICY_NOT_FOUND"));
+    }
+    log::info!("Column policy for {} removed by user {}", status, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Column policy removed successfully", true)))
+}
+
+/// Get the board's SLA rules
+#[utoipa::path(
+    get,
+    path = "/api/board/sla-rules",
+    operation_id = "listSlaRules",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "SLA rules retrieved successfully", body = ApiResponse<Vec<SlaRule>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_sla_rules(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/sla-rules");
+    user.requires(Permission::TaskRead)?;
+
+    let rules = sla::list_rules(&db).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("SLA rules retrieved successfully", rules)))
+}
+
+/// Add an SLA rule: tasks (of `team`, if given) must leave the `status`
+/// column within `max_hours`. Tasks are checked every
+/// SLA_CHECK_INTERVAL_SECS; a breach flags the task with `sla_breached` and
+/// emails its owner.
+#[utoipa::path(
+    post,
+    path = "/api/board/sla-rules",
+    operation_id = "createSlaRule",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = CreateSlaRuleRequest,
+    responses(
+        (status = 201, description = "SLA rule created successfully", body = ApiResponse<SlaRule>),
+        (status = 400, description = "Validation error, invalid column or unknown team", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn create_sla_rule(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    rule_req: web::Json<CreateSlaRuleRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("POST /api/board/sla-rules");
+    user.requires(Permission::BoardManage)?;
+
+    let mut rule_req = rule_req.into_inner();
+    rule_req.normalize()?;
+
+    let rule = sla::create_rule(&db, &rule_req, user.id).await?;
+    log::info!("SLA rule {} created by user {}", rule.id, user.id);
+    Ok(HttpResponse::Created().json(ApiResponse::success("SLA rule created successfully", rule)))
+}
+
+/// Delete an SLA rule; its breaches, open or resolved, go with it
+#[utoipa::path(
+    delete,
+    path = "/api/board/sla-rules/{id}",
+    operation_id = "deleteSlaRule",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "SLA rule ID")
+    ),
+    responses(
+        (status = 200, description = "SLA rule deleted successfully", body = ApiResponse<bool>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "SLA rule not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn delete_sla_rule(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("DELETE /api/board/sla-rules/{}", id);
+    user.requires(Permission::BoardManage)?;
+
+    if !sla::delete_rule(&db, id).await? {
+        return Err(ServiceError::NotFound("SLA rule not found".to_string()).with_code("SLA_RULE_NOT_FOUND"));
+    }
+    log::info!("SLA rule {} deleted by user {}", id, user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("SLA rule deleted successfully", true)))
+}
+
+/// Report SLA breaches, current and resolved, most recent first, one page at
+/// a time
+#[utoipa::path(
+    get,
+    path = "/api/board/sla-breaches",
+    operation_id = "listSlaBreaches",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(SlaBreachQuery, ListParams),
+    responses(
+        (status = 200, description = "SLA breaches retrieved successfully; X-Total-Count holds the unpaged total", body = ApiResponse<Vec<SlaBreach>>),
+        (status = 400, description = "Invalid list parameters", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_sla_breaches(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<SlaBreachQuery>,
+    params: ListParams,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/sla-breaches");
+    user.requires(Permission::TaskRead)?;
+
+    let (breaches, total) = sla::list_breaches(&db, &query, params.per_page(), params.offset()).await?;
+    log::info!("Retrieved {} of {} SLA breaches", breaches.len(), total);
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(ApiResponse::success("SLA breaches retrieved successfully", breaches)))
+}
+
+/// Get the escalation chain followed while SLA breaches stay open
+#[utoipa::path(
+    get,
+    path = "/api/board/escalation-chain",
+    operation_id = "getEscalationChain",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Escalation chain retrieved successfully", body = ApiResponse<Vec<EscalationStep>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_escalation_chain(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/escalation-chain");
+    user.requires(Permission::TaskRead)?;
+
+    let steps = escalations::get_chain(&db).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Escalation chain retrieved successfully", steps)))
+}
+
+/// Replace the escalation chain, e.g. the owner after 4 hours, a team lead
+/// after 24 and the administrators after 72. Each new SLA breach schedules
+/// every step; steps still pending when the task leaves the column are
+/// cancelled.
+#[utoipa::path(
+    put,
+    path = "/api/board/escalation-chain",
+    operation_id = "setEscalationChain",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = SetEscalationChainRequest,
+    responses(
+        (status = 200, description = "Escalation chain saved successfully", body = ApiResponse<Vec<EscalationStep>>),
+        (status = 400, description = "Invalid step or unknown user", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn set_escalation_chain(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    chain_req: web::Json<SetEscalationChainRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/board/escalation-chain");
+    user.requires(Permission::BoardManage)?;
+
+    let mut chain_req = chain_req.into_inner();
+    chain_req.normalize()?;
+
+    let steps = escalations::set_chain(&db, &chain_req, user.id).await?;
+    log::info!("Escalation chain of {} steps set by user {}", steps.len(), user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Escalation chain saved successfully", steps)))
+}
+
+/// Get the escalation steps scheduled for an SLA breach: sent, pending or
+/// cancelled
+#[utoipa::path(
+    get,
+    path = "/api/board/sla-breaches/{id}/escalations",
+    operation_id = "listBreachEscalations",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(
+        ("id" = i32, Path, description = "SLA breach ID")
+    ),
+    responses(
+        (status = 200, description = "Escalations retrieved successfully", body = ApiResponse<Vec<Escalation>>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "SLA breach not found", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn list_breach_escalations(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse, ServiceError> {
+    let id = path.into_inner();
+    log::info!("GET /api/board/sla-breaches/{}/escalations", id);
+    user.requires(Permission::TaskRead)?;
+
+    let escalations = escalations::list_for_breach(&db, id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Escalations retrieved successfully", escalations)))
+}
+
+/// Get the business calendar of working hours and holidays; `data` is left
+/// out when none is set and every hour counts
+#[utoipa::path(
+    get,
+    path = "/api/board/calendar",
+    operation_id = "getBusinessCalendar",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Business calendar retrieved successfully", body = ApiResponse<BusinessCalendar>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_business_calendar(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/calendar");
+    user.requires(Permission::TaskRead)?;
+
+    let calendar = business_hours::get(&db).await?;
+    let message = if calendar.is_some() {
+        "Business calendar retrieved successfully"
+    } else {
+        "No business calendar set; every hour counts"
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse { status: "success".to_string(), message: message.to_string(), data: calendar }))
+}
+
+/// Replace the business calendar. SLA limits and escalation steps then
+/// count only working hours on working days outside holidays, for breaches
+/// checked from now on.
+#[utoipa::path(
+    put,
+    path = "/api/board/calendar",
+    operation_id = "updateBusinessCalendar",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    request_body = UpdateBusinessCalendarRequest,
+    responses(
+        (status = 200, description = "Business calendar saved successfully", body = ApiResponse<BusinessCalendar>),
+        (status = 400, description = "Invalid calendar", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn update_business_calendar(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    calendar_req: web::Json<UpdateBusinessCalendarRequest>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("PUT /api/board/calendar");
+    user.requires(Permission::BoardManage)?;
+
+    let mut calendar_req = calendar_req.into_inner();
+    let hours = calendar_req.normalize()?;
+
+    let calendar = business_hours::save(&db, &calendar_req, hours).await?;
+    log::info!("Business calendar updated by user {}", user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Business calendar saved successfully", calendar)))
+}
+
+/// Remove the business calendar so every hour counts again
+#[utoipa::path(
+    delete,
+    path = "/api/board/calendar",
+    operation_id = "deleteBusinessCalendar",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    responses(
+        (status = 200, description = "Business calendar removed successfully", body = ApiResponse<bool>),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError),
+        (status = 404, description = "No business calendar set", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn delete_business_calendar(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("DELETE /api/board/calendar");
+    user.requires(Permission::BoardManage)?;
+
+    if !business_hours::clear(&db).await? {
+        return Err(ServiceError::NotFound("No business calendar set".to_string()).with_code("CALENDAR_NOT_FOUND"));
+    }
+    log::info!("Business calendar removed by user {}", user.id);
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Business calendar removed successfully", true)))
+}
+
+/// What changed on the board between two points in time: tasks created,
+/// moved, closed and deleted, and team assignment changes, for status reports
+#[utoipa::path(
+    get,
+    path = "/api/board/diff",
+    operation_id = "getBoardDiff",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(BoardDiffQuery),
+    responses(
+        (status = 200, description = "Board diff computed successfully", body = ApiResponse<BoardDiff>),
+        (status = 400, description = "from is not before to", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn get_board_diff(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    query: web::Query<BoardDiffQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/diff");
+    user.requires(Permission::TaskRead)?;
+
+    let to = query.to.unwrap_or_else(Utc::now);
+    if query.from >= to {
+        return Err(ServiceError::ValidationError("from must be before to".to_string()).with_code("INVALID_DATE_RANGE"));
+    }
+
+    let diff = board_diff::compute(&db, query.from, to).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success("Board diff computed successfully", diff)))
+}
+
+/// Preview the weekly digest emailed to admins once WEEKLY_DIGEST_AT is
+/// set: as data, as an HTML page, or as the email text
+#[utoipa::path(
+    get,
+    path = "/api/board/weekly-digest",
+    operation_id = "previewWeeklyDigest",
+    tag = "board",
+    security(
+        ("bearer_auth" = [])
+    ),
+    params(WeeklyDigestQuery),
+    responses(
+        (status = 200, description = "Weekly digest built successfully", body = ApiResponse<WeeklyDigest>),
+        (status = 400, description = "Unsupported format or locale", body = crate::utils::errors::ServiceError),
+        (status = 401, description = "Unauthorized", body = crate::utils::errors::ServiceError),
+        (status = 403, description = "Not an administrator", body = crate::utils::errors::ServiceError)
+    )
+)]
+pub async fn preview_weekly_digest(
+    user: AuthenticatedUser,
+    db: web::Data<Database>,
+    templates: web::Data<EmailTemplates>,
+    query: web::Query<WeeklyDigestQuery>,
+) -> Result<HttpResponse, ServiceError> {
+    log::info!("GET /api/board/weekly-digest");
+    user.requires(Permission::BoardManage)?;
+
+    let format = query.format.as_deref().unwrap_or("json");
+    if !["json", "html", "text"].contains(&format) {
+        return Err(ServiceError::ValidationError(format!("Unsupported digest format '{}'", format)));
+    }
+    let locale = query.locale.as_deref().map_or(Ok(locale::ENGLISH), locale::parse)?;
+
+    let digest = weekly_digest::build(&db, query.to.unwrap_or_else(Utc::now)).await?;
+    match format {
+        "html" => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(weekly_digest::render_html(&digest)?)),
+        "text" => {
+            let rendered = weekly_digest::render_email(&db, &templates, &digest, locale).await?;
+            Ok(HttpResponse::Ok()
+                .content_type("text/plain; charset=utf-8")
+                .body(format!("{}\n\n{}", rendered.subject, rendered.body)))
+        }
+        _ => Ok(HttpResponse::Ok().json(ApiResponse::success("Weekly digest built successfully", digest))),
+    }
+}
+
+pub fn task_config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/tasks")
+            .route("", web::post().to(create_task))
+            .route("", web::get().to(get_tasks))
+            .route("/export", web::get().to(export_tasks))
+            .route("/export", web::post().to(start_export))
+            .route("/drafts", web::get().to(list_drafts))
+            .route("/drafts", web::post().to(create_draft))
+            .route("/drafts/{id}", web::put().to(update_draft))
+            .route("/drafts/{id}", web::delete().to(delete_draft))
+            .route("/drafts/{id}/publish", web::post().to(publish_draft))
+            .service(
+                web::resource("/import")
+                    .app_data(web::PayloadConfig::new(MAX_IMPORT_BYTES))
+                    .route(web::post().to(import_tasks))
+            )
+            .route("/{id}", web::get().to(get_task))
+            .route("/{id}", web::put().to(update_task))
+            .route("/{id}", web::delete().to(delete_task))
+            .route("/{id}/transfer", web::post().to(transfer_task))
+            .route("/{id}/vote", web::post().to(vote_task))
+            .route("/{id}/vote", web::delete().to(unvote_task))
+            .route("/{id}/events", web::get().to(get_task_events))
+            .route("/{id}/events/replay", web::post().to(replay_task_events))
+    )
+    .service(
+        web::scope("/api/teams")
+            .route("", web::get().to(get_teams))
+    )
+    .service(
+        web::scope("/api/board")
+            .route("/settings", web::get().to(get_board_settings))
+            .route("/settings", web::put().to(update_board_settings))
+            .route("/column-policies", web::get().to(list_column_policies))
+            .route("/column-policies/{status}", web::put().to(set_column_policy))
+            .route("/column-policies/{status}", web::delete().to(delete_column_policy))
+            .route("/sla-rules", web::get().to(list_sla_rules))
+            .route("/sla-rules", web::post().to(create_sla_rule))
+            .route("/sla-rules/{id}", web::delete().to(delete_sla_rule))
+            .route("/sla-breaches", web::get().to(list_sla_breaches))
+            .route("/sla-breaches/{id}/escalations", web::get().to(list_breach_escalations))
+            .route("/escalation-chain", web::get().to(get_escalation_chain))
+            .route("/escalation-chain", web::put().to(set_escalation_chain))
+            .route("/calendar", web::get().to(get_business_calendar))
+            .route("/calendar", web::put().to(update_business_calendar))
+            .route("/calendar", web::delete().to(delete_business_calendar))
+            .route("/diff", web::get().to(get_board_diff))
+            .route("/weekly-digest", web::get().to(preview_weekly_digest))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db::{signed_in, QueryCounter, TestDb};
+
+    async fn add_related_tasks(test: &TestDb, count: usize, user: UserId) {
+        for i in 0..count {
+            let task = test.insert_task(&format!("task {}", i), user).await;
+            let other = test.insert_task(&format!("linked {}", i), user).await;
+            test.relate(task, other, user).await;
+        }
+    }
+
+    // List the tasks and return them with how many statements it took
+    async fn list_tasks(test: &TestDb, user: UserId) -> (Vec<serde_json::Value>, usize) {
+        let (response, queries) = QueryCounter::count(get_tasks(
+            signed_in(user, "member"),
+            test.db.clone(),
+            web::Query(TaskListQuery { sort: None }),
+        )).await;
+        let response = response.expect("list tasks");
+        let body = actix_web::body::to_bytes(response.into_body()).await.expect("read body");
+        let body: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        (body["data"].as_array().expect("task list").clone(), queries)
+    }
+
+    #[tokio::test]
+    async fn listing_tasks_takes_a_fixed_number_of_queries() {
+        let Some(test) = TestDb::create().await else { return };
+        let user = test.insert_user("lister", "member").await;
+
+        add_related_tasks(&test, 3, user).await;
+        let (tasks, few) = list_tasks(&test, user).await;
+        assert_eq!(tasks.len(), 6);
+
+        add_related_tasks(&test, 12, user).await;
+        let (tasks, many) = list_tasks(&test, user).await;
+        assert_eq!(tasks.len(), 30);
+
+        // The board cards, then one batched query each for away owners and
+        // the viewer's votes
+        assert_eq!(few, 3);
+        assert_eq!(many, few);
+
+        test.drop().await;
+    }
+
+    #[tokio::test]
+    async fn board_cards_follow_changes_to_relations() {
+        let Some(test) = TestDb::create().await else { return };
+        let user = test.insert_user("carder", "member").await;
+        let task = test.insert_task("card", user).await;
+        let other = test.insert_task("other", user).await;
+        test.relate(task, other, user).await;
+
+        let card = |tasks: &[serde_json::Value]| tasks.iter()
+            .find(|t| t["id"] == serde_json::json!(task.0))
+            .cloned()
+            .expect("card listed");
+
+        let (tasks, _) = list_tasks(&test, user).await;
+        let listed = card(&tasks);
+        assert_eq!(listed["teams"].as_array().map(Vec::len), Some(1));
+        assert_eq!(listed["attachments"][0]["name"], "a.txt");
+        assert_eq!(listed["references"][0]["name"], "other");
+        assert_eq!(listed["votes"], 1);
+        assert_eq!(listed["voted_by_me"], true);
+        assert_eq!(listed["sla_breached"], true);
+
+        sqlx::raw_sql(&format!(
+            "DELETE FROM task_votes WHERE task_id = {task};
+             UPDATE sla_breaches SET resolved_at = NOW() WHERE task_id = {task};
+             UPDATE task_attachments SET processing_status = 'flagged' WHERE task_id = {task};
+             UPDATE tasks SET name = 'renamed' WHERE id = {other};
+             UPDATE teams SET name = 'RENAMED' WHERE id IN (SELECT team_id FROM task_teams WHERE task_id = {task})",
+            task = task, other = other
+        ))
+        .execute(&test.db.pool)
+        .await
+        .expect("change relations");
+
+        let (tasks, _) = list_tasks(&test, user).await;
+        let listed = card(&tasks);
+        assert_eq!(listed["teams"][0], "RENAMED");
+        assert_eq!(listed["attachments"].as_array().map(Vec::len), Some(0));
+        assert_eq!(listed["references"][0]["name"], "renamed");
+        assert_eq!(listed["votes"], 0);
+        assert_eq!(listed["voted_by_me"], false);
+        assert_eq!(listed["sla_breached"], false);
+
+        test.drop().await;
+    }
+
+    #[tokio::test]
+    async fn retried_creates_return_the_original_task() {
+        let Some(test) = TestDb::create().await else { return };
+        let user = test.insert_user("creator", "member").await;
+        let request = |teams: Vec<&str>| web::Json(CreateTaskRequest {
+            name: "offline task".to_string(),
+            description: Some("written on a plane".to_string()),
+            status: "TO_DO".to_string(),
+            external_link: None,
+            teams: Some(teams.into_iter().map(str::to_string).collect()),
+            client_id: Some(uuid::Uuid::nil()),
+        });
+        let create = |teams| create_task(signed_in(user, "member"), test.db.clone(), request(teams));
+
+        let created = create(vec!["FRONTEND", "BACKEND"]).await.expect("create task");
+        assert_eq!(created.status(), actix_web::http::StatusCode::CREATED);
+        let body = actix_web::body::to_bytes(created.into_body()).await.expect("read body");
+        let created: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        assert_eq!(created["data"]["teams"], serde_json::json!(["FRONTEND", "BACKEND"]));
+
+        let retried = create(vec!["FRONTEND", "BACKEND"]).await.expect("retry create");
+        assert_eq!(retried.status(), actix_web::http::StatusCode::OK);
+        let body = actix_web::body::to_bytes(retried.into_body()).await.expect("read body");
+        let retried: serde_json::Value = serde_json::from_slice(&body).expect("JSON body");
+        assert_eq!(retried["data"]["id"], created["data"]["id"]);
+
+        let error = create(vec!["NOBODY"]).await.expect_err("unknown team");
+        assert_eq!(error.error_code(), "TEAM_NOT_FOUND");
+
+        let (tasks, events): (i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM tasks), (SELECT COUNT(*) FROM task_events)"
+        )
+        .fetch_one(&test.db.pool)
+        .await
+        .expect("count rows");
+        assert_eq!((tasks, events), (1, 1));
+
+        test.drop().await;
+    }
 }
@@ -1,9 +1,10 @@
-use actix_web::{web, App, HttpServer, middleware::Logger};
+use actix_web::{guard, web, App, HttpServer, error::JsonPayloadError, middleware::{Compress, Condition, Logger}};
 use actix_cors::Cors;
-use env_logger;
+use std::sync::Arc;
+use std::time::Duration;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
-use utoipa::{Modify, openapi::security::{SecurityScheme, HttpAuthScheme, Http}};
+use utoipa::{Modify, openapi::security::{ApiKey, ApiKeyValue, SecurityScheme, HttpAuthScheme, Http}};
 
 mod config;
 mod database;
@@ -13,9 +14,26 @@ mod handlers;
 mod middleware;
 mod utils;
 
-use config::AppConfig;
+use config::{ApiDocsAccess, AppConfig, LogFormat};
 use database::Database;
-use handlers::{auth_config, task_config, file_config, health};
+use handlers::{auth_config, task_config, file_config, events_config, sync_config, operations_config, admin_config, invitation_config, feedback_config, report_config, health};
+use middleware::{AccessLog, AdminSurface, AuditLog, CatchPanic, CompressionFilter, LoadShedder, PropagateContext, RateLimit, RequestTimeout, SecurityHeaders, ServerTiming};
+use services::mailer::{self, Mailer};
+use services::{encryption, index_advisor, outbox, sla, telemetry, usage, weekly_digest};
+use services::outbox::Fanout;
+use services::pool_monitor::PoolMonitor;
+use services::email_templates::EmailTemplates;
+use services::feedback::FeedbackLimiter;
+use services::login_limiter::LoginLimiter;
+use services::plugins::PluginRegistry;
+use services::scripts::ScriptRunner;
+use services::realtime::Broker;
+use utils::boot_report::BootReport;
+use utils::docs_session;
+use utils::errors::ServiceError;
+use utils::jwt::JwtKeys;
+use utils::logging;
+use utils::text;
 
 struct SecurityAddon;
 
@@ -25,7 +43,11 @@ impl Modify for SecurityAddon {
             components.add_security_scheme(
                 "bearer_auth",
                 SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer))
-            )
+            );
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key")))
+            );
         }
     }
 }
@@ -35,17 +57,108 @@ impl Modify for SecurityAddon {
     paths(
         handlers::auth::login,
         handlers::auth::logout,
+        handlers::auth::logout_all,
+        handlers::auth::refresh_token,
         handlers::auth::get_me,
+        handlers::auth::update_me,
+        handlers::auth::get_my_availability,
+        handlers::auth::set_my_availability,
+        handlers::auth::get_my_locale,
+        handlers::auth::set_my_locale,
+        handlers::auth::get_my_email,
+        handlers::auth::set_my_email,
+        handlers::auth::verify_email,
+        handlers::auth::get_my_usage,
+        handlers::auth::change_password,
+        handlers::auth::forgot_password,
+        handlers::auth::reset_password,
+        handlers::auth::request_magic_link,
+        handlers::auth::verify_magic_link,
+        handlers::auth::accept_invite,
+        handlers::auth::create_api_key,
+        handlers::auth::revoke_api_key,
+        handlers::auth::oauth_authorize,
+        handlers::auth::oauth_callback,
+        handlers::auth::jwks,
         handlers::task::create_task,
         handlers::task::get_tasks,
+        handlers::task::export_tasks,
+        handlers::task::start_export,
+        handlers::task::import_tasks,
+        handlers::task::list_drafts,
+        handlers::task::create_draft,
+        handlers::task::update_draft,
+        handlers::task::delete_draft,
+        handlers::task::publish_draft,
         handlers::task::get_task,
         handlers::task::update_task,
         handlers::task::delete_task,
+        handlers::task::transfer_task,
+        handlers::task::vote_task,
+        handlers::task::unvote_task,
         handlers::task::get_teams,
+        handlers::task::get_board_settings,
+        handlers::task::update_board_settings,
+        handlers::task::list_column_policies,
+        handlers::task::set_column_policy,
+        handlers::task::delete_column_policy,
+        handlers::task::list_sla_rules,
+        handlers::task::create_sla_rule,
+        handlers::task::delete_sla_rule,
+        handlers::task::list_sla_breaches,
+        handlers::task::list_breach_escalations,
+        handlers::task::get_escalation_chain,
+        handlers::task::set_escalation_chain,
+        handlers::task::get_business_calendar,
+        handlers::task::update_business_calendar,
+        handlers::task::delete_business_calendar,
+        handlers::task::get_board_diff,
+        handlers::task::preview_weekly_digest,
+        handlers::report::get_activity_heatmap,
+        handlers::report::get_forecast,
+        handlers::admin::list_index_advice,
+        handlers::admin::apply_index_advice,
+        handlers::task::get_task_events,
+        handlers::task::replay_task_events,
         handlers::file::upload_file,
         handlers::file::get_task_attachments,
         handlers::file::download_file,
+        handlers::file::get_attachment_preview,
         handlers::file::delete_attachment,
+        handlers::file::delete_attachments,
+        handlers::file::get_attachment_downloads,
+        handlers::file::get_storage_report,
+        handlers::events::stream_events,
+        handlers::events::websocket_events,
+        handlers::sync::get_changes,
+        handlers::sync::push_changes,
+        handlers::operations::get_operation,
+        handlers::operations::download_operation_result,
+        handlers::admin::list_dead_letters,
+        handlers::admin::get_dead_letter,
+        handlers::admin::requeue_dead_letter,
+        handlers::admin::delete_dead_letter,
+        handlers::admin::purge_dead_letters,
+        handlers::admin::offboard_user,
+        handlers::admin::deactivate_user,
+        handlers::admin::start_docs_session,
+        handlers::admin::list_permissions,
+        handlers::admin::list_usage,
+        handlers::admin::list_scripts,
+        handlers::admin::create_script,
+        handlers::admin::update_script,
+        handlers::admin::delete_script,
+        handlers::admin::list_email_templates,
+        handlers::admin::update_email_template,
+        handlers::admin::reset_email_template,
+        handlers::admin::preview_email_template,
+        handlers::admin::list_moderation_reviews,
+        handlers::admin::list_audit_logs,
+        handlers::admin::approve_moderation_review,
+        handlers::admin::reject_moderation_review,
+        handlers::invitation::create_invitation,
+        handlers::feedback::submit_feedback,
+        handlers::feedback::get_feedback_submission,
     ),
     components(
         schemas(
@@ -54,16 +167,78 @@ impl Modify for SecurityAddon {
             models::auth::UserResponse,
             models::auth::ApiResponse<models::auth::LoginResponseData>,
             models::auth::ApiResponse<models::auth::UserResponse>,
+            models::auth::UpdateProfileRequest,
+            models::availability::Availability,
+            models::availability::SetAvailabilityRequest,
+            models::auth::ApiResponse<models::availability::Availability>,
+            models::auth::ChangePasswordRequest,
+            models::auth::ChangePasswordResponse,
+            models::auth::ApiResponse<models::auth::ChangePasswordResponse>,
+            models::auth::ForgotPasswordRequest,
+            models::auth::ResetPasswordRequest,
             models::auth::ApiResponse<bool>,
+            models::api_key::CreateApiKeyRequest,
+            models::api_key::ApiKeyResponse,
+            models::api_key::CreatedApiKey,
+            models::auth::ApiResponse<models::api_key::CreatedApiKey>,
             models::auth::ErrorResponse,
             models::task::Task,
             models::task::TaskResponse,
+            models::task::TaskReference,
             models::task::CreateTaskRequest,
             models::task::UpdateTaskRequest,
+            models::task::TransferTaskRequest,
+            models::task::TaskDraft,
+            models::task::SaveTaskDraftRequest,
+            models::auth::ApiResponse<models::task::TaskDraft>,
+            models::auth::ApiResponse<Vec<models::task::TaskDraft>>,
+            models::task::TaskDefaults,
+            models::task::UpdateTaskDefaultsRequest,
+            models::auth::ApiResponse<models::task::TaskDefaults>,
+            models::task::ColumnPolicy,
+            models::task::SetColumnPolicyRequest,
+            models::auth::ApiResponse<models::task::ColumnPolicy>,
+            models::auth::ApiResponse<Vec<models::task::ColumnPolicy>>,
+            models::task::SlaRule,
+            models::task::CreateSlaRuleRequest,
+            models::task::SlaBreach,
+            models::auth::ApiResponse<models::task::SlaRule>,
+            models::auth::ApiResponse<Vec<models::task::SlaRule>>,
+            models::auth::ApiResponse<Vec<models::task::SlaBreach>>,
+            models::task::EscalationStep,
+            models::task::SetEscalationChainRequest,
+            models::task::Escalation,
+            models::auth::ApiResponse<Vec<models::task::EscalationStep>>,
+            models::auth::ApiResponse<Vec<models::task::Escalation>>,
+            models::task::BusinessCalendar,
+            models::task::Holiday,
+            models::task::UpdateBusinessCalendarRequest,
+            models::auth::ApiResponse<models::task::BusinessCalendar>,
+            models::task::BoardDiff,
+            models::task::TaskMove,
+            models::task::TaskTeamChange,
+            models::auth::ApiResponse<models::task::BoardDiff>,
+            models::report::Contributor,
+            models::report::WeeklyDigest,
+            models::auth::ApiResponse<models::report::WeeklyDigest>,
+            models::report::ActivityDay,
+            models::report::UserActivity,
+            models::report::ActivityHeatmap,
+            models::auth::ApiResponse<models::report::ActivityHeatmap>,
+            models::report::ForecastBand,
+            models::report::Forecast,
+            models::auth::ApiResponse<models::report::Forecast>,
+            models::admin::IndexAdvice,
+            models::auth::ApiResponse<Vec<models::admin::IndexAdvice>>,
             models::task::Team,
+            models::task::TaskEvent,
             models::auth::ApiResponse<models::task::TaskResponse>,
             models::auth::ApiResponse<Vec<models::task::TaskResponse>>,
+            models::task::ImportRowError,
+            models::task::ImportReport,
+            models::auth::ApiResponse<models::task::ImportReport>,
             models::auth::ApiResponse<Vec<models::task::Team>>,
+            models::auth::ApiResponse<Vec<models::task::TaskEvent>>,
             models::file::TaskAttachment,
             models::file::AttachmentResponse,
             models::file::UploadResponse,
@@ -72,15 +247,95 @@ impl Modify for SecurityAddon {
             models::file::UploadFileRequest,
             models::auth::ApiResponse<models::file::UploadResponse>,
             models::auth::ApiResponse<Vec<models::file::AttachmentResponse>>,
+            models::file::AttachmentDownload,
+            models::auth::ApiResponse<Vec<models::file::AttachmentDownload>>,
+            models::file::StoredFile,
+            models::file::DeleteAttachmentsRequest,
+            models::file::DeletedAttachments,
+            models::auth::ApiResponse<models::file::DeletedAttachments>,
+            models::file::StorageReport,
+            models::auth::ApiResponse<models::file::StorageReport>,
+            models::sync::SyncResponse,
+            models::auth::ApiResponse<models::sync::SyncResponse>,
+            models::sync::SyncPushRequest,
+            models::sync::TaskChange,
+            models::sync::FieldConflict,
+            models::sync::TaskChangeResult,
+            models::auth::ApiResponse<Vec<models::sync::TaskChangeResult>>,
+            models::operation::Operation,
+            models::auth::ApiResponse<models::operation::Operation>,
+            models::dead_letter::DeadLetter,
+            models::auth::ApiResponse<models::dead_letter::DeadLetter>,
+            models::auth::ApiResponse<Vec<models::dead_letter::DeadLetter>>,
+            models::admin::OffboardUserRequest,
+            models::admin::OffboardReport,
+            models::admin::PermissionPolicy,
+            middleware::Permission,
+            models::auth::ApiResponse<Vec<models::admin::PermissionPolicy>>,
+            models::usage::UsageEntry,
+            models::auth::AcceptInviteRequest,
+            models::auth::MagicLinkRequest,
+            models::auth::MagicLinkLoginRequest,
+            models::invitation::CreateInvitationRequest,
+            models::invitation::Invitation,
+            models::auth::ApiResponse<models::invitation::Invitation>,
+            models::auth::ApiResponse<Vec<models::usage::UsageEntry>>,
+            models::auth::ApiResponse<models::admin::OffboardReport>,
+            models::admin::DeactivationReport,
+            models::auth::ApiResponse<models::admin::DeactivationReport>,
+            models::script::Script,
+            models::script::CreateScriptRequest,
+            models::script::UpdateScriptRequest,
+            models::auth::ApiResponse<models::script::Script>,
+            models::auth::ApiResponse<Vec<models::script::Script>>,
+            models::email_template::EmailTemplate,
+            models::auth::LocaleSetting,
+            models::auth::ApiResponse<models::auth::LocaleSetting>,
+            models::auth::EmailSetting,
+            models::auth::UpdateEmailRequest,
+            models::auth::VerifyEmailRequest,
+            models::auth::ApiResponse<models::auth::EmailSetting>,
+            models::email_template::UpdateEmailTemplateRequest,
+            models::email_template::PreviewEmailTemplateRequest,
+            models::email_template::RenderedEmail,
+            models::auth::ApiResponse<models::email_template::EmailTemplate>,
+            models::auth::ApiResponse<Vec<models::email_template::EmailTemplate>>,
+            models::auth::ApiResponse<models::email_template::RenderedEmail>,
+            models::feedback::SubmitFeedbackRequest,
+            models::feedback::FeedbackReceipt,
+            models::feedback::FeedbackSubmission,
+            models::auth::ApiResponse<models::feedback::FeedbackReceipt>,
+            models::auth::ApiResponse<models::feedback::FeedbackSubmission>,
+            models::moderation::ModerationReview,
+            models::auth::ApiResponse<models::moderation::ModerationReview>,
+            models::auth::ApiResponse<Vec<models::moderation::ModerationReview>>,
+            models::audit::AuditLogEntry,
+            models::auth::ApiResponse<Vec<models::audit::AuditLogEntry>>,
             utils::errors::ServiceError
         )
     ),
     modifiers(&SecurityAddon),
     tags(
         (name = "auth", description = "Authentication endpoints"),
+        (name = "account", description = "The signed-in user's profile, password and API keys"),
         (name = "tasks", description = "Task management endpoints"),
+        (name = "drafts", description = "Task drafts saved before creating a task"),
+        (name = "board", description = "Board-wide settings, column policies, SLAs and the business calendar"),
         (name = "teams", description = "Team management endpoints"),
-        (name = "attachments", description = "File attachment endpoints")
+        (name = "attachments", description = "File attachment endpoints"),
+        (name = "events", description = "Realtime event stream over server-sent events or a WebSocket"),
+        (name = "sync", description = "Offline delta sync endpoints"),
+        (name = "operations", description = "Long-running operation status"),
+        (name = "admin", description = "Administrative endpoints"),
+        (name = "users", description = "User administration"),
+        (name = "email-templates", description = "Editable notification emails"),
+        (name = "scripts", description = "Automation scripts run on task events"),
+        (name = "dead-letters", description = "Outbox deliveries that ran out of retries"),
+        (name = "moderation", description = "Uploads held by the moderation service for review"),
+        (name = "audit", description = "Record of every mutating request"),
+        (name = "reports", description = "Activity reports and forecasts across the board"),
+        (name = "invitations", description = "Invitation-based signup"),
+        (name = "feedback", description = "Public feedback form")
     ),
     info(
         title = "Kanban Backend API",
@@ -94,20 +349,63 @@ impl Modify for SecurityAddon {
 )]
 struct ApiDoc;
 
-// API info endpoint
+// Swagger UI and the OpenAPI document as allowed by API_DOCS. Admin-only docs
+// sit behind a guard checking the docs session cookie; disabled docs are not
+// registered at all. Either way a refused request falls through to a 404.
+fn api_docs_config(access: ApiDocsAccess, secret: String) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |cfg| {
+        let swagger = SwaggerUi::new("/swagger-ui/{_:.*}")
+            .url("/api-docs/openapi.json", ApiDoc::openapi());
+        match access {
+            ApiDocsAccess::Public => {
+                cfg.service(swagger);
+            }
+            ApiDocsAccess::Admin => {
+                cfg.service(
+                    web::scope("")
+                        .guard(guard::fn_guard(move |ctx| docs_session::is_valid(&secret, ctx.head().headers())))
+                        .service(swagger)
+                );
+            }
+            ApiDocsAccess::Disabled => {}
+        }
+    }
+}
+
+// Every route of the handlers; the permission policy lists each guarded one
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.configure(health::configure)
+        .configure(auth_config)
+        // Scopes match by prefix and the first match wins, so nested
+        // scopes must be registered before their parents
+        .configure(file_config)
+        .configure(task_config)
+        .configure(events_config)
+        .configure(sync_config)
+        .configure(operations_config)
+        .configure(admin_config)
+        .configure(invitation_config)
+        .configure(feedback_config)
+        .configure(report_config);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    // Initialize logger
-    env_logger::init();
-    
     // Load and validate configuration
     let config = AppConfig::from_env()
         .expect("Failed to load configuration");
 
+    // Initialize logger in the configured format
+    logging::init(config.log_format);
+
+    // Load the org key sealing descriptions and files on a sensitive board
+    encryption::init(&config);
+
     // Create database connection
-    let database = Database::new(&config.database_url)
+    let database = Database::new(&config.database_url, config.row_level_security)
         .await
-        .expect("Failed to connect to database");
+        .expect("Failed to connect to database")
+        .with_transaction_tagging(config.sql_context_tagging);
 
     // Run database checks
     if let Err(e) = database.health_check().await {
@@ -115,9 +413,22 @@ async fn main() -> std::io::Result<()> {
         std::process::exit(1);
     }
 
-    if let Err(e) = database.check_tables().await {
-        log::error!("Database table check failed: {}", e);
-        std::process::exit(1);
+    let missing_tables = match database.check_tables().await {
+        Ok(missing_tables) => missing_tables,
+        Err(e) => {
+            log::error!("Database table check failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Missing indexes only slow things down, so they are reported, not fatal
+    match index_advisor::advise(&database).await {
+        Ok(advice) => {
+            for advice in &advice {
+                log::warn!("⚠️  Missing index on {}({}): {}. Add it with: {}", advice.table, advice.columns.join(", "), advice.reason, advice.statement);
+            }
+        }
+        Err(e) => log::warn!("Index advisor failed: {}", e),
     }
 
     // Log database stats
@@ -125,18 +436,106 @@ async fn main() -> std::io::Result<()> {
         stats.log_stats();
     }
 
-    println!("🚀 Starting Kanban Backend API on port {}", config.port);
-    println!("🔧 Environment: {}", config.environment);
-    
-    if config.is_development() {
-        println!("📖 Swagger UI available at: http://localhost:{}/swagger-ui/", config.port);
-    }
+    let jwt_keys = match JwtKeys::from_config(&config) {
+        Ok(keys) => web::Data::new(keys),
+        Err(e) => {
+            log::error!("Failed to load JWT keys: {}", e);
+            std::process::exit(1);
+        }
+    };
 
     let port = config.port;
     let server_config = web::Data::new(config.clone());
     let db_data = web::Data::new(database);
 
-    HttpServer::new(move || {
+    // Realtime broker fed by the outbox relay once task events commit
+    let broker = Arc::new(Broker::new(config.realtime_queue_capacity));
+    let broker_data = web::Data::from(broker.clone());
+
+    let mailer: Arc<dyn Mailer> = Arc::from(mailer::from_config(&config));
+    let mailer_data: web::Data<dyn Mailer> = web::Data::from(mailer);
+
+    let email_templates = match EmailTemplates::from_config(&config) {
+        Ok(templates) => web::Data::new(templates),
+        Err(e) => {
+            log::error!("Failed to load email templates: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let plugins = match PluginRegistry::from_config(&config) {
+        Ok(plugins) => Arc::new(plugins),
+        Err(e) => {
+            log::error!("Failed to load plugins: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    outbox::spawn_relay(
+        db_data.clone().into_inner(),
+        Arc::new(Fanout(vec![
+            ("realtime", broker),
+            ("plugins", plugins.clone()),
+            ("scripts", Arc::new(ScriptRunner::new(db_data.clone().into_inner()))),
+        ])),
+        Duration::from_secs(config.outbox_poll_interval_secs),
+        config.outbox_max_attempts,
+    );
+    usage::spawn_flusher(
+        db_data.clone().into_inner(),
+        Duration::from_secs(config.usage_flush_interval_secs),
+    );
+    sla::spawn_checker(
+        db_data.clone().into_inner(),
+        email_templates.clone().into_inner(),
+        mailer_data.clone().into_inner(),
+        Duration::from_secs(config.sla_check_interval_secs),
+    );
+    if let Some((weekday, at)) = config.weekly_digest_at {
+        weekly_digest::spawn_sender(
+            db_data.clone().into_inner(),
+            email_templates.clone().into_inner(),
+            mailer_data.clone().into_inner(),
+            weekday,
+            at,
+        );
+    }
+    telemetry::spawn_reporter(db_data.clone().into_inner(), &config);
+
+    // One probe of the pool for health checks, alerts and load shedding
+    let pool_monitor = PoolMonitor::start(&config, db_data.pool.clone());
+    let pool_monitor_data = web::Data::from(pool_monitor.clone());
+    // Shared across workers so the in-flight count covers the whole process
+    let load_shedder = LoadShedder::new(&config, pool_monitor);
+    let request_timeout = RequestTimeout::new(&config);
+    let server_timing = ServerTiming::new(&config);
+    let rate_limit = RateLimit::new(&config);
+    let security_headers = SecurityHeaders::new(&config);
+    let audit_log = AuditLog::new(db_data.pool.clone());
+    let admin_surface = AdminSurface::new(&config);
+    let admin_bind_address = config.admin_bind_address;
+    let login_limiter = web::Data::new(LoginLimiter::new(&config));
+    let feedback_limiter = web::Data::new(FeedbackLimiter::new(&config));
+    let worker_threads = config.worker_threads;
+    // Oversized JSON bodies get the API's own 413 error instead of actix's
+    // plain-text one
+    let max_json_body_bytes = config.max_json_body_bytes;
+    let json_config = web::JsonConfig::default()
+        .limit(max_json_body_bytes)
+        .error_handler(move |err, _req| match err {
+            JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+                ServiceError::PayloadTooLarge(format!("Request body exceeds {} limit", text::byte_size(max_json_body_bytes)))
+                    .with_code("BODY_TOO_LARGE")
+                    .into()
+            }
+            err => err.into(),
+        });
+    // JSON logs get structured access lines in place of actix's text ones
+    let json_logs = config.log_format == LogFormat::Json;
+    let compression = config.compression;
+    let compression_filter = CompressionFilter::new(&config);
+
+    let server = HttpServer::new(move || {
         let mut cors = Cors::default()
             .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
             .allowed_headers(vec![
@@ -145,29 +544,78 @@ async fn main() -> std::io::Result<()> {
                 "Accept",
                 "Origin",
                 "X-Requested-With",
+                "X-Api-Key",
+            ])
+            .expose_headers(vec![
+                "X-RateLimit-Limit",
+                "X-RateLimit-Remaining",
+                "X-RateLimit-Reset",
+                "X-Total-Count",
+                "Server-Timing",
             ])
             .supports_credentials();
         
         // Add allowed origins
-        for origin in &config.frontend_urls {
+        for origin in &server_config.frontend_urls {
             cors = cors.allowed_origin(origin);
         }
         
         App::new()
             .app_data(server_config.clone())
+            .app_data(json_config.clone())
             .app_data(db_data.clone())
+            .app_data(pool_monitor_data.clone())
+            .app_data(jwt_keys.clone())
+            .app_data(broker_data.clone())
+            .app_data(mailer_data.clone())
+            .app_data(email_templates.clone())
+            .app_data(login_limiter.clone())
+            .app_data(feedback_limiter.clone())
+            .wrap(CatchPanic)
+            .wrap(rate_limit.clone())
+            .wrap(request_timeout.clone())
+            .wrap(server_timing.clone())
+            .wrap(load_shedder.clone())
+            .wrap(Condition::new(compression, compression_filter.clone()))
+            .wrap(Condition::new(compression, Compress::default()))
             .wrap(cors)
-            .wrap(Logger::default())
-            .configure(health::configure)
-            .configure(auth_config)
-            .configure(task_config)
-            .configure(file_config)
-            .service(
-                SwaggerUi::new("/swagger-ui/{_:.*}")
-                    .url("/api-docs/openapi.json", ApiDoc::openapi())
-            )
-    })
-    .bind(format!("0.0.0.0:{}", port))?
-    .run()
-    .await
+            .wrap(audit_log.clone())
+            .wrap(admin_surface.clone())
+            .wrap(Condition::new(json_logs, AccessLog))
+            .wrap(Condition::new(!json_logs, Logger::default()))
+            .wrap(PropagateContext)
+            .wrap(security_headers.clone())
+            .configure(routes)
+            .configure(|cfg| plugins.configure(cfg))
+            .configure(api_docs_config(server_config.api_docs, server_config.jwt_secret.clone()))
+    });
+
+    let server = match worker_threads {
+        Some(workers) => server.workers(workers),
+        None => server,
+    };
+
+    let server = server.bind(format!("0.0.0.0:{}", port))?;
+    let server = match admin_bind_address {
+        Some(addr) => server.bind(addr)?,
+        None => server,
+    };
+
+    // Report only once the sockets are bound, so the readiness file means
+    // the server is actually accepting connections
+    let boot_report = BootReport::new(&config, missing_tables, &server.addrs());
+    boot_report.print(&config);
+    if let Some(ref path) = config.readiness_file {
+        if let Err(e) = boot_report.write_readiness_file(path) {
+            log::warn!("Failed to write readiness file {}: {}", path, e);
+        }
+    }
+
+    let result = server.run().await;
+
+    if let Some(ref path) = config.readiness_file {
+        let _ = std::fs::remove_file(path);
+    }
+
+    result
 }
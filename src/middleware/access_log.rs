@@ -0,0 +1,75 @@
+use std::future::{ready, Ready};
+use std::time::Instant;
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+/// Logs one line per answered request with its method, matched route, status
+/// and latency as structured fields, for the JSON log format. Wrapped inside
+/// `PropagateContext` so the line carries the request and user ids.
+pub struct AccessLog;
+
+impl<S, B> Transform<S, ServiceRequest> for AccessLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AccessLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AccessLogMiddleware { service }))
+    }
+}
+
+pub struct AccessLogMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for AccessLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let result = fut.await;
+            // The route template, e.g. /api/tasks/{id}, groups requests for
+            // dashboards where the raw path would not
+            let (status, route) = match &result {
+                Ok(res) => (res.status(), res.request().match_pattern()),
+                Err(e) => (e.as_response_error().status_code(), None),
+            };
+            let route = route.unwrap_or_else(|| path.clone());
+            let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+            log::info!(
+                target: "access",
+                method = method.as_str(),
+                route = route.as_str(),
+                path = path.as_str(),
+                status = status.as_u16(),
+                latency_ms = (latency_ms * 10.0).round() / 10.0;
+                "{} {} {} {:.1}ms", method, path, status.as_u16(), latency_ms
+            );
+            result
+        })
+    }
+}
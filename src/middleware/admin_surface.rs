@@ -0,0 +1,115 @@
+use std::future::{ready, Ready};
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, ResponseError};
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::AppConfig;
+use crate::utils::errors::ServiceError;
+
+// Probes may hit either listener
+const SHARED_PATHS: &[&str] = &["/health", "/health/ready"];
+
+fn is_admin_path(path: &str) -> bool {
+    path == "/metrics" || path == "/api/admin" || path.starts_with("/api/admin/")
+}
+
+/// Keeps /api/admin/* and /metrics on the admin listener when
+/// ADMIN_BIND_ADDRESS is set: they answer 404 on the public port, and every
+/// other route answers 404 on the admin port. Requests are told apart by the
+/// local port of their connection. Does nothing without an admin address.
+#[derive(Clone)]
+pub struct AdminSurface {
+    admin_port: Option<u16>,
+}
+
+impl AdminSurface {
+    pub fn new(config: &AppConfig) -> Self {
+        AdminSurface { admin_port: config.admin_bind_address.map(|addr| addr.port()) }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AdminSurface
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = AdminSurfaceMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AdminSurfaceMiddleware { service, admin_port: self.admin_port }))
+    }
+}
+
+pub struct AdminSurfaceMiddleware<S> {
+    service: S,
+    admin_port: Option<u16>,
+}
+
+impl<S, B> Service<ServiceRequest> for AdminSurfaceMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let misrouted = self.admin_port.is_some_and(|admin_port| {
+            // Routes are matched on the percent-decoded path, so /%6detrics
+            // reaches /metrics; judge the request by that path, not the raw URI
+            let path = req.match_info().as_str();
+            let on_admin_listener = req.app_config().local_addr().port() == admin_port;
+            !SHARED_PATHS.contains(&path) && on_admin_listener != is_admin_path(path)
+        });
+        if misrouted {
+            let response = ServiceError::NotFound("Not found".to_string()).error_response();
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let fut = self.service.call(req);
+        Box::pin(async move { fut.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+
+    use super::*;
+
+    // Test requests arrive on port 8080, so this makes them public
+    const ADMIN_PORT: u16 = 9000;
+
+    #[actix_web::test]
+    async fn encoded_admin_paths_stay_off_the_public_listener() {
+        let app = init_service(
+            App::new()
+                .wrap(AdminSurface { admin_port: Some(ADMIN_PORT) })
+                .route("/metrics", web::get().to(HttpResponse::Ok))
+                .route("/api/admin/stats", web::get().to(HttpResponse::Ok))
+                .route("/api/tasks", web::get().to(HttpResponse::Ok))
+        ).await;
+
+        for uri in ["/metrics", "/%6detrics", "/api/admin/stats", "/api/%61dmin/stats", "/api/admin/%73tats"] {
+            let res = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), StatusCode::NOT_FOUND, "{} is served publicly", uri);
+        }
+        for uri in ["/api/tasks", "/api/%74asks"] {
+            let res = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+            assert_eq!(res.status(), StatusCode::OK, "{} is not served publicly", uri);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use sqlx::PgPool;
+
+use crate::middleware::request_context;
+use crate::services::audit::{self, AuditRecord};
+
+// The resource a route acts on: the id named last in the route and the
+// segment before it (/api/tasks/{task_id}/attachments/{attachment_id} is
+// attachment <attachment_id>), or the last segment when it names no id
+fn entity(route: &str, res: &ServiceResponse<impl Sized>) -> (Option<String>, Option<String>) {
+    let segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    let last_param = segments.iter().rposition(|s| s.starts_with('{'));
+    match last_param {
+        Some(index) => {
+            let name = segments[index].trim_matches(|c| c == '{' || c == '}');
+            let entity_type = index.checked_sub(1)
+                .map(|i| segments[i])
+                .filter(|s| !s.starts_with('{'))
+                .map(|s| s.to_string());
+            (entity_type, res.request().match_info().get(name).map(|id| id.to_string()))
+        }
+        None => (segments.last().map(|s| s.to_string()), None),
+    }
+}
+
+/// Records every POST, PUT, PATCH and DELETE that reached a route in the
+/// audit log: who made it, the route and the entity it names, the status,
+/// and the before/after diff the handler recorded, if any. Wrapped inside
+/// `PropagateContext` to see the request's user and diff.
+#[derive(Clone)]
+pub struct AuditLog {
+    pool: PgPool,
+}
+
+impl AuditLog {
+    pub fn new(pool: PgPool) -> Self {
+        AuditLog { pool }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for AuditLog
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = AuditLogMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuditLogMiddleware { service, pool: self.pool.clone() }))
+    }
+}
+
+pub struct AuditLogMiddleware<S> {
+    service: S,
+    pool: PgPool,
+}
+
+impl<S, B> Service<ServiceRequest> for AuditLogMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let mutating = matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH | Method::DELETE);
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let pool = self.pool.clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+            if !mutating {
+                return Ok(res);
+            }
+            // Requests that matched no route never reached a handler
+            let Some(route) = res.request().match_pattern() else {
+                return Ok(res);
+            };
+
+            let (entity_type, entity_id) = entity(&route, &res);
+            let ctx = request_context::current();
+            audit::record(pool, AuditRecord {
+                user_id: ctx.as_ref().and_then(|ctx| ctx.user_id()),
+                method,
+                route,
+                path,
+                entity_type,
+                entity_id,
+                status: res.status().as_u16(),
+                changes: ctx.as_ref().and_then(|ctx| ctx.changes().cloned()),
+                request_id: ctx.map(|ctx| ctx.request_id.clone()).unwrap_or_default(),
+            });
+            Ok(res)
+        })
+    }
+}
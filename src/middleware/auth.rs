@@ -0,0 +1,295 @@
+use actix_web::dev::Payload;
+use actix_web::http::Method;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest};
+use chrono::{DateTime, Utc};
+use futures_util::future::LocalBoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::config::{AppConfig, AuthTransport};
+use crate::Database;
+use crate::middleware::request_context;
+use crate::models::api_key::SCOPE_WRITE;
+use crate::models::ids::{TeamId, UserId};
+use crate::services::api_keys;
+use crate::utils::errors::ServiceError;
+use crate::utils::jwt::JwtKeys;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String, // Subject (user id)
+    pub username: String,
+    pub name: String,
+    pub exp: usize, // Expiration time (Unix timestamp)
+    pub iat: usize, // Issued at (Unix timestamp)
+    #[serde(default)]
+    pub jti: String, // Token id, used to revoke it on logout
+    #[serde(default)]
+    pub role: String, // `member` or `admin`
+    #[serde(default)]
+    pub team_ids: Vec<TeamId>, // Teams the user is a member of
+    #[serde(default)]
+    pub ver: i32, // The user's claims_version when issued
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub email_unverified: bool, // Email set but not verified; restricts the token to reading
+}
+
+/// The caller identified by the request's bearer token, session cookie (in
+/// cookie auth mode) or `X-Api-Key` header. Taking this as a handler argument rejects the request with 401
+/// before the handler runs. Tokens issued before the user's
+/// `tokens_valid_after` (set when they change their password or log out
+/// everywhere) are refused, and so are tokens whose role and team claims
+/// are out of date; `POST /api/auth/refresh` replaces those.
+/// The result is cached on the request so the credentials are only checked
+/// once.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedUser {
+    pub id: UserId,
+    pub claims: Claims,
+    /// `member` or `admin`, from the token's claims or the API key's owner
+    pub role: String,
+    /// Scopes of the API key the request used; None for a login token,
+    /// which may do anything the user can
+    pub scopes: Option<Vec<String>>,
+    /// Id of the API key the request used; None for a login token
+    pub api_key_id: Option<i32>,
+}
+
+impl AuthenticatedUser {
+    fn from_token(req: &HttpRequest) -> Result<Self, ServiceError> {
+        let keys = req.app_data::<web::Data<JwtKeys>>().ok_or_else(|| {
+            log::error!("JwtKeys is not registered as app data");
+            ServiceError::InternalError("Server misconfigured".to_string())
+        })?;
+
+        let config = req.app_data::<web::Data<AppConfig>>().ok_or_else(|| {
+            log::error!("AppConfig is not registered as app data");
+            ServiceError::InternalError("Server misconfigured".to_string())
+        })?;
+
+        let bearer = req.headers().get("Authorization")
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map(str::to_string);
+        let token = match bearer {
+            Some(token) => token,
+            None if config.auth_transport == AuthTransport::Cookie => {
+                let token = req.cookie(&config.auth_cookie_name)
+                    .map(|cookie| cookie.value().to_string())
+                    .ok_or_else(|| ServiceError::Unauthorized("Authentication required".to_string()))?;
+                // Browsers attach cookies to cross-site requests too. A custom
+                // header cannot be sent cross-origin without passing CORS, so
+                // requiring it on writes stops forged form posts.
+                let read_only = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+                if !read_only && !req.headers().contains_key("X-Requested-With") {
+                    return Err(ServiceError::Forbidden("X-Requested-With header is required".to_string())
+                        .with_code("CSRF_HEADER_REQUIRED"));
+                }
+                token
+            }
+            None => return Err(ServiceError::Unauthorized("Authentication required".to_string())),
+        };
+
+        let claims = keys.verify::<Claims>(&token)
+            .map_err(|e| {
+                log::warn!("JWT validation error: {}", e);
+                ServiceError::Unauthorized("Invalid token".to_string())
+            })?;
+
+        let user_id: i32 = claims.sub.parse()
+            .map_err(|_| ServiceError::Unauthorized("Invalid user ID in token".to_string()))?;
+
+        Ok(AuthenticatedUser { id: UserId(user_id), claims, role: String::new(), scopes: None, api_key_id: None })
+    }
+
+    // Resolve an API key to its owner. There is no JWT behind a key, so the
+    // expiry, issue time and token id claims are left empty.
+    async fn from_api_key(req: &HttpRequest, db: &Database, key: &str) -> Result<Self, ServiceError> {
+        let config = req.app_data::<web::Data<AppConfig>>().ok_or_else(|| {
+            log::error!("AppConfig is not registered as app data");
+            ServiceError::InternalError("Server misconfigured".to_string())
+        })?;
+
+        let owner = api_keys::authenticate(db, config, key).await?
+            .ok_or_else(|| ServiceError::Unauthorized("Invalid API key".to_string()))?;
+
+        if !owner.is_active {
+            return Err(ServiceError::Unauthorized("Account is deactivated".to_string()).with_code("ACCOUNT_DEACTIVATED"));
+        }
+
+        let claims = Claims {
+            sub: owner.user_id.to_string(),
+            username: owner.username,
+            name: owner.name,
+            exp: 0,
+            iat: 0,
+            jti: String::new(),
+            role: owner.role.clone(),
+            team_ids: owner.team_ids,
+            ver: 0,
+            email_unverified: owner.email_unverified,
+        };
+        log::debug!("Request authenticated with API key {}", owner.key_id);
+
+        Ok(AuthenticatedUser {
+            id: owner.user_id,
+            claims,
+            role: owner.role,
+            scopes: Some(owner.scopes),
+            api_key_id: Some(owner.key_id),
+        })
+    }
+
+    // API keys without the write scope may only read
+    fn check_scope(&self, method: &Method) -> Result<(), ServiceError> {
+        let Some(scopes) = &self.scopes else {
+            return Ok(());
+        };
+        let read_only = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+        if read_only || scopes.iter().any(|scope| scope == SCOPE_WRITE) {
+            return Ok(());
+        }
+        Err(ServiceError::Forbidden("API key is not allowed to modify data".to_string()).with_code("INSUFFICIENT_SCOPE"))
+    }
+
+    // Reject tokens of deleted or deactivated users, tokens revoked by a
+    // password change and tokens logged out. Tokens issued before the user's
+    // role or teams changed are stale and refused unless `allow_stale`.
+    // Revocations set `tokens_valid_after` to the start of the current
+    // second and a token counts as issued before it when its whole-second
+    // iat is lower, so logins within that second survive; the request that
+    // revoked everything also revokes its own token by jti.
+    async fn load_account(&mut self, db: &Database, allow_stale: bool) -> Result<(), ServiceError> {
+        let row = sqlx::query(
+            "SELECT claims_version, tokens_valid_after, is_active,
+                EXISTS (SELECT 1 FROM revoked_tokens WHERE jti = $2) AS revoked
+             FROM users WHERE id = $1"
+        )
+        .bind(self.id)
+        .bind(&self.claims.jti)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking token validity: {}", e);
+            ServiceError::DatabaseError("Failed to verify token".to_string())
+        })?
+        .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+        if !row.get::<bool, _>("is_active") {
+            return Err(ServiceError::Unauthorized("Account is deactivated".to_string()).with_code("ACCOUNT_DEACTIVATED"));
+        }
+
+        let tokens_valid_after: Option<DateTime<Utc>> = row.get("tokens_valid_after");
+        let issued_before_cutoff = tokens_valid_after
+            .is_some_and(|valid_after| (self.claims.iat as i64) < valid_after.timestamp());
+        if issued_before_cutoff || row.get::<bool, _>("revoked") {
+            return Err(ServiceError::Unauthorized("Token has been revoked".to_string()).with_code("TOKEN_REVOKED"));
+        }
+
+        if !allow_stale && row.get::<i32, _>("claims_version") != self.claims.ver {
+            return Err(ServiceError::Unauthorized("Token is out of date; refresh it".to_string()).with_code("TOKEN_STALE"));
+        }
+
+        self.role = self.claims.role.clone();
+        Ok(())
+    }
+
+    /// Authenticate a login token the way the extractor does, except that a
+    /// stale token is accepted, so its claims can be refreshed
+    pub async fn allowing_stale(req: &HttpRequest) -> Result<Self, ServiceError> {
+        let db = req.app_data::<web::Data<Database>>().ok_or_else(|| {
+            log::error!("Database is not registered as app data");
+            ServiceError::InternalError("Server misconfigured".to_string())
+        })?;
+
+        let mut user = Self::from_token(req)?;
+        user.load_account(db, true).await?;
+        Ok(user)
+    }
+
+    pub fn is_api_key(&self) -> bool {
+        self.scopes.is_some()
+    }
+
+    /// Check the credentials again for a response that outlives its request,
+    /// like an event stream: the token must not have expired, been revoked
+    /// or logged out, the API key must not have been revoked, and the
+    /// account must still be active. Stale claims are accepted.
+    pub async fn recheck(&self, db: &Database) -> Result<(), ServiceError> {
+        if let Some(key_id) = self.api_key_id {
+            if !api_keys::is_usable(db, key_id).await? {
+                return Err(ServiceError::Unauthorized("API key is no longer valid".to_string()).with_code("API_KEY_REVOKED"));
+            }
+            return Ok(());
+        }
+        if (self.claims.exp as i64) <= Utc::now().timestamp() {
+            return Err(ServiceError::Unauthorized("Token has expired".to_string()).with_code("TOKEN_EXPIRED"));
+        }
+        self.clone().load_account(db, true).await
+    }
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ServiceError;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let req = req.clone();
+        Box::pin(async move {
+            if let Some(user) = req.extensions().get::<AuthenticatedUser>() {
+                return Ok(user.clone());
+            }
+
+            let db = req.app_data::<web::Data<Database>>().ok_or_else(|| {
+                log::error!("Database is not registered as app data");
+                ServiceError::InternalError("Server misconfigured".to_string())
+            })?;
+
+            let api_key = req.headers().get("X-Api-Key").and_then(|h| h.to_str().ok());
+            let user = match api_key {
+                Some(key) => Self::from_api_key(&req, db, key).await?,
+                None => {
+                    let mut user = Self::from_token(&req)?;
+                    user.load_account(db, false).await?;
+                    user
+                }
+            };
+            user.check_scope(req.method())?;
+
+            req.extensions_mut().insert(user.clone());
+            request_context::set_user(user.id, user.api_key_id);
+            Ok(user)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::test_db::{signed_in, TestDb};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn the_revocation_cutoff_spares_tokens_from_its_own_second() {
+        let Some(test) = TestDb::create().await else { return };
+        let user_id = test.insert_user("alice", "member").await;
+        let cutoff: DateTime<Utc> = sqlx::query_scalar(
+            "UPDATE users SET tokens_valid_after = date_trunc('second', NOW()) WHERE id = $1 RETURNING tokens_valid_after"
+        )
+        .bind(user_id)
+        .fetch_one(&test.db.pool)
+        .await
+        .expect("revoke tokens");
+
+        let mut fresh = signed_in(user_id, "member");
+        fresh.claims.iat = cutoff.timestamp() as usize;
+        assert!(fresh.load_account(&test.db, false).await.is_ok());
+
+        let mut old = signed_in(user_id, "member");
+        old.claims.iat = cutoff.timestamp() as usize - 1;
+        let err = old.load_account(&test.db, false).await.expect_err("token from before the cutoff");
+        assert_eq!(err.error_code(), "TOKEN_REVOKED");
+
+        test.drop().await;
+    }
+}
@@ -0,0 +1,96 @@
+use std::future::{ready, Ready};
+use std::panic::AssertUnwindSafe;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{Error, HttpResponse};
+use futures_util::future::{FutureExt, LocalBoxFuture};
+use uuid::Uuid;
+
+use crate::middleware::request_context;
+use crate::models::auth::ErrorResponse;
+use crate::services::metrics::METRICS;
+
+/// Turns a panic inside a handler into the standard JSON 500 instead of an
+/// aborted connection. The panic is logged with the request line and a
+/// correlation id that is also returned to the client.
+pub struct CatchPanic;
+
+impl<S, B> Transform<S, ServiceRequest> for CatchPanic
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CatchPanicMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CatchPanicMiddleware { service }))
+    }
+}
+
+pub struct CatchPanicMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for CatchPanicMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let http_req = req.request().clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            match AssertUnwindSafe(fut).catch_unwind().await {
+                Ok(result) => result.map(ServiceResponse::map_into_left_body),
+                Err(panic) => {
+                    let correlation_id = request_context::current()
+                        .map(|ctx| ctx.request_id.clone())
+                        .unwrap_or_else(|| Uuid::new_v4().to_string());
+                    METRICS.record_handler_panic();
+                    log::error!(
+                        "Handler panicked [{}] {} {}: {}",
+                        correlation_id,
+                        http_req.method(),
+                        http_req.path(),
+                        panic_message(panic.as_ref())
+                    );
+
+                    let response = HttpResponse::InternalServerError()
+                        .insert_header(("X-Correlation-Id", correlation_id.clone()))
+                        .json(ErrorResponse {
+                            status: "error".to_string(),
+                            message: "Something went wrong".to_string(),
+                            error_code: "INTERNAL_ERROR".to_string(),
+                            details: Vec::new(),
+                            correlation_id: Some(correlation_id),
+                        });
+                    Ok(ServiceResponse::new(http_req, response).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "non-string panic payload"
+    }
+}
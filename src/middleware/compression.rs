@@ -0,0 +1,93 @@
+use std::future::{ready, Ready};
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::AppConfig;
+
+// Text formats worth compressing; uploads are mostly images, PDFs and
+// archives that are compressed already. Event streams are left alone so
+// realtime events are not held back in an encoder buffer.
+fn compressible_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    (essence.starts_with("text/") && essence != "text/event-stream")
+        || essence == "application/json"
+        || essence.ends_with("+json")
+        || essence == "application/x-ndjson"
+        || essence == "application/javascript"
+}
+
+/// Decides which responses the `Compress` middleware wrapped around it may
+/// encode: text responses of at least COMPRESSION_MIN_BYTES, and streamed
+/// ones whose size is unknown. Everything else is marked
+/// `Content-Encoding: identity`, which `Compress` leaves alone.
+#[derive(Clone)]
+pub struct CompressionFilter {
+    min_bytes: u64,
+}
+
+impl CompressionFilter {
+    pub fn new(config: &AppConfig) -> Self {
+        CompressionFilter { min_bytes: config.compression_min_bytes as u64 }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CompressionFilter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = CompressionFilterMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CompressionFilterMiddleware { service, min_bytes: self.min_bytes }))
+    }
+}
+
+pub struct CompressionFilterMiddleware<S> {
+    service: S,
+    min_bytes: u64,
+}
+
+impl<S, B> Service<ServiceRequest> for CompressionFilterMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let min_bytes = self.min_bytes;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+            let large_enough = match res.response().body().size() {
+                BodySize::Sized(size) => size >= min_bytes,
+                BodySize::Stream => true,
+                BodySize::None => false,
+            };
+            let encode = large_enough && res.headers().get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(compressible_type);
+
+            if !encode && !res.headers().contains_key(CONTENT_ENCODING) {
+                res.headers_mut().insert(CONTENT_ENCODING, HeaderValue::from_static("identity"));
+            }
+            Ok(res)
+        })
+    }
+}
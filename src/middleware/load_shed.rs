@@ -0,0 +1,132 @@
+use std::future::{ready, Ready};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::{Error, ResponseError};
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::AppConfig;
+use crate::services::metrics::METRICS;
+use crate::services::pool_monitor::PoolMonitor;
+use crate::utils::errors::ServiceError;
+
+// Routes that may be refused while saturated; everything else is interactive
+// board traffic and is always let through
+const LOW_PRIORITY_PREFIXES: &[&str] = &["/api/tasks/export", "/api/reports", "/api/storage"];
+
+struct LoadState {
+    in_flight: AtomicUsize,
+    pool: Arc<PoolMonitor>,
+    max_in_flight: usize,
+    max_pool_wait_ms: u64,
+    retry_after_secs: u64,
+}
+
+impl LoadState {
+    fn is_saturated(&self) -> bool {
+        self.in_flight.load(Ordering::Relaxed) > self.max_in_flight
+            || self.pool.acquire_wait_ms() > self.max_pool_wait_ms
+    }
+}
+
+// Decrements the in-flight count when the request finishes or is dropped
+struct InFlightGuard(Arc<LoadState>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Rejects low-priority requests (reports, exports) with 503 and Retry-After
+/// while the service is saturated, judged by the number of requests in flight
+/// and by how long it currently takes to get a connection from the pool.
+#[derive(Clone)]
+pub struct LoadShedder {
+    state: Arc<LoadState>,
+}
+
+impl LoadShedder {
+    /// Create the shedder, judging pool wait times by `pool`'s probe
+    pub fn new(config: &AppConfig, pool: Arc<PoolMonitor>) -> Self {
+        let state = Arc::new(LoadState {
+            in_flight: AtomicUsize::new(0),
+            pool,
+            max_in_flight: config.load_shed_max_in_flight,
+            max_pool_wait_ms: config.load_shed_max_pool_wait_ms,
+            retry_after_secs: config.load_shed_retry_after_secs,
+        });
+        LoadShedder { state }
+    }
+}
+
+fn is_low_priority(path: &str) -> bool {
+    LOW_PRIORITY_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+impl<S, B> Transform<S, ServiceRequest> for LoadShedder
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = LoadShedderMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LoadShedderMiddleware { service, state: self.state.clone() }))
+    }
+}
+
+pub struct LoadShedderMiddleware<S> {
+    service: S,
+    state: Arc<LoadState>,
+}
+
+impl<S, B> Service<ServiceRequest> for LoadShedderMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if is_low_priority(req.path()) && self.state.is_saturated() {
+            METRICS.record_request_shed();
+            log::warn!(
+                "Shedding {} {} (in flight: {}, pool wait: {}ms)",
+                req.method(),
+                req.path(),
+                self.state.in_flight.load(Ordering::Relaxed),
+                self.state.pool.acquire_wait_ms()
+            );
+
+            let error = ServiceError::ServiceUnavailable("Server is busy, please retry later".to_string())
+                .with_code("SERVER_SATURATED");
+            let mut response = error.error_response();
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(self.state.retry_after_secs));
+            return Box::pin(ready(Ok(req.into_response(response).map_into_right_body())));
+        }
+
+        self.state.in_flight.fetch_add(1, Ordering::Relaxed);
+        let guard = InFlightGuard(self.state.clone());
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let response = fut.await;
+            drop(guard);
+            response.map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
@@ -1 +1,27 @@
-// Middleware module - currently empty
+pub mod access_log;
+pub mod admin_surface;
+pub mod audit_log;
+pub mod auth;
+pub mod catch_panic;
+pub mod compression;
+pub mod load_shed;
+pub mod policy;
+pub mod rate_limit;
+pub mod request_context;
+pub mod security_headers;
+pub mod server_timing;
+pub mod timeout;
+
+pub use access_log::AccessLog;
+pub use admin_surface::AdminSurface;
+pub use audit_log::AuditLog;
+pub use auth::AuthenticatedUser;
+pub use catch_panic::CatchPanic;
+pub use compression::CompressionFilter;
+pub use load_shed::LoadShedder;
+pub use policy::Permission;
+pub use rate_limit::RateLimit;
+pub use request_context::PropagateContext;
+pub use security_headers::SecurityHeaders;
+pub use server_timing::ServerTiming;
+pub use timeout::RequestTimeout;
@@ -0,0 +1,435 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::middleware::auth::AuthenticatedUser;
+use crate::utils::errors::ServiceError;
+
+const MEMBER: &str = "member";
+const ADMIN: &str = "admin";
+
+/// Something a caller can be allowed to do. Handlers declare what they need
+/// with `user.requires(Permission::...)`; who holds each permission is
+/// decided here only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    TaskRead,
+    TaskWrite,
+    TaskDelete,
+    TaskTransferAny,
+    TaskReplay,
+    AttachmentRead,
+    AttachmentWrite,
+    ApiKeyManage,
+    DeadLetterManage,
+    UserOffboard,
+    UserInvite,
+    ApiDocs,
+    PolicyRead,
+    UsageRead,
+    ScriptManage,
+    EmailTemplateManage,
+    BoardManage,
+    FeedbackRead,
+    ContentModerate,
+    AuditRead,
+    DatabaseManage,
+}
+
+/// Who holds a permission and which endpoints ask for it
+#[derive(Debug, Clone, Copy)]
+pub struct Policy {
+    pub description: &'static str,
+    pub roles: &'static [&'static str],
+    /// Whether API keys may use it; keys still need the write scope for
+    /// anything but GET, HEAD and OPTIONS
+    pub api_keys: bool,
+    /// Whether accounts whose email is not yet verified may use it
+    pub unverified: bool,
+    pub endpoints: &'static [&'static str],
+}
+
+impl Permission {
+    pub const ALL: [Permission; 21] = [
+        Permission::TaskRead,
+        Permission::TaskWrite,
+        Permission::TaskDelete,
+        Permission::TaskTransferAny,
+        Permission::TaskReplay,
+        Permission::AttachmentRead,
+        Permission::AttachmentWrite,
+        Permission::ApiKeyManage,
+        Permission::DeadLetterManage,
+        Permission::UserOffboard,
+        Permission::UserInvite,
+        Permission::ApiDocs,
+        Permission::PolicyRead,
+        Permission::UsageRead,
+        Permission::ScriptManage,
+        Permission::EmailTemplateManage,
+        Permission::BoardManage,
+        Permission::FeedbackRead,
+        Permission::ContentModerate,
+        Permission::AuditRead,
+        Permission::DatabaseManage,
+    ];
+
+    pub fn policy(self) -> Policy {
+        match self {
+            Permission::TaskRead => Policy {
+                description: "Read tasks, teams, task history and exports",
+                roles: &[MEMBER, ADMIN],
+                api_keys: true,
+                unverified: true,
+                endpoints: &[
+                    "GET /api/tasks",
+                    "GET /api/tasks/export",
+                    "POST /api/tasks/export",
+                    "GET /api/tasks/{id}",
+                    "GET /api/tasks/{id}/events",
+                    "GET /api/teams",
+                    "GET /api/board/settings",
+                    "GET /api/board/column-policies",
+                    "GET /api/board/sla-rules",
+                    "GET /api/board/sla-breaches",
+                    "GET /api/board/sla-breaches/{id}/escalations",
+                    "GET /api/board/escalation-chain",
+                    "GET /api/board/calendar",
+                    "GET /api/board/diff",
+                    "GET /api/reports/activity-heatmap",
+                    "GET /api/reports/forecast",
+                    "GET /api/sync",
+                    "GET /api/events/stream",
+                    "GET /api/events/ws",
+                    "GET /api/operations/{id}",
+                    "GET /api/operations/{id}/download",
+                ],
+            },
+            Permission::TaskWrite => Policy {
+                description: "Create, update, import and sync tasks; manage own drafts; vote; transfer own tasks",
+                roles: &[MEMBER, ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &[
+                    "POST /api/tasks",
+                    "POST /api/tasks/import",
+                    "GET /api/tasks/drafts",
+                    "POST /api/tasks/drafts",
+                    "PUT /api/tasks/drafts/{id}",
+                    "DELETE /api/tasks/drafts/{id}",
+                    "POST /api/tasks/drafts/{id}/publish",
+                    "PUT /api/tasks/{id}",
+                    "POST /api/tasks/{id}/transfer",
+                    "POST /api/tasks/{id}/vote",
+                    "DELETE /api/tasks/{id}/vote",
+                    "POST /api/sync",
+                ],
+            },
+            Permission::TaskDelete => Policy {
+                description: "Delete tasks",
+                roles: &[MEMBER, ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["DELETE /api/tasks/{id}"],
+            },
+            Permission::TaskTransferAny => Policy {
+                description: "Transfer tasks owned by someone else",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["POST /api/tasks/{id}/transfer"],
+            },
+            Permission::TaskReplay => Policy {
+                description: "Rebuild a task from its event history, overwriting its current state",
+                roles: &[ADMIN],
+                api_keys: false,
+                unverified: false,
+                endpoints: &["POST /api/tasks/{id}/events/replay"],
+            },
+            Permission::AttachmentRead => Policy {
+                description: "List, download and preview attachments; read download logs and storage usage",
+                roles: &[MEMBER, ADMIN],
+                api_keys: true,
+                unverified: true,
+                endpoints: &[
+                    "GET /api/tasks/{task_id}/attachments",
+                    "GET /api/tasks/{task_id}/attachments/{attachment_id}/download",
+                    "GET /api/tasks/{task_id}/attachments/{attachment_id}/preview",
+                    "GET /api/tasks/{task_id}/attachments/{attachment_id}/downloads",
+                    "GET /api/storage",
+                ],
+            },
+            Permission::AttachmentWrite => Policy {
+                description: "Upload and delete attachments",
+                roles: &[MEMBER, ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &[
+                    "POST /api/tasks/{task_id}/attachments",
+                    "DELETE /api/tasks/{task_id}/attachments",
+                    "DELETE /api/tasks/{task_id}/attachments/{attachment_id}",
+                ],
+            },
+            // A leaked key must not be able to mint itself successors
+            Permission::ApiKeyManage => Policy {
+                description: "Create and revoke own API keys",
+                roles: &[MEMBER, ADMIN],
+                api_keys: false,
+                unverified: false,
+                endpoints: &["POST /api/auth/api-keys", "DELETE /api/auth/api-keys/{id}"],
+            },
+            Permission::DeadLetterManage => Policy {
+                description: "Inspect, requeue and purge dead-lettered jobs",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &[
+                    "GET /api/admin/dead-letters",
+                    "DELETE /api/admin/dead-letters",
+                    "GET /api/admin/dead-letters/{id}",
+                    "DELETE /api/admin/dead-letters/{id}",
+                    "POST /api/admin/dead-letters/{id}/requeue",
+                ],
+            },
+            Permission::UserOffboard => Policy {
+                description: "Deactivate users, optionally reassigning their open tasks",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["DELETE /api/admin/users/{id}", "POST /api/admin/users/{id}/offboard"],
+            },
+            Permission::UserInvite => Policy {
+                description: "Invite new users by email into teams",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["POST /api/invitations"],
+            },
+            Permission::ApiDocs => Policy {
+                description: "Open admin-only API docs in a browser",
+                roles: &[ADMIN],
+                api_keys: false,
+                unverified: false,
+                endpoints: &["POST /api/admin/docs-session"],
+            },
+            Permission::PolicyRead => Policy {
+                description: "List this authorization policy",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["GET /api/admin/permissions"],
+            },
+            Permission::UsageRead => Policy {
+                description: "Read request counts and error rates of all users and API keys",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["GET /api/admin/usage"],
+            },
+            // Scripts act on every task as their creator, so they are only
+            // written from a signed-in session
+            Permission::ScriptManage => Policy {
+                description: "Manage validation and automation scripts run on task events",
+                roles: &[ADMIN],
+                api_keys: false,
+                unverified: false,
+                endpoints: &[
+                    "GET /api/admin/scripts",
+                    "POST /api/admin/scripts",
+                    "PUT /api/admin/scripts/{id}",
+                    "DELETE /api/admin/scripts/{id}",
+                ],
+            },
+            Permission::EmailTemplateManage => Policy {
+                description: "Customize and preview the emails sent by the server",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &[
+                    "GET /api/admin/email-templates",
+                    "PUT /api/admin/email-templates/{name}",
+                    "DELETE /api/admin/email-templates/{name}",
+                    "POST /api/admin/email-templates/{name}/preview",
+                ],
+            },
+            Permission::BoardManage => Policy {
+                description: "Change the defaults applied to new tasks, the columns' auto-assignment policies, SLA rules, the escalation chain and the business calendar, and preview the weekly digest",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &[
+                    "PUT /api/board/settings",
+                    "PUT /api/board/column-policies/{status}",
+                    "DELETE /api/board/column-policies/{status}",
+                    "POST /api/board/sla-rules",
+                    "DELETE /api/board/sla-rules/{id}",
+                    "PUT /api/board/escalation-chain",
+                    "PUT /api/board/calendar",
+                    "DELETE /api/board/calendar",
+                    "GET /api/board/weekly-digest",
+                ],
+            },
+            Permission::FeedbackRead => Policy {
+                description: "See who filed a task through the public feedback form",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["GET /api/admin/feedback/{id}"],
+            },
+            Permission::ContentModerate => Policy {
+                description: "Review uploads held by the moderation service and see them in attachment lists",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &[
+                    "GET /api/admin/moderation",
+                    "POST /api/admin/moderation/{id}/approve",
+                    "POST /api/admin/moderation/{id}/reject",
+                ],
+            },
+            Permission::AuditRead => Policy {
+                description: "Read the audit log of every mutating request and what it changed",
+                roles: &[ADMIN],
+                api_keys: true,
+                unverified: false,
+                endpoints: &["GET /api/admin/audit-logs"],
+            },
+            Permission::DatabaseManage => Policy {
+                description: "Review the index advisor's recommendations and create the missing indexes",
+                roles: &[ADMIN],
+                api_keys: false,
+                unverified: false,
+                endpoints: &[
+                    "GET /api/admin/index-advice",
+                    "POST /api/admin/index-advice/apply",
+                ],
+            },
+        }
+    }
+}
+
+impl AuthenticatedUser {
+    /// Whether the caller holds a permission
+    pub fn can(&self, permission: Permission) -> bool {
+        let policy = permission.policy();
+        policy.roles.contains(&self.role.as_str())
+            && (policy.api_keys || !self.is_api_key())
+            && (policy.unverified || !self.claims.email_unverified)
+    }
+
+    /// Reject the request with 403 unless the caller holds a permission
+    pub fn requires(&self, permission: Permission) -> Result<(), ServiceError> {
+        if self.can(permission) {
+            return Ok(());
+        }
+
+        let policy = permission.policy();
+        if !policy.api_keys && self.is_api_key() {
+            return Err(ServiceError::Forbidden("Not allowed with an API key; use a login session".to_string())
+                .with_code("API_KEY_NOT_ALLOWED"));
+        }
+        if !policy.unverified && self.claims.email_unverified && policy.roles.contains(&self.role.as_str()) {
+            return Err(ServiceError::Forbidden("Verify your email address first".to_string())
+                .with_code("EMAIL_UNVERIFIED"));
+        }
+        if policy.roles == [ADMIN] {
+            return Err(ServiceError::Forbidden("Administrator access required".to_string()).with_code("ADMIN_REQUIRED"));
+        }
+        Err(ServiceError::Forbidden("You do not have permission to do this".to_string()).with_code("PERMISSION_DENIED"))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{Method, StatusCode};
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+    use utoipa::OpenApi;
+
+    use super::*;
+
+    // Any signed-in caller may use these on their own account
+    const OWN_ACCOUNT: &[&str] = &[
+        "POST /api/auth/logout",
+        "POST /api/auth/logout-all",
+        "POST /api/auth/refresh",
+        "PUT /api/auth/password",
+        "GET /api/auth/me",
+        "PUT /api/auth/me",
+        "GET /api/auth/me/availability",
+        "PUT /api/auth/me/availability",
+        "GET /api/auth/me/email",
+        "PUT /api/auth/me/email",
+        "GET /api/auth/me/locale",
+        "PUT /api/auth/me/locale",
+        "GET /api/auth/me/usage",
+    ];
+
+    // Permissions a route asks for on top of its own, for some requests only
+    const ALSO_REQUIRED: &[(Permission, &str)] = &[(Permission::TaskTransferAny, "POST /api/tasks/{id}/transfer")];
+
+    /// Every documented operation as "METHOD path", with whether it needs
+    /// a signed-in caller
+    fn operations() -> Vec<(Method, String, bool)> {
+        let doc = crate::ApiDoc::openapi();
+        let mut operations = Vec::new();
+        for (path, item) in doc.paths.paths {
+            let methods = [
+                (Method::GET, item.get),
+                (Method::POST, item.post),
+                (Method::PUT, item.put),
+                (Method::DELETE, item.delete),
+                (Method::PATCH, item.patch),
+            ];
+            for (method, operation) in methods {
+                if let Some(operation) = operation {
+                    let signed_in = operation.security.is_some_and(|security| !security.is_empty());
+                    operations.push((method, path.clone(), signed_in));
+                }
+            }
+        }
+        operations
+    }
+
+    fn listing(permission: Permission, endpoint: &str) -> bool {
+        permission.policy().endpoints.contains(&endpoint)
+            && !ALSO_REQUIRED.contains(&(permission, endpoint))
+    }
+
+    #[test]
+    fn every_guarded_route_is_in_exactly_one_policy() {
+        let operations = operations();
+        for (method, path, signed_in) in &operations {
+            let endpoint = format!("{} {}", method, path);
+            let policies: Vec<Permission> = Permission::ALL.into_iter()
+                .filter(|permission| listing(*permission, &endpoint))
+                .collect();
+            let expected = usize::from(*signed_in && !OWN_ACCOUNT.contains(&endpoint.as_str()));
+            assert_eq!(policies.len(), expected, "{} is listed by {:?}", endpoint, policies);
+        }
+
+        let documented: Vec<String> = operations.iter()
+            .map(|(method, path, _)| format!("{} {}", method, path))
+            .collect();
+        for permission in Permission::ALL {
+            for endpoint in permission.policy().endpoints {
+                assert!(documented.iter().any(|documented| documented == endpoint), "{:?} lists unknown {}", permission, endpoint);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn every_documented_route_is_registered() {
+        let app = init_service(App::new().configure(crate::routes)).await;
+        for (method, path, _) in operations() {
+            let uri = path.split('/')
+                .map(|segment| if segment.starts_with('{') { "1" } else { segment })
+                .collect::<Vec<_>>()
+                .join("/");
+            let response = call_service(&app, TestRequest::default().method(method.clone()).uri(&uri).to_request()).await;
+            assert_ne!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{} {} is not registered", method, path);
+            assert_eq!(response.request().match_pattern().as_deref(), Some(path.as_str()), "{} {} is not registered", method, path);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{Error, FromRequest, ResponseError};
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::AppConfig;
+use crate::middleware::AuthenticatedUser;
+use crate::models::ids::UserId;
+use crate::utils::client_ip::client_ip;
+use crate::utils::errors::ServiceError;
+
+// Buckets are swept once the map grows past this many keys
+const SWEEP_THRESHOLD: usize = 10_000;
+
+// Probes from load balancers and uptime monitors are never limited
+const EXEMPT_PATHS: &[&str] = &["/health"];
+
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+/// Request budget of one user or IP for the current fixed window
+pub struct RateLimitState {
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_secs: u64,
+    /// Whether this request went over the limit
+    pub exceeded: bool,
+}
+
+impl RateLimitState {
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        headers.insert(HeaderName::from_static("x-ratelimit-limit"), HeaderValue::from(self.limit));
+        headers.insert(HeaderName::from_static("x-ratelimit-remaining"), HeaderValue::from(self.remaining));
+        headers.insert(HeaderName::from_static("x-ratelimit-reset"), HeaderValue::from(self.reset_secs));
+    }
+}
+
+struct Limiter<K> {
+    limit: u32,
+    window: Duration,
+    windows: Mutex<HashMap<K, Window>>,
+}
+
+impl<K: Hash + Eq> Limiter<K> {
+    fn new(limit: u32, window: Duration) -> Self {
+        Limiter { limit, window, windows: Mutex::new(HashMap::new()) }
+    }
+
+    fn hit(&self, key: K) -> RateLimitState {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+
+        if windows.len() > SWEEP_THRESHOLD {
+            windows.retain(|_, w| now.duration_since(w.started) < self.window);
+        }
+
+        let window = windows.entry(key).or_insert(Window { started: now, count: 0 });
+        if now.duration_since(window.started) >= self.window {
+            window.started = now;
+            window.count = 0;
+        }
+        window.count = window.count.saturating_add(1);
+
+        let reset = self.window.saturating_sub(now.duration_since(window.started));
+        RateLimitState {
+            limit: self.limit,
+            remaining: self.limit.saturating_sub(window.count),
+            reset_secs: reset.as_secs_f64().ceil() as u64,
+            exceeded: window.count > self.limit,
+        }
+    }
+}
+
+struct Limiters {
+    by_user: Limiter<UserId>,
+    by_ip: Limiter<Option<IpAddr>>,
+    trusted_proxies: Vec<IpAddr>,
+}
+
+/// Limits requests in fixed windows: per user (or API key owner) for
+/// authenticated requests, per client IP for the rest. Every response
+/// reports the budget in X-RateLimit-Limit/Remaining/Reset headers; requests
+/// over it get 429 with Retry-After. Counts are kept in memory, so each
+/// server process limits on its own.
+#[derive(Clone)]
+pub struct RateLimit {
+    limiters: Arc<Limiters>,
+}
+
+impl RateLimit {
+    pub fn new(config: &AppConfig) -> Self {
+        let window = Duration::from_secs(config.rate_limit_window_secs);
+        RateLimit {
+            limiters: Arc::new(Limiters {
+                by_user: Limiter::new(config.rate_limit_requests, window),
+                by_ip: Limiter::new(config.rate_limit_ip_requests, window),
+                trusted_proxies: config.trusted_proxies.clone(),
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiters: self.limiters.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiters: Arc<Limiters>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let limiters = self.limiters.clone();
+
+        Box::pin(async move {
+            if EXEMPT_PATHS.contains(&req.path()) {
+                return service.call(req).await.map(ServiceResponse::map_into_left_body);
+            }
+
+            // Authenticating here caches the user on the request, so the
+            // handler's extractor does not repeat the work. Requests whose
+            // credentials fail count against their IP; the handler still
+            // rejects them itself.
+            let user = AuthenticatedUser::extract(req.request()).await.ok();
+            let state = match user {
+                Some(user) => limiters.by_user.hit(user.id),
+                None => limiters.by_ip.hit(client_ip(req.request(), &limiters.trusted_proxies)),
+            };
+
+            if state.exceeded {
+                let error = ServiceError::RateLimited("Too many requests; slow down".to_string())
+                    .with_retry_after(state.reset_secs.max(1));
+                let mut response = error.error_response();
+                state.write_headers(response.headers_mut());
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            let mut res = service.call(req).await?;
+            state.write_headers(res.headers_mut());
+            Ok(res.map_into_left_body())
+        })
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::future::{ready, Ready};
+use std::sync::{Arc, OnceLock};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+use uuid::Uuid;
+
+use crate::models::ids::UserId;
+use crate::services::usage;
+
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static CONTEXT: Arc<RequestContext>;
+}
+
+/// Who a request is for and how to find it in the logs. The user is filled
+/// in by the `AuthenticatedUser` extractor once the token has been checked.
+#[derive(Debug)]
+pub struct RequestContext {
+    pub request_id: String,
+    user_id: OnceLock<UserId>,
+    api_key_id: OnceLock<i32>,
+    changes: OnceLock<serde_json::Value>,
+}
+
+impl RequestContext {
+    pub fn user_id(&self) -> Option<UserId> {
+        self.user_id.get().copied()
+    }
+
+    pub fn api_key_id(&self) -> Option<i32> {
+        self.api_key_id.get().copied()
+    }
+
+    /// The before/after diff a handler recorded for the audit log
+    pub fn changes(&self) -> Option<&serde_json::Value> {
+        self.changes.get()
+    }
+}
+
+impl fmt::Display for RequestContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.user_id() {
+            Some(user_id) => write!(f, "req={} user={}", self.request_id, user_id),
+            None => write!(f, "req={} user=-", self.request_id),
+        }
+    }
+}
+
+/// The context of the request being handled on this task, if any.
+/// Background jobs spawned from a request do not inherit it.
+pub fn current() -> Option<Arc<RequestContext>> {
+    CONTEXT.try_with(|ctx| ctx.clone()).ok()
+}
+
+/// Run `future` as if handling a request, signed in as `user_id` if given
+#[cfg(test)]
+pub async fn run_as<F: std::future::Future>(user_id: Option<UserId>, future: F) -> F::Output {
+    let ctx = Arc::new(RequestContext {
+        request_id: Uuid::new_v4().to_string(),
+        user_id: OnceLock::new(),
+        api_key_id: OnceLock::new(),
+        changes: OnceLock::new(),
+    });
+    if let Some(user_id) = user_id {
+        let _ = ctx.user_id.set(user_id);
+    }
+    CONTEXT.scope(ctx, future).await
+}
+
+/// Record the authenticated user, and the API key they used if any, on the
+/// current request's context
+pub fn set_user(user_id: UserId, api_key_id: Option<i32>) {
+    if let Some(ctx) = current() {
+        let _ = ctx.user_id.set(user_id);
+        if let Some(api_key_id) = api_key_id {
+            let _ = ctx.api_key_id.set(api_key_id);
+        }
+    }
+}
+
+/// Record what the current request changed, as {field: {before, after}},
+/// for its audit log entry
+pub fn record_changes(changes: serde_json::Value) {
+    if let Some(ctx) = current() {
+        let _ = ctx.changes.set(changes);
+    }
+}
+
+/// Runs each request inside a task-local `RequestContext`. The request id is
+/// taken from an incoming `X-Request-Id` header or generated, and echoed on
+/// the response so clients can quote it in bug reports. Authenticated
+/// requests are counted towards their caller's usage once answered.
+pub struct PropagateContext;
+
+impl<S, B> Transform<S, ServiceRequest> for PropagateContext
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = PropagateContextMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(PropagateContextMiddleware { service }))
+    }
+}
+
+pub struct PropagateContextMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for PropagateContextMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // Only accept short, printable ids so a client cannot inject log lines
+        let request_id = req.headers().get(REQUEST_ID_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_graphic()))
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+        let ctx = Arc::new(RequestContext {
+            request_id: request_id.clone(),
+            user_id: OnceLock::new(),
+            api_key_id: OnceLock::new(),
+            changes: OnceLock::new(),
+        });
+
+        let fut = CONTEXT.sync_scope(ctx.clone(), || self.service.call(req));
+
+        Box::pin(CONTEXT.scope(ctx.clone(), async move {
+            let result = fut.await;
+            if let Some(user_id) = ctx.user_id() {
+                let status = match &result {
+                    Ok(res) => res.status(),
+                    Err(e) => e.as_response_error().status_code(),
+                };
+                usage::record(user_id, ctx.api_key_id(), status);
+            }
+            let mut res = result?;
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut().insert(HeaderName::from_static(REQUEST_ID_HEADER), value);
+            }
+            Ok(res)
+        }))
+    }
+}
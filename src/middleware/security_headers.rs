@@ -0,0 +1,103 @@
+use std::future::{ready, Ready};
+use std::sync::Arc;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+    X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::AppConfig;
+
+/// Sets HSTS, X-Content-Type-Options, X-Frame-Options, Referrer-Policy and
+/// the configured Content-Security-Policy on every response. Headers a
+/// handler already set are left alone. Does nothing unless SECURITY_HEADERS
+/// is on, which it is by default in production.
+#[derive(Clone)]
+pub struct SecurityHeaders {
+    headers: Option<Arc<Vec<(HeaderName, HeaderValue)>>>,
+}
+
+impl SecurityHeaders {
+    pub fn new(config: &AppConfig) -> Self {
+        if !config.security_headers {
+            return SecurityHeaders { headers: None };
+        }
+        let csp = HeaderValue::from_str(&config.content_security_policy)
+            .expect("CONTENT_SECURITY_POLICY is checked to be a single line");
+        SecurityHeaders {
+            headers: Some(Arc::new(vec![
+                (STRICT_TRANSPORT_SECURITY, HeaderValue::from_static("max-age=31536000; includeSubDomains")),
+                (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+                (X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+                (REFERRER_POLICY, HeaderValue::from_static("strict-origin-when-cross-origin")),
+                (CONTENT_SECURITY_POLICY, csp),
+            ])),
+        }
+    }
+}
+
+fn apply(headers: &mut HeaderMap, defaults: &[(HeaderName, HeaderValue)]) {
+    for (name, value) in defaults {
+        if !headers.contains_key(name) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware { service, headers: self.headers.clone() }))
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: S,
+    headers: Option<Arc<Vec<(HeaderName, HeaderValue)>>>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let headers = self.headers.clone();
+        // Kept to answer errors from inner layers here, so error responses
+        // carry the headers too
+        let http_req = req.request().clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = match fut.await {
+                Ok(res) => res.map_into_left_body(),
+                Err(e) => ServiceResponse::new(http_req, e.error_response()).map_into_right_body(),
+            };
+            if let Some(headers) = headers {
+                apply(res.headers_mut(), &headers);
+            }
+            Ok(res)
+        })
+    }
+}
@@ -0,0 +1,97 @@
+use std::future::{ready, Ready};
+use std::time::{Duration, Instant};
+
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::Error;
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::AppConfig;
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+/// Times each request from routing to response, reports it to the client as
+/// `Server-Timing: app;dur=<ms>` and logs a warning for requests slower than
+/// SLOW_REQUEST_MS. Wrapped inside `PropagateContext` so the warning carries
+/// the request and user ids.
+#[derive(Clone)]
+pub struct ServerTiming {
+    slow_after: Duration,
+}
+
+impl ServerTiming {
+    pub fn new(config: &AppConfig) -> Self {
+        ServerTiming { slow_after: Duration::from_millis(config.slow_request_ms) }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ServerTiming
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ServerTimingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ServerTimingMiddleware { service, slow_after: self.slow_after }))
+    }
+}
+
+pub struct ServerTimingMiddleware<S> {
+    service: S,
+    slow_after: Duration,
+}
+
+impl<S, B> Service<ServiceRequest> for ServerTimingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let slow_after = self.slow_after;
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut result = fut.await;
+            let elapsed = started.elapsed();
+            let duration_ms = elapsed.as_secs_f64() * 1000.0;
+
+            if elapsed >= slow_after {
+                let (status, route) = match &result {
+                    Ok(res) => (res.status(), res.request().match_pattern()),
+                    Err(e) => (e.as_response_error().status_code(), None),
+                };
+                let route = route.unwrap_or_else(|| path.clone());
+                log::warn!(
+                    method = method.as_str(),
+                    route = route.as_str(),
+                    status = status.as_u16(),
+                    duration_ms = (duration_ms * 10.0).round() / 10.0;
+                    "Slow request: {} {} took {:.1}ms (threshold {}ms)", method, route, duration_ms, slow_after.as_millis()
+                );
+            }
+
+            if let Ok(res) = &mut result {
+                if let Ok(value) = HeaderValue::from_str(&format!("app;dur={:.1}", duration_ms)) {
+                    res.headers_mut().insert(SERVER_TIMING, value);
+                }
+            }
+            result
+        })
+    }
+}
@@ -0,0 +1,99 @@
+use std::future::{ready, Ready};
+use std::time::Duration;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::{Error, ResponseError};
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::AppConfig;
+use crate::utils::errors::ServiceError;
+
+// Bulk endpoints that get the long budget instead of the CRUD one
+const LONG_RUNNING_PREFIXES: &[&str] = &["/api/tasks/export", "/api/tasks/import"];
+
+/// Bounds how long a handler may take to produce its response. Bulk routes
+/// (exports, imports, uploads) get the long budget, everything else the short
+/// one. Streamed bodies are not limited once the response has started.
+#[derive(Clone)]
+pub struct RequestTimeout {
+    default_timeout: Duration,
+    long_timeout: Duration,
+}
+
+impl RequestTimeout {
+    pub fn new(config: &AppConfig) -> Self {
+        RequestTimeout {
+            default_timeout: Duration::from_secs(config.request_timeout_secs),
+            long_timeout: Duration::from_secs(config.long_request_timeout_secs),
+        }
+    }
+
+    fn budget_for(&self, req: &ServiceRequest) -> Duration {
+        let path = req.path();
+        let is_upload = req.method() == Method::POST && path.ends_with("/attachments");
+        if is_upload || LONG_RUNNING_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+            self.long_timeout
+        } else {
+            self.default_timeout
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestTimeout
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequestTimeoutMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTimeoutMiddleware { service, timeouts: self.clone() }))
+    }
+}
+
+pub struct RequestTimeoutMiddleware<S> {
+    service: S,
+    timeouts: RequestTimeout,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestTimeoutMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let budget = self.timeouts.budget_for(&req);
+        let http_req = req.request().clone();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            match tokio::time::timeout(budget, fut).await {
+                Ok(response) => response.map(ServiceResponse::map_into_left_body),
+                Err(_) => {
+                    log::warn!(
+                        "{} {} exceeded its {}s budget",
+                        http_req.method(),
+                        http_req.path(),
+                        budget.as_secs()
+                    );
+                    let error = ServiceError::GatewayTimeout("Request took too long to complete".to_string());
+                    Ok(ServiceResponse::new(http_req, error.error_response()).map_into_right_body())
+                }
+            }
+        })
+    }
+}
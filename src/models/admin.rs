@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::middleware::Permission;
+use crate::models::ids::{TaskId, UserId};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct OffboardUserRequest {
+    /// User who takes over the leaver's open tasks
+    pub reassign_to: UserId,
+}
+
+/// What offboarding a user changed
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OffboardReport {
+    pub user_id: UserId,
+    pub reassigned_to: UserId,
+    /// Open (not DONE) tasks that changed owner
+    pub reassigned_tasks: Vec<TaskId>,
+    pub deactivated_at: DateTime<Utc>,
+}
+
+/// A user soft-deleted without handing over their tasks
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeactivationReport {
+    pub user_id: UserId,
+    pub deactivated_at: DateTime<Utc>,
+}
+
+/// One entry of the authorization policy
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PermissionPolicy {
+    pub permission: Permission,
+    pub description: String,
+    /// Roles holding the permission
+    pub roles: Vec<String>,
+    /// Whether API keys may use it (writes also need the key's write scope)
+    pub api_keys: bool,
+    /// Whether accounts with an unverified email may use it
+    pub unverified: bool,
+    /// Endpoints that require it, as `METHOD /path`
+    pub endpoints: Vec<String>,
+}
+
+impl From<Permission> for PermissionPolicy {
+    fn from(permission: Permission) -> Self {
+        let policy = permission.policy();
+        PermissionPolicy {
+            permission,
+            description: policy.description.to_string(),
+            roles: policy.roles.iter().map(|role| role.to_string()).collect(),
+            api_keys: policy.api_keys,
+            unverified: policy.unverified,
+            endpoints: policy.endpoints.iter().map(|endpoint| endpoint.to_string()).collect(),
+        }
+    }
+}
+
+/// An index the database is missing, with the statement that adds it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IndexAdvice {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub reason: String,
+    /// Sequential scans of the table since statistics were last reset
+    pub seq_scans: Option<i64>,
+    pub live_rows: Option<i64>,
+    /// `CREATE INDEX CONCURRENTLY` statement; applying it does not block
+    /// writes to the table
+    pub statement: String,
+}
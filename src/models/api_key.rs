@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::ids::UserId;
+
+/// Key may call read-only (GET/HEAD) endpoints
+pub const SCOPE_READ: &str = "read";
+/// Key may also create, change and delete data
+pub const SCOPE_WRITE: &str = "write";
+
+pub const SCOPES: &[&str] = &[SCOPE_READ, SCOPE_WRITE];
+
+fn default_scopes() -> Vec<String> {
+    vec![SCOPE_READ.to_string()]
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    /// Label to tell keys apart, e.g. "ci-bot"
+    pub name: String,
+    /// Any of `read` and `write`; defaults to `read`
+    #[serde(default = "default_scopes")]
+    pub scopes: Vec<String>,
+    /// Days until the key stops working; keys without it never expire
+    pub expires_in_days: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyResponse {
+    pub id: i32,
+    pub user_id: UserId,
+    pub name: String,
+    /// First characters of the key, to recognise it in listings
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedApiKey {
+    /// The secret to send as `X-Api-Key`; it is shown only once
+    pub key: String,
+    pub api_key: ApiKeyResponse,
+}
@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::ids::UserId;
+
+/// One mutating request, as recorded by the audit log
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    /// None for requests made before signing in
+    pub user_id: Option<UserId>,
+    pub username: Option<String>,
+    pub method: String,
+    /// The route template, e.g. /api/tasks/{id}
+    pub route: String,
+    pub path: String,
+    /// The resource the route acts on, e.g. tasks
+    pub entity_type: Option<String>,
+    /// The id the route names for that resource; None when creating one
+    pub entity_id: Option<String>,
+    pub status: i32,
+    /// Fields the request changed, as {field: {before, after}}, where the
+    /// handler records them
+    pub changes: Option<serde_json::Value>,
+    pub request_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct AuditLogQuery {
+    /// Only requests made by this user
+    pub user_id: Option<UserId>,
+    /// Only requests on this kind of resource, e.g. tasks
+    pub entity_type: Option<String>,
+    /// Only requests naming this id; usually combined with entity_type
+    pub entity_id: Option<String>,
+    /// Only requests made at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only requests made before this time
+    pub to: Option<DateTime<Utc>>,
+}
@@ -1,11 +1,18 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use chrono::{DateTime, Utc};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use crate::models::ids::UserId;
+use crate::utils::errors::ServiceError;
+use crate::utils::text;
+
+const NAME_MAX: usize = 255;
+const USERNAME_MIN: usize = 3;
+const USERNAME_MAX: usize = 50;
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct User {
-    pub id: i32,
+    pub id: UserId,
     pub username: String,
     #[serde(skip_serializing)]
     #[allow(dead_code)]
@@ -15,9 +22,37 @@ pub struct User {
     pub updated_at: DateTime<Utc>,
 }
 
+/// The language emails are sent to a user in
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LocaleSetting {
+    /// `en` or `id`; region tags such as `id-ID` are accepted
+    pub locale: String,
+}
+
+/// The current user's email address and whether it has been verified
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmailSetting {
+    pub email: Option<String>,
+    /// Until this is true the account can only read the board
+    pub verified: bool,
+}
+
+/// A new email address for the current user; a verification link is sent
+/// to it
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateEmailRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyEmailRequest {
+    /// Token from the emailed verification link
+    pub token: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UserResponse {
-    pub id: i32,
+    pub id: UserId,
     pub username: String,
     pub name: String,
     pub created_at: DateTime<Utc>,
@@ -40,14 +75,139 @@ impl From<User> for UserResponse {
 pub struct LoginRequest {
     pub username: String,
     pub password: String,
+    /// Issue a longer-lived token (REMEMBER_ME_TTL_HOURS instead of
+    /// TOKEN_TTL_HOURS)
+    #[serde(default)]
+    pub remember_me: bool,
+    /// Captcha response, needed after repeated failed logins when captcha
+    /// checks are on
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct LoginResponseData {
-    pub token: String,
+    /// Omitted in cookie auth mode, where the token is set as an HttpOnly cookie
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
     pub user: UserResponse,
 }
 
+/// Fields of the current user's profile to change; omitted fields are kept
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateProfileRequest {
+    pub name: Option<String>,
+    /// 3 to 50 letters, digits, '.', '_' or '-'
+    pub username: Option<String>,
+}
+
+impl UpdateProfileRequest {
+    /// Normalize the display name and check both fields
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        if self.name.is_none() && self.username.is_none() {
+            return Err(ServiceError::ValidationError("Nothing to update; send a name or username".to_string()));
+        }
+        if let Some(name) = &self.name {
+            self.name = Some(normalize_name(name)?);
+        }
+        if let Some(username) = &self.username {
+            self.username = Some(normalize_username(username)?);
+        }
+        Ok(())
+    }
+}
+
+fn normalize_name(name: &str) -> Result<String, ServiceError> {
+    let name = text::single_line("Name", name, NAME_MAX, NAME_MAX)?;
+    if name.is_empty() {
+        return Err(ServiceError::ValidationError("Name cannot be empty".to_string()));
+    }
+    Ok(name)
+}
+
+fn normalize_username(username: &str) -> Result<String, ServiceError> {
+    let username = username.trim();
+    let valid_chars = username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid_chars || !(USERNAME_MIN..=USERNAME_MAX).contains(&username.len()) {
+        return Err(ServiceError::ValidationError(format!(
+            "Username must be {} to {} letters, digits, '.', '_' or '-'", USERNAME_MIN, USERNAME_MAX
+        )).with_code("INVALID_USERNAME"));
+    }
+    Ok(username.to_string())
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+    /// Reject every token issued before the change, signing out other
+    /// devices; the caller gets a fresh token in the response
+    #[serde(default)]
+    pub invalidate_tokens: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ResetPasswordRequest {
+    /// Token from the emailed reset link
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MagicLinkRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MagicLinkLoginRequest {
+    /// Token from the emailed login link
+    pub token: String,
+}
+
+/// Account details chosen by someone accepting an invitation; the email
+/// comes from the invitation
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AcceptInviteRequest {
+    /// Token from the emailed invite link
+    pub token: String,
+    /// 3 to 50 letters, digits, '.', '_' or '-'
+    pub username: String,
+    pub name: String,
+    pub password: String,
+    /// Captcha response, needed when captcha checks are on
+    pub captcha_token: Option<String>,
+}
+
+impl AcceptInviteRequest {
+    /// Normalize the display name and username and check both
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.name = normalize_name(&self.name)?;
+        self.username = normalize_username(&self.username)?;
+        Ok(())
+    }
+}
+
+/// Query string the OAuth provider redirects back with
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct OAuthCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    /// Set instead of `code` when the user declined or the provider failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChangePasswordResponse {
+    /// Replacement token, present when existing tokens were invalidated; set
+    /// as a cookie instead in cookie auth mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ApiResponse<T> {
     pub status: String,
@@ -70,4 +230,12 @@ impl<T> ApiResponse<T> {
 pub struct ErrorResponse {
     pub status: String,
     pub message: String,
+    /// Stable machine-readable code, e.g. TASK_NOT_FOUND
+    pub error_code: String,
+    /// Individual problems, e.g. each unmet password rule
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<String>,
+    /// Id to quote when reporting unexpected server errors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
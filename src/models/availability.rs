@@ -0,0 +1,34 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::ids::UserId;
+
+pub const AVAILABLE: &str = "available";
+pub const OUT_OF_OFFICE: &str = "out_of_office";
+pub const STATUSES: &[&str] = &[AVAILABLE, OUT_OF_OFFICE];
+
+/// A user's availability, set by the user themselves
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Availability {
+    pub user_id: UserId,
+    /// `available` or `out_of_office`
+    pub status: String,
+    /// Start of the absence; open-ended when missing
+    pub away_from: Option<DateTime<Utc>>,
+    /// End of the absence; open-ended when missing
+    pub away_until: Option<DateTime<Utc>>,
+    /// Who should take new work while the user is away
+    pub delegate_id: Option<UserId>,
+    /// Whether the user is out of office right now
+    pub away_now: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetAvailabilityRequest {
+    /// `available` or `out_of_office`
+    pub status: String,
+    pub away_from: Option<DateTime<Utc>>,
+    pub away_until: Option<DateTime<Utc>>,
+    pub delegate_id: Option<UserId>,
+}
@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// A background job parked after exhausting its retries
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeadLetter {
+    pub id: i64,
+    /// Job type, e.g. `outbox_event`
+    pub kind: String,
+    /// Everything needed to run the job again
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub first_attempted_at: Option<DateTime<Utc>>,
+    pub dead_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeadLetterQuery {
+    /// Only show dead letters of this kind
+    pub kind: Option<String>,
+    /// Maximum number of entries to return (default 50, max 500)
+    pub limit: Option<i64>,
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::utils::errors::ServiceError;
+use crate::utils::{locale, text};
+
+const SUBJECT_MAX: usize = 200;
+const BODY_MAX: usize = 20_000;
+
+/// An email template as currently in effect
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmailTemplate {
+    pub name: String,
+    /// Language of this version of the template
+    pub locale: String,
+    pub description: String,
+    /// `built_in`, `file` (from EMAIL_TEMPLATE_DIR) or `custom` (saved
+    /// through the admin API)
+    pub source: String,
+    pub subject: String,
+    pub body: String,
+    /// Variables available to the template
+    pub variables: Vec<String>,
+    /// When a custom template was last saved
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Which language version of a template to work on
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EmailTemplateQuery {
+    /// Locale such as `id`; defaults to `en`
+    pub locale: Option<String>,
+}
+
+impl EmailTemplateQuery {
+    pub fn locale(&self) -> Result<&'static str, ServiceError> {
+        self.locale.as_deref().map_or(Ok(locale::ENGLISH), locale::parse)
+    }
+}
+
+/// Replacement subject and body in Tera syntax, e.g. `{{ link }}`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateEmailTemplateRequest {
+    pub subject: String,
+    pub body: String,
+}
+
+impl UpdateEmailTemplateRequest {
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.subject = text::single_line("Subject", &self.subject, SUBJECT_MAX, SUBJECT_MAX)?;
+        self.body = text::multi_line("Body", &self.body, BODY_MAX)?;
+        if self.subject.is_empty() || self.body.trim().is_empty() {
+            return Err(ServiceError::ValidationError("Subject and body cannot be empty".to_string())
+                .with_code("INVALID_TEMPLATE"));
+        }
+        Ok(())
+    }
+}
+
+/// Draft subject and body to preview; omitted parts use the template in
+/// effect
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PreviewEmailTemplateRequest {
+    pub subject: Option<String>,
+    pub body: Option<String>,
+}
+
+/// A template rendered against sample data
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub body: String,
+}
@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::ids::TaskId;
+use crate::utils::errors::ServiceError;
+use crate::utils::text;
+
+const TITLE_MAX: usize = 200;
+const DESCRIPTION_MAX: usize = 5_000;
+const CONTACT_NAME_MAX: usize = 100;
+const CAPTCHA_TOKEN_MAX: usize = 4_096;
+
+/// Feedback or a bug report from someone without an account
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SubmitFeedbackRequest {
+    pub title: String,
+    pub description: String,
+    /// How to reach the submitter; only administrators can see it
+    pub contact_name: Option<String>,
+    pub contact_email: Option<String>,
+    /// Response token from the captcha widget on the form
+    pub captcha_token: String,
+}
+
+impl SubmitFeedbackRequest {
+    /// Normalize the text fields, drop blank contact details and check the
+    /// email looks like one
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.title = text::single_line("Title", &self.title, TITLE_MAX, TITLE_MAX)?;
+        self.description = text::multi_line("Description", &self.description, DESCRIPTION_MAX)?;
+        if self.title.is_empty() || self.description.trim().is_empty() {
+            return Err(ServiceError::ValidationError("Title and description cannot be empty".to_string())
+                .with_code("INVALID_FEEDBACK"));
+        }
+
+        self.contact_name = self.contact_name.as_deref()
+            .map(|name| text::single_line("Contact name", name, CONTACT_NAME_MAX, CONTACT_NAME_MAX))
+            .transpose()?
+            .filter(|name| !name.is_empty());
+        self.contact_email = self.contact_email.as_deref()
+            .map(str::trim)
+            .filter(|email| !email.is_empty())
+            .map(text::email)
+            .transpose()?;
+
+        if self.captcha_token.len() > CAPTCHA_TOKEN_MAX {
+            return Err(ServiceError::ValidationError("Captcha check failed; please try again".to_string())
+                .with_code("CAPTCHA_FAILED"));
+        }
+        Ok(())
+    }
+}
+
+/// Returned to the submitter so they can quote their report
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedbackReceipt {
+    /// Key of the triage task, e.g. `KAN-12`
+    pub reference: String,
+}
+
+/// Who filed a triage task through the feedback form
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedbackSubmission {
+    pub task_id: TaskId,
+    pub contact_name: Option<String>,
+    pub contact_email: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
@@ -1,30 +1,52 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+use crate::models::ids::{AttachmentId, TaskId, UserId};
+use crate::utils::errors::ServiceError;
+
+pub const ATTACHMENT_SCANNING: &str = "scanning";
+pub const ATTACHMENT_READY: &str = "ready";
+pub const ATTACHMENT_INFECTED: &str = "infected";
+pub const ATTACHMENT_FAILED: &str = "failed";
+/// Held for admin review by the moderation service
+pub const ATTACHMENT_FLAGGED: &str = "flagged";
+/// Refused by an admin after moderation; the file is removed
+pub const ATTACHMENT_REJECTED: &str = "rejected";
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TaskAttachment {
-    pub id: i32,
-    pub task_id: i32,
+    pub id: AttachmentId,
+    pub task_id: TaskId,
     pub file_name: String,
     pub original_name: String,
     pub file_path: String,
     pub file_size: i64,
     pub mime_type: String,
-    pub uploaded_by: i32,
+    pub uploaded_by: UserId,
     pub created_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct AttachmentResponse {
-    pub id: i32,
-    pub task_id: i32,
+    pub id: AttachmentId,
+    pub task_id: TaskId,
     pub file_name: String,
     pub original_name: String,
     pub file_size: i64,
     pub mime_type: String,
-    pub uploaded_by: i32,
+    pub uploaded_by: UserId,
+    /// One of uploaded, scanning, ready, infected, failed, flagged, rejected;
+    /// only ready files can be downloaded
+    pub processing_status: String,
     pub download_url: String,
+    /// First-page PNG thumbnail for documents, once it has been rendered
+    pub preview_url: Option<String>,
+    /// Hex SHA-256 of the file as uploaded; None for files uploaded before
+    /// checksums were stored
+    pub sha256: Option<String>,
+    /// True when a search matched text inside the document rather than
+    /// only its name
+    pub content_match: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -34,6 +56,31 @@ pub struct UploadResponse {
     pub message: String,
 }
 
+/// Attachments of one task to delete: the listed ones, or all of them
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeleteAttachmentsRequest {
+    #[serde(default)]
+    pub attachment_ids: Vec<AttachmentId>,
+    /// Delete every attachment of the task; `attachment_ids` must be empty
+    #[serde(default)]
+    pub all: bool,
+}
+
+impl DeleteAttachmentsRequest {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        if self.all != self.attachment_ids.is_empty() {
+            return Err(ServiceError::ValidationError("Set either attachment_ids or all".to_string())
+                .with_code("INVALID_ATTACHMENT_SELECTION"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DeletedAttachments {
+    pub deleted: Vec<AttachmentId>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct FileUploadInfo {
     pub file_name: String,
@@ -47,8 +94,66 @@ pub struct TaskAttachmentSimple {
     pub url: String,
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UploadQuery {
+    /// Hex SHA-256 of the file; the upload is rejected if the received bytes
+    /// do not match it
+    pub sha256: Option<String>,
+}
+
+impl UploadQuery {
+    /// The expected checksum in lowercase, if one was given
+    pub fn expected_sha256(&self) -> Result<Option<String>, ServiceError> {
+        let Some(sha256) = self.sha256.as_deref().map(str::trim).filter(|s| !s.is_empty()) else {
+            return Ok(None);
+        };
+        if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ServiceError::ValidationError("sha256 must be 64 hexadecimal characters".to_string())
+                .with_code("INVALID_CHECKSUM"));
+        }
+        Ok(Some(sha256.to_ascii_lowercase()))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UploadFileRequest {
     #[schema(format = "binary")]
     pub file: String,
 }
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StorageQuery {
+    /// How many of the largest files to list (default 10, max 100)
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StoredFile {
+    pub id: AttachmentId,
+    pub task_id: TaskId,
+    pub task_name: String,
+    pub original_name: String,
+    pub file_size: i64,
+    pub mime_type: String,
+    pub uploaded_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StorageReport {
+    pub attachment_count: i64,
+    pub total_bytes: i64,
+    /// Largest attachments first
+    pub largest_files: Vec<StoredFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AttachmentDownload {
+    pub id: i64,
+    pub attachment_id: AttachmentId,
+    pub user_id: UserId,
+    pub username: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub downloaded_at: DateTime<Utc>,
+}
@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use utoipa::ToSchema;
+
+// Declares an integer id newtype that serializes, binds and documents exactly
+// like the plain i32 it wraps, so the wire format and schema are unchanged
+macro_rules! id_newtype {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, ToSchema)]
+        #[serde(transparent)]
+        #[sqlx(transparent)]
+        pub struct $name(pub i32);
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+
+        impl From<i32> for $name {
+            fn from(id: i32) -> Self {
+                $name(id)
+            }
+        }
+    };
+}
+
+id_newtype!(
+    /// Primary key of a row in `tasks`
+    TaskId
+);
+id_newtype!(
+    /// Primary key of a row in `users`
+    UserId
+);
+id_newtype!(
+    /// Primary key of a row in `teams`
+    TeamId
+);
+id_newtype!(
+    /// Primary key of a row in `task_attachments`
+    AttachmentId
+);
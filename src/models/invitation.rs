@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::ids::{TeamId, UserId};
+use crate::utils::errors::ServiceError;
+use crate::utils::{locale, text};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateInvitationRequest {
+    pub email: String,
+    /// Teams the new account joins when the invitation is accepted
+    pub team_ids: Vec<TeamId>,
+    /// Language of the email and the new account, e.g. `id`; defaults to
+    /// the inviter's
+    pub locale: Option<String>,
+}
+
+impl CreateInvitationRequest {
+    /// Trim the email, drop duplicate teams and check the fields
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.email = text::email(&self.email)?;
+
+        self.team_ids.sort();
+        self.team_ids.dedup();
+        if self.team_ids.is_empty() {
+            return Err(ServiceError::ValidationError("Invite to at least one team".to_string())
+                .with_code("INVALID_TEAMS"));
+        }
+
+        if let Some(locale) = &self.locale {
+            self.locale = Some(locale::parse(locale)?.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A pending invitation. The token itself is only ever sent by email.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Invitation {
+    pub id: i32,
+    pub email: String,
+    pub team_ids: Vec<TeamId>,
+    pub invited_by: UserId,
+    pub locale: String,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,87 @@
+use std::future::{ready, Ready};
+
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::utils::errors::ServiceError;
+use crate::utils::sql::Sort;
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+/// Query parameters shared by list endpoints. Take `ListParams` as a handler
+/// argument to get them parsed, with a 400 for malformed values.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+pub struct ListParams {
+    /// Page number, starting at 1
+    pub page: Option<i64>,
+    /// Items per page (default 50, max 200)
+    pub per_page: Option<i64>,
+    /// Case-insensitive text search; on attachments it also searches the
+    /// text inside documents
+    pub q: Option<String>,
+    /// Field to sort by; prefix with `-` for descending, e.g. `-created_at`
+    pub sort: Option<String>,
+}
+
+impl ListParams {
+    pub fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    pub fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE)
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page() - 1).saturating_mul(self.per_page())
+    }
+
+    /// The trimmed search term, or None when no search was requested
+    pub fn search_text(&self) -> Option<String> {
+        self.q.as_deref().map(str::trim).filter(|q| !q.is_empty()).map(str::to_string)
+    }
+
+    /// The search term as an ILIKE pattern with wildcards escaped, or None
+    /// when no search was requested
+    pub fn search_pattern(&self) -> Option<String> {
+        let q = self.search_text()?;
+        let escaped = q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        Some(format!("%{}%", escaped))
+    }
+
+    /// The requested sort. `allowed` maps public field names to columns, so
+    /// only whitelisted columns ever reach the SQL.
+    pub fn sort(&self, allowed: &[(&str, &'static str)], default: &str) -> Result<Sort, ServiceError> {
+        let sort = self.sort.as_deref().map(str::trim).filter(|s| !s.is_empty()).unwrap_or(default);
+        let (field, descending) = match sort.strip_prefix('-') {
+            Some(field) => (field, true),
+            None => (sort, false),
+        };
+
+        let column = allowed.iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| {
+                let fields: Vec<&str> = allowed.iter().map(|(name, _)| *name).collect();
+                ServiceError::ValidationError(format!("Cannot sort by '{}'; expected one of: {}", field, fields.join(", ")))
+                    .with_code("INVALID_SORT")
+            })?;
+
+        Ok(Sort { column, descending })
+    }
+}
+
+impl FromRequest for ListParams {
+    type Error = ServiceError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = web::Query::<ListParams>::from_query(req.query_string())
+            .map(|query| query.into_inner())
+            .map_err(|e| ServiceError::ValidationError(format!("Invalid list parameters: {}", e)));
+        ready(result)
+    }
+}
@@ -1,3 +1,20 @@
+pub mod ids;
 pub mod auth;
+pub mod availability;
 pub mod task;
 pub mod file;
+pub mod sync;
+pub mod operation;
+pub mod dead_letter;
+pub mod list;
+pub mod params;
+pub mod admin;
+pub mod api_key;
+pub mod invitation;
+pub mod usage;
+pub mod script;
+pub mod email_template;
+pub mod feedback;
+pub mod moderation;
+pub mod audit;
+pub mod report;
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::ids::{AttachmentId, TaskId, UserId};
+
+pub const DECISION_APPROVED: &str = "approved";
+pub const DECISION_REJECTED: &str = "rejected";
+
+/// An upload the moderation service held back, and what an admin decided
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ModerationReview {
+    pub id: i32,
+    pub attachment_id: AttachmentId,
+    pub task_id: TaskId,
+    pub original_name: String,
+    pub mime_type: String,
+    pub uploaded_by: UserId,
+    /// Why it was held, as given by the service
+    pub reason: String,
+    pub flagged_at: DateTime<Utc>,
+    /// approved or rejected; None while waiting for review
+    pub decision: Option<String>,
+    pub decided_by: Option<UserId>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ModerationQuery {
+    /// true (default) for reviews still waiting for a decision, false for
+    /// decided ones
+    pub pending: Option<bool>,
+}
@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::models::ids::UserId;
+
+pub const OPERATION_RUNNING: &str = "running";
+pub const OPERATION_SUCCEEDED: &str = "succeeded";
+pub const OPERATION_FAILED: &str = "failed";
+
+/// A long-running job started by an endpoint that answered 202 Accepted
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Operation {
+    pub id: Uuid,
+    /// What the operation does, e.g. `task_export`
+    pub kind: String,
+    /// One of pending, running, succeeded, failed
+    pub status: String,
+    /// Completion percentage, 0-100
+    pub progress: i32,
+    /// Kind-specific result once succeeded, including any download links
+    pub result: Option<serde_json::Value>,
+    pub error: Option<String>,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,54 @@
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::models::ids::{AttachmentId, TaskId};
+
+// Path parameters shared by several operations. Handlers extract them with
+// `web::Path` and list them in `params(...)`, so every operation documents
+// them the same way and generated clients get the same argument names.
+
+/// A task addressed as `/api/tasks/{id}`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct TaskPath {
+    /// Task ID
+    #[param(value_type = i32)]
+    pub id: TaskId,
+}
+
+/// A task addressed as `/api/tasks/{task_id}/...`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct TaskAttachmentsPath {
+    /// Task ID
+    #[param(value_type = i32)]
+    pub task_id: TaskId,
+}
+
+/// An attachment addressed as `/api/tasks/{task_id}/attachments/{attachment_id}`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct AttachmentPath {
+    /// Task ID
+    #[param(value_type = i32)]
+    pub task_id: TaskId,
+    /// Attachment ID
+    #[param(value_type = i32)]
+    pub attachment_id: AttachmentId,
+}
+
+/// A board column addressed as `/api/board/column-policies/{status}`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct ColumnPath {
+    /// Column: TO_DO, DOING or DONE
+    pub status: String,
+}
+
+/// An email template addressed as `/api/admin/email-templates/{name}`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+pub struct EmailTemplatePath {
+    /// Template name, e.g. invitation
+    pub name: String,
+}
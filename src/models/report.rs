@@ -0,0 +1,108 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::ids::UserId;
+use crate::models::task::{SlaBreach, TaskReference};
+
+/// Someone who updated tasks during the week
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Contributor {
+    pub user_id: UserId,
+    pub name: String,
+    /// Task events they caused: creations, edits, moves and deletions
+    pub updates: i64,
+}
+
+/// The week on the board, as emailed to admins by the weekly digest
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WeeklyDigest {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Tasks that moved into DONE during the week
+    pub completed: Vec<TaskReference>,
+    /// Tasks created during the week and still on the board
+    pub created: Vec<TaskReference>,
+    /// SLA breaches open at the end of the week, most recent first
+    pub overdue: Vec<SlaBreach>,
+    /// The most active people, most updates first
+    pub top_contributors: Vec<Contributor>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct WeeklyDigestQuery {
+    /// `json` (default), `html`, or `text` for the email as it would be sent
+    pub format: Option<String>,
+    /// End of the week to report; defaults to now
+    pub to: Option<DateTime<Utc>>,
+    /// Locale of the email for the `text` format, such as `id`; defaults to `en`
+    pub locale: Option<String>,
+}
+
+/// Task events one person caused on a day
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActivityDay {
+    pub date: NaiveDate,
+    pub count: i64,
+}
+
+/// One row of the activity heatmap; days without events are left out
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserActivity {
+    pub user_id: UserId,
+    pub name: String,
+    pub total: i64,
+    pub days: Vec<ActivityDay>,
+}
+
+/// Task events per person per day, like a contribution graph. Days are in
+/// the business calendar's local time, or UTC without one.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ActivityHeatmap {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub utc_offset_minutes: i32,
+    /// Most active first
+    pub users: Vec<UserActivity>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ActivityHeatmapQuery {
+    /// First day, inclusive; defaults to a year before `to`
+    pub from: Option<NaiveDate>,
+    /// Last day, inclusive; defaults to today
+    pub to: Option<NaiveDate>,
+    /// Only this person's activity
+    pub user_id: Option<UserId>,
+}
+
+/// How many weeks the backlog needs at a confidence level, and the date
+/// that lands on
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ForecastBand {
+    /// Share of simulations that finished within `weeks`, e.g. 85
+    pub percentile: u8,
+    pub weeks: u32,
+    pub date: NaiveDate,
+}
+
+/// When the backlog is likely done, from a Monte Carlo simulation that
+/// replays randomly picked weeks of past throughput
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Forecast {
+    /// Tasks left to complete
+    pub remaining: i64,
+    /// Tasks completed in each past week, most recent first
+    pub weekly_throughput: Vec<i64>,
+    pub simulations: u32,
+    /// The 50th, 85th and 95th percentiles
+    pub bands: Vec<ForecastBand>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ForecastQuery {
+    /// Weeks of history to sample throughput from, 4 to 52; defaults to 12
+    pub weeks: Option<i32>,
+    /// Tasks to forecast; defaults to those not yet DONE
+    pub remaining: Option<i64>,
+}
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::models::ids::UserId;
+use crate::services::task_events::{TASK_CREATED, TASK_UPDATED};
+use crate::services::task_writes::TASK_STATUS_CHANGED;
+use crate::utils::errors::ServiceError;
+use crate::utils::text;
+
+/// Runs inside the write and can reject it
+pub const KIND_VALIDATE: &str = "validate";
+/// Runs after the change committed and can update the task
+pub const KIND_AUTOMATE: &str = "automate";
+
+// Events each kind of script can be attached to
+const VALIDATE_EVENTS: &[&str] = &[TASK_CREATED, TASK_UPDATED];
+const AUTOMATE_EVENTS: &[&str] = &[TASK_CREATED, TASK_STATUS_CHANGED];
+
+const NAME_MAX: usize = 100;
+const SOURCE_MAX: usize = 20_000;
+
+/// An admin-managed Rhai script run on task events
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Script {
+    pub id: i32,
+    pub name: String,
+    /// `validate` or `automate`
+    pub kind: String,
+    /// `task.created`, `task.updated` (validate only) or
+    /// `task.status_changed` (automate only)
+    pub event: String,
+    pub source: String,
+    pub enabled: bool,
+    /// Automation changes are recorded as made by this user
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A new script. Validation scripts see `task` (the task as it would be
+/// saved) and, for updates, `changes`; returning `false` or a message, or
+/// throwing, rejects the write. Automation scripts see `event` and `task`
+/// and may return a map of `name`, `description`, `status` and
+/// `external_link` to set on the task.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateScriptRequest {
+    pub name: String,
+    pub kind: String,
+    pub event: String,
+    pub source: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl CreateScriptRequest {
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.name = normalize_name(&self.name)?;
+        check_kind_and_event(&self.kind, &self.event)?;
+        check_source(&self.source)
+    }
+}
+
+/// Fields of a script to change; omitted fields are kept
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateScriptRequest {
+    pub name: Option<String>,
+    pub event: Option<String>,
+    pub source: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+impl UpdateScriptRequest {
+    /// Check the fields against the kind of the script being changed
+    pub fn normalize(&mut self, kind: &str) -> Result<(), ServiceError> {
+        if let Some(name) = &self.name {
+            self.name = Some(normalize_name(name)?);
+        }
+        if let Some(event) = &self.event {
+            check_kind_and_event(kind, event)?;
+        }
+        if let Some(source) = &self.source {
+            check_source(source)?;
+        }
+        Ok(())
+    }
+}
+
+fn normalize_name(name: &str) -> Result<String, ServiceError> {
+    let name = text::single_line("Script name", name, NAME_MAX, NAME_MAX)?;
+    if name.is_empty() {
+        return Err(ServiceError::ValidationError("Script name cannot be empty".to_string()));
+    }
+    Ok(name)
+}
+
+fn check_kind_and_event(kind: &str, event: &str) -> Result<(), ServiceError> {
+    let events = match kind {
+        KIND_VALIDATE => VALIDATE_EVENTS,
+        KIND_AUTOMATE => AUTOMATE_EVENTS,
+        _ => {
+            return Err(ServiceError::ValidationError(format!("Kind must be {} or {}", KIND_VALIDATE, KIND_AUTOMATE))
+                .with_code("INVALID_SCRIPT"));
+        }
+    };
+    if !events.contains(&event) {
+        return Err(ServiceError::ValidationError(format!("{} scripts run on: {}", kind, events.join(", ")))
+            .with_code("INVALID_SCRIPT"));
+    }
+    Ok(())
+}
+
+fn check_source(source: &str) -> Result<(), ServiceError> {
+    if source.trim().is_empty() || source.len() > SOURCE_MAX {
+        return Err(ServiceError::ValidationError(format!("Source must be 1 to {} bytes", SOURCE_MAX))
+            .with_code("INVALID_SCRIPT"));
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::ids::TaskId;
+use crate::models::task::{TaskResponse, Team, UpdateTaskRequest};
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SyncQuery {
+    /// Cursor returned by a previous sync; omit for a full snapshot
+    pub since: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SyncResponse {
+    /// Pass this back as `since` on the next sync
+    pub cursor: i64,
+    /// Tasks created or changed since the cursor
+    pub tasks: Vec<TaskResponse>,
+    /// Tombstones for tasks deleted since the cursor
+    pub deleted_task_ids: Vec<TaskId>,
+    /// Teams added since the cursor
+    pub teams: Vec<Team>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SyncPushRequest {
+    pub changes: Vec<TaskChange>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TaskChange {
+    pub task_id: TaskId,
+    /// `updated_at` of the task as the client last saw it
+    pub base_updated_at: DateTime<Utc>,
+    pub fields: UpdateTaskRequest,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FieldConflict {
+    pub field: String,
+    pub server_value: serde_json::Value,
+    pub client_value: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaskChangeResult {
+    pub task_id: TaskId,
+    /// One of `applied`, `conflict` or `deleted`
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<TaskResponse>,
+    pub conflicts: Vec<FieldConflict>,
+}
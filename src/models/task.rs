@@ -1,31 +1,99 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use chrono::{DateTime, Utc};
-use utoipa::ToSchema;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::models::availability::Availability;
 use crate::models::file::TaskAttachmentSimple;
+use crate::models::ids::{TaskId, TeamId, UserId};
+use crate::utils::errors::ServiceError;
+use crate::utils::text;
+
+// Length limits in user-visible characters; names also fit their columns
+const TASK_NAME_MAX: usize = 255;
+const TEAM_NAME_MAX: usize = 50;
+const DESCRIPTION_MAX: usize = 10_000;
+const STATUS_MAX: usize = 20;
+const EXTERNAL_LINK_MAX: usize = 2048;
+const SLA_RULE_NAME_MAX: usize = 100;
+// A year; longer limits are better served by a due date
+const SLA_MAX_HOURS: i32 = 24 * 365;
+const ESCALATION_MAX_STEPS: usize = 10;
+const HOLIDAY_NAME_MAX: usize = 100;
+const HOLIDAYS_MAX: usize = 1000;
+
+/// Prefix of task keys: task 123 is `KAN-123`
+pub const TASK_KEY_PREFIX: &str = "KAN";
+
+pub fn task_key(id: TaskId) -> String {
+    format!("{}-{}", TASK_KEY_PREFIX, id)
+}
+
+fn normalize_teams(teams: &mut Option<Vec<String>>) -> Result<(), ServiceError> {
+    if let Some(teams) = teams {
+        for team in teams.iter_mut() {
+            *team = text::single_line("Team name", team, TEAM_NAME_MAX, TEAM_NAME_MAX)?;
+        }
+    }
+    Ok(())
+}
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct Task {
-    pub id: i32,
+    pub id: TaskId,
     pub name: String,
     pub description: Option<String>,
     pub status: String,
     pub external_link: Option<String>,
-    pub created_by: i32,
+    pub client_id: Option<Uuid>,
+    pub created_by: UserId,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+/// Another task mentioned by its key, e.g. `KAN-12`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskReference {
+    pub id: TaskId,
+    pub key: String,
+    pub name: String,
+    pub status: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct TaskResponse {
-    pub id: i32,
+    pub id: TaskId,
+    /// Key to mention the task by in other tasks' descriptions, e.g. `KAN-12`
+    #[serde(default)]
+    pub key: String,
     pub name: String,
     pub description: Option<String>,
     pub status: String,
     pub external_link: Option<String>,
-    pub created_by: i32,
+    pub created_by: UserId,
+    /// Client-generated identifier supplied at creation, if any
+    pub client_id: Option<Uuid>,
     pub teams: Vec<String>,
     pub attachments: Vec<TaskAttachmentSimple>,
+    /// Present while the owner is out of office, with their delegate if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner_away: Option<Availability>,
+    /// Tasks this task's description mentions
+    #[serde(default)]
+    pub references: Vec<TaskReference>,
+    /// Tasks whose descriptions mention this task
+    #[serde(default)]
+    pub mentioned_in: Vec<TaskReference>,
+    /// Number of users who upvoted the task
+    #[serde(default)]
+    pub votes: i64,
+    /// Whether the caller upvoted the task; left out where there is no
+    /// single caller, e.g. in exports and event payloads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voted_by_me: Option<bool>,
+    /// Whether the task has overstayed its column under an SLA rule
+    #[serde(default)]
+    pub sla_breached: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -37,6 +105,401 @@ pub struct CreateTaskRequest {
     pub status: String,
     pub external_link: Option<String>,
     pub teams: Option<Vec<String>>,
+    /// Optional client-generated UUID; resubmitting the same value returns the existing task
+    pub client_id: Option<Uuid>,
+}
+
+impl CreateTaskRequest {
+    /// Normalize the text fields in place (NFC, no control or invisible
+    /// characters) and enforce their length limits
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.name = text::single_line("Task name", &self.name, TASK_NAME_MAX, TASK_NAME_MAX)?;
+        self.description = self.description.as_deref()
+            .map(|description| text::multi_line("Description", description, DESCRIPTION_MAX))
+            .transpose()?;
+        self.external_link = self.external_link.as_deref()
+            .map(|link| text::single_line("External link", link, EXTERNAL_LINK_MAX, EXTERNAL_LINK_MAX))
+            .transpose()?;
+        normalize_teams(&mut self.teams)
+    }
+}
+
+/// Board-wide defaults filled in when a new task omits those fields
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct TaskDefaults {
+    /// Teams assigned when `teams` is omitted; deleted teams drop out
+    pub default_teams: Vec<String>,
+    /// Description used when `description` is omitted, e.g. a checklist
+    pub default_description: Option<String>,
+    /// When the defaults were last changed, if ever
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateTaskDefaultsRequest {
+    #[serde(default)]
+    pub default_teams: Vec<String>,
+    pub default_description: Option<String>,
+}
+
+impl UpdateTaskDefaultsRequest {
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        let mut teams = Some(std::mem::take(&mut self.default_teams));
+        normalize_teams(&mut teams)?;
+        self.default_teams = teams.unwrap_or_default();
+        self.default_teams.sort();
+        self.default_teams.dedup();
+        self.default_description = self.default_description.as_deref()
+            .map(|description| text::multi_line("Default description", description, DESCRIPTION_MAX))
+            .transpose()?
+            .filter(|description| !description.trim().is_empty());
+        Ok(())
+    }
+}
+
+/// Who a task is assigned to (made the owner of) when it moves into a
+/// status column: a fixed user, or the next active member of a team in turn
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ColumnPolicy {
+    pub status: String,
+    pub assign_to: Option<UserId>,
+    pub round_robin_team_id: Option<TeamId>,
+    /// Last member the round-robin picked; the next move goes to the member after them
+    pub last_assigned_user_id: Option<UserId>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Exactly one of `assign_to` and `round_robin_team_id`
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetColumnPolicyRequest {
+    pub assign_to: Option<UserId>,
+    pub round_robin_team_id: Option<TeamId>,
+}
+
+impl SetColumnPolicyRequest {
+    pub fn validate(&self) -> Result<(), ServiceError> {
+        if self.assign_to.is_some() == self.round_robin_team_id.is_some() {
+            return Err(ServiceError::ValidationError("Set either assign_to or round_robin_team_id".to_string())
+                .with_code("INVALID_COLUMN_POLICY"));
+        }
+        Ok(())
+    }
+}
+
+/// How long tasks may stay in a status column before they breach the SLA,
+/// e.g. tasks of the BACKEND team must leave TO_DO within 48 hours
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SlaRule {
+    pub id: i32,
+    pub name: String,
+    pub status: String,
+    /// Team whose tasks the rule covers; all tasks when absent
+    pub team: Option<String>,
+    /// Counted in working hours once a business calendar is set
+    pub max_hours: i32,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateSlaRuleRequest {
+    pub name: String,
+    /// Column the task must leave: TO_DO, DOING or DONE
+    pub status: String,
+    pub team: Option<String>,
+    pub max_hours: i32,
+}
+
+impl CreateSlaRuleRequest {
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.name = text::single_line("Rule name", &self.name, SLA_RULE_NAME_MAX, SLA_RULE_NAME_MAX)?;
+        self.team = self.team.as_deref()
+            .map(|team| text::single_line("Team name", team, TEAM_NAME_MAX, TEAM_NAME_MAX))
+            .transpose()?;
+        if !(1..=SLA_MAX_HOURS).contains(&self.max_hours) {
+            return Err(ServiceError::ValidationError(format!("max_hours must be between 1 and {}", SLA_MAX_HOURS))
+                .with_code("INVALID_SLA_HOURS"));
+        }
+        Ok(())
+    }
+}
+
+/// A task's stay in a column that went past an SLA rule's limit. Open while
+/// the task is still there; resolved once it leaves, or once the rule no
+/// longer covers it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SlaBreach {
+    pub id: i32,
+    pub rule_id: i32,
+    pub rule_name: String,
+    pub task_id: TaskId,
+    pub task_key: String,
+    pub task_name: String,
+    pub status: String,
+    /// Owner of the task, who was notified
+    pub owner: UserId,
+    pub entered_at: DateTime<Utc>,
+    pub breached_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// Filters of the SLA breach report
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct SlaBreachQuery {
+    /// true for current breaches only, false for resolved ones only; both
+    /// when absent
+    pub open: Option<bool>,
+    pub rule_id: Option<i32>,
+}
+
+/// One step of the escalation chain: `after_hours` into an SLA breach that
+/// is still open, notify the task owner (`owner`), a given user such as a
+/// team lead (`user`, with `user_id`) or every administrator (`admins`)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EscalationStep {
+    /// Counted in working hours once a business calendar is set
+    pub after_hours: i32,
+    pub notify: String,
+    pub user_id: Option<UserId>,
+}
+
+impl EscalationStep {
+    pub const NOTIFY: [&'static str; 3] = ["owner", "user", "admins"];
+}
+
+/// The whole chain; saving replaces it. Breaches already open keep the steps
+/// scheduled when they opened.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetEscalationChainRequest {
+    pub steps: Vec<EscalationStep>,
+}
+
+impl SetEscalationChainRequest {
+    /// Check each step and order the chain by `after_hours`
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        if self.steps.len() > ESCALATION_MAX_STEPS {
+            return Err(ServiceError::ValidationError(format!("An escalation chain has at most {} steps", ESCALATION_MAX_STEPS))
+                .with_code("INVALID_ESCALATION_CHAIN"));
+        }
+        for step in &mut self.steps {
+            step.notify = step.notify.trim().to_lowercase();
+            if !EscalationStep::NOTIFY.contains(&step.notify.as_str()) {
+                return Err(ServiceError::ValidationError("notify must be owner, user or admins".to_string())
+                    .with_code("INVALID_ESCALATION_CHAIN"));
+            }
+            if (step.notify == "user") != step.user_id.is_some() {
+                return Err(ServiceError::ValidationError("Give user_id exactly when notify is user".to_string())
+                    .with_code("INVALID_ESCALATION_CHAIN"));
+            }
+            if !(1..=SLA_MAX_HOURS).contains(&step.after_hours) {
+                return Err(ServiceError::ValidationError(format!("after_hours must be between 1 and {}", SLA_MAX_HOURS))
+                    .with_code("INVALID_ESCALATION_CHAIN"));
+            }
+        }
+        self.steps.sort_by_key(|step| step.after_hours);
+        if self.steps.windows(2).any(|pair| pair[0].after_hours == pair[1].after_hours) {
+            return Err(ServiceError::ValidationError("Each step needs a different after_hours".to_string())
+                .with_code("INVALID_ESCALATION_CHAIN"));
+        }
+        Ok(())
+    }
+}
+
+/// A chain step scheduled for a breach. It goes out at `due_at` unless the
+/// breach resolves first, which cancels it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Escalation {
+    pub id: i32,
+    pub breach_id: i32,
+    pub after_hours: i32,
+    pub notify: String,
+    pub user_id: Option<UserId>,
+    pub due_at: DateTime<Utc>,
+    pub escalated_at: Option<DateTime<Utc>>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+}
+
+/// The organization's working hours and holidays. SLA timers and
+/// escalation steps count only working time; without a calendar every hour
+/// counts.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BusinessCalendar {
+    /// Offset of local time from UTC in minutes, e.g. 420 for WIB
+    pub utc_offset_minutes: i32,
+    /// ISO weekdays that are working days, 1 = Monday to 7 = Sunday
+    pub work_days: Vec<i32>,
+    /// Start of the working day in local time, `HH:MM`
+    pub day_start: String,
+    /// End of the working day in local time, `HH:MM`
+    pub day_end: String,
+    pub holidays: Vec<Holiday>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// A day off in local time, whatever its weekday
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+/// The whole calendar; saving replaces it, holidays included
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateBusinessCalendarRequest {
+    pub utc_offset_minutes: i32,
+    pub work_days: Vec<i32>,
+    pub day_start: String,
+    pub day_end: String,
+    #[serde(default)]
+    pub holidays: Vec<Holiday>,
+}
+
+impl UpdateBusinessCalendarRequest {
+    /// Check the calendar and return its working day's start and end
+    pub fn normalize(&mut self) -> Result<(NaiveTime, NaiveTime), ServiceError> {
+        let invalid = |message: &str| ServiceError::ValidationError(message.to_string()).with_code("INVALID_CALENDAR");
+
+        if !(-12 * 60..=14 * 60).contains(&self.utc_offset_minutes) {
+            return Err(invalid("utc_offset_minutes must be between -720 and 840"));
+        }
+        self.work_days.sort();
+        self.work_days.dedup();
+        if self.work_days.is_empty() || self.work_days.iter().any(|day| !(1..=7).contains(day)) {
+            return Err(invalid("work_days must list weekdays from 1 (Monday) to 7 (Sunday)"));
+        }
+
+        let start = NaiveTime::parse_from_str(self.day_start.trim(), "%H:%M")
+            .map_err(|_| invalid("day_start must be a time like 09:00"))?;
+        let end = NaiveTime::parse_from_str(self.day_end.trim(), "%H:%M")
+            .map_err(|_| invalid("day_end must be a time like 17:00"))?;
+        if start >= end {
+            return Err(invalid("day_end must be after day_start"));
+        }
+
+        if self.holidays.len() > HOLIDAYS_MAX {
+            return Err(invalid(&format!("A calendar has at most {} holidays", HOLIDAYS_MAX)));
+        }
+        for holiday in &mut self.holidays {
+            holiday.name = text::single_line("Holiday name", &holiday.name, HOLIDAY_NAME_MAX, HOLIDAY_NAME_MAX)?;
+        }
+        self.holidays.sort_by_key(|holiday| holiday.date);
+        if self.holidays.windows(2).any(|pair| pair[0].date == pair[1].date) {
+            return Err(invalid("Each holiday needs a different date"));
+        }
+        Ok((start, end))
+    }
+}
+
+/// The window of a board diff
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct BoardDiffQuery {
+    /// Start of the window
+    pub from: DateTime<Utc>,
+    /// End of the window, exclusive; defaults to now
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// A task that changed column within the window
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaskMove {
+    pub id: TaskId,
+    pub key: String,
+    pub name: String,
+    pub from_status: String,
+    pub to_status: String,
+}
+
+/// A task whose teams changed within the window
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaskTeamChange {
+    pub id: TaskId,
+    pub key: String,
+    pub name: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// What changed on the board between two points in time, folded from the
+/// task event log. Each task is compared as it stood at `from` and at `to`,
+/// so a task moved there and back again within the window is not listed.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BoardDiff {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Tasks created within the window and still on the board at `to`
+    pub created: Vec<TaskReference>,
+    /// Tasks that were on the board at `from` and changed column, other
+    /// than into DONE
+    pub moved: Vec<TaskMove>,
+    /// Tasks that were on the board at `from` and moved into DONE
+    pub closed: Vec<TaskReference>,
+    /// Tasks deleted within the window, with their last name and status
+    pub deleted: Vec<TaskReference>,
+    pub team_changes: Vec<TaskTeamChange>,
+}
+
+/// An unfinished task, visible only to its author. Every field may be
+/// missing; the task is validated when the draft is published.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TaskDraft {
+    pub id: i32,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub status: Option<String>,
+    pub external_link: Option<String>,
+    /// Omitted teams get the board's default teams on publish
+    pub teams: Option<Vec<String>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TaskDraft {
+    /// The task this draft would create, before validation
+    pub fn into_create_request(self) -> CreateTaskRequest {
+        CreateTaskRequest {
+            name: self.name.unwrap_or_default(),
+            description: self.description,
+            status: self.status.unwrap_or_default(),
+            external_link: self.external_link,
+            teams: self.teams,
+            client_id: None,
+        }
+    }
+}
+
+/// Contents of a draft; saving replaces the whole draft
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SaveTaskDraftRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub status: Option<String>,
+    pub external_link: Option<String>,
+    pub teams: Option<Vec<String>>,
+}
+
+impl SaveTaskDraftRequest {
+    /// Normalize the text fields like `CreateTaskRequest::normalize`; only
+    /// length limits are enforced
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        self.name = self.name.as_deref()
+            .map(|name| text::single_line("Task name", name, TASK_NAME_MAX, TASK_NAME_MAX))
+            .transpose()?;
+        self.description = self.description.as_deref()
+            .map(|description| text::multi_line("Description", description, DESCRIPTION_MAX))
+            .transpose()?;
+        self.status = self.status.as_deref()
+            .map(|status| text::single_line("Status", status, STATUS_MAX, STATUS_MAX))
+            .transpose()?;
+        self.external_link = self.external_link.as_deref()
+            .map(|link| text::single_line("External link", link, EXTERNAL_LINK_MAX, EXTERNAL_LINK_MAX))
+            .transpose()?;
+        normalize_teams(&mut self.teams)
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TransferTaskRequest {
+    /// User who becomes the task's owner (`created_by`)
+    pub new_owner_id: UserId,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -44,13 +507,100 @@ pub struct UpdateTaskRequest {
     pub name: Option<String>,
     pub description: Option<String>,
     pub status: Option<String>,
+    /// An empty link clears it
     pub external_link: Option<String>,
     pub teams: Option<Vec<String>>,
 }
 
+/// Ordering of the task list
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TaskListQuery {
+    /// `created_at` or `votes`; prefix with `-` for descending. Defaults to
+    /// `-created_at`; `-votes` puts the most wanted tasks first.
+    pub sort: Option<String>,
+}
+
+/// Column mapping for a CSV import: each value is the header of the CSV
+/// column holding that field. Unmapped fields fall back to a column with the
+/// field's own name, if there is one.
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ImportQuery {
+    /// Column with the task name (required)
+    pub name: Option<String>,
+    /// Column with the status, e.g. `TO_DO` or `To do`; defaults to TO_DO
+    pub status: Option<String>,
+    pub description: Option<String>,
+    pub external_link: Option<String>,
+    /// Column with team names separated by `;`
+    pub teams: Option<String>,
+    /// Column with the username of the task's owner; defaults to the importer
+    pub assignee: Option<String>,
+    /// Not supported: tasks have no due date
+    pub due_date: Option<String>,
+    /// Validate only and report row errors without creating anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportRowError {
+    /// Spreadsheet row number; the header is row 1
+    pub row: usize,
+    pub column: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    /// Tasks created from the valid rows; empty for a dry run
+    pub created: Vec<TaskId>,
+    pub errors: Vec<ImportRowError>,
+}
+
+impl UpdateTaskRequest {
+    /// Normalize the provided text fields in place, like
+    /// `CreateTaskRequest::normalize`; a name must not end up empty
+    pub fn normalize(&mut self) -> Result<(), ServiceError> {
+        if let Some(name) = &self.name {
+            let name = text::single_line("Task name", name, TASK_NAME_MAX, TASK_NAME_MAX)?;
+            if name.is_empty() {
+                return Err(ServiceError::ValidationError("Task name cannot be empty".to_string()));
+            }
+            self.name = Some(name);
+        }
+        self.description = self.description.as_deref()
+            .map(|description| text::multi_line("Description", description, DESCRIPTION_MAX))
+            .transpose()?;
+        self.external_link = self.external_link.as_deref()
+            .map(|link| text::single_line("External link", link, EXTERNAL_LINK_MAX, EXTERNAL_LINK_MAX))
+            .transpose()?;
+        normalize_teams(&mut self.teams)
+    }
+}
+
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct Team {
-    pub id: i32,
+    pub id: TeamId,
     pub name: String,
     pub created_at: DateTime<Utc>,
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskEvent {
+    pub id: i64,
+    pub task_id: TaskId,
+    pub version: i32,
+    pub event_type: String,
+    pub actor_id: UserId,
+    pub data: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportQuery {
+    /// Export format, currently only `ndjson`
+    pub format: Option<String>,
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::ids::UserId;
+use crate::utils::errors::ServiceError;
+
+const DEFAULT_DAYS: i64 = 7;
+const MAX_DAYS: i64 = 90;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UsageQuery {
+    /// Days to cover, counting today (default 7, max 90)
+    pub days: Option<i64>,
+}
+
+impl UsageQuery {
+    pub fn days(&self) -> Result<i64, ServiceError> {
+        let days = self.days.unwrap_or(DEFAULT_DAYS);
+        if !(1..=MAX_DAYS).contains(&days) {
+            return Err(ServiceError::ValidationError(format!("days must be between 1 and {}", MAX_DAYS)));
+        }
+        Ok(days)
+    }
+}
+
+/// Requests made by one user with a login token or one of their API keys
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsageEntry {
+    pub user_id: UserId,
+    pub username: String,
+    /// None for requests made with a login token
+    pub api_key_id: Option<i32>,
+    pub api_key_name: Option<String>,
+    pub requests: i64,
+    /// Responses with a 4xx status
+    pub client_errors: i64,
+    /// Responses with a 5xx status
+    pub server_errors: i64,
+    /// Share of requests that failed, 0 to 1
+    pub error_rate: f64,
+}
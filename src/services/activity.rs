@@ -0,0 +1,60 @@
+use chrono::{Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::ids::UserId;
+use crate::models::report::{ActivityDay, ActivityHeatmap, UserActivity};
+use crate::services::business_hours;
+use crate::utils::errors::ServiceError;
+
+/// Longest range a heatmap covers, in days
+pub const MAX_DAYS: i64 = 366;
+
+/// Task events per person per day from `from` to `to`, both inclusive, in
+/// the business calendar's local time
+pub async fn heatmap(db: &Database, from: NaiveDate, to: NaiveDate, user_id: Option<UserId>) -> Result<ActivityHeatmap, ServiceError> {
+    let utc_offset_minutes = business_hours::get(db).await?
+        .map_or(0, |calendar| calendar.utc_offset_minutes);
+    let offset = FixedOffset::east_opt(utc_offset_minutes * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("UTC is a valid offset"));
+    let start = |day: NaiveDate| offset.from_local_datetime(&day.and_hms_opt(0, 0, 0).expect("midnight is a valid time"))
+        .single()
+        .expect("fixed offsets map local times one to one")
+        .with_timezone(&Utc);
+
+    let rows = sqlx::query(
+        "SELECT e.actor_id, u.name, ((e.created_at AT TIME ZONE 'UTC') + make_interval(mins => $3))::date AS day,
+                COUNT(*) AS events
+         FROM task_events e JOIN users u ON u.id = e.actor_id
+         WHERE e.created_at >= $1 AND e.created_at < $2
+           AND ($4::int IS NULL OR e.actor_id = $4)
+         GROUP BY e.actor_id, u.name, day
+         ORDER BY e.actor_id, day"
+    )
+    .bind(start(from))
+    .bind(start(to + Duration::days(1)))
+    .bind(utc_offset_minutes)
+    .bind(user_id)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error building activity heatmap: {}", e);
+        ServiceError::DatabaseError("Failed to build activity heatmap".to_string())
+    })?;
+
+    let mut users: Vec<UserActivity> = Vec::new();
+    for row in &rows {
+        let actor_id: UserId = row.get("actor_id");
+        let day = ActivityDay { date: row.get("day"), count: row.get("events") };
+        match users.last_mut() {
+            Some(user) if user.user_id == actor_id => {
+                user.total += day.count;
+                user.days.push(day);
+            }
+            _ => users.push(UserActivity { user_id: actor_id, name: row.get("name"), total: day.count, days: vec![day] }),
+        }
+    }
+    users.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.name.cmp(&b.name)));
+
+    Ok(ActivityHeatmap { from, to, utc_offset_minutes, users })
+}
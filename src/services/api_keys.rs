@@ -0,0 +1,194 @@
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::database;
+use crate::models::api_key::ApiKeyResponse;
+use crate::models::ids::{TeamId, UserId};
+use crate::utils::errors::ServiceError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Marks the string as one of our keys, which helps secret scanners
+const KEY_PREFIX: &str = "kbk_";
+// Characters of the key kept in clear text for display
+const DISPLAY_PREFIX_LEN: usize = 12;
+
+/// The owner of a valid API key, as loaded by `authenticate`
+#[derive(Debug, Clone)]
+pub struct ApiKeyOwner {
+    pub key_id: i32,
+    pub scopes: Vec<String>,
+    pub user_id: UserId,
+    pub username: String,
+    pub name: String,
+    pub role: String,
+    pub team_ids: Vec<TeamId>,
+    pub email_unverified: bool,
+    pub is_active: bool,
+}
+
+// Keys are looked up on every request, so they are stored as a keyed hash
+// rather than with bcrypt; they carry 256 random bits and need no stretching
+fn key_hash(config: &AppConfig, key: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(config.token_hash_key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(key.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Create a key for a user and return it with its stored metadata
+pub async fn mint(
+    db: &Database,
+    config: &AppConfig,
+    user_id: UserId,
+    name: &str,
+    scopes: &[String],
+    expires_in_days: Option<i64>,
+) -> Result<(String, ApiKeyResponse), ServiceError> {
+    let key = format!("{}{}{}", KEY_PREFIX, Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let expires_at = expires_in_days.map(|days| Utc::now() + Duration::days(days));
+
+    let row = sqlx::query(
+        "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING id, user_id, name, prefix, scopes, expires_at, created_at"
+    )
+    .bind(user_id)
+    .bind(name)
+    .bind(&key[..DISPLAY_PREFIX_LEN])
+    .bind(key_hash(config, &key))
+    .bind(scopes)
+    .bind(expires_at)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error creating API key: {}", e);
+        ServiceError::DatabaseError("Failed to create API key".to_string())
+    })?;
+
+    let api_key = ApiKeyResponse {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        name: row.get("name"),
+        prefix: row.get("prefix"),
+        scopes: row.get("scopes"),
+        expires_at: row.get("expires_at"),
+        created_at: row.get("created_at"),
+    };
+
+    Ok((key, api_key))
+}
+
+/// Resolve a presented key to its owner and record that it was used.
+/// Unknown, revoked and expired keys yield None.
+pub async fn authenticate(db: &Database, config: &AppConfig, key: &str) -> Result<Option<ApiKeyOwner>, ServiceError> {
+    find_owner(db, &key_hash(config, key)).await
+}
+
+// Keys are looked up before anyone is signed in, so as the service itself
+async fn find_owner(db: &Database, key_hash: &str) -> Result<Option<ApiKeyOwner>, ServiceError> {
+    let failed = |e: sqlx::Error| {
+        log::error!("Database error checking API key: {}", e);
+        ServiceError::DatabaseError("Failed to verify API key".to_string())
+    };
+    let mut tx = db.begin().await.map_err(failed)?;
+    database::act_as_service(&mut tx).await.map_err(failed)?;
+
+    let row = sqlx::query(
+        "WITH used AS (
+            UPDATE api_keys SET last_used_at = NOW()
+            WHERE key_hash = $1 AND revoked_at IS NULL AND (expires_at IS NULL OR expires_at > NOW())
+            RETURNING id, user_id, scopes
+         )
+         SELECT used.id, used.scopes, u.id AS user_id, u.username, u.name, u.role, u.is_active,
+                u.email IS NOT NULL AND u.email_verified_at IS NULL AS email_unverified,
+                ARRAY(SELECT team_id FROM team_members WHERE user_id = u.id ORDER BY team_id) AS team_ids
+         FROM used JOIN users u ON u.id = used.user_id"
+    )
+    .bind(key_hash)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(failed)?;
+    tx.commit().await.map_err(failed)?;
+
+    Ok(row.map(|row| ApiKeyOwner {
+        key_id: row.get("id"),
+        scopes: row.get("scopes"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        name: row.get("name"),
+        role: row.get("role"),
+        team_ids: row.get("team_ids"),
+        email_unverified: row.get("email_unverified"),
+        is_active: row.get("is_active"),
+    }))
+}
+
+/// Whether a key that authenticated earlier still would: it has not been
+/// revoked or expired since, and its owner is still active
+pub async fn is_usable(db: &Database, key_id: i32) -> Result<bool, ServiceError> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (
+            SELECT 1 FROM api_keys k JOIN users u ON u.id = k.user_id
+            WHERE k.id = $1 AND k.revoked_at IS NULL AND (k.expires_at IS NULL OR k.expires_at > NOW())
+              AND u.is_active
+         )"
+    )
+    .bind(key_id)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error checking API key: {}", e);
+        ServiceError::DatabaseError("Failed to verify API key".to_string())
+    })
+}
+
+/// Revoke one of a user's keys. Returns false when the user has no such
+/// active key.
+pub async fn revoke(db: &Database, user_id: UserId, key_id: i32) -> Result<bool, ServiceError> {
+    let result = sqlx::query("UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL")
+        .bind(key_id)
+        .bind(user_id)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error revoking API key: {}", e);
+            ServiceError::DatabaseError("Failed to revoke API key".to_string())
+        })?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::request_context::run_as;
+    use crate::utils::test_db::TestDb;
+
+    #[actix_web::test]
+    async fn keys_resolve_before_sign_in_under_row_level_security() {
+        let Some(test) = TestDb::create_with(true).await else { return };
+        let alice = test.insert_user("alice", "member").await;
+        sqlx::query("INSERT INTO api_keys (user_id, name, prefix, key_hash) VALUES ($1, 'key', 'kbk_', 'hash')")
+            .bind(alice)
+            .execute(&test.db.pool)
+            .await
+            .expect("insert key");
+
+        let owner = run_as(None, find_owner(&test.db, "hash")).await.expect("look up key");
+        assert_eq!(owner.map(|owner| owner.user_id), Some(alice));
+        assert!(run_as(None, find_owner(&test.db, "other")).await.expect("look up key").is_none());
+
+        // The lookup's access ends with its transaction
+        let visible: i64 = run_as(None, sqlx::query_scalar("SELECT COUNT(*) FROM api_keys").fetch_one(&test.db.pool))
+            .await
+            .expect("count keys");
+        assert_eq!(visible, 0);
+        test.drop().await;
+    }
+}
@@ -0,0 +1,82 @@
+use std::path::{Path, PathBuf};
+
+use crate::Database;
+use crate::models::ids::AttachmentId;
+use crate::services::encryption;
+use crate::utils::errors::ServiceError;
+
+/// Whether a first-page preview can be rendered for this type
+pub fn supports(mime_type: &str) -> bool {
+    mime_type == "application/pdf"
+        || mime_type == "application/msword"
+        || mime_type.starts_with("application/vnd.openxmlformats-officedocument.")
+}
+
+/// Where the preview of an attachment is stored
+pub fn preview_path(attachment_id: AttachmentId) -> PathBuf {
+    Path::new("uploads").join("previews").join(format!("{}.png", attachment_id))
+}
+
+/// Render the first page of a document as PNG by running the configured
+/// preview command as `<command> <input> <output.png>`, then record the
+/// preview on the attachment. Failures are only logged: an attachment
+/// without a preview is still fully usable.
+pub async fn generate(db: &Database, command: &str, attachment_id: AttachmentId, file_path: &Path) {
+    let output = preview_path(attachment_id);
+    let rendered = match render(command, file_path, &output).await {
+        Ok(()) => encryption::seal_file(&output).await,
+        Err(e) => Err(e),
+    };
+    if let Err(e) = rendered {
+        log::warn!("No preview for attachment {}: {}", attachment_id, e);
+        let _ = tokio::fs::remove_file(&output).await;
+        return;
+    }
+
+    let result = sqlx::query("UPDATE task_attachments SET preview_path = $2 WHERE id = $1")
+        .bind(attachment_id)
+        .bind(output.to_string_lossy().to_string())
+        .execute(&db.pool)
+        .await;
+
+    match result {
+        Ok(result) if result.rows_affected() > 0 => log::info!("Rendered preview for attachment {}", attachment_id),
+        // Attachment was deleted while the preview was rendered
+        Ok(_) => {
+            let _ = tokio::fs::remove_file(&output).await;
+        }
+        Err(e) => log::error!("Database error recording preview for attachment {}: {}", attachment_id, e),
+    }
+}
+
+async fn render(command: &str, input: &Path, output: &Path) -> Result<(), ServiceError> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next()
+        .ok_or_else(|| ServiceError::InternalError("PREVIEW_COMMAND is empty".to_string()))?;
+
+    if let Some(dir) = output.parent() {
+        tokio::fs::create_dir_all(dir).await.map_err(|e| {
+            ServiceError::InternalError(format!("Failed to create preview directory: {}", e))
+        })?;
+    }
+
+    let result = tokio::process::Command::new(program)
+        .args(parts)
+        .arg(input)
+        .arg(output)
+        .output()
+        .await
+        .map_err(|e| ServiceError::InternalError(format!("Failed to run preview command: {}", e)))?;
+
+    if !result.status.success() {
+        return Err(ServiceError::InternalError(format!(
+            "Preview command exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    if !output.exists() {
+        return Err(ServiceError::InternalError("Preview command produced no file".to_string()));
+    }
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use sqlx::{PgConnection, Row};
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::models::file::{ATTACHMENT_FAILED, ATTACHMENT_INFECTED, ATTACHMENT_READY, ATTACHMENT_SCANNING};
+use crate::models::ids::{AttachmentId, TaskId};
+use crate::services::{attachment_preview, attachment_text, moderation, outbox};
+use crate::services::encryption::PlainFile;
+use crate::utils::errors::ServiceError;
+
+pub const ATTACHMENT_STATUS_CHANGED: &str = "attachment.status_changed";
+
+/// Move an attachment to a new processing status and publish the change to
+/// realtime clients through the outbox, in one transaction
+pub async fn set_status(db: &Database, attachment_id: AttachmentId, status: &str) -> Result<(), ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    update_status(&mut tx, attachment_id, status).await?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(())
+}
+
+/// Move an attachment to a new processing status and publish the change,
+/// inside the caller's transaction; false if the attachment is gone
+pub async fn update_status(conn: &mut PgConnection, attachment_id: AttachmentId, status: &str) -> Result<bool, ServiceError> {
+    let row = sqlx::query(
+        "UPDATE task_attachments SET processing_status = $2 WHERE id = $1 RETURNING task_id"
+    )
+    .bind(attachment_id)
+    .bind(status)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error updating attachment status: {}", e);
+        ServiceError::DatabaseError("Failed to update attachment".to_string())
+    })?;
+
+    // Attachment was deleted while it was being processed
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    let task_id: TaskId = row.get("task_id");
+
+    outbox::enqueue(conn, "attachment", attachment_id.0, ATTACHMENT_STATUS_CHANGED, &serde_json::json!({
+        "id": attachment_id,
+        "task_id": task_id,
+        "processing_status": status,
+    })).await?;
+
+    Ok(true)
+}
+
+/// Scan a freshly uploaded file in the background. Without a configured scan
+/// command files go straight to ready; infected files are removed from disk.
+/// Clean images are then reviewed by the moderation service when one is
+/// configured, and held for an admin if it flags them. Clean documents get a
+/// preview when a preview command is given, and their text is indexed for
+/// search. Encrypted files are processed from a decrypted temporary copy.
+pub fn spawn_scan(
+    db: Arc<Database>,
+    config: &AppConfig,
+    attachment_id: AttachmentId,
+    file_path: PathBuf,
+    file_name: String,
+    mime_type: String,
+) {
+    let scan_command = config.virus_scan_command.clone();
+    let preview_command = config.preview_command.clone();
+    let pdf_text_command = config.pdf_text_command.clone();
+    let moderation = config.moderation.clone().filter(|_| moderation::supports(&mime_type));
+
+    tokio::spawn(async move {
+        if let Err(e) = set_status(&db, attachment_id, ATTACHMENT_SCANNING).await {
+            log::error!("Failed to mark attachment {} as scanning: {}", attachment_id, e);
+        }
+
+        // Commands and the moderation service read the file in the clear
+        let plain = match PlainFile::open(&file_path).await {
+            Ok(plain) => plain,
+            Err(e) => {
+                log::error!("Failed to prepare attachment {} for processing: {}", attachment_id, e);
+                if let Err(e) = set_status(&db, attachment_id, ATTACHMENT_FAILED).await {
+                    log::error!("Failed to record scan result for attachment {}: {}", attachment_id, e);
+                }
+                return;
+            }
+        };
+
+        let status = match scan_command {
+            Some(command) => run_scan(&command, plain.path()).await,
+            None => ATTACHMENT_READY,
+        };
+
+        if status == ATTACHMENT_INFECTED {
+            log::warn!("Attachment {} is infected, removing {}", attachment_id, file_path.display());
+            if let Err(e) = tokio::fs::remove_file(&file_path).await {
+                log::error!("Failed to remove infected file {}: {}", file_path.display(), e);
+            }
+        }
+
+        if status == ATTACHMENT_READY {
+            if let Some(ref service) = moderation {
+                if let Some(reason) = moderation::review_image(service, plain.path(), &file_name, &mime_type).await {
+                    log::warn!("Attachment {} held for moderation: {}", attachment_id, reason);
+                    if let Err(e) = moderation::flag(&db, attachment_id, &reason).await {
+                        log::error!("Failed to hold attachment {} for moderation: {}", attachment_id, e);
+                    }
+                    return;
+                }
+            }
+        }
+
+        if let Err(e) = set_status(&db, attachment_id, status).await {
+            log::error!("Failed to record scan result for attachment {}: {}", attachment_id, e);
+        }
+
+        if status != ATTACHMENT_READY {
+            return;
+        }
+
+        if let Some(command) = preview_command {
+            if attachment_preview::supports(&mime_type) {
+                attachment_preview::generate(&db, &command, attachment_id, plain.path()).await;
+            }
+        }
+
+        if attachment_text::supports(&mime_type, pdf_text_command.as_deref()) {
+            attachment_text::index(&db, pdf_text_command.as_deref(), attachment_id, plain.path(), &mime_type).await;
+        }
+    });
+}
+
+async fn run_scan(command: &str, file_path: &Path) -> &'static str {
+    let mut parts = command.split_whitespace();
+    let Some(program) = parts.next() else {
+        return ATTACHMENT_FAILED;
+    };
+
+    let output = tokio::process::Command::new(program)
+        .args(parts)
+        .arg(file_path)
+        .output()
+        .await;
+
+    match output {
+        Ok(output) => match output.status.code() {
+            Some(0) => ATTACHMENT_READY,
+            Some(1) => ATTACHMENT_INFECTED,
+            code => {
+                log::error!(
+                    "Virus scan of {} failed ({:?}): {}",
+                    file_path.display(),
+                    code,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+                ATTACHMENT_FAILED
+            }
+        },
+        Err(e) => {
+            log::error!("Failed to run virus scan command: {}", e);
+            ATTACHMENT_FAILED
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::io::Read;
+use std::path::Path;
+
+use crate::Database;
+use crate::models::ids::AttachmentId;
+use crate::services::encryption;
+use crate::utils::errors::ServiceError;
+
+const DOCX_MIME: &str = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+// Postgres caps a tsvector at 1MB; indexing the first part of a very long
+// document is enough to find it
+const MAX_TEXT_CHARS: usize = 500_000;
+
+/// Whether text can be extracted from this type. PDFs need a configured
+/// text command.
+pub fn supports(mime_type: &str, pdf_text_command: Option<&str>) -> bool {
+    match mime_type {
+        "text/plain" | "text/csv" | DOCX_MIME => true,
+        "application/pdf" => pdf_text_command.is_some(),
+        _ => false,
+    }
+}
+
+/// Extract the text of a document and store it as the attachment's search
+/// vector. Like previews, failures are only logged: the attachment can
+/// still be found by name. Sensitive boards are not indexed, since the
+/// vector holds the document's words in the clear.
+pub async fn index(db: &Database, pdf_text_command: Option<&str>, attachment_id: AttachmentId, file_path: &Path, mime_type: &str) {
+    if encryption::seals_writes() {
+        return;
+    }
+    let text = match extract(pdf_text_command, file_path, mime_type).await {
+        Ok(text) => text,
+        Err(e) => {
+            log::warn!("No text extracted from attachment {}: {}", attachment_id, e);
+            return;
+        }
+    };
+    let text: String = text.chars().take(MAX_TEXT_CHARS).collect();
+
+    let result = sqlx::query("UPDATE task_attachments SET content_tsv = to_tsvector('simple', $2) WHERE id = $1")
+        .bind(attachment_id)
+        .bind(&text)
+        .execute(&db.pool)
+        .await;
+
+    match result {
+        Ok(result) if result.rows_affected() > 0 => log::info!("Indexed text of attachment {}", attachment_id),
+        // Attachment was deleted while it was being indexed
+        Ok(_) => {}
+        Err(e) => log::error!("Database error indexing attachment {}: {}", attachment_id, e),
+    }
+}
+
+async fn extract(pdf_text_command: Option<&str>, file_path: &Path, mime_type: &str) -> Result<String, ServiceError> {
+    match mime_type {
+        "application/pdf" => {
+            let command = pdf_text_command
+                .ok_or_else(|| ServiceError::InternalError("PDF_TEXT_COMMAND is not set".to_string()))?;
+            run_pdf_text(command, file_path).await
+        }
+        DOCX_MIME => {
+            let file_path = file_path.to_path_buf();
+            tokio::task::spawn_blocking(move || docx_text(&file_path))
+                .await
+                .map_err(|e| ServiceError::InternalError(format!("Text extraction panicked: {}", e)))?
+        }
+        _ => {
+            let bytes = tokio::fs::read(file_path).await
+                .map_err(|e| ServiceError::InternalError(format!("Failed to read file: {}", e)))?;
+            Ok(String::from_utf8_lossy(&bytes).into_owned())
+        }
+    }
+}
+
+// Run the configured command as `<command> <input> -`, the pdftotext
+// convention for writing the text to stdout
+async fn run_pdf_text(command: &str, input: &Path) -> Result<String, ServiceError> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next()
+        .ok_or_else(|| ServiceError::InternalError("PDF_TEXT_COMMAND is empty".to_string()))?;
+
+    let result = tokio::process::Command::new(program)
+        .args(parts)
+        .arg(input)
+        .arg("-")
+        .output()
+        .await
+        .map_err(|e| ServiceError::InternalError(format!("Failed to run PDF text command: {}", e)))?;
+
+    if !result.status.success() {
+        return Err(ServiceError::InternalError(format!(
+            "PDF text command exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).into_owned())
+}
+
+// A DOCX file is a zip archive; the body text lives in word/document.xml
+fn docx_text(file_path: &Path) -> Result<String, ServiceError> {
+    let file = std::fs::File::open(file_path)
+        .map_err(|e| ServiceError::InternalError(format!("Failed to open file: {}", e)))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| ServiceError::InternalError(format!("Not a DOCX archive: {}", e)))?;
+    let mut entry = archive.by_name("word/document.xml")
+        .map_err(|e| ServiceError::InternalError(format!("DOCX has no document body: {}", e)))?;
+
+    let mut xml = Vec::new();
+    entry.by_ref()
+        .take((MAX_TEXT_CHARS * 8) as u64)
+        .read_to_end(&mut xml)
+        .map_err(|e| ServiceError::InternalError(format!("Failed to read DOCX body: {}", e)))?;
+
+    Ok(xml_text(&String::from_utf8_lossy(&xml)))
+}
+
+// Drop the markup, keeping paragraph, tab and line breaks as whitespace so
+// words from neighbouring runs do not run together
+fn xml_text(xml: &str) -> String {
+    let mut text = String::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&unescape(&rest[..start]));
+        let Some(end) = rest[start..].find('>') else { break };
+        let tag = &rest[start + 1..start + end];
+        if tag == "/w:p" || tag.starts_with("w:tab") || tag.starts_with("w:br") {
+            text.push(if tag == "/w:p" { '\n' } else { ' ' });
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text
+}
+
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_db::TestDb;
+
+    #[actix_web::test]
+    async fn sensitive_boards_are_not_indexed() {
+        let Some(test) = TestDb::create().await else { return };
+        encryption::init_for_tests();
+        let user = test.insert_user("owner", "member").await;
+        let task = test.insert_task("task", user).await;
+        let attachment: AttachmentId = sqlx::query_scalar(
+            "INSERT INTO task_attachments (task_id, file_name, file_size, mime_type, cloudinary_public_id,
+                                           cloudinary_url, cloudinary_secure_url, uploaded_by, processing_status)
+             VALUES ($1, 'notes.txt', 1, 'text/plain', 'a', 'http://a', 'https://a', $2, 'ready') RETURNING id"
+        )
+        .bind(task)
+        .bind(user)
+        .fetch_one(&test.db.pool)
+        .await
+        .expect("insert attachment");
+        let file = std::env::temp_dir().join(format!("attachment-text-{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&file, "secret plans").expect("write file");
+
+        index(&test.db, None, attachment, &file, "text/plain").await;
+        std::fs::remove_file(&file).ok();
+
+        let indexed: bool = sqlx::query_scalar("SELECT content_tsv IS NOT NULL FROM task_attachments WHERE id = $1")
+            .bind(attachment)
+            .fetch_one(&test.db.pool)
+            .await
+            .expect("attachment");
+        assert!(!indexed);
+        test.drop().await;
+    }
+}
@@ -0,0 +1,144 @@
+use serde_json::{json, Map, Value};
+use sqlx::{PgPool, Row};
+
+use crate::Database;
+use crate::models::audit::{AuditLogEntry, AuditLogQuery};
+use crate::models::ids::UserId;
+use crate::services::encryption;
+use crate::utils::errors::ServiceError;
+
+/// A mutating request to record, as seen by the `AuditLog` middleware
+pub struct AuditRecord {
+    pub user_id: Option<UserId>,
+    pub method: String,
+    pub route: String,
+    pub path: String,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub status: u16,
+    pub changes: Option<Value>,
+    pub request_id: String,
+}
+
+/// The fields of `after` whose value differs from `before`, as
+/// {field: {before, after}}; None when nothing changed
+pub fn diff(before: &Value, after: &Map<String, Value>) -> Option<Value> {
+    let changes: Map<String, Value> = after.iter()
+        .filter_map(|(field, new_value)| {
+            let old_value = before.get(field).cloned().unwrap_or(Value::Null);
+            (old_value != *new_value).then(|| (field.clone(), json!({ "before": old_value, "after": new_value })))
+        })
+        .collect();
+    (!changes.is_empty()).then_some(Value::Object(changes))
+}
+
+// Descriptions are kept sealed on a sensitive board, in the log as in tasks
+fn map_description(changes: &mut Value, f: impl Fn(Option<String>) -> Option<String>) {
+    if let Some(description) = changes.get_mut("description").and_then(|d| d.as_object_mut()) {
+        for side in ["before", "after"] {
+            if let Some(value) = description.get_mut(side) {
+                *value = f(value.as_str().map(|s| s.to_string())).map(Value::String).unwrap_or(Value::Null);
+            }
+        }
+    }
+}
+
+/// Write an entry in the background; failures are only logged so auditing
+/// never fails the request it records
+pub fn record(pool: PgPool, mut entry: AuditRecord) {
+    tokio::spawn(async move {
+        // A description that cannot be sealed is dropped, never stored in
+        // the clear
+        if let Some(ref mut changes) = entry.changes {
+            map_description(changes, |text| encryption::seal_text(text.as_deref()).ok().flatten());
+        }
+
+        let result = sqlx::query(
+            "INSERT INTO audit_logs (user_id, method, route, path, entity_type, entity_id, status, changes, request_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+        )
+        .bind(entry.user_id)
+        .bind(&entry.method)
+        .bind(&entry.route)
+        .bind(&entry.path)
+        .bind(&entry.entity_type)
+        .bind(&entry.entity_id)
+        .bind(entry.status as i16)
+        .bind(&entry.changes)
+        .bind(&entry.request_id)
+        .execute(&pool)
+        .await;
+
+        if let Err(e) = result {
+            log::error!("Failed to record audit log for {} {}: {}", entry.method, entry.path, e);
+        }
+    });
+}
+
+/// Entries matching the query, most recent first, with the unpaged total
+pub async fn list(db: &Database, query: &AuditLogQuery, limit: i64, offset: i64) -> Result<(Vec<AuditLogEntry>, i64), ServiceError> {
+    const FILTER: &str = "($1::int IS NULL OR l.user_id = $1)
+           AND ($2::varchar IS NULL OR l.entity_type = $2)
+           AND ($3::varchar IS NULL OR l.entity_id = $3)
+           AND ($4::timestamptz IS NULL OR l.created_at >= $4)
+           AND ($5::timestamptz IS NULL OR l.created_at < $5)";
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM audit_logs l WHERE {}", FILTER))
+        .bind(query.user_id)
+        .bind(&query.entity_type)
+        .bind(&query.entity_id)
+        .bind(query.from)
+        .bind(query.to)
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error counting audit logs: {}", e);
+            ServiceError::DatabaseError("Failed to fetch audit logs".to_string())
+        })?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT l.id, l.user_id, u.username, l.method, l.route, l.path, l.entity_type, l.entity_id,
+                l.status, l.changes, l.request_id, l.created_at
+         FROM audit_logs l LEFT JOIN users u ON u.id = l.user_id
+         WHERE {}
+         ORDER BY l.created_at DESC, l.id DESC
+         LIMIT $6 OFFSET $7",
+        FILTER
+    ))
+    .bind(query.user_id)
+    .bind(&query.entity_type)
+    .bind(&query.entity_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching audit logs: {}", e);
+        ServiceError::DatabaseError("Failed to fetch audit logs".to_string())
+    })?;
+
+    let entries = rows.iter().map(|row| {
+        let mut changes: Option<Value> = row.get("changes");
+        if let Some(ref mut changes) = changes {
+            map_description(changes, encryption::open_text);
+        }
+        AuditLogEntry {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            username: row.get("username"),
+            method: row.get("method"),
+            route: row.get("route"),
+            path: row.get("path"),
+            entity_type: row.get("entity_type"),
+            entity_id: row.get("entity_id"),
+            status: row.get::<i16, _>("status") as i32,
+            changes,
+            request_id: row.get("request_id"),
+            created_at: row.get("created_at"),
+        }
+    }).collect();
+
+    Ok((entries, total))
+}
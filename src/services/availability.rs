@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+
+use crate::Database;
+use crate::models::availability::{Availability, SetAvailabilityRequest, OUT_OF_OFFICE, STATUSES};
+use crate::models::ids::{TaskId, UserId};
+use crate::utils::errors::ServiceError;
+
+// Availability columns of `users u`, with whether the absence covers now
+const COLUMNS: &str =
+    "u.id AS user_id, u.availability_status, u.away_from, u.away_until, u.delegate_id,
+     (u.availability_status = 'out_of_office'
+      AND (u.away_from IS NULL OR u.away_from <= NOW())
+      AND (u.away_until IS NULL OR u.away_until > NOW())) AS away_now";
+
+fn from_row(row: &PgRow) -> Availability {
+    Availability {
+        user_id: row.get("user_id"),
+        status: row.get("availability_status"),
+        away_from: row.get("away_from"),
+        away_until: row.get("away_until"),
+        delegate_id: row.get("delegate_id"),
+        away_now: row.get("away_now"),
+    }
+}
+
+/// Load a user's availability, or None for an unknown user
+pub async fn load(db: &Database, user_id: UserId) -> Result<Option<Availability>, ServiceError> {
+    let row = sqlx::query(&format!("SELECT {} FROM users u WHERE u.id = $1", COLUMNS))
+        .bind(user_id)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading availability: {}", e);
+            ServiceError::DatabaseError("Failed to load availability".to_string())
+        })?;
+
+    Ok(row.as_ref().map(from_row))
+}
+
+/// Replace a user's availability. Going back to available clears the range
+/// and the delegate.
+pub async fn set(db: &Database, user_id: UserId, req: &SetAvailabilityRequest) -> Result<Availability, ServiceError> {
+    if !STATUSES.contains(&req.status.as_str()) {
+        return Err(ServiceError::ValidationError(format!("Status must be one of: {}", STATUSES.join(", ")))
+            .with_code("INVALID_AVAILABILITY"));
+    }
+    let away = req.status == OUT_OF_OFFICE;
+    if let (Some(from), Some(until)) = (req.away_from, req.away_until) {
+        if until <= from {
+            return Err(ServiceError::ValidationError("away_until must be after away_from".to_string())
+                .with_code("INVALID_AVAILABILITY"));
+        }
+    }
+
+    let delegate_id = req.delegate_id.filter(|_| away);
+    if let Some(delegate_id) = delegate_id {
+        if delegate_id == user_id {
+            return Err(ServiceError::ValidationError("You cannot delegate to yourself".to_string())
+                .with_code("INVALID_DELEGATE"));
+        }
+        let active: Option<bool> = sqlx::query_scalar("SELECT is_active FROM users WHERE id = $1")
+            .bind(delegate_id)
+            .fetch_optional(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error checking delegate: {}", e);
+                ServiceError::DatabaseError("Failed to update availability".to_string())
+            })?;
+        if active != Some(true) {
+            return Err(ServiceError::ValidationError("Delegate does not exist or is deactivated".to_string())
+                .with_code("INVALID_DELEGATE"));
+        }
+    }
+
+    let row = sqlx::query(&format!(
+        "UPDATE users u SET availability_status = $2, away_from = $3, away_until = $4, delegate_id = $5, updated_at = NOW()
+         WHERE u.id = $1
+         RETURNING {}",
+        COLUMNS
+    ))
+    .bind(user_id)
+    .bind(&req.status)
+    .bind(req.away_from.filter(|_| away))
+    .bind(req.away_until.filter(|_| away))
+    .bind(delegate_id)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error updating availability: {}", e);
+        ServiceError::DatabaseError("Failed to update availability".to_string())
+    })?
+    .ok_or_else(|| ServiceError::Unauthorized("User not found".to_string()))?;
+
+    Ok(from_row(&row))
+}
+
+/// Availability of the owners of the given tasks who are away right now,
+/// keyed by task id; owners who are available are left out
+pub async fn away_owners_for_tasks(db: &Database, task_ids: &[TaskId]) -> Result<HashMap<TaskId, Availability>, ServiceError> {
+    if task_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let rows = sqlx::query(&format!(
+        "SELECT t.id AS task_id, {} FROM tasks t JOIN users u ON u.id = t.created_by
+         WHERE t.id = ANY($1) AND u.availability_status = 'out_of_office'",
+        COLUMNS
+    ))
+    .bind(task_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error getting owner availability for tasks: {}", e);
+        ServiceError::DatabaseError("Failed to query owner availability".to_string())
+    })?;
+
+    Ok(rows.iter()
+        .map(|row| (row.get("task_id"), from_row(row)))
+        .filter(|(_, availability): &(TaskId, Availability)| availability.away_now)
+        .collect())
+}
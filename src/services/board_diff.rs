@@ -0,0 +1,114 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::ids::TaskId;
+use crate::models::task::{task_key, BoardDiff, TaskEvent, TaskMove, TaskReference, TaskTeamChange};
+use crate::services::task_events::{TaskState, TASK_CREATED};
+use crate::utils::errors::ServiceError;
+
+const DONE: &str = "DONE";
+
+/// Events of every task touched in [from, to), up to `to`, grouped by task.
+/// Descriptions are left sealed; the diff never reads them.
+async fn load_window(db: &Database, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<BTreeMap<TaskId, Vec<TaskEvent>>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT id, task_id, version, event_type, actor_id, data, created_at
+         FROM task_events
+         WHERE created_at < $2
+           AND task_id IN (SELECT task_id FROM task_events WHERE created_at >= $1 AND created_at < $2)
+         ORDER BY task_id, version"
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading task events for board diff: {}", e);
+        ServiceError::DatabaseError("Failed to compute board diff".to_string())
+    })?;
+
+    let mut events: BTreeMap<TaskId, Vec<TaskEvent>> = BTreeMap::new();
+    for row in &rows {
+        let event = TaskEvent {
+            id: row.get("id"),
+            task_id: row.get("task_id"),
+            version: row.get("version"),
+            event_type: row.get("event_type"),
+            actor_id: row.get("actor_id"),
+            data: row.get("data"),
+            created_at: row.get("created_at"),
+        };
+        events.entry(event.task_id).or_default().push(event);
+    }
+    Ok(events)
+}
+
+fn reference(id: TaskId, state: &TaskState) -> TaskReference {
+    TaskReference { id, key: task_key(id), name: state.name.clone(), status: state.status.clone() }
+}
+
+/// Compare every task touched in [from, to) as it stood at `from` with how
+/// it stood at `to`
+pub async fn compute(db: &Database, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<BoardDiff, ServiceError> {
+    let mut diff = BoardDiff {
+        from,
+        to,
+        created: Vec::new(),
+        moved: Vec::new(),
+        closed: Vec::new(),
+        deleted: Vec::new(),
+        team_changes: Vec::new(),
+    };
+
+    for (id, events) in load_window(db, from, to).await? {
+        let mut before = TaskState::default();
+        let mut existed = false;
+        let mut after = TaskState::default();
+        for event in &events {
+            if event.created_at < from {
+                before.apply(event);
+                existed |= event.event_type == TASK_CREATED;
+            }
+            after.apply(event);
+        }
+        let existed = existed && !before.deleted;
+
+        if after.deleted {
+            // Tasks created and deleted within the window were never on the
+            // board at either end
+            if existed {
+                diff.deleted.push(reference(id, &after));
+            }
+            continue;
+        }
+        if !existed {
+            diff.created.push(reference(id, &after));
+            continue;
+        }
+
+        if before.status != after.status {
+            if after.status == DONE {
+                diff.closed.push(reference(id, &after));
+            } else {
+                diff.moved.push(TaskMove {
+                    id,
+                    key: task_key(id),
+                    name: after.name.clone(),
+                    from_status: before.status.clone(),
+                    to_status: after.status.clone(),
+                });
+            }
+        }
+
+        let added: Vec<String> = after.teams.iter().filter(|team| !before.teams.contains(team)).cloned().collect();
+        let removed: Vec<String> = before.teams.iter().filter(|team| !after.teams.contains(team)).cloned().collect();
+        if !added.is_empty() || !removed.is_empty() {
+            diff.team_changes.push(TaskTeamChange { id, key: task_key(id), name: after.name, added, removed });
+        }
+    }
+
+    Ok(diff)
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone, Utc};
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::task::{BusinessCalendar, Holiday, UpdateBusinessCalendarRequest};
+use crate::utils::errors::ServiceError;
+
+// How far ahead working time is looked for before giving up on a calendar
+// that is all holidays and counting wall-clock time instead
+const MAX_DAYS_AHEAD: usize = 366 * 5;
+
+struct WorkingHours {
+    offset: FixedOffset,
+    /// Indexed by days from Monday
+    work_days: [bool; 7],
+    start: NaiveTime,
+    end: NaiveTime,
+    holidays: HashSet<NaiveDate>,
+}
+
+impl WorkingHours {
+    fn is_working_day(&self, day: NaiveDate) -> bool {
+        self.work_days[day.weekday().num_days_from_monday() as usize] && !self.holidays.contains(&day)
+    }
+
+    fn at(&self, day: NaiveDate, time: NaiveTime) -> DateTime<FixedOffset> {
+        self.offset.from_local_datetime(&day.and_time(time))
+            .single()
+            .expect("fixed offsets map local times one to one")
+    }
+}
+
+/// Working time as loaded from the business calendar, for SLA timers
+pub struct Calendar {
+    /// None when no calendar is set and every hour counts
+    hours: Option<WorkingHours>,
+}
+
+impl Calendar {
+    /// The moment `hours` of working time after `from`
+    pub fn add_hours(&self, from: DateTime<Utc>, hours: i32) -> DateTime<Utc> {
+        let wall_clock = from + Duration::hours(hours as i64);
+        let Some(ref working) = self.hours else {
+            return wall_clock;
+        };
+
+        let mut remaining = Duration::hours(hours as i64);
+        let mut cursor = from.with_timezone(&working.offset);
+        for _ in 0..MAX_DAYS_AHEAD {
+            let day = cursor.date_naive();
+            if working.is_working_day(day) {
+                let start = cursor.max(working.at(day, working.start));
+                let close = working.at(day, working.end);
+                if start < close {
+                    let available = close - start;
+                    if remaining <= available {
+                        return (start + remaining).with_timezone(&Utc);
+                    }
+                    remaining -= available;
+                }
+            }
+            cursor = working.at(day + Duration::days(1), NaiveTime::MIN);
+        }
+
+        log::warn!("Business calendar has no working time in the next {} days; counting every hour", MAX_DAYS_AHEAD);
+        wall_clock
+    }
+}
+
+async fn load_holidays(db: &Database) -> Result<Vec<Holiday>, ServiceError> {
+    let rows = sqlx::query("SELECT day, name FROM holidays ORDER BY day")
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching holidays: {}", e);
+            ServiceError::DatabaseError("Failed to fetch business calendar".to_string())
+        })?;
+
+    Ok(rows.iter().map(|row| Holiday { date: row.get("day"), name: row.get("name") }).collect())
+}
+
+/// The working time SLA timers count
+pub async fn load(db: &Database) -> Result<Calendar, ServiceError> {
+    let row = sqlx::query("SELECT utc_offset_minutes, work_days, day_start, day_end FROM business_calendar")
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching business calendar: {}", e);
+            ServiceError::DatabaseError("Failed to fetch business calendar".to_string())
+        })?;
+    let Some(row) = row else {
+        return Ok(Calendar { hours: None });
+    };
+
+    let mut work_days = [false; 7];
+    for day in row.get::<Vec<i32>, _>("work_days") {
+        if let Some(slot) = work_days.get_mut((day - 1) as usize) {
+            *slot = true;
+        }
+    }
+    let offset = FixedOffset::east_opt(row.get::<i32, _>("utc_offset_minutes") * 60)
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("UTC is a valid offset"));
+
+    Ok(Calendar {
+        hours: Some(WorkingHours {
+            offset,
+            work_days,
+            start: row.get("day_start"),
+            end: row.get("day_end"),
+            holidays: load_holidays(db).await?.into_iter().map(|holiday| holiday.date).collect(),
+        }),
+    })
+}
+
+/// The business calendar, or None when every hour counts
+pub async fn get(db: &Database) -> Result<Option<BusinessCalendar>, ServiceError> {
+    let row = sqlx::query("SELECT utc_offset_minutes, work_days, day_start, day_end, updated_at FROM business_calendar")
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching business calendar: {}", e);
+            ServiceError::DatabaseError("Failed to fetch business calendar".to_string())
+        })?;
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    Ok(Some(BusinessCalendar {
+        utc_offset_minutes: row.get("utc_offset_minutes"),
+        work_days: row.get("work_days"),
+        day_start: row.get::<NaiveTime, _>("day_start").format("%H:%M").to_string(),
+        day_end: row.get::<NaiveTime, _>("day_end").format("%H:%M").to_string(),
+        holidays: load_holidays(db).await?,
+        updated_at: row.get("updated_at"),
+    }))
+}
+
+/// Replace the business calendar and its holidays. `hours` are the start
+/// and end of the working day returned by `normalize`.
+pub async fn save(
+    db: &Database,
+    req: &UpdateBusinessCalendarRequest,
+    hours: (NaiveTime, NaiveTime),
+) -> Result<BusinessCalendar, ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    sqlx::query(
+        "INSERT INTO business_calendar (id, utc_offset_minutes, work_days, day_start, day_end, updated_at)
+         VALUES (TRUE, $1, $2, $3, $4, NOW())
+         ON CONFLICT (id) DO UPDATE
+         SET utc_offset_minutes = EXCLUDED.utc_offset_minutes,
+             work_days = EXCLUDED.work_days,
+             day_start = EXCLUDED.day_start,
+             day_end = EXCLUDED.day_end,
+             updated_at = EXCLUDED.updated_at"
+    )
+    .bind(req.utc_offset_minutes)
+    .bind(&req.work_days)
+    .bind(hours.0)
+    .bind(hours.1)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error saving business calendar: {}", e);
+        ServiceError::DatabaseError("Failed to save business calendar".to_string())
+    })?;
+
+    sqlx::query("DELETE FROM holidays")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error clearing holidays: {}", e);
+            ServiceError::DatabaseError("Failed to save business calendar".to_string())
+        })?;
+
+    let days: Vec<NaiveDate> = req.holidays.iter().map(|holiday| holiday.date).collect();
+    let names: Vec<&str> = req.holidays.iter().map(|holiday| holiday.name.as_str()).collect();
+    sqlx::query("INSERT INTO holidays (day, name) SELECT * FROM UNNEST($1::date[], $2::varchar[])")
+        .bind(&days)
+        .bind(&names)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error saving holidays: {}", e);
+            ServiceError::DatabaseError("Failed to save business calendar".to_string())
+        })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    get(db).await?
+        .ok_or_else(|| ServiceError::InternalError("Business calendar vanished after saving".to_string()))
+}
+
+/// Remove the business calendar and its holidays so every hour counts
+/// again; false if none was set
+pub async fn clear(db: &Database) -> Result<bool, ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let removed = sqlx::query("DELETE FROM business_calendar")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error removing business calendar: {}", e);
+            ServiceError::DatabaseError("Failed to remove business calendar".to_string())
+        })?
+        .rows_affected() > 0;
+
+    sqlx::query("DELETE FROM holidays")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error removing holidays: {}", e);
+            ServiceError::DatabaseError("Failed to remove business calendar".to_string())
+        })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(removed)
+}
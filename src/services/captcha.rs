@@ -0,0 +1,63 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::config::AppConfig;
+use crate::utils::errors::ServiceError;
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent("kanban-be")
+            .build()
+            .expect("HTTP client configuration is valid")
+    })
+}
+
+#[derive(Deserialize)]
+struct Verdict {
+    success: bool,
+}
+
+/// Check a captcha response token with the configured provider. Passes
+/// without a call when captcha checks are off; otherwise a missing token is
+/// rejected with 400 CAPTCHA_REQUIRED and a refused one with CAPTCHA_FAILED.
+pub async fn verify(config: &AppConfig, token: Option<&str>, ip: Option<&str>) -> Result<(), ServiceError> {
+    let Some(ref captcha) = config.captcha else {
+        return Ok(());
+    };
+    let Some(token) = token.map(str::trim).filter(|token| !token.is_empty()) else {
+        return Err(ServiceError::ValidationError("Complete the captcha".to_string()).with_code("CAPTCHA_REQUIRED"));
+    };
+
+    let mut form = vec![("secret", captcha.secret.as_str()), ("response", token)];
+    if let Some(ip) = ip {
+        form.push(("remoteip", ip));
+    }
+
+    let verdict = client()
+        .post(captcha.provider.verify_url())
+        .form(&form)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            log::error!("Captcha verification request failed: {}", e);
+            ServiceError::ServiceUnavailable("Captcha verification is unavailable".to_string())
+        })?
+        .json::<Verdict>()
+        .await
+        .map_err(|e| {
+            log::error!("Unexpected captcha verification response: {}", e);
+            ServiceError::ServiceUnavailable("Captcha verification is unavailable".to_string())
+        })?;
+
+    if !verdict.success {
+        return Err(ServiceError::ValidationError("Captcha check failed; please try again".to_string())
+            .with_code("CAPTCHA_FAILED"));
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use sqlx::{PgConnection, Row};
+
+use crate::Database;
+use crate::models::ids::{TaskId, TeamId, UserId};
+use crate::models::task::{ColumnPolicy, SetColumnPolicyRequest, UpdateTaskRequest};
+use crate::services::task_events;
+use crate::utils::errors::ServiceError;
+
+const COLUMNS: [&str; 3] = ["TO_DO", "DOING", "DONE"];
+
+fn check_status(status: &str) -> Result<(), ServiceError> {
+    if !COLUMNS.contains(&status) {
+        return Err(ServiceError::ValidationError("Invalid task status".to_string()).with_code("INVALID_STATUS"));
+    }
+    Ok(())
+}
+
+fn from_row(row: &sqlx::postgres::PgRow) -> ColumnPolicy {
+    ColumnPolicy {
+        status: row.get("status"),
+        assign_to: row.get("assign_to"),
+        round_robin_team_id: row.get("team_id"),
+        last_assigned_user_id: row.get("last_assigned_user_id"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// Every column's policy, in board order
+pub async fn list(db: &Database) -> Result<Vec<ColumnPolicy>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT status, assign_to, team_id, last_assigned_user_id, updated_at FROM column_policies
+         ORDER BY array_position(ARRAY['TO_DO', 'DOING', 'DONE']::varchar[], status)"
+    )
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching column policies: {}", e);
+        ServiceError::DatabaseError("Failed to fetch column policies".to_string())
+    })?;
+
+    Ok(rows.iter().map(from_row).collect())
+}
+
+/// Replace the policy of a column. The user or team must exist; changing
+/// the policy restarts its round-robin.
+pub async fn set(
+    db: &Database,
+    status: &str,
+    req: &SetColumnPolicyRequest,
+    actor_id: UserId,
+) -> Result<ColumnPolicy, ServiceError> {
+    check_status(status)?;
+    req.validate()?;
+
+    if let Some(user_id) = req.assign_to {
+        let active: Option<bool> = sqlx::query_scalar("SELECT is_active FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error checking user: {}", e);
+                ServiceError::DatabaseError("Failed to check user".to_string())
+            })?;
+        if active != Some(true) {
+            return Err(ServiceError::ValidationError("User does not exist or is deactivated".to_string())
+                .with_code("USER_NOT_FOUND"));
+        }
+    }
+    if let Some(team_id) = req.round_robin_team_id {
+        let exists = sqlx::query("SELECT 1 FROM teams WHERE id = $1")
+            .bind(team_id)
+            .fetch_optional(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error checking team: {}", e);
+                ServiceError::DatabaseError("Failed to check team".to_string())
+            })?
+            .is_some();
+        if !exists {
+            return Err(ServiceError::ValidationError("Team not found".to_string()).with_code("TEAM_NOT_FOUND"));
+        }
+    }
+
+    let row = sqlx::query(
+        "INSERT INTO column_policies (status, assign_to, team_id, created_by, updated_at)
+         VALUES ($1, $2, $3, $4, NOW())
+         ON CONFLICT (status) DO UPDATE
+         SET assign_to = EXCLUDED.assign_to,
+             team_id = EXCLUDED.team_id,
+             last_assigned_user_id = NULL,
+             created_by = EXCLUDED.created_by,
+             updated_at = EXCLUDED.updated_at
+         RETURNING status, assign_to, team_id, last_assigned_user_id, updated_at"
+    )
+    .bind(status)
+    .bind(req.assign_to)
+    .bind(req.round_robin_team_id)
+    .bind(actor_id)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error saving column policy: {}", e);
+        ServiceError::DatabaseError("Failed to save column policy".to_string())
+    })?;
+
+    Ok(from_row(&row))
+}
+
+/// Remove the policy of a column; false if it had none
+pub async fn remove(db: &Database, status: &str) -> Result<bool, ServiceError> {
+    check_status(status)?;
+    let result = sqlx::query("DELETE FROM column_policies WHERE status = $1")
+        .bind(status)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error removing column policy: {}", e);
+            ServiceError::DatabaseError("Failed to remove column policy".to_string())
+        })?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Run the policy of the column an update moves a task into, inside the
+/// caller's transaction, after the update itself. `status_before` is from
+/// `task_writes::status_before_update`. The reassignment is recorded as a
+/// transfer by `actor_id` naming the policy's column, and the new owner is
+/// returned; None when the task did not change column, the column has no
+/// policy or the policy picked the current owner.
+pub async fn apply(
+    conn: &mut PgConnection,
+    task_id: TaskId,
+    actor_id: UserId,
+    status_before: Option<&str>,
+    update: &UpdateTaskRequest,
+) -> Result<Option<UserId>, ServiceError> {
+    let (Some(from), Some(to)) = (status_before, update.status.as_deref()) else {
+        return Ok(None);
+    };
+    if from == to {
+        return Ok(None);
+    }
+
+    // Locked so concurrent moves take turns in the round-robin
+    let policy = sqlx::query(
+        "SELECT assign_to, team_id, last_assigned_user_id FROM column_policies WHERE status = $1 FOR UPDATE"
+    )
+    .bind(to)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading column policy: {}", e);
+        ServiceError::DatabaseError("Failed to apply column policy".to_string())
+    })?;
+    let Some(policy) = policy else {
+        return Ok(None);
+    };
+
+    let assign_to: Option<UserId> = policy.get("assign_to");
+    let assignee: Option<UserId> = match (assign_to, policy.get::<Option<TeamId>, _>("team_id")) {
+        (Some(user_id), _) => sqlx::query_scalar("SELECT id FROM users WHERE id = $1 AND is_active")
+            .bind(user_id)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| {
+                log::error!("Database error checking column policy user: {}", e);
+                ServiceError::DatabaseError("Failed to apply column policy".to_string())
+            })?,
+        (None, Some(team_id)) => {
+            // The first active member after the last one picked, wrapping
+            // around to the lowest id
+            let next: Option<UserId> = sqlx::query_scalar(
+                "SELECT u.id FROM team_members tm JOIN users u ON u.id = tm.user_id
+                 WHERE tm.team_id = $1 AND u.is_active
+                 ORDER BY u.id <= COALESCE($2, 0), u.id
+                 LIMIT 1"
+            )
+            .bind(team_id)
+            .bind(policy.get::<Option<UserId>, _>("last_assigned_user_id"))
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| {
+                log::error!("Database error picking round-robin assignee: {}", e);
+                ServiceError::DatabaseError("Failed to apply column policy".to_string())
+            })?;
+
+            if let Some(next) = next {
+                sqlx::query("UPDATE column_policies SET last_assigned_user_id = $2 WHERE status = $1")
+                    .bind(to)
+                    .bind(next)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Database error advancing round-robin: {}", e);
+                        ServiceError::DatabaseError("Failed to apply column policy".to_string())
+                    })?;
+            }
+            next
+        }
+        (None, None) => None,
+    };
+    let Some(assignee) = assignee else {
+        log::warn!("Column policy for {} has nobody to assign task {} to", to, task_id);
+        return Ok(None);
+    };
+
+    let previous_owner: UserId = sqlx::query_scalar(
+        "UPDATE tasks t SET created_by = $2
+         FROM (SELECT created_by FROM tasks WHERE id = $1) previous
+         WHERE t.id = $1
+         RETURNING previous.created_by"
+    )
+    .bind(task_id)
+    .bind(assignee)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error assigning task by column policy: {}", e);
+        ServiceError::DatabaseError("Failed to apply column policy".to_string())
+    })?;
+    if previous_owner == assignee {
+        return Ok(None);
+    }
+
+    task_events::append(conn, task_id, task_events::TASK_TRANSFERRED, actor_id, &serde_json::json!({
+        "created_by": assignee,
+        "previous_owner": previous_owner,
+        "column_policy": to,
+    })).await?;
+
+    log::info!("Task {} assigned to user {} by the {} column policy", task_id, assignee, to);
+    Ok(Some(assignee))
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, Row};
+
+use crate::Database;
+use crate::models::dead_letter::DeadLetter;
+use crate::services::outbox;
+use crate::utils::errors::ServiceError;
+use crate::utils::sql::{Select, Sort};
+
+/// Outbox event the relay could not publish; payload is the full event
+pub const KIND_OUTBOX_EVENT: &str = "outbox_event";
+
+// Outbox events are parked with their payload as stored, sealed on a
+// sensitive board; admins see it opened
+fn dead_letter_from_row(row: &PgRow) -> DeadLetter {
+    let kind: String = row.get("kind");
+    let mut payload: serde_json::Value = row.get("payload");
+    if kind == KIND_OUTBOX_EVENT {
+        if let Some(event_payload) = payload.get_mut("payload") {
+            match outbox::open_payload(event_payload.take()) {
+                Ok(opened) => *event_payload = opened,
+                Err(e) => log::error!("Failed to open payload of dead letter {}: {}", row.get::<i64, _>("id"), e),
+            }
+        }
+    }
+    DeadLetter {
+        id: row.get("id"),
+        kind,
+        payload,
+        attempts: row.get("attempts"),
+        last_error: row.get("last_error"),
+        first_attempted_at: row.get("first_attempted_at"),
+        dead_at: row.get("dead_at"),
+    }
+}
+
+/// Park a job in the dead-letter table inside the caller's transaction, so
+/// removing it from its own queue and parking it happen together
+pub async fn park(
+    conn: &mut PgConnection,
+    kind: &str,
+    payload: &serde_json::Value,
+    attempts: i32,
+    last_error: &str,
+    first_attempted_at: Option<DateTime<Utc>>,
+) -> Result<(), ServiceError> {
+    sqlx::query(
+        "INSERT INTO dead_letters (kind, payload, attempts, last_error, first_attempted_at)
+         VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(kind)
+    .bind(payload)
+    .bind(attempts)
+    .bind(last_error)
+    .bind(first_attempted_at)
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error parking dead letter: {}", e);
+        ServiceError::DatabaseError("Failed to park dead letter".to_string())
+    })?;
+
+    Ok(())
+}
+
+pub async fn list(db: &Database, kind: Option<&str>, limit: i64) -> Result<Vec<DeadLetter>, ServiceError> {
+    let rows = Select::new("dead_letters", "id, kind, payload, attempts, last_error, first_attempted_at, dead_at")
+        .eq_opt("kind", kind)
+        .order_by(Sort { column: "id", descending: true })
+        .paginate(limit, 0)
+        .build()
+        .build()
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error listing dead letters: {}", e);
+            ServiceError::DatabaseError("Failed to fetch dead letters".to_string())
+        })?;
+
+    Ok(rows.iter().map(dead_letter_from_row).collect())
+}
+
+pub async fn get(db: &Database, id: i64) -> Result<Option<DeadLetter>, ServiceError> {
+    let row = sqlx::query(
+        "SELECT id, kind, payload, attempts, last_error, first_attempted_at, dead_at
+         FROM dead_letters WHERE id = $1"
+    )
+    .bind(id)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching dead letter: {}", e);
+        ServiceError::DatabaseError("Failed to fetch dead letter".to_string())
+    })?;
+
+    Ok(row.as_ref().map(dead_letter_from_row))
+}
+
+/// Put a dead job back on its original queue with a fresh retry budget.
+/// Returns false if there is no such dead letter.
+pub async fn requeue(db: &Database, id: i64) -> Result<bool, ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let row = sqlx::query("DELETE FROM dead_letters WHERE id = $1 RETURNING kind, payload")
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error removing dead letter: {}", e);
+            ServiceError::DatabaseError("Failed to requeue dead letter".to_string())
+        })?;
+
+    let Some(row) = row else {
+        return Ok(false);
+    };
+    let kind: String = row.get("kind");
+    let payload: serde_json::Value = row.get("payload");
+
+    match kind.as_str() {
+        KIND_OUTBOX_EVENT => {
+            let aggregate_type = payload.get("aggregate_type").and_then(|v| v.as_str());
+            let aggregate_id = payload.get("aggregate_id").and_then(|v| v.as_i64());
+            let event_type = payload.get("event_type").and_then(|v| v.as_str());
+            let (Some(aggregate_type), Some(aggregate_id), Some(event_type)) = (aggregate_type, aggregate_id, event_type) else {
+                return Err(ServiceError::ValidationError("Dead letter payload is not an outbox event".to_string()));
+            };
+            let event_payload = outbox::open_payload(payload.get("payload").cloned().unwrap_or_default())?;
+            let delivered_to: Vec<String> = payload.get("delivered_to")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default();
+            outbox::enqueue_partial(&mut tx, aggregate_type, aggregate_id as i32, event_type, &event_payload, &delivered_to).await?;
+        }
+        other => {
+            return Err(ServiceError::ValidationError(format!("Dead letters of kind '{}' cannot be requeued", other)));
+        }
+    }
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(true)
+}
+
+/// Delete one dead letter, or every dead letter (optionally of one kind)
+/// when `id` is None. Returns the number of rows removed.
+pub async fn purge(db: &Database, id: Option<i64>, kind: Option<&str>) -> Result<u64, ServiceError> {
+    let result = sqlx::query(
+        "DELETE FROM dead_letters
+         WHERE ($1::bigint IS NULL OR id = $1) AND ($2::text IS NULL OR kind = $2)"
+    )
+    .bind(id)
+    .bind(kind)
+    .execute(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error purging dead letters: {}", e);
+        ServiceError::DatabaseError("Failed to purge dead letters".to_string())
+    })?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,478 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::Row;
+use tera::{Context, Tera};
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::models::email_template::{EmailTemplate, PreviewEmailTemplateRequest, RenderedEmail, UpdateEmailTemplateRequest};
+use crate::models::ids::UserId;
+use crate::utils::errors::ServiceError;
+use crate::utils::locale;
+
+pub const INVITATION: &str = "invitation";
+pub const PASSWORD_RESET: &str = "password_reset";
+pub const MAGIC_LINK: &str = "magic_link";
+pub const EMAIL_VERIFICATION: &str = "email_verification";
+pub const SLA_BREACH: &str = "sla_breach";
+pub const SLA_ESCALATION: &str = "sla_escalation";
+pub const WEEKLY_DIGEST: &str = "weekly_digest";
+
+const SOURCE_BUILT_IN: &str = "built_in";
+const SOURCE_FILE: &str = "file";
+const SOURCE_CUSTOM: &str = "custom";
+
+struct Translation {
+    locale: &'static str,
+    subject: &'static str,
+    body: &'static str,
+}
+
+struct BuiltIn {
+    name: &'static str,
+    description: &'static str,
+    /// One per supported locale, English first
+    translations: &'static [Translation],
+    /// Example context for a locale, used for previews and to check
+    /// overrides
+    sample: fn(&str) -> serde_json::Value,
+}
+
+impl BuiltIn {
+    fn translation(&self, locale: &str) -> &'static Translation {
+        self.translations.iter()
+            .find(|translation| translation.locale == locale)
+            .unwrap_or(&self.translations[0])
+    }
+}
+
+const BUILT_IN: &[BuiltIn] = &[
+    BuiltIn {
+        name: INVITATION,
+        description: "Sent to an invited email address with the signup link",
+        translations: &[
+            Translation {
+                locale: locale::ENGLISH,
+                subject: "You're invited to Kanban",
+                body: "{{ inviter_name }} invited you to join their Kanban board.\n\n\
+                       Open this link within {{ ttl_hours }} hours (by {{ expires_at }}) to create your account:\n\
+                       {{ link }}\n\n\
+                       If you weren't expecting this, you can ignore this email.",
+            },
+            Translation {
+                locale: locale::INDONESIAN,
+                subject: "Anda diundang ke Kanban",
+                body: "{{ inviter_name }} mengundang Anda untuk bergabung ke papan Kanban mereka.\n\n\
+                       Buka tautan ini dalam {{ ttl_hours }} jam (sebelum {{ expires_at }}) untuk membuat akun Anda:\n\
+                       {{ link }}\n\n\
+                       Jika Anda tidak merasa diundang, abaikan saja email ini.",
+            },
+        ],
+        sample: |locale| json!({
+            "inviter_name": "Jane Doe",
+            "ttl_hours": 72,
+            "expires_at": locale::format_datetime(Utc::now() + Duration::hours(72), locale),
+            "link": "http://localhost:3000/accept-invite?token=sample",
+        }),
+    },
+    BuiltIn {
+        name: PASSWORD_RESET,
+        description: "Sent when someone asks to reset the password of an account",
+        translations: &[
+            Translation {
+                locale: locale::ENGLISH,
+                subject: "Reset your Kanban password",
+                body: "Someone asked to reset the password for your Kanban account.\n\n\
+                       Open this link within {{ ttl_minutes }} minutes (by {{ expires_at }}) to choose a new password:\n\
+                       {{ link }}\n\n\
+                       If this wasn't you, you can ignore this email.",
+            },
+            Translation {
+                locale: locale::INDONESIAN,
+                subject: "Atur ulang kata sandi Kanban Anda",
+                body: "Seseorang meminta untuk mengatur ulang kata sandi akun Kanban Anda.\n\n\
+                       Buka tautan ini dalam {{ ttl_minutes }} menit (sebelum {{ expires_at }}) untuk memilih kata sandi baru:\n\
+                       {{ link }}\n\n\
+                       Jika ini bukan Anda, abaikan saja email ini.",
+            },
+        ],
+        sample: |locale| json!({
+            "ttl_minutes": 30,
+            "expires_at": locale::format_datetime(Utc::now() + Duration::minutes(30), locale),
+            "link": "http://localhost:3000/reset-password?token=sample",
+        }),
+    },
+    BuiltIn {
+        name: MAGIC_LINK,
+        description: "Sent when someone asks to log in by email instead of a password",
+        translations: &[
+            Translation {
+                locale: locale::ENGLISH,
+                subject: "Your Kanban login link",
+                body: "Open this link within {{ ttl_minutes }} minutes (by {{ expires_at }}) to log in to Kanban:\n\
+                       {{ link }}\n\n\
+                       The link works once. If you didn't ask for it, you can ignore this email.",
+            },
+            Translation {
+                locale: locale::INDONESIAN,
+                subject: "Tautan masuk Kanban Anda",
+                body: "Buka tautan ini dalam {{ ttl_minutes }} menit (sebelum {{ expires_at }}) untuk masuk ke Kanban:\n\
+                       {{ link }}\n\n\
+                       Tautan ini hanya bisa dipakai sekali. Jika Anda tidak memintanya, abaikan saja email ini.",
+            },
+        ],
+        sample: |locale| json!({
+            "ttl_minutes": 15,
+            "expires_at": locale::format_datetime(Utc::now() + Duration::minutes(15), locale),
+            "link": "http://localhost:3000/magic-link?token=sample",
+        }),
+    },
+    BuiltIn {
+        name: EMAIL_VERIFICATION,
+        description: "Sent when a user sets their email address, to confirm they receive mail there",
+        translations: &[
+            Translation {
+                locale: locale::ENGLISH,
+                subject: "Verify your email for Kanban",
+                body: "Open this link within {{ ttl_hours }} hours (by {{ expires_at }}) to verify the email address of your Kanban account:\n\
+                       {{ link }}\n\n\
+                       Until then your account can only read the board. If you didn't set this address, you can ignore this email.",
+            },
+            Translation {
+                locale: locale::INDONESIAN,
+                subject: "Verifikasi email Anda untuk Kanban",
+                body: "Buka tautan ini dalam {{ ttl_hours }} jam (sebelum {{ expires_at }}) untuk memverifikasi alamat email akun Kanban Anda:\n\
+                       {{ link }}\n\n\
+                       Sampai saat itu akun Anda hanya bisa membaca papan. Jika Anda tidak memasukkan alamat ini, abaikan saja email ini.",
+            },
+        ],
+        sample: |locale| json!({
+            "ttl_hours": 48,
+            "expires_at": locale::format_datetime(Utc::now() + Duration::hours(48), locale),
+            "link": "http://localhost:3000/verify-email?token=sample",
+        }),
+    },
+    BuiltIn {
+        name: SLA_BREACH,
+        description: "Sent to the owner of a task that stayed in a column longer than an SLA rule allows",
+        translations: &[
+            Translation {
+                locale: locale::ENGLISH,
+                subject: "{{ task_key }} breached the \"{{ rule_name }}\" SLA",
+                body: "Your task {{ task_key }} \"{{ task_name }}\" has been in {{ status }} since {{ entered_at }}, \
+                       longer than the {{ max_hours }} hours the \"{{ rule_name }}\" rule allows.\n\n\
+                       The breach is resolved once the task moves out of {{ status }}.",
+            },
+            Translation {
+                locale: locale::INDONESIAN,
+                subject: "{{ task_key }} melanggar SLA \"{{ rule_name }}\"",
+                body: "Tugas Anda {{ task_key }} \"{{ task_name }}\" sudah berada di {{ status }} sejak {{ entered_at }}, \
+                       melebihi {{ max_hours }} jam yang diizinkan aturan \"{{ rule_name }}\".\n\n\
+                       Pelanggaran ini selesai begitu tugas dipindahkan dari {{ status }}.",
+            },
+        ],
+        sample: |locale| json!({
+            "task_key": "KAN-12",
+            "task_name": "Login page crashes on Safari",
+            "status": "TO_DO",
+            "entered_at": locale::format_datetime(Utc::now() - Duration::hours(49), locale),
+            "max_hours": 48,
+            "rule_name": "Bugs leave TO_DO within 48h",
+        }),
+    },
+    BuiltIn {
+        name: SLA_ESCALATION,
+        description: "Sent to the next people in the escalation chain while a task keeps breaching an SLA rule",
+        translations: &[
+            Translation {
+                locale: locale::ENGLISH,
+                subject: "Escalation: {{ task_key }} is still breaching the \"{{ rule_name }}\" SLA",
+                body: "Task {{ task_key }} \"{{ task_name }}\", owned by {{ owner_name }}, has breached the \"{{ rule_name }}\" SLA \
+                       for {{ hours }} hours and is still in {{ status }}.\n\n\
+                       You are receiving this as part of the board's escalation chain. \
+                       Escalation stops once the task moves out of {{ status }}.",
+            },
+            Translation {
+                locale: locale::INDONESIAN,
+                subject: "Eskalasi: {{ task_key }} masih melanggar SLA \"{{ rule_name }}\"",
+                body: "Tugas {{ task_key }} \"{{ task_name }}\" milik {{ owner_name }} sudah melanggar SLA \"{{ rule_name }}\" \
+                       selama {{ hours }} jam dan masih berada di {{ status }}.\n\n\
+                       Anda menerima email ini sebagai bagian dari rantai eskalasi papan. \
+                       Eskalasi berhenti begitu tugas dipindahkan dari {{ status }}.",
+            },
+        ],
+        sample: |_| json!({
+            "task_key": "KAN-12",
+            "task_name": "Login page crashes on Safari",
+            "owner_name": "Jane Doe",
+            "rule_name": "Bugs leave TO_DO within 48h",
+            "status": "TO_DO",
+            "hours": 24,
+        }),
+    },
+    BuiltIn {
+        name: WEEKLY_DIGEST,
+        description: "Sent to admins every week with the tasks completed and created, SLA breaches still open and the most active people",
+        translations: &[
+            Translation {
+                locale: locale::ENGLISH,
+                subject: "Kanban weekly digest: {{ completed | length }} completed, {{ created | length }} new",
+                body: "Here is the board from {{ from }} to {{ to }}.\n\n\
+                       Completed ({{ completed | length }}):\n\
+                       {% for task in completed %}- {{ task.key }} {{ task.name }}\n{% else %}- None\n{% endfor %}\n\
+                       New ({{ created | length }}):\n\
+                       {% for task in created %}- {{ task.key }} {{ task.name }} ({{ task.status }})\n{% else %}- None\n{% endfor %}\n\
+                       Overdue ({{ overdue | length }}):\n\
+                       {% for task in overdue %}- {{ task.key }} {{ task.name }}, in {{ task.status }} past \"{{ task.rule_name }}\"\n{% else %}- None\n{% endfor %}\n\
+                       Top contributors:\n\
+                       {% for person in contributors %}- {{ person.name }}: {{ person.updates }} updates\n{% else %}- None\n{% endfor %}",
+            },
+            Translation {
+                locale: locale::INDONESIAN,
+                subject: "Ringkasan mingguan Kanban: {{ completed | length }} selesai, {{ created | length }} baru",
+                body: "Berikut keadaan papan dari {{ from }} sampai {{ to }}.\n\n\
+                       Selesai ({{ completed | length }}):\n\
+                       {% for task in completed %}- {{ task.key }} {{ task.name }}\n{% else %}- Tidak ada\n{% endfor %}\n\
+                       Baru ({{ created | length }}):\n\
+                       {% for task in created %}- {{ task.key }} {{ task.name }} ({{ task.status }})\n{% else %}- Tidak ada\n{% endfor %}\n\
+                       Terlambat ({{ overdue | length }}):\n\
+                       {% for task in overdue %}- {{ task.key }} {{ task.name }}, di {{ task.status }} melewati \"{{ task.rule_name }}\"\n{% else %}- Tidak ada\n{% endfor %}\n\
+                       Kontributor teratas:\n\
+                       {% for person in contributors %}- {{ person.name }}: {{ person.updates }} pembaruan\n{% else %}- Tidak ada\n{% endfor %}",
+            },
+        ],
+        sample: |locale| json!({
+            "from": locale::format_datetime(Utc::now() - Duration::days(7), locale),
+            "to": locale::format_datetime(Utc::now(), locale),
+            "completed": [{ "key": "KAN-9", "name": "Add dark mode", "status": "DONE" }],
+            "created": [{ "key": "KAN-14", "name": "Export tasks to CSV", "status": "TO_DO" }],
+            "overdue": [{ "key": "KAN-12", "name": "Login page crashes on Safari", "status": "TO_DO", "rule_name": "Bugs leave TO_DO within 48h" }],
+            "contributors": [{ "name": "Jane Doe", "updates": 17 }],
+        }),
+    },
+];
+
+fn built_in(name: &str) -> Result<&'static BuiltIn, ServiceError> {
+    BUILT_IN.iter()
+        .find(|template| template.name == name)
+        .ok_or_else(|| ServiceError::NotFound("Email template not found".to_string()).with_code("TEMPLATE_NOT_FOUND"))
+}
+
+// Tera keeps the useful part of an error, such as the undefined variable,
+// in its source chain
+fn describe(e: &tera::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+fn render_pair(subject: &str, body: &str, context: &serde_json::Value) -> Result<RenderedEmail, String> {
+    let context = Context::from_value(context.clone()).map_err(|e| describe(&e))?;
+    let subject = Tera::one_off(subject, &context, false).map_err(|e| format!("subject: {}", describe(&e)))?;
+    let body = Tera::one_off(body, &context, false).map_err(|e| format!("body: {}", describe(&e)))?;
+    // The subject becomes a mail header, so it must stay on one line
+    let subject = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+    Ok(RenderedEmail { subject, body })
+}
+
+struct Override {
+    subject: String,
+    body: String,
+}
+
+/// The email templates of this deployment, one per locale: the built-in
+/// ones, replaced by files from EMAIL_TEMPLATE_DIR, replaced in turn by
+/// templates saved through the admin API
+pub struct EmailTemplates {
+    files: HashMap<(&'static str, &'static str), Override>,
+}
+
+impl EmailTemplates {
+    /// Load the overrides in EMAIL_TEMPLATE_DIR, failing on templates that
+    /// do not render against their sample data
+    pub fn from_config(config: &AppConfig) -> Result<Self, String> {
+        let mut files = HashMap::new();
+        let Some(ref dir) = config.email_template_dir else {
+            return Ok(EmailTemplates { files });
+        };
+
+        let read = |file: String| -> Result<Option<String>, String> {
+            let path = Path::new(dir).join(file);
+            match std::fs::read_to_string(&path) {
+                Ok(content) => Ok(Some(content)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+            }
+        };
+
+        for template in BUILT_IN {
+            for &locale in locale::SUPPORTED {
+                let subject = read(format!("{}.{}.subject.txt", template.name, locale))?;
+                let body = read(format!("{}.{}.body.txt", template.name, locale))?;
+                if subject.is_none() && body.is_none() {
+                    continue;
+                }
+
+                let built_in = template.translation(locale);
+                let file = Override {
+                    subject: subject.map_or_else(|| built_in.subject.to_string(), |s| s.trim().to_string()),
+                    body: body.unwrap_or_else(|| built_in.body.to_string()),
+                };
+                render_pair(&file.subject, &file.body, &(template.sample)(locale))
+                    .map_err(|e| format!("Email template {}.{} in {} is invalid: {}", template.name, locale, dir, e))?;
+                log::info!("Using email template {}.{} from {}", template.name, locale, dir);
+                files.insert((template.name, locale), file);
+            }
+        }
+        Ok(EmailTemplates { files })
+    }
+
+    async fn current(&self, db: &Database, template: &BuiltIn, locale: &'static str) -> Result<EmailTemplate, ServiceError> {
+        let row = sqlx::query("SELECT subject, body, updated_at FROM email_templates WHERE name = $1 AND locale = $2")
+            .bind(template.name)
+            .bind(locale)
+            .fetch_optional(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error loading email template: {}", e);
+                ServiceError::DatabaseError("Failed to load email template".to_string())
+            })?;
+
+        let built_in = template.translation(locale);
+        let (source, subject, body, updated_at) = match (row, self.files.get(&(template.name, locale))) {
+            (Some(row), _) => (SOURCE_CUSTOM, row.get("subject"), row.get("body"), row.get("updated_at")),
+            (None, Some(file)) => (SOURCE_FILE, file.subject.clone(), file.body.clone(), None),
+            (None, None) => (SOURCE_BUILT_IN, built_in.subject.to_string(), built_in.body.to_string(), None),
+        };
+
+        let variables = match (template.sample)(locale) {
+            serde_json::Value::Object(sample) => sample.keys().cloned().collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(EmailTemplate {
+            name: template.name.to_string(),
+            locale: locale.to_string(),
+            description: template.description.to_string(),
+            source: source.to_string(),
+            subject,
+            body,
+            variables,
+            updated_at,
+        })
+    }
+
+    /// Render the template in effect for `locale`, falling back to English
+    /// for unsupported locales. If an override fails, the built-in template
+    /// is used so the email still goes out.
+    pub async fn render(
+        &self,
+        db: &Database,
+        name: &str,
+        locale: &str,
+        context: &serde_json::Value,
+    ) -> Result<RenderedEmail, ServiceError> {
+        let template = built_in(name)?;
+        let locale = locale::resolve_or_default(Some(locale));
+        let current = self.current(db, template, locale).await?;
+
+        match render_pair(&current.subject, &current.body, context) {
+            Ok(rendered) => Ok(rendered),
+            Err(e) if current.source != SOURCE_BUILT_IN => {
+                log::warn!("Email template {}.{} ({}) failed, using the built-in one: {}", name, locale, current.source, e);
+                let built_in = template.translation(locale);
+                render_pair(built_in.subject, built_in.body, context)
+                    .map_err(|e| ServiceError::InternalError(format!("Failed to render email: {}", e)))
+            }
+            Err(e) => Err(ServiceError::InternalError(format!("Failed to render email: {}", e))),
+        }
+    }
+
+    pub async fn list(&self, db: &Database) -> Result<Vec<EmailTemplate>, ServiceError> {
+        let mut templates = Vec::with_capacity(BUILT_IN.len() * locale::SUPPORTED.len());
+        for template in BUILT_IN {
+            for &locale in locale::SUPPORTED {
+                templates.push(self.current(db, template, locale).await?);
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Save a custom template after checking it renders against the sample
+    /// data
+    pub async fn save(
+        &self,
+        db: &Database,
+        name: &str,
+        locale: &'static str,
+        updated_by: UserId,
+        req: &UpdateEmailTemplateRequest,
+    ) -> Result<EmailTemplate, ServiceError> {
+        let template = built_in(name)?;
+        render_pair(&req.subject, &req.body, &(template.sample)(locale))
+            .map_err(|e| ServiceError::ValidationError(format!("Template does not render: {}", e))
+                .with_code("INVALID_TEMPLATE"))?;
+
+        sqlx::query(
+            "INSERT INTO email_templates (name, locale, subject, body, updated_by)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (name, locale) DO UPDATE
+             SET subject = EXCLUDED.subject, body = EXCLUDED.body, updated_by = EXCLUDED.updated_by, updated_at = NOW()"
+        )
+        .bind(name)
+        .bind(locale)
+        .bind(&req.subject)
+        .bind(&req.body)
+        .bind(updated_by)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error saving email template: {}", e);
+            ServiceError::DatabaseError("Failed to save email template".to_string())
+        })?;
+
+        self.current(db, template, locale).await
+    }
+
+    /// Drop the custom template, returning to the file or built-in one
+    pub async fn reset(&self, db: &Database, name: &str, locale: &'static str) -> Result<EmailTemplate, ServiceError> {
+        let template = built_in(name)?;
+        sqlx::query("DELETE FROM email_templates WHERE name = $1 AND locale = $2")
+            .bind(name)
+            .bind(locale)
+            .execute(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error resetting email template: {}", e);
+                ServiceError::DatabaseError("Failed to reset email template".to_string())
+            })?;
+
+        self.current(db, template, locale).await
+    }
+
+    /// Render a draft, or the template in effect, against the sample data
+    pub async fn preview(
+        &self,
+        db: &Database,
+        name: &str,
+        locale: &'static str,
+        req: PreviewEmailTemplateRequest,
+    ) -> Result<RenderedEmail, ServiceError> {
+        let template = built_in(name)?;
+        let current = self.current(db, template, locale).await?;
+        let subject = req.subject.unwrap_or(current.subject);
+        let body = req.body.unwrap_or(current.body);
+
+        render_pair(&subject, &body, &(template.sample)(locale))
+            .map_err(|e| ServiceError::ValidationError(format!("Template does not render: {}", e))
+                .with_code("INVALID_TEMPLATE"))
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, Row};
+
+use crate::config::AppConfig;
+use crate::models::ids::UserId;
+use crate::services::single_use_tokens::{self, Purpose};
+use crate::utils::errors::ServiceError;
+
+/// Create a verification token for `email` inside the caller's
+/// transaction and return it. Earlier unused links for the same user stop
+/// working.
+pub async fn issue(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    user_id: UserId,
+    email: &str,
+) -> Result<String, ServiceError> {
+    let token = single_use_tokens::generate();
+    let expires_at = Utc::now() + Duration::hours(config.email_verification_ttl_hours);
+
+    sqlx::query("UPDATE email_verifications SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            log::error!("Database error retiring email verifications: {}", e);
+            ServiceError::DatabaseError("Failed to send verification email".to_string())
+        })?;
+
+    sqlx::query("INSERT INTO email_verifications (user_id, email, token_hash, expires_at) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(email)
+        .bind(single_use_tokens::hash(config, Purpose::EmailVerification, &token))
+        .bind(expires_at)
+        .execute(conn)
+        .await
+        .map_err(|e| {
+            log::error!("Database error storing email verification: {}", e);
+            ServiceError::DatabaseError("Failed to send verification email".to_string())
+        })?;
+
+    Ok(token)
+}
+
+/// Use up a verification token and mark the address it was sent to as
+/// verified, returning its user. Fails for unknown, expired or used tokens
+/// and for addresses the user has since replaced.
+pub async fn consume(conn: &mut PgConnection, config: &AppConfig, token: &str) -> Result<UserId, ServiceError> {
+    let row = sqlx::query(
+        "WITH used AS (
+             UPDATE email_verifications SET used_at = NOW()
+             WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+             RETURNING user_id, email
+         )
+         UPDATE users u SET email_verified_at = NOW(), updated_at = NOW()
+         FROM used
+         WHERE u.id = used.user_id AND LOWER(u.email) = LOWER(used.email)
+         RETURNING u.id"
+    )
+    .bind(single_use_tokens::hash(config, Purpose::EmailVerification, token))
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error verifying email: {}", e);
+        ServiceError::DatabaseError("Failed to verify email".to_string())
+    })?;
+
+    row.map(|row| row.get("id"))
+        .ok_or_else(|| ServiceError::ValidationError("Verification link is invalid or has expired".to_string())
+            .with_code("INVALID_VERIFICATION_LINK"))
+}
@@ -0,0 +1,323 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::utils::errors::ServiceError;
+
+// Leading bytes of every sealed blob; bumped if the layout ever changes
+const MAGIC: &[u8] = b"KBENC\x01";
+// Sealed text is stored as this prefix followed by the blob in base64
+const TEXT_PREFIX: &str = "enc:v1:";
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + NONCE_LEN + KEY_LEN + TAG_LEN + NONCE_LEN;
+
+struct Keys {
+    org_key: LessSafeKey,
+    /// False once a board stops being sensitive: existing data is still
+    /// decrypted, new data is stored in the clear
+    seal_writes: bool,
+}
+
+static KEYS: OnceLock<Option<Keys>> = OnceLock::new();
+
+/// Load the org key from the configuration; call once at startup
+pub fn init(config: &AppConfig) {
+    let keys = config.encryption_key.map(|key| Keys {
+        org_key: LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &key).expect("32 bytes is a valid AES-256 key")),
+        seal_writes: config.sensitive_board,
+    });
+    if KEYS.set(keys).is_err() {
+        log::warn!("Encryption keys were already initialized");
+    }
+}
+
+/// Seal new data under a fixed key, as on a sensitive board
+#[cfg(test)]
+pub fn init_for_tests() {
+    KEYS.get_or_init(|| Some(Keys {
+        org_key: LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &[7; KEY_LEN]).expect("32 bytes is a valid AES-256 key")),
+        seal_writes: true,
+    }));
+}
+
+fn keys() -> Option<&'static Keys> {
+    KEYS.get().and_then(|keys| keys.as_ref())
+}
+
+fn sealing_key() -> Option<&'static LessSafeKey> {
+    keys().filter(|keys| keys.seal_writes).map(|keys| &keys.org_key)
+}
+
+/// Whether new data is sealed, i.e. the board is sensitive
+pub fn seals_writes() -> bool {
+    sealing_key().is_some()
+}
+
+fn random<const N: usize>() -> Result<[u8; N], ServiceError> {
+    let mut bytes = [0u8; N];
+    SystemRandom::new().fill(&mut bytes).map_err(|_| {
+        log::error!("System random number generator failed");
+        ServiceError::InternalError("Failed to encrypt data".to_string())
+    })?;
+    Ok(bytes)
+}
+
+fn seal_with(key: &LessSafeKey, nonce: [u8; NONCE_LEN], in_out: &mut Vec<u8>) -> Result<(), ServiceError> {
+    key.seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::from(MAGIC), in_out)
+        .map_err(|_| ServiceError::InternalError("Failed to encrypt data".to_string()))
+}
+
+fn open_with<'a>(key: &LessSafeKey, nonce: &[u8], in_out: &'a mut [u8]) -> Option<&'a mut [u8]> {
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    key.open_in_place(nonce, Aad::from(MAGIC), in_out).ok()
+}
+
+/// Encrypt `data` under a fresh data key wrapped by the org key, laid out as
+/// magic, wrap nonce, wrapped data key, data nonce, ciphertext. Data is
+/// returned as is unless the board is sensitive.
+pub fn seal(data: Vec<u8>) -> Result<Vec<u8>, ServiceError> {
+    let Some(org_key) = sealing_key() else {
+        return Ok(data);
+    };
+    seal_under(org_key, data)
+}
+
+fn seal_under(org_key: &LessSafeKey, data: Vec<u8>) -> Result<Vec<u8>, ServiceError> {
+    let data_key_bytes: [u8; KEY_LEN] = random()?;
+    let data_key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &data_key_bytes)
+        .map_err(|_| ServiceError::InternalError("Failed to encrypt data".to_string()))?);
+
+    let mut wrapped_key = data_key_bytes.to_vec();
+    let wrap_nonce: [u8; NONCE_LEN] = random()?;
+    seal_with(org_key, wrap_nonce, &mut wrapped_key)?;
+
+    let mut ciphertext = data;
+    let data_nonce: [u8; NONCE_LEN] = random()?;
+    seal_with(&data_key, data_nonce, &mut ciphertext)?;
+
+    let mut sealed = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    sealed.extend_from_slice(MAGIC);
+    sealed.extend_from_slice(&wrap_nonce);
+    sealed.extend_from_slice(&wrapped_key);
+    sealed.extend_from_slice(&data_nonce);
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Whether `data` was produced by `seal`
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Decrypt a blob produced by `seal`; data stored in the clear is returned
+/// as is
+pub fn open(data: Vec<u8>) -> Result<Vec<u8>, ServiceError> {
+    if !is_sealed(&data) {
+        return Ok(data);
+    }
+    let failed = || ServiceError::InternalError("Failed to decrypt stored data".to_string()).with_code("DECRYPTION_FAILED");
+
+    let Some(keys) = keys() else {
+        log::error!("Found encrypted data but no ENCRYPTION_KEY is configured");
+        return Err(failed());
+    };
+    if data.len() < HEADER_LEN + TAG_LEN {
+        log::error!("Encrypted data is truncated");
+        return Err(failed());
+    }
+
+    let (wrap_nonce, rest) = data[MAGIC.len()..].split_at(NONCE_LEN);
+    let (wrapped_key, rest) = rest.split_at(KEY_LEN + TAG_LEN);
+    let (data_nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    let mut wrapped_key = wrapped_key.to_vec();
+    let data_key = open_with(&keys.org_key, wrap_nonce, &mut wrapped_key)
+        .and_then(|key_bytes| UnboundKey::new(&AES_256_GCM, key_bytes).ok())
+        .map(LessSafeKey::new)
+        .ok_or_else(|| {
+            log::error!("Failed to unwrap data key; was ENCRYPTION_KEY changed?");
+            failed()
+        })?;
+
+    let mut plaintext = ciphertext.to_vec();
+    let length = open_with(&data_key, data_nonce, &mut plaintext)
+        .map(|opened| opened.len())
+        .ok_or_else(|| {
+            log::error!("Encrypted data failed authentication");
+            failed()
+        })?;
+    plaintext.truncate(length);
+    Ok(plaintext)
+}
+
+/// Encrypt a task text field for storage; text is stored as is unless the
+/// board is sensitive
+pub fn seal_text(text: Option<&str>) -> Result<Option<String>, ServiceError> {
+    let Some(text) = text else {
+        return Ok(None);
+    };
+    if sealing_key().is_none() {
+        return Ok(Some(text.to_string()));
+    }
+    Ok(Some(format!("{}{}", TEXT_PREFIX, BASE64.encode(seal(text.as_bytes().to_vec())?))))
+}
+
+/// Whether `text` was produced by `seal_text`
+pub fn is_sealed_text(text: &str) -> bool {
+    text.starts_with(TEXT_PREFIX)
+}
+
+/// Decrypt a stored task text field. Text that cannot be decrypted is logged
+/// and returned still encrypted, so its contents are never guessed at.
+pub fn open_text(text: Option<String>) -> Option<String> {
+    let text = text?;
+    let Some(encoded) = text.strip_prefix(TEXT_PREFIX) else {
+        return Some(text);
+    };
+    let opened = BASE64.decode(encoded).ok()
+        .map(open)
+        .and_then(|result| result.ok())
+        .and_then(|bytes| String::from_utf8(bytes).ok());
+    match opened {
+        Some(plaintext) => Some(plaintext),
+        None => {
+            log::error!("Failed to decrypt stored task text");
+            Some(text)
+        }
+    }
+}
+
+/// Encrypt a file in place once an external command has written it
+pub async fn seal_file(path: &Path) -> Result<(), ServiceError> {
+    if sealing_key().is_none() {
+        return Ok(());
+    }
+    let data = tokio::fs::read(path).await.map_err(|e| {
+        log::error!("Failed to read {} for encryption: {}", path.display(), e);
+        ServiceError::InternalError("Failed to encrypt file".to_string())
+    })?;
+    tokio::fs::write(path, seal(data)?).await.map_err(|e| {
+        log::error!("Failed to write encrypted {}: {}", path.display(), e);
+        ServiceError::InternalError("Failed to encrypt file".to_string())
+    })
+}
+
+/// A stored file in the clear, for commands that read it from disk.
+/// Unencrypted files are used in place; decrypted copies live in the temp
+/// directory and are removed when this is dropped.
+pub struct PlainFile {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl PlainFile {
+    pub async fn open(path: &Path) -> Result<Self, ServiceError> {
+        if keys().is_none() {
+            return Ok(PlainFile { path: path.to_path_buf(), temporary: false });
+        }
+        let data = tokio::fs::read(path).await.map_err(|e| {
+            log::error!("Failed to read {}: {}", path.display(), e);
+            ServiceError::InternalError("Failed to read file".to_string())
+        })?;
+        if !is_sealed(&data) {
+            return Ok(PlainFile { path: path.to_path_buf(), temporary: false });
+        }
+
+        // Commands may pick a format by extension, so the copy keeps it
+        let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("bin");
+        let copy = std::env::temp_dir().join(format!("plain_{}.{}", Uuid::new_v4(), extension));
+        tokio::fs::write(&copy, open(data)?).await.map_err(|e| {
+            log::error!("Failed to write decrypted copy of {}: {}", path.display(), e);
+            ServiceError::InternalError("Failed to read file".to_string())
+        })?;
+        Ok(PlainFile { path: copy, temporary: true })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PlainFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            if let Err(e) = std::fs::remove_file(&self.path) {
+                log::error!("Failed to remove decrypted copy {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_data_opens_to_the_original() {
+        init_for_tests();
+        let sealed = seal(b"quarterly numbers".to_vec()).expect("seal");
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(9).any(|window| window == b"quarterly"));
+        assert_eq!(open(sealed).expect("open"), b"quarterly numbers");
+
+        // Empty data still carries a tag
+        assert_eq!(open(seal(Vec::new()).expect("seal")).expect("open"), b"");
+    }
+
+    #[test]
+    fn tampered_data_does_not_open() {
+        init_for_tests();
+        let sealed = seal(b"quarterly numbers".to_vec()).expect("seal");
+
+        let mut tampered_tag = sealed.clone();
+        *tampered_tag.last_mut().expect("tag") ^= 1;
+        assert_eq!(open(tampered_tag).expect_err("tampered tag").error_code(), "DECRYPTION_FAILED");
+
+        let mut tampered_key = sealed.clone();
+        tampered_key[MAGIC.len() + NONCE_LEN] ^= 1;
+        assert_eq!(open(tampered_key).expect_err("tampered data key").error_code(), "DECRYPTION_FAILED");
+    }
+
+    #[test]
+    fn truncated_data_does_not_open() {
+        init_for_tests();
+        let sealed = seal(b"quarterly numbers".to_vec()).expect("seal");
+
+        for length in [MAGIC.len(), HEADER_LEN, sealed.len() - 1] {
+            let truncated = sealed[..length].to_vec();
+            assert_eq!(open(truncated).expect_err("truncated").error_code(), "DECRYPTION_FAILED");
+        }
+    }
+
+    #[test]
+    fn data_sealed_under_another_key_does_not_open() {
+        init_for_tests();
+        let other_key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, &[9; KEY_LEN]).expect("32 bytes is a valid AES-256 key"));
+        let sealed = seal_under(&other_key, b"quarterly numbers".to_vec()).expect("seal");
+        assert_eq!(open(sealed).expect_err("wrong key").error_code(), "DECRYPTION_FAILED");
+    }
+
+    #[test]
+    fn text_round_trips_and_failures_stay_sealed() {
+        init_for_tests();
+        let sealed = seal_text(Some("launch plan")).expect("seal").expect("text");
+        assert!(is_sealed_text(&sealed));
+        assert_eq!(open_text(Some(sealed.clone())).as_deref(), Some("launch plan"));
+
+        assert_eq!(seal_text(None).expect("seal"), None);
+        assert_eq!(open_text(None), None);
+        assert_eq!(open_text(Some("plain".to_string())).as_deref(), Some("plain"));
+
+        // Text that does not decrypt is returned as stored, never guessed at
+        let mut tampered = sealed.clone();
+        tampered.truncate(tampered.len() - 4);
+        assert_eq!(open_text(Some(tampered.clone())), Some(tampered));
+    }
+}
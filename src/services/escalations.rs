@@ -0,0 +1,251 @@
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, Row};
+
+use crate::Database;
+use crate::models::ids::{TaskId, UserId};
+use crate::models::task::{task_key, Escalation, EscalationStep, SetEscalationChainRequest};
+use crate::services::business_hours::Calendar;
+use crate::services::email_templates::{self, EmailTemplates};
+use crate::services::mailer::{Email, Mailer};
+use crate::utils::errors::ServiceError;
+
+/// The escalation chain, earliest step first
+pub async fn get_chain(db: &Database) -> Result<Vec<EscalationStep>, ServiceError> {
+    let rows = sqlx::query("SELECT after_hours, notify, user_id FROM escalation_steps ORDER BY after_hours")
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error fetching escalation chain: {}", e);
+            ServiceError::DatabaseError("Failed to fetch escalation chain".to_string())
+        })?;
+
+    Ok(rows.iter().map(|row| EscalationStep {
+        after_hours: row.get("after_hours"),
+        notify: row.get("notify"),
+        user_id: row.get("user_id"),
+    }).collect())
+}
+
+/// Replace the escalation chain with the normalized steps of `req`. Users
+/// named by steps must exist and be active.
+pub async fn set_chain(db: &Database, req: &SetEscalationChainRequest, actor_id: UserId) -> Result<Vec<EscalationStep>, ServiceError> {
+    let user_ids: Vec<UserId> = req.steps.iter().filter_map(|step| step.user_id).collect();
+    if !user_ids.is_empty() {
+        let active: i64 = sqlx::query_scalar("SELECT COUNT(DISTINCT id) FROM users WHERE id = ANY($1) AND is_active")
+            .bind(&user_ids)
+            .fetch_one(&db.pool)
+            .await
+            .map_err(|e| {
+                log::error!("Database error checking escalation users: {}", e);
+                ServiceError::DatabaseError("Failed to check users".to_string())
+            })?;
+        let mut distinct = user_ids.clone();
+        distinct.sort_by_key(|id| id.0);
+        distinct.dedup();
+        if active as usize != distinct.len() {
+            return Err(ServiceError::ValidationError("User does not exist or is deactivated".to_string())
+                .with_code("USER_NOT_FOUND"));
+        }
+    }
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    sqlx::query("DELETE FROM escalation_steps")
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error clearing escalation chain: {}", e);
+            ServiceError::DatabaseError("Failed to save escalation chain".to_string())
+        })?;
+
+    let after_hours: Vec<i32> = req.steps.iter().map(|step| step.after_hours).collect();
+    let notify: Vec<&str> = req.steps.iter().map(|step| step.notify.as_str()).collect();
+    let step_users: Vec<Option<i32>> = req.steps.iter().map(|step| step.user_id.map(|id| id.0)).collect();
+    sqlx::query(
+        "INSERT INTO escalation_steps (after_hours, notify, user_id, created_by)
+         SELECT after_hours, notify, user_id, $4
+         FROM UNNEST($1::int[], $2::varchar[], $3::int[]) AS s(after_hours, notify, user_id)"
+    )
+    .bind(&after_hours)
+    .bind(&notify)
+    .bind(&step_users)
+    .bind(actor_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error saving escalation chain: {}", e);
+        ServiceError::DatabaseError("Failed to save escalation chain".to_string())
+    })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(req.steps.clone())
+}
+
+/// Schedule every step of the chain for newly opened breaches, counting the
+/// working hours of `calendar` from now, inside the caller's transaction
+pub async fn schedule(conn: &mut PgConnection, calendar: &Calendar, breach_ids: &[i32]) -> Result<(), ServiceError> {
+    if breach_ids.is_empty() {
+        return Ok(());
+    }
+    let steps: Vec<i32> = sqlx::query_scalar("SELECT after_hours FROM escalation_steps")
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading escalation chain: {}", e);
+            ServiceError::DatabaseError("Failed to schedule escalations".to_string())
+        })?;
+    if steps.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let due_at: Vec<DateTime<Utc>> = steps.iter().map(|hours| calendar.add_hours(now, *hours)).collect();
+    sqlx::query(
+        "INSERT INTO sla_escalations (breach_id, after_hours, notify, user_id, due_at)
+         SELECT b.id, s.after_hours, s.notify, s.user_id, d.due_at
+         FROM sla_breaches b
+         CROSS JOIN UNNEST($2::int[], $3::timestamptz[]) AS d(after_hours, due_at)
+         JOIN escalation_steps s ON s.after_hours = d.after_hours
+         WHERE b.id = ANY($1)"
+    )
+    .bind(breach_ids)
+    .bind(&steps)
+    .bind(&due_at)
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error scheduling escalations: {}", e);
+        ServiceError::DatabaseError("Failed to schedule escalations".to_string())
+    })?;
+    Ok(())
+}
+
+/// Cancel the steps still pending for breaches that just resolved, inside
+/// the caller's transaction
+pub async fn cancel(conn: &mut PgConnection, breach_ids: &[i32]) -> Result<(), ServiceError> {
+    if breach_ids.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        "UPDATE sla_escalations SET cancelled_at = NOW()
+         WHERE breach_id = ANY($1) AND escalated_at IS NULL AND cancelled_at IS NULL"
+    )
+    .bind(breach_ids)
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error cancelling escalations: {}", e);
+        ServiceError::DatabaseError("Failed to cancel escalations".to_string())
+    })?;
+    Ok(())
+}
+
+/// Mark the pending steps that came due as escalated and return their ids.
+/// Each step is claimed by one update, so several servers escalate it once.
+pub async fn claim_due(db: &Database) -> Result<Vec<i32>, ServiceError> {
+    sqlx::query_scalar(
+        "UPDATE sla_escalations SET escalated_at = NOW()
+         WHERE escalated_at IS NULL AND cancelled_at IS NULL AND due_at <= NOW()
+         RETURNING id"
+    )
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error claiming due escalations: {}", e);
+        ServiceError::DatabaseError("Failed to escalate SLA breaches".to_string())
+    })
+}
+
+/// The steps scheduled for a breach, earliest first
+pub async fn list_for_breach(db: &Database, breach_id: i32) -> Result<Vec<Escalation>, ServiceError> {
+    let exists = sqlx::query("SELECT 1 FROM sla_breaches WHERE id = $1")
+        .bind(breach_id)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error checking SLA breach: {}", e);
+            ServiceError::DatabaseError("Failed to fetch escalations".to_string())
+        })?
+        .is_some();
+    if !exists {
+        return Err(ServiceError::NotFound("SLA breach not found".to_string()).with_code("SLA_BREACH_NOT_FOUND"));
+    }
+
+    let rows = sqlx::query(
+        "SELECT id, breach_id, after_hours, notify, user_id, due_at, escalated_at, cancelled_at
+         FROM sla_escalations WHERE breach_id = $1 ORDER BY after_hours"
+    )
+    .bind(breach_id)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching escalations: {}", e);
+        ServiceError::DatabaseError("Failed to fetch escalations".to_string())
+    })?;
+
+    Ok(rows.iter().map(|row| Escalation {
+        id: row.get("id"),
+        breach_id: row.get("breach_id"),
+        after_hours: row.get("after_hours"),
+        notify: row.get("notify"),
+        user_id: row.get("user_id"),
+        due_at: row.get("due_at"),
+        escalated_at: row.get("escalated_at"),
+        cancelled_at: row.get("cancelled_at"),
+    }).collect())
+}
+
+/// Email everyone the given escalations are addressed to. Recipients
+/// without a verified email address are skipped.
+pub async fn notify(db: &Database, templates: &EmailTemplates, mailer: &dyn Mailer, escalation_ids: &[i32]) -> Result<(), ServiceError> {
+    let rows = sqlx::query(
+        "SELECT e.after_hours, b.task_id, t.name AS task_name, r.name AS rule_name, r.status,
+                owner.name AS owner_name, u.email, u.locale
+         FROM sla_escalations e
+         JOIN sla_breaches b ON b.id = e.breach_id
+         JOIN sla_rules r ON r.id = b.rule_id
+         JOIN tasks t ON t.id = b.task_id
+         JOIN users owner ON owner.id = t.created_by
+         JOIN users u ON (e.notify = 'owner' AND u.id = t.created_by)
+                      OR (e.notify = 'user' AND u.id = e.user_id)
+                      OR (e.notify = 'admins' AND u.role = 'admin')
+         WHERE e.id = ANY($1) AND u.is_active AND u.email IS NOT NULL AND u.email_verified_at IS NOT NULL"
+    )
+    .bind(escalation_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading escalations to notify: {}", e);
+        ServiceError::DatabaseError("Failed to notify escalations".to_string())
+    })?;
+
+    for row in rows {
+        let task_id: TaskId = row.get("task_id");
+        let recipient_locale: String = row.get("locale");
+        let context = serde_json::json!({
+            "task_key": task_key(task_id),
+            "task_name": row.get::<String, _>("task_name"),
+            "owner_name": row.get::<String, _>("owner_name"),
+            "rule_name": row.get::<String, _>("rule_name"),
+            "status": row.get::<String, _>("status"),
+            "hours": row.get::<i32, _>("after_hours"),
+        });
+        let result = match templates.render(db, email_templates::SLA_ESCALATION, &recipient_locale, &context).await {
+            Ok(rendered) => mailer.send(&Email { to: row.get("email"), subject: rendered.subject, body: rendered.body }).await,
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to send escalation email for task {}: {}", task_id, e);
+        }
+    }
+    Ok(())
+}
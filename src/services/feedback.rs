@@ -0,0 +1,139 @@
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+
+use crate::config::{AppConfig, FeedbackIntake};
+use crate::Database;
+use crate::models::feedback::{FeedbackSubmission, SubmitFeedbackRequest};
+use crate::models::ids::{TaskId, UserId};
+use crate::models::task::{CreateTaskRequest, TaskResponse};
+use crate::services::login_limiter::Buckets;
+use crate::services::task_writes;
+use crate::utils::errors::ServiceError;
+
+/// Limits feedback submissions per client IP. Like `LoginLimiter`, counts
+/// are kept in memory per server process.
+pub struct FeedbackLimiter {
+    by_ip: Option<Buckets>,
+}
+
+impl FeedbackLimiter {
+    pub fn new(config: &AppConfig) -> Self {
+        FeedbackLimiter {
+            by_ip: config.feedback.as_ref()
+                .map(|intake| Buckets::new(intake.max_per_ip, Duration::from_secs(intake.window_secs))),
+        }
+    }
+
+    /// Count a submission, rejecting it with 429 once the IP has used up its
+    /// allowance for the window
+    pub fn check(&self, ip: Option<&str>) -> Result<(), ServiceError> {
+        let Some(by_ip) = &self.by_ip else {
+            return Ok(());
+        };
+        let ip = ip.unwrap_or("unknown");
+        by_ip.take(ip).map_err(|retry_after| {
+            log::warn!("Feedback rate limited for {}", ip);
+            ServiceError::RateLimited("Too much feedback from this address; try again later".to_string())
+                .with_code("FEEDBACK_RATE_LIMITED")
+                .with_retry_after(retry_after)
+        })
+    }
+}
+
+/// The feedback form settings if the form is enabled and `token` is its
+/// token; 404 otherwise, so the form's existence is not given away
+pub fn intake<'a>(config: &'a AppConfig, token: &str) -> Result<&'a FeedbackIntake, ServiceError> {
+    // Comparing digests keeps the comparison time independent of how much
+    // of the token a guess got right
+    config.feedback.as_ref()
+        .filter(|intake| Sha256::digest(intake.token.as_bytes()) == Sha256::digest(token.as_bytes()))
+        .ok_or_else(|| ServiceError::NotFound("Feedback form not found".to_string()))
+}
+
+/// File feedback as a triage task owned by the form's owner and store the
+/// submitter's contact details beside it
+pub async fn submit(
+    db: &Database,
+    intake: &FeedbackIntake,
+    feedback: SubmitFeedbackRequest,
+    ip: Option<&str>,
+) -> Result<TaskResponse, ServiceError> {
+    let owner: Option<UserId> = sqlx::query_scalar("SELECT id FROM users WHERE username = $1 AND is_active")
+        .bind(&intake.owner)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error finding feedback owner: {}", e);
+            ServiceError::DatabaseError("Failed to submit feedback".to_string())
+        })?;
+    let Some(owner) = owner else {
+        log::error!("Feedback owner {} does not exist or is deactivated", intake.owner);
+        return Err(ServiceError::ServiceUnavailable("Feedback cannot be accepted right now".to_string()));
+    };
+
+    let task = CreateTaskRequest {
+        name: feedback.title,
+        description: Some(feedback.description),
+        status: intake.status.clone(),
+        external_link: None,
+        teams: None,
+        client_id: None,
+    };
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let task_response = task_writes::insert(&mut tx, &task, owner, owner, &[]).await?;
+
+    sqlx::query(
+        "INSERT INTO feedback_submissions (task_id, contact_name, contact_email, ip_address)
+         VALUES ($1, $2, $3, $4)"
+    )
+    .bind(task_response.id)
+    .bind(&feedback.contact_name)
+    .bind(&feedback.contact_email)
+    .bind(ip)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error storing feedback contact: {}", e);
+        ServiceError::DatabaseError("Failed to submit feedback".to_string())
+    })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(task_response)
+}
+
+/// The submitter of a task filed through the feedback form
+pub async fn get_submission(db: &Database, task_id: TaskId) -> Result<FeedbackSubmission, ServiceError> {
+    let row = sqlx::query(
+        "SELECT task_id, contact_name, contact_email, ip_address, created_at
+         FROM feedback_submissions WHERE task_id = $1"
+    )
+    .bind(task_id)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching feedback submission: {}", e);
+        ServiceError::DatabaseError("Failed to fetch feedback submission".to_string())
+    })?
+    .ok_or_else(|| ServiceError::NotFound("Task was not filed through the feedback form".to_string()))?;
+
+    Ok(FeedbackSubmission {
+        task_id: row.get("task_id"),
+        contact_name: row.get("contact_name"),
+        contact_email: row.get("contact_email"),
+        ip_address: row.get("ip_address"),
+        created_at: row.get("created_at"),
+    })
+}
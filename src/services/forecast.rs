@@ -0,0 +1,117 @@
+use chrono::{DateTime, Duration, Utc};
+use ring::rand::{SecureRandom, SystemRandom};
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::report::{Forecast, ForecastBand};
+use crate::services::task_events::{TASK_CREATED, TASK_UPDATED};
+use crate::utils::errors::ServiceError;
+
+pub const DEFAULT_WEEKS: i32 = 12;
+pub const MIN_WEEKS: i32 = 4;
+pub const MAX_WEEKS: i32 = 52;
+const SIMULATIONS: u32 = 10_000;
+const PERCENTILES: [u8; 3] = [50, 85, 95];
+// A simulation still short of the backlog after ten years stops there
+const MAX_SIMULATED_WEEKS: u32 = 520;
+
+/// xorshift64*: plenty for sampling past weeks, and needs no extra crate
+struct Rng(u64);
+
+impl Rng {
+    fn seeded() -> Result<Self, ServiceError> {
+        let mut seed = [0u8; 8];
+        SystemRandom::new().fill(&mut seed).map_err(|_| {
+            log::error!("System random number generator failed");
+            ServiceError::InternalError("Failed to build forecast".to_string())
+        })?;
+        // Zero would stay zero forever
+        Ok(Rng(u64::from_le_bytes(seed) | 1))
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) % n as u64) as usize
+    }
+}
+
+/// Tasks moved into DONE in each of the `weeks` weeks before `now`, most
+/// recent first; a task is counted once per week however often it moved
+async fn weekly_throughput(db: &Database, now: DateTime<Utc>, weeks: i32) -> Result<Vec<i64>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT FLOOR(EXTRACT(EPOCH FROM ($1 - created_at)) / 604800)::int AS week, COUNT(DISTINCT task_id) AS completed
+         FROM task_events
+         WHERE created_at <= $1 AND created_at > $1 - make_interval(weeks => $2)
+           AND event_type IN ($3, $4) AND data->>'status' = 'DONE'
+         GROUP BY week"
+    )
+    .bind(now)
+    .bind(weeks)
+    .bind(TASK_CREATED)
+    .bind(TASK_UPDATED)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading weekly throughput: {}", e);
+        ServiceError::DatabaseError("Failed to build forecast".to_string())
+    })?;
+
+    let mut throughput = vec![0; weeks as usize];
+    for row in &rows {
+        if let Some(slot) = throughput.get_mut(row.get::<i32, _>("week") as usize) {
+            *slot = row.get("completed");
+        }
+    }
+    Ok(throughput)
+}
+
+async fn open_tasks(db: &Database) -> Result<i64, ServiceError> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE status <> 'DONE'")
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error counting open tasks: {}", e);
+            ServiceError::DatabaseError("Failed to build forecast".to_string())
+        })
+}
+
+/// Weeks each simulation took to complete `remaining` tasks, sorted
+fn simulate(remaining: i64, throughput: &[i64], rng: &mut Rng) -> Vec<u32> {
+    let mut outcomes: Vec<u32> = (0..SIMULATIONS).map(|_| {
+        let mut done = 0;
+        let mut weeks = 0;
+        while done < remaining && weeks < MAX_SIMULATED_WEEKS {
+            done += throughput[rng.below(throughput.len())];
+            weeks += 1;
+        }
+        weeks
+    }).collect();
+    outcomes.sort_unstable();
+    outcomes
+}
+
+/// Forecast when `remaining` tasks (by default every task not yet DONE)
+/// are complete, sampling the throughput of the past `weeks` weeks
+pub async fn forecast(db: &Database, weeks: i32, remaining: Option<i64>) -> Result<Forecast, ServiceError> {
+    let now = Utc::now();
+    let weekly_throughput = weekly_throughput(db, now, weeks).await?;
+    let remaining = match remaining {
+        Some(remaining) => remaining,
+        None => open_tasks(db).await?,
+    };
+    if remaining > 0 && weekly_throughput.iter().all(|completed| *completed == 0) {
+        return Err(ServiceError::ValidationError(format!("No tasks were completed in the last {} weeks to forecast from", weeks))
+            .with_code("NO_THROUGHPUT"));
+    }
+
+    let outcomes = simulate(remaining, &weekly_throughput, &mut Rng::seeded()?);
+    let bands = PERCENTILES.iter().map(|&percentile| {
+        let index = (outcomes.len() * percentile as usize).div_ceil(100).saturating_sub(1);
+        let weeks = outcomes[index];
+        ForecastBand { percentile, weeks, date: (now + Duration::weeks(weeks as i64)).date_naive() }
+    }).collect();
+
+    Ok(Forecast { remaining, weekly_throughput, simulations: SIMULATIONS, bands })
+}
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::admin::IndexAdvice;
+use crate::utils::errors::ServiceError;
+
+// Columns the handlers filter or join on for most requests; kanban_db.sql
+// indexes them, but databases set up by hand or restored partially may not
+const HOT_FILTERS: &[(&str, &[&str], &str)] = &[
+    ("task_teams", &["task_id"], "Joined on every task read"),
+    ("task_attachments", &["task_id"], "Joined on every task read"),
+    ("tasks", &["status"], "Filtered by board column, SLA checks and reports"),
+    ("task_events", &["created_at"], "Scanned by time by the board diff and reports"),
+];
+
+// Postgres truncates longer identifiers
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn index_name(table: &str, columns: &[String]) -> String {
+    let mut name = format!("idx_{}_{}", table, columns.join("_"));
+    name.truncate(MAX_IDENTIFIER_LEN);
+    name
+}
+
+fn statement(table: &str, columns: &[String]) -> String {
+    format!(
+        "CREATE INDEX CONCURRENTLY IF NOT EXISTS {} ON {} ({})",
+        quote(&index_name(table, columns)),
+        quote(table),
+        columns.iter().map(|column| quote(column)).collect::<Vec<_>>().join(", ")
+    )
+}
+
+fn failed(e: sqlx::Error) -> ServiceError {
+    log::error!("Database error inspecting indexes: {}", e);
+    ServiceError::DatabaseError("Failed to inspect indexes".to_string())
+}
+
+/// Indexes of every table in the public schema, as their column lists in
+/// order; expression columns are left out
+async fn indexes(db: &Database) -> Result<HashMap<String, Vec<Vec<String>>>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT t.relname::text AS table_name,
+                ARRAY(SELECT a.attname::text
+                      FROM unnest(i.indkey::int2[]) WITH ORDINALITY AS k(attnum, ord)
+                      JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = k.attnum
+                      ORDER BY k.ord) AS columns
+         FROM pg_index i JOIN pg_class t ON t.oid = i.indrelid
+         WHERE t.relnamespace = 'public'::regnamespace AND i.indisvalid"
+    )
+    .fetch_all(&db.pool)
+    .await
+    .map_err(failed)?;
+
+    let mut indexes: HashMap<String, Vec<Vec<String>>> = HashMap::new();
+    for row in &rows {
+        indexes.entry(row.get("table_name")).or_default().push(row.get("columns"));
+    }
+    Ok(indexes)
+}
+
+/// Whether an index starts with `columns`, in any order, so lookups on them
+/// can use it
+fn covered(indexes: &HashMap<String, Vec<Vec<String>>>, table: &str, columns: &[String]) -> bool {
+    indexes.get(table).is_some_and(|table_indexes| table_indexes.iter().any(|index| {
+        index.len() >= columns.len() && columns.iter().all(|column| index[..columns.len()].contains(column))
+    }))
+}
+
+/// Missing indexes on foreign keys, which deletes of the referenced rows
+/// and joins need, and on the columns of `HOT_FILTERS`, busiest tables
+/// first. Tables that do not exist are skipped.
+pub async fn advise(db: &Database) -> Result<Vec<IndexAdvice>, ServiceError> {
+    let indexes = indexes(db).await?;
+
+    let stats: HashMap<String, (i64, i64)> = sqlx::query("SELECT relname::text AS table_name, seq_scan, n_live_tup FROM pg_stat_user_tables WHERE schemaname = 'public'")
+        .fetch_all(&db.pool)
+        .await
+        .map_err(failed)?
+        .iter()
+        .map(|row| (row.get("table_name"), (row.get::<Option<i64>, _>("seq_scan").unwrap_or(0), row.get::<Option<i64>, _>("n_live_tup").unwrap_or(0))))
+        .collect();
+
+    let foreign_keys = sqlx::query(
+        "SELECT t.relname::text AS table_name, c.conname::text AS constraint_name,
+                ARRAY(SELECT a.attname::text
+                      FROM unnest(c.conkey) WITH ORDINALITY AS k(attnum, ord)
+                      JOIN pg_attribute a ON a.attrelid = c.conrelid AND a.attnum = k.attnum
+                      ORDER BY k.ord) AS columns
+         FROM pg_constraint c JOIN pg_class t ON t.oid = c.conrelid
+         WHERE c.contype = 'f' AND t.relnamespace = 'public'::regnamespace
+         ORDER BY t.relname, c.conname"
+    )
+    .fetch_all(&db.pool)
+    .await
+    .map_err(failed)?;
+
+    let mut candidates: Vec<(String, Vec<String>, String)> = foreign_keys.iter()
+        .map(|row| {
+            let constraint: String = row.get("constraint_name");
+            (row.get("table_name"), row.get("columns"), format!("Foreign key {} has no index", constraint))
+        })
+        .collect();
+    candidates.extend(HOT_FILTERS.iter()
+        .filter(|(table, _, _)| stats.contains_key(*table))
+        .map(|(table, columns, reason)| (table.to_string(), columns.iter().map(|c| c.to_string()).collect(), reason.to_string())));
+
+    let mut advice: Vec<IndexAdvice> = Vec::new();
+    for (table, columns, reason) in candidates {
+        if columns.is_empty() || covered(&indexes, &table, &columns)
+            || advice.iter().any(|existing| existing.table == table && existing.columns == columns) {
+            continue;
+        }
+        let table_stats = stats.get(&table);
+        advice.push(IndexAdvice {
+            statement: statement(&table, &columns),
+            seq_scans: table_stats.map(|(seq_scans, _)| *seq_scans),
+            live_rows: table_stats.map(|(_, live_rows)| *live_rows),
+            table,
+            columns,
+            reason,
+        });
+    }
+    advice.sort_by(|a, b| b.seq_scans.cmp(&a.seq_scans).then_with(|| a.table.cmp(&b.table)));
+    Ok(advice)
+}
+
+/// Create every recommended index, one at a time and without blocking
+/// writes, returning the ones created. Stops at the first failure; a failed
+/// concurrent build leaves an invalid index behind that is dropped so the
+/// next attempt starts clean.
+pub async fn apply(db: &Database) -> Result<Vec<IndexAdvice>, ServiceError> {
+    let mut applied = Vec::new();
+    for advice in advise(db).await? {
+        // CONCURRENTLY cannot run in a transaction or as a prepared statement
+        if let Err(e) = sqlx::raw_sql(&advice.statement).execute(&db.pool).await {
+            log::error!("Failed to create index on {}({}): {}", advice.table, advice.columns.join(", "), e);
+            let name = quote(&index_name(&advice.table, &advice.columns));
+            if let Err(e) = sqlx::raw_sql(&format!("DROP INDEX CONCURRENTLY IF EXISTS {}", name)).execute(&db.pool).await {
+                log::error!("Failed to drop invalid index {}: {}", name, e);
+            }
+            return Err(ServiceError::DatabaseError("Failed to create index".to_string()).with_code("INDEX_CREATE_FAILED"));
+        }
+        log::info!("Created index on {}({})", advice.table, advice.columns.join(", "));
+        applied.push(advice);
+    }
+    Ok(applied)
+}
@@ -0,0 +1,118 @@
+use chrono::{Duration, Utc};
+use sqlx::{PgConnection, Row};
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::models::ids::{TeamId, UserId};
+use crate::models::invitation::{CreateInvitationRequest, Invitation};
+use crate::services::single_use_tokens::{self, Purpose};
+use crate::utils::errors::ServiceError;
+
+/// Create an invitation and return it with its token. Earlier pending
+/// invitations for the same email stop working.
+pub async fn issue(
+    db: &Database,
+    config: &AppConfig,
+    invited_by: UserId,
+    req: &CreateInvitationRequest,
+) -> Result<(Invitation, String), ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let row = sqlx::query(
+        "SELECT EXISTS (SELECT 1 FROM users WHERE LOWER(email) = LOWER($1)) AS email_taken,
+                (SELECT COUNT(*) FROM teams WHERE id = ANY($2)) AS known_teams"
+    )
+    .bind(&req.email)
+    .bind(&req.team_ids)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error checking invitation: {}", e);
+        ServiceError::DatabaseError("Failed to create invitation".to_string())
+    })?;
+
+    if row.get::<bool, _>("email_taken") {
+        return Err(ServiceError::Conflict("An account with this email already exists".to_string())
+            .with_code("EMAIL_TAKEN"));
+    }
+    if row.get::<i64, _>("known_teams") != req.team_ids.len() as i64 {
+        return Err(ServiceError::ValidationError("One or more teams do not exist".to_string())
+            .with_code("INVALID_TEAMS"));
+    }
+
+    sqlx::query("UPDATE invitations SET revoked_at = NOW() WHERE LOWER(email) = LOWER($1) AND accepted_at IS NULL AND revoked_at IS NULL")
+        .bind(&req.email)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error retiring invitations: {}", e);
+            ServiceError::DatabaseError("Failed to create invitation".to_string())
+        })?;
+
+    let token = single_use_tokens::generate();
+    let expires_at = Utc::now() + Duration::hours(config.invite_ttl_hours);
+    let row = sqlx::query(
+        "INSERT INTO invitations (email, team_ids, token_hash, invited_by, expires_at, locale)
+         VALUES ($1, $2, $3, $4, $5, COALESCE($6, (SELECT locale FROM users WHERE id = $4), 'en'))
+         RETURNING id, email, team_ids, invited_by, locale, expires_at, created_at"
+    )
+    .bind(&req.email)
+    .bind(&req.team_ids)
+    .bind(single_use_tokens::hash(config, Purpose::Invitation, &token))
+    .bind(invited_by)
+    .bind(expires_at)
+    .bind(&req.locale)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error storing invitation: {}", e);
+        ServiceError::DatabaseError("Failed to create invitation".to_string())
+    })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let invitation = Invitation {
+        id: row.get("id"),
+        email: row.get("email"),
+        team_ids: row.get("team_ids"),
+        invited_by: row.get("invited_by"),
+        locale: row.get("locale"),
+        expires_at: row.get("expires_at"),
+        created_at: row.get("created_at"),
+    };
+    Ok((invitation, token))
+}
+
+/// Mark an invitation accepted by a new user inside the caller's
+/// transaction and return its email, teams and locale. Fails for unknown,
+/// expired, revoked or already accepted invitations.
+pub async fn consume(
+    conn: &mut PgConnection,
+    config: &AppConfig,
+    token: &str,
+) -> Result<(String, Vec<TeamId>, String), ServiceError> {
+    let row = sqlx::query(
+        "UPDATE invitations SET accepted_at = NOW()
+         WHERE token_hash = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+         RETURNING email, team_ids, locale"
+    )
+    .bind(single_use_tokens::hash(config, Purpose::Invitation, token))
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error consuming invitation: {}", e);
+        ServiceError::DatabaseError("Failed to accept invitation".to_string())
+    })?;
+
+    row.map(|row| (row.get("email"), row.get("team_ids"), row.get("locale")))
+        .ok_or_else(|| ServiceError::ValidationError("Invitation is invalid or has expired".to_string())
+            .with_code("INVALID_INVITE"))
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::AppConfig;
+use crate::utils::errors::ServiceError;
+
+// Buckets are swept once a map grows past this many keys
+const SWEEP_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token buckets holding up to `capacity` attempts per key, refilled evenly
+/// so an empty bucket is full again after `refill`
+pub struct Buckets {
+    capacity: f64,
+    refill: Duration,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl Buckets {
+    pub fn new(capacity: u32, refill: Duration) -> Self {
+        Buckets { capacity: capacity as f64, refill, buckets: Mutex::new(HashMap::new()) }
+    }
+
+    fn per_sec(&self) -> f64 {
+        self.capacity / self.refill.as_secs_f64()
+    }
+
+    /// Take one token, or return how many seconds until one is available
+    pub fn take(&self, key: &str) -> Result<(), u64> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        if buckets.len() > SWEEP_THRESHOLD {
+            // Buckets that have refilled completely hold no state worth keeping
+            buckets.retain(|_, bucket| now.duration_since(bucket.updated) < self.refill);
+        }
+
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket { tokens: self.capacity, updated: now });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec()).min(self.capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - bucket.tokens) / self.per_sec()).ceil() as u64)
+        }
+    }
+
+    /// Whether `key` has no token left, without taking one
+    pub fn is_empty(&self, key: &str) -> bool {
+        let buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        buckets.get(key).is_some_and(|bucket| {
+            let elapsed = Instant::now().duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens + elapsed * self.per_sec() < 1.0
+        })
+    }
+
+    fn reset(&self, key: &str) {
+        self.buckets.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+    }
+}
+
+/// Limits login attempts per client IP and per username, independently of
+/// the general request budget. Counts are kept in memory, so each server
+/// process limits on its own.
+pub struct LoginLimiter {
+    by_ip: Buckets,
+    by_username: Buckets,
+    /// Failed logins per username before a captcha is needed; None when
+    /// captcha checks are off or required on every login
+    failures: Option<Buckets>,
+    captcha_always: bool,
+}
+
+impl LoginLimiter {
+    pub fn new(config: &AppConfig) -> Self {
+        let refill = Duration::from_secs(config.login_attempt_window_secs);
+        LoginLimiter {
+            by_ip: Buckets::new(config.login_max_attempts_per_ip, refill),
+            by_username: Buckets::new(config.login_max_attempts_per_username, refill),
+            failures: config.captcha.as_ref()
+                .filter(|captcha| captcha.after_failed_logins > 0)
+                .map(|captcha| Buckets::new(captcha.after_failed_logins, refill)),
+            captcha_always: config.captcha.as_ref().is_some_and(|captcha| captcha.after_failed_logins == 0),
+        }
+    }
+
+    /// Count a login attempt, rejecting it with 429 when either the IP or the
+    /// username has run out of attempts
+    pub fn check(&self, ip: Option<&str>, username: &str) -> Result<(), ServiceError> {
+        // Usernames are matched case-insensitively so varying the case does
+        // not buy extra guesses
+        let username = username.trim().to_lowercase();
+        let limited = ip.map_or(Ok(()), |ip| self.by_ip.take(ip))
+            .and_then(|_| self.by_username.take(&username));
+
+        limited.map_err(|retry_after| {
+            log::warn!("Login rate limited for {} from {}", username, ip.unwrap_or("unknown address"));
+            ServiceError::RateLimited("Too many login attempts; try again later".to_string())
+                .with_code("LOGIN_RATE_LIMITED")
+                .with_retry_after(retry_after)
+        })
+    }
+
+    /// Whether the next login for a username needs a captcha, after
+    /// CAPTCHA_AFTER_FAILED_LOGINS recent failures
+    pub fn captcha_required(&self, username: &str) -> bool {
+        self.captcha_always
+            || self.failures.as_ref().is_some_and(|failures| failures.is_empty(&username.trim().to_lowercase()))
+    }
+
+    /// Count a failed login towards requiring a captcha
+    pub fn failed(&self, username: &str) {
+        if let Some(failures) = &self.failures {
+            let _ = failures.take(&username.trim().to_lowercase());
+        }
+    }
+
+    /// Forget failed attempts against a username once its owner logs in
+    pub fn succeeded(&self, username: &str) {
+        let username = username.trim().to_lowercase();
+        self.by_username.reset(&username);
+        if let Some(failures) = &self.failures {
+            failures.reset(&username);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn take_spends_tokens_per_key() {
+        let buckets = Buckets::new(2, HOUR);
+        assert_eq!(buckets.take("a"), Ok(()));
+        assert_eq!(buckets.take("a"), Ok(()));
+        // Half an hour until one of the two tokens an hour comes back
+        let retry_after = buckets.take("a").expect_err("bucket is empty");
+        assert!((1799..=1800).contains(&retry_after), "retry after {}", retry_after);
+
+        assert_eq!(buckets.take("b"), Ok(()));
+    }
+
+    #[test]
+    fn tokens_come_back_over_the_refill_period() {
+        let buckets = Buckets::new(1, Duration::from_millis(50));
+        assert_eq!(buckets.take("a"), Ok(()));
+        assert!(buckets.take("a").is_err());
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(buckets.take("a"), Ok(()));
+    }
+
+    #[test]
+    fn is_empty_does_not_take_a_token() {
+        let buckets = Buckets::new(1, HOUR);
+        assert!(!buckets.is_empty("a"));
+        assert!(!buckets.is_empty("a"));
+        assert_eq!(buckets.take("a"), Ok(()));
+        assert!(buckets.is_empty("a"));
+        assert!(!buckets.is_empty("b"));
+    }
+
+    #[test]
+    fn reset_refills_only_that_key() {
+        let buckets = Buckets::new(1, HOUR);
+        assert_eq!(buckets.take("a"), Ok(()));
+        assert_eq!(buckets.take("b"), Ok(()));
+        buckets.reset("a");
+        assert!(!buckets.is_empty("a"));
+        assert_eq!(buckets.take("a"), Ok(()));
+        assert!(buckets.is_empty("b"));
+    }
+}
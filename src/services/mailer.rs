@@ -0,0 +1,82 @@
+use std::process::Stdio;
+
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::AppConfig;
+
+/// A plain-text email
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Outgoing mail transport
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+/// Writes messages to the log instead of sending them; used when no mail
+/// command is configured, e.g. in development
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        log::info!("Mail to {} - {}\n{}", email.to, email.subject, email.body);
+        Ok(())
+    }
+}
+
+/// Pipes an RFC 822 message to a sendmail-compatible command such as
+/// `sendmail -t`, which reads the recipients from the headers
+pub struct CommandMailer {
+    command: String,
+    from: String,
+}
+
+#[async_trait]
+impl Mailer for CommandMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let mut parts = self.command.split_whitespace();
+        let program = parts.next().ok_or("MAIL_COMMAND is empty")?;
+
+        let mut child = Command::new(program)
+            .args(parts)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start mail command: {}", e))?;
+
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            self.from, email.to, email.subject, email.body
+        );
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(message.as_bytes()).await
+                .map_err(|e| format!("Failed to write to mail command: {}", e))?;
+        }
+
+        let status = child.wait().await
+            .map_err(|e| format!("Mail command failed: {}", e))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("Mail command exited with {}", status))
+        }
+    }
+}
+
+/// Pick the transport from configuration
+pub fn from_config(config: &AppConfig) -> Box<dyn Mailer> {
+    match config.mail_command {
+        Some(ref command) => Box::new(CommandMailer {
+            command: command.clone(),
+            from: config.mail_from.clone(),
+        }),
+        None => Box::new(LogMailer),
+    }
+}
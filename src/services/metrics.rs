@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use serde::Serialize;
+
+/// Process-wide metrics reported by the health and metrics endpoints
+#[derive(Debug)]
+pub struct Metrics {
+    handler_panics: AtomicU64,
+    requests_shed: AtomicU64,
+    pool_wait_ms: AtomicU64,
+    pool_exhaustions: AtomicU64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    pub handler_panics: u64,
+    pub requests_shed: u64,
+    /// Latest wait for a pool connection
+    pub pool_wait_ms: u64,
+    pub pool_exhaustions: u64,
+}
+
+pub static METRICS: Metrics = Metrics {
+    handler_panics: AtomicU64::new(0),
+    requests_shed: AtomicU64::new(0),
+    pool_wait_ms: AtomicU64::new(0),
+    pool_exhaustions: AtomicU64::new(0),
+};
+
+impl Metrics {
+    pub fn record_handler_panic(&self) {
+        self.handler_panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_request_shed(&self) {
+        self.requests_shed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_pool_wait(&self, waited_ms: u64) {
+        self.pool_wait_ms.store(waited_ms, Ordering::Relaxed);
+    }
+
+    pub fn record_pool_exhaustion(&self) {
+        self.pool_exhaustions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            handler_panics: self.handler_panics.load(Ordering::Relaxed),
+            requests_shed: self.requests_shed.load(Ordering::Relaxed),
+            pool_wait_ms: self.pool_wait_ms.load(Ordering::Relaxed),
+            pool_exhaustions: self.pool_exhaustions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        let metrics = [
+            ("kanban_handler_panics_total", "counter", "Handler panics answered with a 500", snapshot.handler_panics),
+            ("kanban_requests_shed_total", "counter", "Requests refused with a 503 while the server was saturated", snapshot.requests_shed),
+            ("kanban_pool_wait_milliseconds", "gauge", "Latest wait for a database pool connection", snapshot.pool_wait_ms),
+            ("kanban_pool_exhaustions_total", "counter", "Times pool waits went over POOL_WAIT_ALERT_MS", snapshot.pool_exhaustions),
+        ];
+        metrics.iter()
+            .map(|(name, kind, help, value)| format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n"))
+            .collect()
+    }
+}
@@ -1 +1,46 @@
-// Services module - currently empty as auth logic is in handlers
+pub mod activity;
+pub mod api_keys;
+pub mod attachment_preview;
+pub mod attachment_text;
+pub mod attachment_scan;
+pub mod audit;
+pub mod availability;
+pub mod board_diff;
+pub mod business_hours;
+pub mod captcha;
+pub mod column_policies;
+pub mod dead_letters;
+pub mod email_templates;
+pub mod email_verification;
+pub mod encryption;
+pub mod escalations;
+pub mod feedback;
+pub mod forecast;
+pub mod index_advisor;
+pub mod invitations;
+pub mod login_limiter;
+pub mod magic_links;
+pub mod mailer;
+pub mod metrics;
+pub mod moderation;
+pub mod oauth;
+pub mod operations;
+pub mod outbox;
+pub mod password_reset;
+pub mod pool_monitor;
+pub mod plugins;
+pub mod realtime;
+pub mod scripts;
+pub mod single_use_tokens;
+pub mod sla;
+pub mod task_defaults;
+pub mod task_drafts;
+pub mod task_events;
+pub mod task_links;
+pub mod task_relations;
+pub mod task_response;
+pub mod task_writes;
+pub mod telemetry;
+pub mod usage;
+pub mod weekly_digest;
+pub mod watermark;
@@ -0,0 +1,232 @@
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde::Deserialize;
+use sqlx::Row;
+
+use crate::config::ModerationService;
+use crate::Database;
+use crate::models::file::{ATTACHMENT_FLAGGED, ATTACHMENT_READY, ATTACHMENT_REJECTED};
+use crate::models::ids::{AttachmentId, UserId};
+use crate::models::moderation::{ModerationReview, DECISION_APPROVED, DECISION_REJECTED};
+use crate::services::attachment_scan;
+use crate::utils::errors::ServiceError;
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent("kanban-be")
+            .build()
+            .expect("HTTP client configuration is valid")
+    })
+}
+
+#[derive(Deserialize)]
+struct Verdict {
+    flagged: bool,
+    reason: Option<String>,
+}
+
+/// Whether uploads of this type go to the moderation service
+pub fn supports(mime_type: &str) -> bool {
+    mime_type.starts_with("image/")
+}
+
+/// Ask the moderation service about an uploaded image. Returns the reason
+/// to hold it for review, or None when it may be shown. Images the service
+/// could not review are held too, so an outage does not let them through.
+pub async fn review_image(service: &ModerationService, file_path: &Path, file_name: &str, mime_type: &str) -> Option<String> {
+    let content = match tokio::fs::read(file_path).await {
+        Ok(content) => content,
+        Err(e) => {
+            log::error!("Failed to read {} for moderation: {}", file_path.display(), e);
+            return Some("Could not be sent for moderation".to_string());
+        }
+    };
+
+    let mut request = client().post(&service.url).json(&serde_json::json!({
+        "kind": "image",
+        "file_name": file_name,
+        "mime_type": mime_type,
+        "content_base64": BASE64.encode(&content),
+    }));
+    if let Some(ref token) = service.token {
+        request = request.bearer_auth(token);
+    }
+
+    let verdict = match request.send().await.and_then(|response| response.error_for_status()) {
+        Ok(response) => response.json::<Verdict>().await,
+        Err(e) => Err(e),
+    };
+    match verdict {
+        Ok(verdict) if verdict.flagged => Some(verdict.reason.unwrap_or_else(|| "Flagged by the moderation service".to_string())),
+        Ok(_) => None,
+        Err(e) => {
+            log::error!("Moderation of {} failed: {}", file_path.display(), e);
+            Some("Moderation service unavailable".to_string())
+        }
+    }
+}
+
+/// Hide an attachment and queue it for an admin, in one transaction
+pub async fn flag(db: &Database, attachment_id: AttachmentId, reason: &str) -> Result<(), ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    if !attachment_scan::update_status(&mut tx, attachment_id, ATTACHMENT_FLAGGED).await? {
+        return Ok(());
+    }
+
+    sqlx::query("INSERT INTO moderation_reviews (attachment_id, reason) VALUES ($1, $2)")
+        .bind(attachment_id)
+        .bind(reason)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error queueing moderation review: {}", e);
+            ServiceError::DatabaseError("Failed to queue moderation review".to_string())
+        })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(())
+}
+
+const REVIEW_COLUMNS: &str =
+    "r.id, r.attachment_id, a.task_id, a.original_name, a.mime_type, a.uploaded_by, r.reason, r.flagged_at,
+     r.decision, r.decided_by, r.decided_at";
+
+fn review_from_row(row: &sqlx::postgres::PgRow) -> ModerationReview {
+    ModerationReview {
+        id: row.get("id"),
+        attachment_id: row.get("attachment_id"),
+        task_id: row.get("task_id"),
+        original_name: row.get("original_name"),
+        mime_type: row.get("mime_type"),
+        uploaded_by: row.get("uploaded_by"),
+        reason: row.get("reason"),
+        flagged_at: row.get("flagged_at"),
+        decision: row.get("decision"),
+        decided_by: row.get("decided_by"),
+        decided_at: row.get("decided_at"),
+    }
+}
+
+/// Pending reviews oldest first, or decided ones most recent first, with the
+/// unpaged total
+pub async fn list(db: &Database, pending: bool, limit: i64, offset: i64) -> Result<(Vec<ModerationReview>, i64), ServiceError> {
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM moderation_reviews WHERE (decision IS NULL) = $1")
+        .bind(pending)
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error counting moderation reviews: {}", e);
+            ServiceError::DatabaseError("Failed to fetch moderation queue".to_string())
+        })?;
+
+    let order = if pending { "r.flagged_at ASC, r.id ASC" } else { "r.decided_at DESC, r.id DESC" };
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM moderation_reviews r JOIN task_attachments a ON a.id = r.attachment_id
+         WHERE (r.decision IS NULL) = $1
+         ORDER BY {}
+         LIMIT $2 OFFSET $3",
+        REVIEW_COLUMNS, order
+    ))
+    .bind(pending)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching moderation reviews: {}", e);
+        ServiceError::DatabaseError("Failed to fetch moderation queue".to_string())
+    })?;
+
+    Ok((rows.iter().map(review_from_row).collect(), total))
+}
+
+/// Approve or reject a pending review. Approved attachments become
+/// downloadable; rejected ones stay hidden and their file is removed.
+pub async fn decide(db: &Database, review_id: i32, approve: bool, actor_id: UserId) -> Result<ModerationReview, ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let decision = if approve { DECISION_APPROVED } else { DECISION_REJECTED };
+    let decided = sqlx::query(
+        "UPDATE moderation_reviews SET decision = $2, decided_by = $3, decided_at = NOW()
+         WHERE id = $1 AND decision IS NULL
+         RETURNING attachment_id"
+    )
+    .bind(review_id)
+    .bind(decision)
+    .bind(actor_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error deciding moderation review: {}", e);
+        ServiceError::DatabaseError("Failed to decide moderation review".to_string())
+    })?;
+
+    let Some(decided) = decided else {
+        let exists = sqlx::query("SELECT 1 FROM moderation_reviews WHERE id = $1")
+            .bind(review_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| {
+                log::error!("Database error checking moderation review: {}", e);
+                ServiceError::DatabaseError("Failed to decide moderation review".to_string())
+            })?
+            .is_some();
+        return Err(if exists {
+            ServiceError::Conflict("Moderation review was already decided".to_string()).with_code("REVIEW_ALREADY_DECIDED")
+        } else {
+            ServiceError::NotFound("Moderation review not found".to_string()).with_code("REVIEW_NOT_FOUND")
+        });
+    };
+    let attachment_id: AttachmentId = decided.get("attachment_id");
+
+    let status = if approve { ATTACHMENT_READY } else { ATTACHMENT_REJECTED };
+    attachment_scan::update_status(&mut tx, attachment_id, status).await?;
+
+    let row = sqlx::query(&format!(
+        "SELECT {}, a.file_path FROM moderation_reviews r JOIN task_attachments a ON a.id = r.attachment_id WHERE r.id = $1",
+        REVIEW_COLUMNS
+    ))
+    .bind(review_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching moderation review: {}", e);
+        ServiceError::DatabaseError("Failed to decide moderation review".to_string())
+    })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    if !approve {
+        let file_path: String = row.get("file_path");
+        if let Err(e) = tokio::fs::remove_file(&file_path).await {
+            log::warn!("Failed to remove rejected file {}: {}", file_path, e);
+        }
+    }
+
+    Ok(review_from_row(&row))
+}
@@ -0,0 +1,437 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use actix_web::cookie::{time, Cookie, SameSite};
+use bcrypt::{hash, DEFAULT_COST};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::config::{AppConfig, OAuthCredentials};
+use crate::Database;
+use crate::models::ids::UserId;
+use crate::utils::errors::ServiceError;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// How long a user has to finish signing in at the provider
+const STATE_TTL_SECS: i64 = 600;
+
+/// Cookie holding the nonce of the state a browser was sent off with, so a
+/// callback only completes in the browser that started the login
+pub const NONCE_COOKIE: &str = "kanban_oauth_nonce";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    Google,
+    GitHub,
+}
+
+/// The provider account that completed the flow. `email` is only set when
+/// the provider vouches for it.
+#[derive(Debug, Clone)]
+pub struct OAuthProfile {
+    pub provider_user_id: String,
+    pub email: Option<String>,
+    pub name: String,
+    /// Preferred local username when a new user has to be created
+    pub username_hint: String,
+}
+
+/// The local user an OAuth login resolved to
+#[derive(Debug, Clone)]
+pub struct LinkedUser {
+    pub id: UserId,
+    pub username: String,
+    pub name: String,
+    pub created: bool,
+}
+
+impl Provider {
+    pub fn parse(name: &str) -> Result<Self, ServiceError> {
+        match name {
+            "google" => Ok(Provider::Google),
+            "github" => Ok(Provider::GitHub),
+            _ => Err(ServiceError::NotFound(format!("Unknown login provider '{}'", name)).with_code("UNKNOWN_OAUTH_PROVIDER")),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Provider::Google => "google",
+            Provider::GitHub => "github",
+        }
+    }
+
+    fn credentials<'a>(&self, config: &'a AppConfig) -> Result<&'a OAuthCredentials, ServiceError> {
+        let credentials = match self {
+            Provider::Google => config.google_oauth.as_ref(),
+            Provider::GitHub => config.github_oauth.as_ref(),
+        };
+        credentials.ok_or_else(|| {
+            ServiceError::NotFound(format!("Login with {} is not enabled", self.name())).with_code("OAUTH_PROVIDER_DISABLED")
+        })
+    }
+
+    fn authorize_endpoint(&self) -> &'static str {
+        match self {
+            Provider::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+            Provider::GitHub => "https://github.com/login/oauth/authorize",
+        }
+    }
+
+    fn token_endpoint(&self) -> &'static str {
+        match self {
+            Provider::Google => "https://oauth2.googleapis.com/token",
+            Provider::GitHub => "https://github.com/login/oauth/access_token",
+        }
+    }
+
+    fn scope(&self) -> &'static str {
+        match self {
+            Provider::Google => "openid email profile",
+            Provider::GitHub => "read:user user:email",
+        }
+    }
+
+    fn redirect_uri(&self, config: &AppConfig) -> String {
+        format!("{}/{}/callback", config.oauth_redirect_base_url, self.name())
+    }
+}
+
+fn client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent("kanban-be")
+            .build()
+            .expect("HTTP client configuration is valid")
+    })
+}
+
+fn provider_error(provider: Provider, what: &str, e: impl std::fmt::Display) -> ServiceError {
+    log::warn!("OAuth {} with {} failed: {}", what, provider.name(), e);
+    ServiceError::AuthenticationError(format!("Login with {} failed", provider.name())).with_code("OAUTH_FAILED")
+}
+
+fn sign(config: &AppConfig, message: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(config.token_hash_key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// The state parameter is `{provider}.{expires}.{nonce}.{signature}`, so the
+// callback can check it came from us without storing anything. The nonce
+// also goes into a cookie, which ties the state to the browser it was issued
+// to: a callback URL someone else started fails in any other browser.
+fn issue_state(config: &AppConfig, provider: Provider, nonce: &str) -> String {
+    let expires = Utc::now().timestamp() + STATE_TTL_SECS;
+    let message = format!("{}.{}.{}", provider.name(), expires, nonce);
+    let signature = sign(config, &message);
+    format!("{}.{}", message, signature)
+}
+
+fn nonce_cookie(value: String) -> Cookie<'static> {
+    // Lax, since the provider sends the browser back with a cross-site GET
+    Cookie::build(NONCE_COOKIE, value)
+        .path("/api/auth/oauth")
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::seconds(STATE_TTL_SECS))
+        .finish()
+}
+
+/// Removal of the nonce cookie, once the callback has used it
+pub fn clear_nonce_cookie() -> Cookie<'static> {
+    let mut cookie = nonce_cookie(String::new());
+    cookie.make_removal();
+    cookie
+}
+
+fn verify_state(config: &AppConfig, provider: Provider, state: &str, nonce: Option<&str>) -> Result<(), ServiceError> {
+    let invalid = || ServiceError::ValidationError("Login session is invalid or has expired".to_string())
+        .with_code("INVALID_OAUTH_STATE");
+
+    let (message, signature) = state.rsplit_once('.').ok_or_else(invalid)?;
+    let mut mac = HmacSha256::new_from_slice(config.token_hash_key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    let signature = hex::decode(signature).map_err(|_| invalid())?;
+    mac.verify_slice(&signature).map_err(|_| invalid())?;
+
+    let mut parts = message.split('.');
+    let name = parts.next().ok_or_else(invalid)?;
+    let expires: i64 = parts.next().and_then(|s| s.parse().ok()).ok_or_else(invalid)?;
+    let state_nonce = parts.next().ok_or_else(invalid)?;
+    if name != provider.name() || expires < Utc::now().timestamp() {
+        return Err(invalid());
+    }
+    if nonce != Some(state_nonce) {
+        log::warn!("OAuth callback for {} came without the nonce cookie of its state", provider.name());
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+/// URL of the provider's consent page to send the browser to, and the nonce
+/// cookie the browser has to bring back to the callback
+pub fn authorize_url(config: &AppConfig, provider: Provider) -> Result<(String, Cookie<'static>), ServiceError> {
+    let credentials = provider.credentials(config)?;
+    let nonce = Uuid::new_v4().simple().to_string();
+    let url = reqwest::Url::parse_with_params(
+        provider.authorize_endpoint(),
+        &[
+            ("client_id", credentials.client_id.as_str()),
+            ("redirect_uri", provider.redirect_uri(config).as_str()),
+            ("response_type", "code"),
+            ("scope", provider.scope()),
+            ("state", issue_state(config, provider, &nonce).as_str()),
+        ],
+    )
+    .map_err(|e| {
+        log::error!("Failed to build authorize URL for {}: {}", provider.name(), e);
+        ServiceError::InternalError("Failed to start login".to_string())
+    })?;
+    Ok((url.to_string(), nonce_cookie(nonce)))
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GoogleUser {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    id: i64,
+    login: String,
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubEmail {
+    email: String,
+    primary: bool,
+    verified: bool,
+}
+
+/// Check the state against the browser's nonce cookie, trade the
+/// authorization code for an access token and fetch the account it belongs to
+pub async fn exchange(
+    config: &AppConfig,
+    provider: Provider,
+    code: &str,
+    state: &str,
+    nonce: Option<&str>,
+) -> Result<OAuthProfile, ServiceError> {
+    verify_state(config, provider, state, nonce)?;
+    let credentials = provider.credentials(config)?;
+    let redirect_uri = provider.redirect_uri(config);
+
+    let token: TokenResponse = client()
+        .post(provider.token_endpoint())
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&[
+            ("client_id", credentials.client_id.as_str()),
+            ("client_secret", credentials.client_secret.as_str()),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("grant_type", "authorization_code"),
+        ])
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| provider_error(provider, "code exchange", e))?
+        .json()
+        .await
+        .map_err(|e| provider_error(provider, "code exchange", e))?;
+
+    match provider {
+        Provider::Google => {
+            let user: GoogleUser = fetch_json(provider, "https://openidconnect.googleapis.com/v1/userinfo", &token.access_token).await?;
+            let email = user.email.filter(|_| user.email_verified);
+            let username_hint = email.as_deref()
+                .and_then(|email| email.split('@').next())
+                .unwrap_or("user")
+                .to_string();
+            Ok(OAuthProfile {
+                provider_user_id: user.sub,
+                name: user.name.unwrap_or_else(|| username_hint.clone()),
+                email,
+                username_hint,
+            })
+        }
+        Provider::GitHub => {
+            let user: GitHubUser = fetch_json(provider, "https://api.github.com/user", &token.access_token).await?;
+            let emails: Vec<GitHubEmail> = fetch_json(provider, "https://api.github.com/user/emails", &token.access_token).await?;
+            let email = emails.into_iter()
+                .find(|email| email.primary && email.verified)
+                .map(|email| email.email);
+            Ok(OAuthProfile {
+                provider_user_id: user.id.to_string(),
+                name: user.name.unwrap_or_else(|| user.login.clone()),
+                email,
+                username_hint: user.login,
+            })
+        }
+    }
+}
+
+async fn fetch_json<T: for<'de> Deserialize<'de>>(provider: Provider, url: &str, access_token: &str) -> Result<T, ServiceError> {
+    client()
+        .get(url)
+        .bearer_auth(access_token)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| provider_error(provider, "profile lookup", e))?
+        .json()
+        .await
+        .map_err(|e| provider_error(provider, "profile lookup", e))
+}
+
+// Lowercase letters, digits, '.', '_' and '-' from the hint, at most 40 long
+fn sanitize_username(hint: &str) -> String {
+    let username: String = hint.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+        .map(|c| c.to_ascii_lowercase())
+        .take(40)
+        .collect();
+    if username.is_empty() { "user".to_string() } else { username }
+}
+
+/// Find the local user for a provider account. An account seen before maps
+/// to its linked user; otherwise it is linked to the user with the same
+/// verified email, or a new user is created for it.
+pub async fn link_or_create_user(db: &Database, provider: Provider, profile: &OAuthProfile) -> Result<LinkedUser, ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let linked = sqlx::query(
+        "SELECT u.id, u.username, u.name, u.is_active FROM user_identities i
+         JOIN users u ON u.id = i.user_id
+         WHERE i.provider = $1 AND i.provider_user_id = $2"
+    )
+    .bind(provider.name())
+    .bind(&profile.provider_user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error looking up linked identity: {}", e);
+        ServiceError::DatabaseError("Failed to log in".to_string())
+    })?;
+
+    let by_email = match (&linked, &profile.email) {
+        // Only a verified address proves the account belongs to whoever
+        // controls the provider's account
+        (None, Some(email)) => sqlx::query(
+            "SELECT id, username, name, is_active FROM users
+             WHERE LOWER(email) = LOWER($1) AND email_verified_at IS NOT NULL"
+        )
+        .bind(email)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error looking up user by email: {}", e);
+            ServiceError::DatabaseError("Failed to log in".to_string())
+        })?,
+        _ => None,
+    };
+
+    let user = match linked.or(by_email) {
+        Some(row) => {
+            if !row.get::<bool, _>("is_active") {
+                return Err(ServiceError::Unauthorized("Account is deactivated".to_string()).with_code("ACCOUNT_DEACTIVATED"));
+            }
+            LinkedUser { id: row.get("id"), username: row.get("username"), name: row.get("name"), created: false }
+        }
+        None => {
+            // The account can only be used through the provider until the
+            // user sets a password via the reset flow
+            let password = hash(Uuid::new_v4().to_string(), DEFAULT_COST)?;
+            let base = sanitize_username(&profile.username_hint);
+
+            let mut row = None;
+            for attempt in 0..5 {
+                let username = match attempt {
+                    0 => base.clone(),
+                    _ => format!("{}-{}", base, &Uuid::new_v4().simple().to_string()[..6]),
+                };
+                row = sqlx::query(
+                    "INSERT INTO users (username, password, name, email, email_verified_at)
+                     VALUES ($1, $2, $3, $4, CASE WHEN $4 IS NULL THEN NULL ELSE NOW() END)
+                     ON CONFLICT (username) DO NOTHING
+                     RETURNING id, username, name"
+                )
+                .bind(&username)
+                .bind(&password)
+                .bind(&profile.name)
+                .bind(&profile.email)
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(|e| match e {
+                    // The address belongs to a local account that never
+                    // verified it, so it cannot be linked automatically
+                    sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+                        ServiceError::Conflict("An account with this email already exists; log in to it and verify the email first".to_string())
+                            .with_code("EMAIL_TAKEN")
+                    }
+                    _ => {
+                        log::error!("Database error creating user from OAuth profile: {}", e);
+                        ServiceError::DatabaseError("Failed to create user".to_string())
+                    }
+                })?;
+                if row.is_some() {
+                    break;
+                }
+            }
+            let row = row.ok_or_else(|| {
+                log::error!("Could not find a free username for '{}'", base);
+                ServiceError::InternalError("Failed to create user".to_string())
+            })?;
+            LinkedUser { id: row.get("id"), username: row.get("username"), name: row.get("name"), created: true }
+        }
+    };
+
+    sqlx::query(
+        "INSERT INTO user_identities (user_id, provider, provider_user_id) VALUES ($1, $2, $3)
+         ON CONFLICT (provider, provider_user_id) DO NOTHING"
+    )
+    .bind(user.id)
+    .bind(provider.name())
+    .bind(&profile.provider_user_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error linking identity: {}", e);
+        ServiceError::DatabaseError("Failed to log in".to_string())
+    })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    if user.created {
+        log::info!("Created user {} from {} account", user.id, provider.name());
+    }
+    Ok(user)
+}
@@ -0,0 +1,144 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+use uuid::Uuid;
+
+use crate::Database;
+use crate::models::ids::UserId;
+use crate::models::operation::{Operation, OPERATION_FAILED, OPERATION_RUNNING, OPERATION_SUCCEEDED};
+use crate::utils::errors::ServiceError;
+
+// Where operations that produce a file (exports, archives) write it
+const RESULTS_DIR: &str = "operations";
+
+const OPERATION_COLUMNS: &str =
+    "id, kind, status, progress, result, error, created_by, created_at, updated_at, completed_at";
+
+fn operation_from_row(row: &PgRow) -> Operation {
+    Operation {
+        id: row.get("id"),
+        kind: row.get("kind"),
+        status: row.get("status"),
+        progress: row.get("progress"),
+        result: row.get("result"),
+        error: row.get("error"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        completed_at: row.get("completed_at"),
+    }
+}
+
+/// Path of the file produced by an operation, served by its download link
+pub fn result_path(id: Uuid) -> PathBuf {
+    PathBuf::from(RESULTS_DIR).join(id.to_string())
+}
+
+/// Record a new pending operation
+pub async fn create(db: &Database, kind: &str, created_by: UserId) -> Result<Operation, ServiceError> {
+    let row = sqlx::query(&format!(
+        "INSERT INTO operations (kind, created_by) VALUES ($1, $2) RETURNING {}",
+        OPERATION_COLUMNS
+    ))
+    .bind(kind)
+    .bind(created_by)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error creating operation: {}", e);
+        ServiceError::DatabaseError("Failed to create operation".to_string())
+    })?;
+
+    Ok(operation_from_row(&row))
+}
+
+/// Look up an operation; users only ever see operations they started
+pub async fn find_for_user(db: &Database, id: Uuid, user_id: UserId) -> Result<Option<Operation>, ServiceError> {
+    let row = sqlx::query(&format!(
+        "SELECT {} FROM operations WHERE id = $1 AND created_by = $2",
+        OPERATION_COLUMNS
+    ))
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching operation: {}", e);
+        ServiceError::DatabaseError("Failed to fetch operation".to_string())
+    })?;
+
+    Ok(row.as_ref().map(operation_from_row))
+}
+
+/// Handle given to a running job for reporting progress
+#[derive(Clone)]
+pub struct OperationHandle {
+    db: Arc<Database>,
+    pub id: Uuid,
+}
+
+impl OperationHandle {
+    /// Update the completion percentage; failures are logged, not fatal
+    pub async fn progress(&self, percent: i32) {
+        let result = sqlx::query("UPDATE operations SET progress = $2, updated_at = NOW() WHERE id = $1")
+            .bind(self.id)
+            .bind(percent.clamp(0, 100))
+            .execute(&self.db.pool)
+            .await;
+        if let Err(e) = result {
+            log::warn!("Failed to record progress for operation {}: {}", self.id, e);
+        }
+    }
+
+    async fn finish(&self, outcome: Result<serde_json::Value, ServiceError>) {
+        let query = match outcome {
+            Ok(result) => sqlx::query(
+                "UPDATE operations SET status = $2, progress = 100, result = $3, updated_at = NOW(), completed_at = NOW()
+                 WHERE id = $1"
+            )
+            .bind(self.id)
+            .bind(OPERATION_SUCCEEDED)
+            .bind(result),
+            Err(e) => {
+                log::error!("Operation {} failed: {}", self.id, e);
+                sqlx::query(
+                    "UPDATE operations SET status = $2, error = $3, updated_at = NOW(), completed_at = NOW()
+                     WHERE id = $1"
+                )
+                .bind(self.id)
+                .bind(OPERATION_FAILED)
+                .bind(e.to_string())
+            }
+        };
+
+        if let Err(e) = query.execute(&self.db.pool).await {
+            log::error!("Failed to record outcome of operation {}: {}", self.id, e);
+        }
+    }
+}
+
+/// Run `job` in the background for an already created operation, marking it
+/// running now and succeeded/failed with the job's result when it finishes
+pub fn spawn<F, Fut>(db: Arc<Database>, operation: &Operation, job: F)
+where
+    F: FnOnce(OperationHandle) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<serde_json::Value, ServiceError>> + Send + 'static,
+{
+    let handle = OperationHandle { db, id: operation.id };
+    tokio::spawn(async move {
+        let started = sqlx::query("UPDATE operations SET status = $2, updated_at = NOW() WHERE id = $1")
+            .bind(handle.id)
+            .bind(OPERATION_RUNNING)
+            .execute(&handle.db.pool)
+            .await;
+        if let Err(e) = started {
+            log::warn!("Failed to mark operation {} running: {}", handle.id, e);
+        }
+
+        let outcome = job(handle.clone()).await;
+        handle.finish(outcome).await;
+    });
+}
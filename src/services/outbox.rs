@@ -0,0 +1,339 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::{PgConnection, Row};
+
+use crate::Database;
+use crate::services::{dead_letters, encryption};
+use crate::utils::errors::ServiceError;
+
+// How many pending events a single relay pass will claim
+const RELAY_BATCH_SIZE: i64 = 50;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub aggregate_type: String,
+    pub aggregate_id: i32,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    /// Subscribers of the `Fanout` that already accepted the event
+    pub delivered_to: Vec<String>,
+}
+
+/// Destination for events drained from the outbox by the relay worker
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), String>;
+}
+
+/// Hands each event to several named subscribers in turn. An event counts as
+/// published only once all of them accepted it; a retry skips those that
+/// already did, so they never see the event twice.
+pub struct Fanout(pub Vec<(&'static str, Arc<dyn EventPublisher>)>);
+
+impl Fanout {
+    /// Publish to every subscriber not yet in `delivered`, adding those that
+    /// accept the event. A failing subscriber does not stop the others; the
+    /// error names every subscriber still waiting for the event.
+    pub async fn publish(&self, event: &OutboxEvent, delivered: &mut Vec<String>) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for (name, publisher) in &self.0 {
+            if delivered.iter().any(|done| done == name) {
+                continue;
+            }
+            match publisher.publish(event).await {
+                Ok(()) => delivered.push(name.to_string()),
+                Err(err) => errors.push(format!("{}: {}", name, err)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+/// Write an event into the outbox using the caller's transaction, so the event
+/// is only visible to the relay if the surrounding mutation commits.
+pub async fn enqueue<T: Serialize>(
+    conn: &mut PgConnection,
+    aggregate_type: &str,
+    aggregate_id: i32,
+    event_type: &str,
+    payload: &T,
+) -> Result<(), ServiceError> {
+    enqueue_partial(conn, aggregate_type, aggregate_id, event_type, payload, &[]).await
+}
+
+/// Write an event some of the `Fanout` subscribers already accepted, as when
+/// a dead letter is requeued
+pub async fn enqueue_partial<T: Serialize>(
+    conn: &mut PgConnection,
+    aggregate_type: &str,
+    aggregate_id: i32,
+    event_type: &str,
+    payload: &T,
+    delivered_to: &[String],
+) -> Result<(), ServiceError> {
+    let payload = serde_json::to_value(payload).map_err(|e| {
+        log::error!("Failed to serialize outbox payload: {}", e);
+        ServiceError::InternalError("Failed to serialize event".to_string())
+    })?;
+    let payload = seal_payload(payload)?;
+
+    sqlx::query(
+        "INSERT INTO event_outbox (aggregate_type, aggregate_id, event_type, payload, delivered_to)
+         VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(aggregate_type)
+    .bind(aggregate_id)
+    .bind(event_type)
+    .bind(payload)
+    .bind(delivered_to)
+    .execute(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error writing outbox event: {}", e);
+        ServiceError::DatabaseError("Failed to record event".to_string())
+    })?;
+
+    Ok(())
+}
+
+// Payloads carry task titles and descriptions, so on a sensitive board the
+// whole payload is stored sealed, as a JSON string
+fn seal_payload(payload: serde_json::Value) -> Result<serde_json::Value, ServiceError> {
+    if !encryption::seals_writes() {
+        return Ok(payload);
+    }
+    Ok(encryption::seal_text(Some(&payload.to_string()))?.into())
+}
+
+/// The payload an event was enqueued with, opening one stored sealed
+pub fn open_payload(payload: serde_json::Value) -> Result<serde_json::Value, ServiceError> {
+    let serde_json::Value::String(text) = payload else {
+        return Ok(payload);
+    };
+    if !encryption::is_sealed_text(&text) {
+        return Ok(serde_json::Value::String(text));
+    }
+    encryption::open_text(Some(text))
+        .filter(|opened| !encryption::is_sealed_text(opened))
+        .and_then(|opened| serde_json::from_str(&opened).ok())
+        .ok_or_else(|| ServiceError::InternalError("Failed to decrypt stored data".to_string()).with_code("DECRYPTION_FAILED"))
+}
+
+/// Claim a batch of pending events and hand them to the subscribers. Rows are
+/// locked with SKIP LOCKED so several relay instances can run side by side.
+/// Published events are deleted; an event that fails `max_attempts` times is
+/// moved to the dead letters.
+pub async fn relay_pending(db: &Database, fanout: &Fanout, max_attempts: i32) -> Result<usize, ServiceError> {
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let rows = sqlx::query(
+        "SELECT id, aggregate_type, aggregate_id, event_type, payload, delivered_to, attempts, created_at
+         FROM event_outbox
+         ORDER BY id
+         LIMIT $1
+         FOR UPDATE SKIP LOCKED"
+    )
+    .bind(RELAY_BATCH_SIZE)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error reading outbox: {}", e);
+        ServiceError::DatabaseError("Failed to read outbox".to_string())
+    })?;
+
+    let mut published = 0;
+    for row in &rows {
+        let stored_payload: serde_json::Value = row.get("payload");
+        let mut event = OutboxEvent {
+            id: row.get("id"),
+            aggregate_type: row.get("aggregate_type"),
+            aggregate_id: row.get("aggregate_id"),
+            event_type: row.get("event_type"),
+            payload: serde_json::Value::Null,
+            delivered_to: row.get("delivered_to"),
+        };
+
+        let mut delivered = std::mem::take(&mut event.delivered_to);
+        let result = match open_payload(stored_payload.clone()) {
+            Ok(payload) => {
+                event.payload = payload;
+                fanout.publish(&event, &mut delivered).await
+            }
+            Err(e) => Err(e.to_string()),
+        };
+        event.delivered_to = delivered;
+        // Subscribers are done with the plaintext; anything written back
+        // keeps the payload as stored
+        event.payload = stored_payload;
+
+        match result {
+            Ok(()) => {
+                sqlx::query("DELETE FROM event_outbox WHERE id = $1")
+                    .bind(event.id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Database error removing published event: {}", e);
+                        ServiceError::DatabaseError("Failed to update outbox".to_string())
+                    })?;
+                published += 1;
+            }
+            Err(err) if row.get::<i32, _>("attempts") + 1 >= max_attempts => {
+                log::error!("Giving up on event {} after {} attempts: {}", event.id, max_attempts, err);
+                let payload = serde_json::to_value(&event).unwrap_or_default();
+                dead_letters::park(
+                    &mut tx,
+                    dead_letters::KIND_OUTBOX_EVENT,
+                    &payload,
+                    max_attempts,
+                    &err,
+                    row.get("created_at"),
+                ).await?;
+                sqlx::query("DELETE FROM event_outbox WHERE id = $1")
+                    .bind(event.id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Database error removing dead outbox event: {}", e);
+                        ServiceError::DatabaseError("Failed to update outbox".to_string())
+                    })?;
+            }
+            Err(err) => {
+                log::warn!("Failed to publish event {}: {}", event.id, err);
+                sqlx::query("UPDATE event_outbox SET attempts = attempts + 1, last_error = $2, delivered_to = $3 WHERE id = $1")
+                    .bind(event.id)
+                    .bind(&err)
+                    .bind(&event.delivered_to)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| {
+                        log::error!("Database error recording publish failure: {}", e);
+                        ServiceError::DatabaseError("Failed to update outbox".to_string())
+                    })?;
+            }
+        }
+    }
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(published)
+}
+
+/// Spawn the background relay that periodically drains the outbox
+pub fn spawn_relay(db: Arc<Database>, fanout: Arc<Fanout>, interval: Duration, max_attempts: i32) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            match relay_pending(&db, &fanout, max_attempts).await {
+                Ok(0) => {}
+                Ok(count) => log::debug!("Relayed {} outbox events", count),
+                Err(e) => log::error!("Outbox relay failed: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::utils::test_db::TestDb;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<serde_json::Value>>);
+
+    #[async_trait]
+    impl EventPublisher for Recorder {
+        async fn publish(&self, event: &OutboxEvent) -> Result<(), String> {
+            self.0.lock().unwrap().push(event.payload.clone());
+            Ok(())
+        }
+    }
+
+    struct Down;
+
+    #[async_trait]
+    impl EventPublisher for Down {
+        async fn publish(&self, _: &OutboxEvent) -> Result<(), String> {
+            Err("down".to_string())
+        }
+    }
+
+    fn payload() -> serde_json::Value {
+        json!({ "id": 1, "name": "Secret title", "description": "Secret plans" })
+    }
+
+    async fn stored(test: &TestDb, table: &str) -> Vec<String> {
+        sqlx::query_scalar(&format!("SELECT payload::text FROM {}", table))
+            .fetch_all(&test.db.pool)
+            .await
+            .expect("stored payloads")
+    }
+
+    async fn enqueue_secret(test: &TestDb) {
+        let mut conn = test.db.pool.acquire().await.expect("connection");
+        enqueue(&mut conn, "task", 1, "task.updated", &payload()).await.expect("enqueue");
+    }
+
+    #[actix_web::test]
+    async fn payloads_are_sealed_at_rest_and_opened_for_subscribers() {
+        let Some(test) = TestDb::create().await else { return };
+        encryption::init_for_tests();
+        enqueue_secret(&test).await;
+
+        let rows = stored(&test, "event_outbox").await;
+        assert_eq!(rows.len(), 1);
+        assert!(!rows[0].contains("Secret"), "outbox payload stored in the clear: {}", rows[0]);
+
+        let recorder = Arc::new(Recorder::default());
+        let fanout = Fanout(vec![("recorder", recorder.clone())]);
+        assert_eq!(relay_pending(&test.db, &fanout, 3).await.expect("relay"), 1);
+        assert_eq!(*recorder.0.lock().unwrap(), vec![payload()]);
+        assert!(stored(&test, "event_outbox").await.is_empty());
+        test.drop().await;
+    }
+
+    #[actix_web::test]
+    async fn dead_letters_keep_payloads_sealed_until_requeued() {
+        let Some(test) = TestDb::create().await else { return };
+        encryption::init_for_tests();
+        enqueue_secret(&test).await;
+
+        let down = Fanout(vec![("subscriber", Arc::new(Down))]);
+        assert_eq!(relay_pending(&test.db, &down, 1).await.expect("relay"), 0);
+        let rows = stored(&test, "dead_letters").await;
+        assert_eq!(rows.len(), 1);
+        assert!(!rows[0].contains("Secret"), "dead letter stored in the clear: {}", rows[0]);
+
+        let parked = dead_letters::list(&test.db, None, 10).await.expect("list");
+        assert_eq!(parked[0].payload["payload"], payload());
+
+        assert!(dead_letters::requeue(&test.db, parked[0].id).await.expect("requeue"));
+        assert!(!stored(&test, "event_outbox").await[0].contains("Secret"));
+        let recorder = Arc::new(Recorder::default());
+        let fanout = Fanout(vec![("subscriber", recorder.clone())]);
+        assert_eq!(relay_pending(&test.db, &fanout, 3).await.expect("relay"), 1);
+        assert_eq!(*recorder.0.lock().unwrap(), vec![payload()]);
+        test.drop().await;
+    }
+}
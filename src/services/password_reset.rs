@@ -0,0 +1,48 @@
+use chrono::{Duration, Utc};
+use sqlx::PgConnection;
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::models::ids::UserId;
+use crate::services::single_use_tokens::{Purpose, UserTokens};
+use crate::utils::errors::ServiceError;
+
+const TOKENS: UserTokens = UserTokens::new("password_reset_tokens", Purpose::PasswordReset);
+
+/// Create a reset token for a user and return it. Earlier unused tokens for
+/// the same user stop working.
+pub async fn issue(db: &Database, config: &AppConfig, user_id: UserId) -> Result<String, ServiceError> {
+    let expires_at = Utc::now() + Duration::minutes(config.password_reset_ttl_minutes);
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let token = TOKENS.issue(&mut tx, config, user_id, expires_at).await
+        .map_err(|e| {
+            log::error!("Database error storing reset token: {}", e);
+            ServiceError::DatabaseError("Failed to issue reset token".to_string())
+        })?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(token)
+}
+
+/// Mark a token used inside the caller's transaction and return its user.
+/// Fails for unknown, expired or already used tokens.
+pub async fn consume(conn: &mut PgConnection, config: &AppConfig, token: &str) -> Result<UserId, ServiceError> {
+    TOKENS.consume(conn, config, token).await
+        .map_err(|e| {
+            log::error!("Database error consuming reset token: {}", e);
+            ServiceError::DatabaseError("Failed to reset password".to_string())
+        })?
+        .ok_or_else(|| ServiceError::ValidationError("Reset link is invalid or has expired".to_string())
+            .with_code("INVALID_RESET_TOKEN"))
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use actix_web::web;
+use async_trait::async_trait;
+
+use crate::config::AppConfig;
+use crate::models::ids::TaskId;
+use crate::models::task::TaskResponse;
+use crate::services::outbox::{EventPublisher, OutboxEvent};
+use crate::services::task_events::TASK_CREATED;
+use crate::services::task_writes::TASK_STATUS_CHANGED;
+
+/// Deployment-specific behavior added without touching the handlers. Hooks
+/// run on the outbox relay after the change has committed, so they never
+/// slow down or fail the request that made it. Every method is optional.
+#[async_trait]
+pub trait Plugin: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn on_task_created(&self, _task: &TaskResponse) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn on_status_changed(&self, _task_id: TaskId, _from: &str, _to: &str) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Register extra routes; use a path prefix of your own, e.g. `/api/x-<name>`
+    fn configure(&self, _cfg: &mut web::ServiceConfig) {}
+}
+
+/// Logs every hook; handy to check that hooks fire
+pub struct AuditLogPlugin;
+
+#[async_trait]
+impl Plugin for AuditLogPlugin {
+    fn name(&self) -> &'static str {
+        "audit_log"
+    }
+
+    async fn on_task_created(&self, task: &TaskResponse) -> Result<(), String> {
+        log::info!("Plugin audit_log: task {} created by user {}", task.id, task.created_by);
+        Ok(())
+    }
+
+    async fn on_status_changed(&self, task_id: TaskId, from: &str, to: &str) -> Result<(), String> {
+        log::info!("Plugin audit_log: task {} moved from {} to {}", task_id, from, to);
+        Ok(())
+    }
+}
+
+type Constructor = fn() -> Arc<dyn Plugin>;
+
+// Plugins compiled into this build, by the name used in PLUGINS
+const AVAILABLE: &[(&str, Constructor)] = &[
+    ("audit_log", || Arc::new(AuditLogPlugin)),
+];
+
+/// The plugins enabled for this deployment, in the order they were listed
+#[derive(Default)]
+pub struct PluginRegistry {
+    plugins: Vec<Arc<dyn Plugin>>,
+}
+
+impl PluginRegistry {
+    /// Enable the plugins named in PLUGINS, failing on unknown names
+    pub fn from_config(config: &AppConfig) -> Result<Self, String> {
+        let mut registry = PluginRegistry::default();
+        for name in &config.plugins {
+            let (_, build) = AVAILABLE.iter()
+                .find(|(available, _)| available == name)
+                .ok_or_else(|| format!(
+                    "Unknown plugin '{}'; available: {}",
+                    name,
+                    AVAILABLE.iter().map(|(available, _)| *available).collect::<Vec<_>>().join(", ")
+                ))?;
+            registry.plugins.push(build());
+        }
+        Ok(registry)
+    }
+
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        for plugin in &self.plugins {
+            plugin.configure(cfg);
+        }
+    }
+}
+
+// A failing plugin is logged and skipped; redelivering the event would
+// repeat it for every other subscriber too
+#[async_trait]
+impl EventPublisher for PluginRegistry {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), String> {
+        if self.plugins.is_empty() {
+            return Ok(());
+        }
+
+        match event.event_type.as_str() {
+            TASK_CREATED => {
+                let task: TaskResponse = match serde_json::from_value(event.payload.clone()) {
+                    Ok(task) => task,
+                    Err(e) => {
+                        log::warn!("Skipping plugins for malformed event {}: {}", event.id, e);
+                        return Ok(());
+                    }
+                };
+                for plugin in &self.plugins {
+                    if let Err(e) = plugin.on_task_created(&task).await {
+                        log::warn!("Plugin {} failed on event {}: {}", plugin.name(), event.id, e);
+                    }
+                }
+            }
+            TASK_STATUS_CHANGED => {
+                let from = event.payload["from"].as_str().unwrap_or_default();
+                let to = event.payload["to"].as_str().unwrap_or_default();
+                for plugin in &self.plugins {
+                    if let Err(e) = plugin.on_status_changed(TaskId(event.aggregate_id), from, to).await {
+                        log::warn!("Plugin {} failed on event {}: {}", plugin.name(), event.id, e);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
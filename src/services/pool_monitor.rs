@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::PgPool;
+
+use crate::config::AppConfig;
+use crate::services::metrics::METRICS;
+
+// How often the pool is probed, and the longest a single probe may wait
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Connection pool usage as of the latest probe
+#[derive(Debug, Serialize)]
+pub struct PoolStats {
+    /// Open connections, busy and idle
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+    /// How long the latest probe waited for a connection
+    pub acquire_wait_ms: u64,
+    /// Whether that wait is over POOL_WAIT_ALERT_MS
+    pub exhausted: bool,
+}
+
+/// Measures how long it takes to get a connection from the pool by checking
+/// one out every second. Waits over POOL_WAIT_ALERT_MS are logged, counted
+/// and sent to POOL_ALERT_WEBHOOK_URL once when they start and once when
+/// they end. The load shedder reads the same measurement.
+pub struct PoolMonitor {
+    pool: PgPool,
+    acquire_wait_ms: AtomicU64,
+    exhausted: AtomicBool,
+    alert_wait_ms: u64,
+}
+
+impl PoolMonitor {
+    /// Create the monitor and start probing
+    pub fn start(config: &AppConfig, pool: PgPool) -> Arc<Self> {
+        let monitor = Arc::new(PoolMonitor {
+            pool,
+            acquire_wait_ms: AtomicU64::new(0),
+            exhausted: AtomicBool::new(false),
+            alert_wait_ms: config.pool_wait_alert_ms,
+        });
+
+        let probe = monitor.clone();
+        let webhook_url = config.pool_alert_webhook_url.clone();
+        tokio::spawn(async move {
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .user_agent("kanban-be")
+                .build()
+                .expect("HTTP client configuration is valid");
+            let mut ticker = tokio::time::interval(PROBE_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let started = Instant::now();
+                let waited = match tokio::time::timeout(PROBE_TIMEOUT, probe.pool.acquire()).await {
+                    Ok(Ok(_conn)) => started.elapsed(),
+                    Ok(Err(e)) => {
+                        log::warn!("Connection pool probe failed: {}", e);
+                        PROBE_TIMEOUT
+                    }
+                    Err(_) => PROBE_TIMEOUT,
+                };
+                probe.record(waited.as_millis() as u64, &client, webhook_url.as_deref());
+            }
+        });
+
+        monitor
+    }
+
+    fn record(&self, waited_ms: u64, client: &reqwest::Client, webhook_url: Option<&str>) {
+        self.acquire_wait_ms.store(waited_ms, Ordering::Relaxed);
+        METRICS.record_pool_wait(waited_ms);
+
+        let exhausted = waited_ms > self.alert_wait_ms;
+        if self.exhausted.swap(exhausted, Ordering::Relaxed) == exhausted {
+            return;
+        }
+        let stats = self.stats();
+        let event = if exhausted {
+            METRICS.record_pool_exhaustion();
+            log::warn!(
+                "Connection pool exhausted: waited {}ms for a connection (threshold {}ms), {} of {} open, {} idle",
+                waited_ms, self.alert_wait_ms, stats.size, stats.max_connections, stats.idle
+            );
+            "pool.exhausted"
+        } else {
+            log::info!("Connection pool recovered: waited {}ms for a connection", waited_ms);
+            "pool.recovered"
+        };
+
+        // Sent in the background so a slow receiver does not hold up probes
+        if let Some(url) = webhook_url {
+            let payload = json!({ "event": event, "at": Utc::now(), "pool": stats });
+            let request = client.post(url).json(&payload);
+            let url = url.to_string();
+            tokio::spawn(async move {
+                if let Err(e) = request.send().await.and_then(|res| res.error_for_status()) {
+                    log::error!("Pool alert webhook to {} failed: {}", url, e);
+                }
+            });
+        }
+    }
+
+    /// Milliseconds the latest probe waited for a connection
+    pub fn acquire_wait_ms(&self) -> u64 {
+        self.acquire_wait_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max_connections: self.pool.options().get_max_connections(),
+            acquire_wait_ms: self.acquire_wait_ms(),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use futures_util::{Stream, StreamExt};
+use tokio::sync::Notify;
+
+use crate::services::outbox::{EventPublisher, OutboxEvent};
+
+/// Message queued for a single subscriber. Messages for the same aggregate are
+/// coalesced while still queued, so a burst of edits to one task only costs
+/// the client the latest version.
+struct QueuedMessage {
+    key: String,
+    body: String,
+}
+
+struct ClientQueue {
+    messages: Mutex<VecDeque<QueuedMessage>>,
+    notify: Notify,
+    disconnected: AtomicBool,
+}
+
+impl ClientQueue {
+    fn disconnect(&self) {
+        self.disconnected.store(true, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+}
+
+/// Fan-out broker for realtime board updates. Each subscriber has a bounded
+/// queue; a client whose queue stays full is considered stuck and dropped
+/// instead of letting its backlog grow without limit.
+pub struct Broker {
+    clients: Mutex<HashMap<u64, Arc<ClientQueue>>>,
+    next_id: AtomicU64,
+    queue_capacity: usize,
+}
+
+impl Broker {
+    pub fn new(queue_capacity: usize) -> Self {
+        Broker {
+            clients: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            queue_capacity,
+        }
+    }
+
+    /// Register a subscriber and return its server-sent event stream
+    pub fn subscribe(self: &Arc<Self>) -> impl Stream<Item = Result<Bytes, std::convert::Infallible>> {
+        self.messages().map(|body| Ok(Bytes::from(format!("data: {}\n\n", body))))
+    }
+
+    /// Register a subscriber and return the JSON bodies queued for it. The
+    /// stream ends when the broker drops the client for being too slow.
+    pub fn messages(self: &Arc<Self>) -> impl Stream<Item = String> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let queue = Arc::new(ClientQueue {
+            messages: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            disconnected: AtomicBool::new(false),
+        });
+        self.clients.lock().unwrap().insert(id, queue.clone());
+        log::info!("Realtime client {} subscribed", id);
+
+        let subscription = Subscription { id, queue, broker: self.clone() };
+        futures_util::stream::unfold(subscription, |subscription| async move {
+            loop {
+                let next = subscription.queue.messages.lock().unwrap().pop_front();
+                if let Some(message) = next {
+                    return Some((message.body, subscription));
+                }
+                if subscription.queue.disconnected.load(Ordering::SeqCst) {
+                    return None;
+                }
+                subscription.queue.notify.notified().await;
+            }
+        })
+    }
+
+    /// Queue a message for every subscriber, coalescing by key
+    pub fn broadcast(&self, key: &str, body: &str) {
+        let mut clients = self.clients.lock().unwrap();
+        let mut slow_clients = Vec::new();
+
+        for (id, queue) in clients.iter() {
+            let mut messages = queue.messages.lock().unwrap();
+
+            if let Some(existing) = messages.iter_mut().find(|m| m.key == key) {
+                existing.body = body.to_string();
+            } else if messages.len() >= self.queue_capacity {
+                slow_clients.push(*id);
+                continue;
+            } else {
+                messages.push_back(QueuedMessage { key: key.to_string(), body: body.to_string() });
+            }
+
+            queue.notify.notify_one();
+        }
+
+        for id in slow_clients {
+            if let Some(queue) = clients.remove(&id) {
+                log::warn!("Dropping slow realtime client {} (queue full at {})", id, self.queue_capacity);
+                queue.disconnect();
+            }
+        }
+    }
+
+    pub fn client_count(&self) -> usize {
+        self.clients.lock().unwrap().len()
+    }
+
+    fn unsubscribe(&self, id: u64) {
+        if self.clients.lock().unwrap().remove(&id).is_some() {
+            log::info!("Realtime client {} unsubscribed", id);
+        }
+    }
+}
+
+// Removes the client from the broker when the response stream is dropped
+struct Subscription {
+    id: u64,
+    queue: Arc<ClientQueue>,
+    broker: Arc<Broker>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.broker.unsubscribe(self.id);
+    }
+}
+
+#[async_trait]
+impl EventPublisher for Broker {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), String> {
+        let key = format!("{}:{}", event.aggregate_type, event.aggregate_id);
+        let body = serde_json::json!({
+            "type": event.event_type,
+            "id": event.aggregate_id,
+            "data": event.payload,
+        });
+        self.broadcast(&key, &body.to_string());
+        log::debug!("Broadcast event {} to {} clients", event.id, self.client_count());
+        Ok(())
+    }
+}
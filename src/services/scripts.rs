@@ -0,0 +1,427 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, Row};
+
+use crate::Database;
+use crate::models::ids::{TaskId, UserId};
+use crate::models::script::{CreateScriptRequest, Script, UpdateScriptRequest, KIND_AUTOMATE, KIND_VALIDATE};
+use crate::models::task::{TaskResponse, UpdateTaskRequest};
+use crate::services::outbox::{self, EventPublisher, OutboxEvent};
+use crate::services::task_events::{TASK_CREATED, TASK_UPDATED};
+use crate::services::task_writes::{self, TASK_STATUS_CHANGED};
+use crate::utils::errors::ServiceError;
+use crate::utils::sql::Patch;
+
+// Limits for a single run; a script that reaches one fails
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_EXPR_DEPTH: usize = 64;
+const MAX_STRING_SIZE: usize = 64 * 1024;
+const MAX_COLLECTION_SIZE: usize = 10_000;
+
+// Time all the scripts run for one event share. Validation runs inside the
+// request's transaction, so this bounds how long scripts can hold it open
+// however many are enabled.
+const MAX_EVENT_RUN_TIME: Duration = Duration::from_millis(100);
+
+// Fields an automation script may set on its task
+const AUTOMATE_FIELDS: &[&str] = &["name", "description", "status", "external_link"];
+
+const COLUMNS: &str = "id, name, kind, event, source, enabled, created_by, created_at, updated_at";
+
+fn script_from_row(row: &PgRow) -> Script {
+    Script {
+        id: row.get("id"),
+        name: row.get("name"),
+        kind: row.get("kind"),
+        event: row.get("event"),
+        source: row.get("source"),
+        enabled: row.get("enabled"),
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+// A fresh engine per run, which stops the script at `deadline`. Scripts
+// cannot import modules or eval code, and the core packages have no file
+// or network access.
+fn engine(script_name: &str, deadline: Instant) -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_expr_depths(MAX_EXPR_DEPTH, MAX_EXPR_DEPTH)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_COLLECTION_SIZE)
+        .set_max_map_size(MAX_COLLECTION_SIZE)
+        .set_module_resolver(DummyModuleResolver::new())
+        .disable_symbol("eval");
+
+    engine.on_progress(move |_| (Instant::now() > deadline).then_some(Dynamic::UNIT));
+
+    let name = script_name.to_string();
+    engine.on_print(move |text| log::info!("Script {}: {}", name, text));
+    let name = script_name.to_string();
+    engine.on_debug(move |text, _, _| log::debug!("Script {}: {}", name, text));
+    engine
+}
+
+/// Reject source that does not compile, so mistakes surface when the script
+/// is saved rather than on the next task event
+pub fn check(source: &str) -> Result<(), ServiceError> {
+    engine("check", Instant::now() + MAX_EVENT_RUN_TIME).compile(source)
+        .map(|_| ())
+        .map_err(|e| ServiceError::ValidationError(format!("Script does not compile: {}", e))
+            .with_code("INVALID_SCRIPT"))
+}
+
+fn run(script: &Script, scope: &mut Scope, deadline: Instant) -> Result<Dynamic, Box<EvalAltResult>> {
+    let engine = engine(&script.name, deadline);
+    let ast = engine.compile(&script.source)?;
+    engine.eval_ast_with_scope(scope, &ast)
+}
+
+// Scripts are CPU bound, so they run on the blocking pool rather than on
+// the worker serving requests
+async fn run_blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, ServiceError> + Send + 'static,
+) -> Result<T, ServiceError> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| {
+            log::error!("Script run panicked: {}", e);
+            ServiceError::InternalError("Failed to run scripts".to_string())
+        })?
+}
+
+async fn load_enabled(conn: &mut PgConnection, kind: &str, event: &str) -> Result<Vec<Script>, ServiceError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM scripts WHERE kind = $1 AND event = $2 AND enabled ORDER BY id",
+        COLUMNS
+    ))
+    .bind(kind)
+    .bind(event)
+    .fetch_all(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading scripts: {}", e);
+        ServiceError::DatabaseError("Failed to load scripts".to_string())
+    })?;
+
+    Ok(rows.iter().map(script_from_row).collect())
+}
+
+pub async fn list(db: &Database) -> Result<Vec<Script>, ServiceError> {
+    let rows = sqlx::query(&format!("SELECT {} FROM scripts ORDER BY id", COLUMNS))
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error listing scripts: {}", e);
+            ServiceError::DatabaseError("Failed to list scripts".to_string())
+        })?;
+
+    Ok(rows.iter().map(script_from_row).collect())
+}
+
+fn name_taken(e: sqlx::Error) -> ServiceError {
+    match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            ServiceError::Conflict("A script with this name already exists".to_string()).with_code("SCRIPT_NAME_TAKEN")
+        }
+        _ => {
+            log::error!("Database error saving script: {}", e);
+            ServiceError::DatabaseError("Failed to save script".to_string())
+        }
+    }
+}
+
+pub async fn create(db: &Database, created_by: UserId, req: &CreateScriptRequest) -> Result<Script, ServiceError> {
+    check(&req.source)?;
+
+    let row = sqlx::query(&format!(
+        "INSERT INTO scripts (name, kind, event, source, enabled, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING {}",
+        COLUMNS
+    ))
+    .bind(&req.name)
+    .bind(&req.kind)
+    .bind(&req.event)
+    .bind(&req.source)
+    .bind(req.enabled)
+    .bind(created_by)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(name_taken)?;
+
+    Ok(script_from_row(&row))
+}
+
+/// Change a script, checking the new fields against its kind; `None` if the
+/// script does not exist
+pub async fn update(db: &Database, id: i32, mut req: UpdateScriptRequest) -> Result<Option<Script>, ServiceError> {
+    let kind: Option<String> = sqlx::query_scalar("SELECT kind FROM scripts WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error loading script: {}", e);
+            ServiceError::DatabaseError("Failed to load script".to_string())
+        })?;
+    let Some(kind) = kind else {
+        return Ok(None);
+    };
+
+    req.normalize(&kind)?;
+    if let Some(source) = &req.source {
+        check(source)?;
+    }
+
+    let mut query = Patch::new("scripts")
+        .set_raw("updated_at", "NOW()")
+        .set_opt("name", req.name.as_ref())
+        .set_opt("event", req.event.as_ref())
+        .set_opt("source", req.source.as_ref())
+        .set_opt("enabled", req.enabled)
+        .build("id", id, Some(COLUMNS))
+        .expect("updated_at is always set");
+    let row = query.build()
+        .fetch_optional(&db.pool)
+        .await
+        .map_err(name_taken)?;
+
+    Ok(row.as_ref().map(script_from_row))
+}
+
+/// Delete a script, returning whether it existed
+pub async fn delete(db: &Database, id: i32) -> Result<bool, ServiceError> {
+    let result = sqlx::query("DELETE FROM scripts WHERE id = $1")
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error deleting script: {}", e);
+            ServiceError::DatabaseError("Failed to delete script".to_string())
+        })?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+fn to_dynamic<T: serde::Serialize + ?Sized>(value: &T) -> Result<Dynamic, ServiceError> {
+    rhai::serde::to_dynamic(value).map_err(|e| {
+        log::error!("Failed to convert script input: {}", e);
+        ServiceError::InternalError("Failed to run scripts".to_string())
+    })
+}
+
+/// Run the enabled validation scripts for `event` against a task about to
+/// be saved, inside the caller's transaction. `changes` holds the fields an
+/// update touched. A script that errors or hits a limit also rejects the
+/// write, so a broken script cannot be bypassed.
+pub async fn validate(
+    conn: &mut PgConnection,
+    event: &str,
+    task: &TaskResponse,
+    changes: Option<&serde_json::Map<String, serde_json::Value>>,
+) -> Result<(), ServiceError> {
+    let scripts = load_enabled(conn, KIND_VALIDATE, event).await?;
+    if scripts.is_empty() {
+        return Ok(());
+    }
+
+    let task = to_dynamic(task)?;
+    let changes = changes.map(to_dynamic).transpose()?.unwrap_or(Dynamic::UNIT);
+    let event = event.to_string();
+    run_blocking(move || validate_all(&scripts, &event, task, changes)).await
+}
+
+fn validate_all(scripts: &[Script], event: &str, task: Dynamic, changes: Dynamic) -> Result<(), ServiceError> {
+    let deadline = Instant::now() + MAX_EVENT_RUN_TIME;
+    for script in scripts {
+        let mut scope = Scope::new();
+        scope.push_constant("event", event.to_string());
+        scope.push_constant("task", task.clone());
+        scope.push_constant("changes", changes.clone());
+
+        let rejection = match run(script, &mut scope, deadline) {
+            Ok(result) if result.is_unit() || result.as_bool() == Ok(true) => continue,
+            Ok(result) if result.as_bool() == Ok(false) => format!("Rejected by script {}", script.name),
+            Ok(result) if result.is_string() => result.to_string(),
+            Ok(result) => {
+                log::warn!("Validation script {} returned a {}", script.name, result.type_name());
+                return Err(ServiceError::ValidationError(format!("Script {} failed", script.name))
+                    .with_code("SCRIPT_FAILED"));
+            }
+            Err(e) => match *e {
+                EvalAltResult::ErrorRuntime(thrown, _) => thrown.to_string(),
+                e => {
+                    log::warn!("Validation script {} failed: {}", script.name, e);
+                    return Err(ServiceError::ValidationError(format!("Script {} failed", script.name))
+                        .with_code("SCRIPT_FAILED"));
+                }
+            },
+        };
+
+        log::info!("Script {} rejected {} of task {}", script.name, event, task_id(&task));
+        return Err(ServiceError::ValidationError(rejection).with_code("SCRIPT_REJECTED"));
+    }
+    Ok(())
+}
+
+fn task_id(task: &Dynamic) -> String {
+    task.read_lock::<rhai::Map>()
+        .and_then(|task| task.get("id").map(|id| id.to_string()))
+        .unwrap_or_default()
+}
+
+/// Runs automation scripts for task events drained from the outbox. A
+/// script sees `event`, `task` and, on status changes, `from`; returning a
+/// map of task fields updates the task as the script's creator. Failures
+/// are logged and never redeliver the event.
+pub struct ScriptRunner {
+    db: Arc<Database>,
+}
+
+impl ScriptRunner {
+    pub fn new(db: Arc<Database>) -> Self {
+        ScriptRunner { db }
+    }
+
+    async fn run_automation(&self, script: &Script, event: &OutboxEvent, deadline: Instant) -> Result<(), ServiceError> {
+        let task_id = TaskId(event.aggregate_id);
+        let mut tx = self.db.begin().await
+            .map_err(|e| {
+                log::error!("Failed to begin transaction: {}", e);
+                ServiceError::DatabaseError("Transaction failed".to_string())
+            })?;
+
+        // Lock the task so the script's update applies to what it saw
+        let exists: Option<TaskId> = sqlx::query_scalar("SELECT id FROM tasks WHERE id = $1 FOR UPDATE")
+            .bind(task_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| {
+                log::error!("Database error locking task: {}", e);
+                ServiceError::DatabaseError("Failed to load task".to_string())
+            })?;
+        if exists.is_none() {
+            return Ok(());
+        }
+
+        let task = task_writes::fetch(&mut tx, task_id).await?;
+        let mut scope = Scope::new();
+        scope.push_constant("event", event.event_type.clone());
+        scope.push_constant("task", to_dynamic(&task)?);
+        scope.push_constant("from", to_dynamic(&event.payload["from"])?);
+
+        let to_run = script.clone();
+        let result = run_blocking(move || run(&to_run, &mut scope, deadline)
+            .map_err(|e| ServiceError::ValidationError(format!("Script failed: {}", e))))
+            .await?;
+        if result.is_unit() {
+            return Ok(());
+        }
+        let Some(fields) = result.try_cast::<rhai::Map>() else {
+            return Err(ServiceError::ValidationError("Script returned neither a map nor nothing".to_string()));
+        };
+
+        let fields: serde_json::Map<String, serde_json::Value> = rhai::serde::from_dynamic(&fields.into())
+            .map_err(|e| ServiceError::ValidationError(format!("Script returned invalid fields: {}", e)))?;
+        let fields: serde_json::Map<_, _> = fields.into_iter()
+            .filter(|(field, _)| AUTOMATE_FIELDS.contains(&field.as_str()))
+            .collect();
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        let mut update: UpdateTaskRequest = serde_json::from_value(serde_json::Value::Object(fields))
+            .map_err(|e| ServiceError::ValidationError(format!("Script returned invalid fields: {}", e)))?;
+        update.normalize()?;
+        if let Some(ref status) = update.status {
+            if !["TO_DO", "DOING", "DONE"].contains(&status.as_str()) {
+                return Err(ServiceError::ValidationError("Invalid task status".to_string()));
+            }
+        }
+
+        task_writes::apply_update(&mut tx, task_id, script.created_by, &update, None, Some(&script.name)).await?;
+        let task = task_writes::fetch(&mut tx, task_id).await?;
+        validate(&mut tx, TASK_UPDATED, &task, Some(&task_writes::changed_fields(&update))).await?;
+        outbox::enqueue(&mut tx, "task", task_id.0, "task.updated", &task).await?;
+
+        tx.commit().await
+            .map_err(|e| {
+                log::error!("Failed to commit transaction: {}", e);
+                ServiceError::DatabaseError("Transaction failed".to_string())
+            })?;
+
+        log::info!("Script {} updated task {} on {}", script.name, task_id, event.event_type);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventPublisher for ScriptRunner {
+    async fn publish(&self, event: &OutboxEvent) -> Result<(), String> {
+        if event.event_type != TASK_CREATED && event.event_type != TASK_STATUS_CHANGED {
+            return Ok(());
+        }
+        // Status changes made by a script do not trigger scripts again, so
+        // two scripts cannot keep moving a task back and forth
+        if event.payload.get("script").is_some() {
+            return Ok(());
+        }
+
+        let mut conn = self.db.pool.acquire().await
+            .map_err(|e| format!("Failed to acquire connection: {}", e))?;
+        let scripts = load_enabled(&mut conn, KIND_AUTOMATE, &event.event_type).await
+            .map_err(|e| e.to_string())?;
+        drop(conn);
+
+        let deadline = Instant::now() + MAX_EVENT_RUN_TIME;
+        for script in &scripts {
+            if let Err(e) = self.run_automation(script, event, deadline).await {
+                log::warn!("Automation script {} failed on event {}: {}", script.name, event.id, e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn script(source: &str) -> Script {
+        Script {
+            id: 1,
+            name: "test".to_string(),
+            kind: KIND_VALIDATE.to_string(),
+            event: TASK_CREATED.to_string(),
+            source: source.to_string(),
+            enabled: true,
+            created_by: UserId(1),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn scripts_stop_at_the_deadline() {
+        let counting = script("let x = 0; for i in 0..1000 { x += i; } x");
+
+        let result = run(&counting, &mut Scope::new(), Instant::now() + MAX_EVENT_RUN_TIME);
+        assert_eq!(result.expect("finishes in time").as_int(), Ok(499_500));
+
+        // A deadline used up by earlier scripts of the event
+        let result = run(&counting, &mut Scope::new(), Instant::now());
+        assert!(matches!(*result.expect_err("out of time"), EvalAltResult::ErrorTerminated(..)));
+    }
+}
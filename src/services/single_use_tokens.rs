@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::config::AppConfig;
+use crate::models::ids::UserId;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// What an emailed single-use token is for. Its hash is prefixed with the
+/// purpose, so a token issued for one never matches a row stored for another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Purpose {
+    PasswordReset,
+    LoginLink,
+    EmailVerification,
+    Invitation,
+}
+
+impl Purpose {
+    fn prefix(self) -> &'static [u8] {
+        match self {
+            Purpose::PasswordReset => b"reset:",
+            Purpose::LoginLink => b"magic:",
+            Purpose::EmailVerification => b"verify:",
+            Purpose::Invitation => b"invite:",
+        }
+    }
+}
+
+/// A new token with 256 random bits, to be sent in a link
+pub fn generate() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// What is stored in place of a token: a keyed hash, so a leaked table
+/// cannot be used without the server secret. Tokens are random enough to
+/// need no stretching.
+pub fn hash(config: &AppConfig, purpose: Purpose, token: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(config.token_hash_key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(purpose.prefix());
+    mac.update(token.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// A table of tokens each letting one user do one thing once, with
+/// `user_id`, `token_hash`, `expires_at` and `used_at` columns
+pub struct UserTokens {
+    table: &'static str,
+    purpose: Purpose,
+}
+
+impl UserTokens {
+    pub const fn new(table: &'static str, purpose: Purpose) -> Self {
+        UserTokens { table, purpose }
+    }
+
+    /// Store a new token for a user inside the caller's transaction and
+    /// return it. Earlier unused tokens of the user stop working.
+    pub async fn issue(
+        &self,
+        conn: &mut PgConnection,
+        config: &AppConfig,
+        user_id: UserId,
+        expires_at: DateTime<Utc>,
+    ) -> Result<String, sqlx::Error> {
+        let token = generate();
+        sqlx::query(&format!(
+            "WITH retired AS (
+                 UPDATE {table} SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL
+             )
+             INSERT INTO {table} (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+            table = self.table
+        ))
+        .bind(user_id)
+        .bind(hash(config, self.purpose, &token))
+        .bind(expires_at)
+        .execute(conn)
+        .await?;
+        Ok(token)
+    }
+
+    /// Mark a token used inside the caller's transaction and return its
+    /// user; None for unknown, expired or already used tokens
+    pub async fn consume(&self, conn: &mut PgConnection, config: &AppConfig, token: &str) -> Result<Option<UserId>, sqlx::Error> {
+        sqlx::query_scalar(&format!(
+            "UPDATE {} SET used_at = NOW()
+             WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+             RETURNING user_id",
+            self.table
+        ))
+        .bind(hash(config, self.purpose, token))
+        .fetch_optional(conn)
+        .await
+    }
+}
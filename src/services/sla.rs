@@ -0,0 +1,355 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::ids::{TaskId, TeamId, UserId};
+use crate::models::task::{task_key, CreateSlaRuleRequest, SlaBreach, SlaBreachQuery, SlaRule};
+use crate::services::email_templates::{self, EmailTemplates};
+use crate::services::mailer::{Email, Mailer};
+use crate::services::{business_hours, escalations, outbox};
+use crate::utils::errors::ServiceError;
+use crate::utils::locale;
+
+/// Outbox event for a task that newly breached an SLA rule; the payload has
+/// the task `id`, `rule_id` and `breached_at`
+pub const TASK_SLA_BREACHED: &str = "task.sla_breached";
+
+const COLUMNS: [&str; 3] = ["TO_DO", "DOING", "DONE"];
+
+/// Every SLA rule, oldest first
+pub async fn list_rules(db: &Database) -> Result<Vec<SlaRule>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT r.id, r.name, r.status, t.name AS team, r.max_hours, r.created_at
+         FROM sla_rules r LEFT JOIN teams t ON t.id = r.team_id
+         ORDER BY r.id"
+    )
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching SLA rules: {}", e);
+        ServiceError::DatabaseError("Failed to fetch SLA rules".to_string())
+    })?;
+
+    Ok(rows.iter().map(|row| SlaRule {
+        id: row.get("id"),
+        name: row.get("name"),
+        status: row.get("status"),
+        team: row.get("team"),
+        max_hours: row.get("max_hours"),
+        created_at: row.get("created_at"),
+    }).collect())
+}
+
+/// Add an SLA rule. Tasks already past its limit breach it on the next
+/// check.
+pub async fn create_rule(db: &Database, req: &CreateSlaRuleRequest, actor_id: UserId) -> Result<SlaRule, ServiceError> {
+    if !COLUMNS.contains(&req.status.as_str()) {
+        return Err(ServiceError::ValidationError("Invalid task status".to_string()).with_code("INVALID_STATUS"));
+    }
+
+    let team_id: Option<TeamId> = match req.team {
+        Some(ref team) => {
+            let team_id = sqlx::query_scalar("SELECT id FROM teams WHERE name = $1")
+                .bind(team)
+                .fetch_optional(&db.pool)
+                .await
+                .map_err(|e| {
+                    log::error!("Database error checking team: {}", e);
+                    ServiceError::DatabaseError("Failed to check team".to_string())
+                })?;
+            if team_id.is_none() {
+                return Err(ServiceError::ValidationError(format!("Team '{}' not found", team))
+                    .with_code("TEAM_NOT_FOUND"));
+            }
+            team_id
+        }
+        None => None,
+    };
+
+    let row = sqlx::query(
+        "INSERT INTO sla_rules (name, status, team_id, max_hours, created_by)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id, created_at"
+    )
+    .bind(&req.name)
+    .bind(&req.status)
+    .bind(team_id)
+    .bind(req.max_hours)
+    .bind(actor_id)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error creating SLA rule: {}", e);
+        ServiceError::DatabaseError("Failed to create SLA rule".to_string())
+    })?;
+
+    Ok(SlaRule {
+        id: row.get("id"),
+        name: req.name.clone(),
+        status: req.status.clone(),
+        team: req.team.clone(),
+        max_hours: req.max_hours,
+        created_at: row.get("created_at"),
+    })
+}
+
+/// Delete an SLA rule along with its breach history; false if there was no
+/// such rule
+pub async fn delete_rule(db: &Database, id: i32) -> Result<bool, ServiceError> {
+    let result = sqlx::query("DELETE FROM sla_rules WHERE id = $1")
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error deleting SLA rule: {}", e);
+            ServiceError::DatabaseError("Failed to delete SLA rule".to_string())
+        })?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// One page of breaches, most recent first, with the unpaged total
+pub async fn list_breaches(
+    db: &Database,
+    query: &SlaBreachQuery,
+    limit: i64,
+    offset: i64,
+) -> Result<(Vec<SlaBreach>, i64), ServiceError> {
+    const FILTER: &str = "($1::bool IS NULL OR (b.resolved_at IS NULL) = $1) AND ($2::int IS NULL OR b.rule_id = $2)";
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM sla_breaches b WHERE {}", FILTER))
+        .bind(query.open)
+        .bind(query.rule_id)
+        .fetch_one(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error counting SLA breaches: {}", e);
+            ServiceError::DatabaseError("Failed to fetch SLA breaches".to_string())
+        })?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT b.id, b.rule_id, r.name AS rule_name, b.task_id, t.name AS task_name, r.status,
+                t.created_by, b.entered_at, b.breached_at, b.resolved_at
+         FROM sla_breaches b
+         JOIN sla_rules r ON r.id = b.rule_id
+         JOIN tasks t ON t.id = b.task_id
+         WHERE {}
+         ORDER BY b.breached_at DESC, b.id DESC
+         LIMIT $3 OFFSET $4",
+        FILTER
+    ))
+    .bind(query.open)
+    .bind(query.rule_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching SLA breaches: {}", e);
+        ServiceError::DatabaseError("Failed to fetch SLA breaches".to_string())
+    })?;
+
+    let breaches = rows.iter().map(|row| {
+        let task_id: TaskId = row.get("task_id");
+        SlaBreach {
+            id: row.get("id"),
+            rule_id: row.get("rule_id"),
+            rule_name: row.get("rule_name"),
+            task_id,
+            task_key: task_key(task_id),
+            task_name: row.get("task_name"),
+            status: row.get("status"),
+            owner: row.get("created_by"),
+            entered_at: row.get("entered_at"),
+            breached_at: row.get("breached_at"),
+            resolved_at: row.get("resolved_at"),
+        }
+    }).collect();
+
+    Ok((breaches, total))
+}
+
+/// Resolve breaches whose task left the column (or is no longer covered by
+/// the rule), cancelling their pending escalations, and open one for every
+/// task past a rule's limit with the escalation chain scheduled, returning
+/// the ids of the new ones. Limits count only the working time of the
+/// business calendar. A task breaches each rule at most once per stay in a
+/// column, so several servers checking at once notify only once.
+pub async fn check(db: &Database) -> Result<Vec<i32>, ServiceError> {
+    let calendar = business_hours::load(db).await?;
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    let resolved: Vec<i32> = sqlx::query_scalar(
+        "UPDATE sla_breaches b SET resolved_at = NOW()
+         WHERE b.resolved_at IS NULL AND NOT EXISTS (
+             SELECT 1 FROM tasks t JOIN sla_rules r ON r.id = b.rule_id
+             WHERE t.id = b.task_id AND t.status = r.status AND t.status_changed_at = b.entered_at
+               AND (r.team_id IS NULL
+                    OR EXISTS (SELECT 1 FROM task_teams tt WHERE tt.task_id = t.id AND tt.team_id = r.team_id))
+         )
+         RETURNING b.id"
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error resolving SLA breaches: {}", e);
+        ServiceError::DatabaseError("Failed to check SLAs".to_string())
+    })?;
+    escalations::cancel(&mut tx, &resolved).await?;
+
+    // Working time is counted here rather than in SQL; wall-clock time is
+    // never shorter, so it narrows the candidates first
+    let candidates = sqlx::query(
+        "SELECT r.id AS rule_id, t.id AS task_id, t.status_changed_at, r.max_hours
+         FROM sla_rules r JOIN tasks t ON t.status = r.status
+         WHERE t.status_changed_at + make_interval(hours => r.max_hours) <= NOW()
+           AND (r.team_id IS NULL
+                OR EXISTS (SELECT 1 FROM task_teams tt WHERE tt.task_id = t.id AND tt.team_id = r.team_id))
+           AND NOT EXISTS (SELECT 1 FROM sla_breaches b
+                           WHERE b.rule_id = r.id AND b.task_id = t.id AND b.entered_at = t.status_changed_at)"
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error finding SLA breaches: {}", e);
+        ServiceError::DatabaseError("Failed to check SLAs".to_string())
+    })?;
+
+    let now = Utc::now();
+    let mut rule_ids = Vec::new();
+    let mut task_ids = Vec::new();
+    let mut entered = Vec::new();
+    let mut deadlines = Vec::new();
+    for row in &candidates {
+        let entered_at: DateTime<Utc> = row.get("status_changed_at");
+        let deadline = calendar.add_hours(entered_at, row.get("max_hours"));
+        if deadline <= now {
+            rule_ids.push(row.get::<i32, _>("rule_id"));
+            task_ids.push(row.get::<TaskId, _>("task_id"));
+            entered.push(entered_at);
+            deadlines.push(deadline);
+        }
+    }
+
+    let opened = sqlx::query(
+        "INSERT INTO sla_breaches (rule_id, task_id, entered_at, breached_at)
+         SELECT * FROM UNNEST($1::int[], $2::int[], $3::timestamptz[], $4::timestamptz[])
+         ON CONFLICT (rule_id, task_id, entered_at) DO NOTHING
+         RETURNING id, rule_id, task_id, breached_at"
+    )
+    .bind(&rule_ids)
+    .bind(&task_ids)
+    .bind(&entered)
+    .bind(&deadlines)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| {
+        log::error!("Database error opening SLA breaches: {}", e);
+        ServiceError::DatabaseError("Failed to check SLAs".to_string())
+    })?;
+
+    for row in &opened {
+        let task_id: TaskId = row.get("task_id");
+        let payload = serde_json::json!({
+            "id": task_id,
+            "rule_id": row.get::<i32, _>("rule_id"),
+            "breached_at": row.get::<DateTime<Utc>, _>("breached_at"),
+        });
+        outbox::enqueue(&mut tx, "task", task_id.0, TASK_SLA_BREACHED, &payload).await?;
+    }
+    let opened: Vec<i32> = opened.iter().map(|row| row.get("id")).collect();
+    escalations::schedule(&mut tx, &calendar, &opened).await?;
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    if !resolved.is_empty() || !opened.is_empty() {
+        log::info!("SLA check opened {} and resolved {} breaches", opened.len(), resolved.len());
+    }
+    Ok(opened)
+}
+
+// Email the owners of newly breached tasks; owners without a verified
+// email address are skipped
+async fn notify(db: &Database, templates: &EmailTemplates, mailer: &dyn Mailer, breach_ids: &[i32]) -> Result<(), ServiceError> {
+    let rows = sqlx::query(
+        "SELECT b.task_id, t.name AS task_name, r.name AS rule_name, r.status, r.max_hours, b.entered_at,
+                u.email, u.locale
+         FROM sla_breaches b
+         JOIN sla_rules r ON r.id = b.rule_id
+         JOIN tasks t ON t.id = b.task_id
+         JOIN users u ON u.id = t.created_by
+         WHERE b.id = ANY($1) AND u.is_active AND u.email IS NOT NULL AND u.email_verified_at IS NOT NULL"
+    )
+    .bind(breach_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading SLA breaches to notify: {}", e);
+        ServiceError::DatabaseError("Failed to notify SLA breaches".to_string())
+    })?;
+
+    for row in rows {
+        let task_id: TaskId = row.get("task_id");
+        let recipient_locale: String = row.get("locale");
+        let context = serde_json::json!({
+            "task_key": task_key(task_id),
+            "task_name": row.get::<String, _>("task_name"),
+            "rule_name": row.get::<String, _>("rule_name"),
+            "status": row.get::<String, _>("status"),
+            "max_hours": row.get::<i32, _>("max_hours"),
+            "entered_at": locale::format_datetime(row.get("entered_at"), &recipient_locale),
+        });
+        let result = match templates.render(db, email_templates::SLA_BREACH, &recipient_locale, &context).await {
+            Ok(rendered) => mailer.send(&Email { to: row.get("email"), subject: rendered.subject, body: rendered.body }).await,
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            log::error!("Failed to send SLA breach email for task {}: {}", task_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Periodically check tasks against the SLA rules, notify the owners of
+/// new breaches and send the escalations that came due
+pub fn spawn_checker(db: Arc<Database>, templates: Arc<EmailTemplates>, mailer: Arc<dyn Mailer>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let breach_ids = match check(&db).await {
+                Ok(breach_ids) => breach_ids,
+                Err(e) => {
+                    log::error!("SLA check failed: {}", e);
+                    continue;
+                }
+            };
+            if !breach_ids.is_empty() {
+                if let Err(e) = notify(&db, &templates, mailer.as_ref(), &breach_ids).await {
+                    log::error!("SLA breach notification failed: {}", e);
+                }
+            }
+
+            match escalations::claim_due(&db).await {
+                Ok(escalation_ids) if escalation_ids.is_empty() => {}
+                Ok(escalation_ids) => {
+                    log::info!("Escalating {} SLA breach steps", escalation_ids.len());
+                    if let Err(e) = escalations::notify(&db, &templates, mailer.as_ref(), &escalation_ids).await {
+                        log::error!("SLA escalation notification failed: {}", e);
+                    }
+                }
+                Err(e) => log::error!("SLA escalation failed: {}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,82 @@
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::ids::TeamId;
+use crate::models::task::{CreateTaskRequest, TaskDefaults, UpdateTaskDefaultsRequest};
+use crate::utils::errors::ServiceError;
+
+/// The board's defaults for new tasks; empty until an admin sets them
+pub async fn get(db: &Database) -> Result<TaskDefaults, ServiceError> {
+    let row = sqlx::query(
+        "SELECT ARRAY(SELECT t.name FROM teams t WHERE t.id = ANY(s.default_team_ids) ORDER BY t.name) AS default_teams,
+                s.default_description, s.updated_at
+         FROM board_settings s"
+    )
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching board settings: {}", e);
+        ServiceError::DatabaseError("Failed to fetch task defaults".to_string())
+    })?;
+
+    Ok(row.map(|row| TaskDefaults {
+        default_teams: row.get("default_teams"),
+        default_description: row.get("default_description"),
+        updated_at: row.get("updated_at"),
+    }).unwrap_or_default())
+}
+
+/// Replace the board's defaults for new tasks. Teams must exist.
+pub async fn save(db: &Database, req: &UpdateTaskDefaultsRequest) -> Result<TaskDefaults, ServiceError> {
+    let team_rows = sqlx::query("SELECT id, name FROM teams WHERE name = ANY($1)")
+        .bind(&req.default_teams)
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error getting teams: {}", e);
+            ServiceError::DatabaseError("Failed to query team".to_string())
+        })?;
+
+    if let Some(missing) = req.default_teams.iter()
+        .find(|name| !team_rows.iter().any(|row| row.get::<String, _>("name") == **name))
+    {
+        return Err(ServiceError::ValidationError(format!("Team '{}' not found", missing)).with_code("TEAM_NOT_FOUND"));
+    }
+    let team_ids: Vec<TeamId> = team_rows.iter().map(|row| row.get("id")).collect();
+
+    sqlx::query(
+        "INSERT INTO board_settings (id, default_team_ids, default_description, updated_at)
+         VALUES (TRUE, $1, $2, NOW())
+         ON CONFLICT (id) DO UPDATE
+         SET default_team_ids = EXCLUDED.default_team_ids,
+             default_description = EXCLUDED.default_description,
+             updated_at = EXCLUDED.updated_at"
+    )
+    .bind(&team_ids)
+    .bind(&req.default_description)
+    .execute(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error saving board settings: {}", e);
+        ServiceError::DatabaseError("Failed to save task defaults".to_string())
+    })?;
+
+    get(db).await
+}
+
+/// Fill in the teams and description a new task omits from the board's
+/// defaults. An explicit empty list or description is kept as given.
+pub async fn apply(db: &Database, task: &mut CreateTaskRequest) -> Result<(), ServiceError> {
+    if task.teams.is_some() && task.description.is_some() {
+        return Ok(());
+    }
+
+    let defaults = get(db).await?;
+    if task.teams.is_none() && !defaults.default_teams.is_empty() {
+        task.teams = Some(defaults.default_teams);
+    }
+    if task.description.is_none() {
+        task.description = defaults.default_description;
+    }
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, Row};
+
+use crate::Database;
+use crate::models::ids::UserId;
+use crate::models::task::{SaveTaskDraftRequest, TaskDraft};
+use crate::services::encryption;
+use crate::utils::errors::ServiceError;
+
+const COLUMNS: &str = "id, name, description, status, external_link, teams, created_at, updated_at";
+
+fn draft_from_row(row: &PgRow) -> TaskDraft {
+    TaskDraft {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: encryption::open_text(row.get("description")),
+        status: row.get("status"),
+        external_link: row.get("external_link"),
+        teams: row.get("teams"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+/// A user's drafts, most recently saved first
+pub async fn list(db: &Database, author: UserId) -> Result<Vec<TaskDraft>, ServiceError> {
+    let rows = sqlx::query(&format!(
+        "SELECT {} FROM task_drafts WHERE created_by = $1 ORDER BY updated_at DESC, id DESC", COLUMNS
+    ))
+    .bind(author)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error listing task drafts: {}", e);
+        ServiceError::DatabaseError("Failed to fetch drafts".to_string())
+    })?;
+
+    Ok(rows.iter().map(draft_from_row).collect())
+}
+
+pub async fn create(db: &Database, author: UserId, req: &SaveTaskDraftRequest) -> Result<TaskDraft, ServiceError> {
+    let stored_description = encryption::seal_text(req.description.as_deref())?;
+    let row = sqlx::query(&format!(
+        "INSERT INTO task_drafts (name, description, status, external_link, teams, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING {}", COLUMNS
+    ))
+    .bind(&req.name)
+    .bind(&stored_description)
+    .bind(&req.status)
+    .bind(&req.external_link)
+    .bind(&req.teams)
+    .bind(author)
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error creating task draft: {}", e);
+        ServiceError::DatabaseError("Failed to save draft".to_string())
+    })?;
+
+    Ok(draft_from_row(&row))
+}
+
+/// Replace one of the author's drafts; None if they have no such draft
+pub async fn update(
+    db: &Database,
+    author: UserId,
+    id: i32,
+    req: &SaveTaskDraftRequest,
+) -> Result<Option<TaskDraft>, ServiceError> {
+    let stored_description = encryption::seal_text(req.description.as_deref())?;
+    let row = sqlx::query(&format!(
+        "UPDATE task_drafts
+         SET name = $3, description = $4, status = $5, external_link = $6, teams = $7, updated_at = NOW()
+         WHERE id = $1 AND created_by = $2
+         RETURNING {}", COLUMNS
+    ))
+    .bind(id)
+    .bind(author)
+    .bind(&req.name)
+    .bind(&stored_description)
+    .bind(&req.status)
+    .bind(&req.external_link)
+    .bind(&req.teams)
+    .fetch_optional(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error updating task draft: {}", e);
+        ServiceError::DatabaseError("Failed to save draft".to_string())
+    })?;
+
+    Ok(row.as_ref().map(draft_from_row))
+}
+
+/// Discard one of the author's drafts; false if they have no such draft
+pub async fn delete(db: &Database, author: UserId, id: i32) -> Result<bool, ServiceError> {
+    let result = sqlx::query("DELETE FROM task_drafts WHERE id = $1 AND created_by = $2")
+        .bind(id)
+        .bind(author)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error deleting task draft: {}", e);
+            ServiceError::DatabaseError("Failed to delete draft".to_string())
+        })?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete one of the author's drafts inside the caller's transaction and
+/// return it; None if they have no such draft. Publishing takes the draft
+/// this way, so it is published at most once.
+pub async fn take(conn: &mut PgConnection, author: UserId, id: i32) -> Result<Option<TaskDraft>, ServiceError> {
+    let row = sqlx::query(&format!(
+        "DELETE FROM task_drafts WHERE id = $1 AND created_by = $2 RETURNING {}", COLUMNS
+    ))
+    .bind(id)
+    .bind(author)
+    .fetch_optional(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error removing task draft: {}", e);
+        ServiceError::DatabaseError("Failed to remove draft".to_string())
+    })?;
+
+    Ok(row.as_ref().map(draft_from_row))
+}
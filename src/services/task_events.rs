@@ -0,0 +1,309 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgConnection, Row};
+use uuid::Uuid;
+
+use crate::Database;
+use crate::models::ids::{TaskId, UserId};
+use crate::models::task::TaskEvent;
+use crate::services::encryption;
+use crate::utils::errors::ServiceError;
+
+pub const TASK_CREATED: &str = "task.created";
+pub const TASK_UPDATED: &str = "task.updated";
+pub const TASK_DELETED: &str = "task.deleted";
+pub const TASK_TRANSFERRED: &str = "task.transferred";
+
+// First key of the advisory lock serializing appends to one task's stream
+const TASK_EVENTS_LOCK: i32 = 0x7461_736b;
+
+/// Task state folded from its event stream
+#[derive(Debug, Clone, Default)]
+pub struct TaskState {
+    pub name: String,
+    pub description: Option<String>,
+    pub status: String,
+    pub external_link: Option<String>,
+    pub created_by: UserId,
+    pub client_id: Option<Uuid>,
+    pub teams: Vec<String>,
+    pub deleted: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl TaskState {
+    /// Apply a single event on top of the current state
+    pub fn apply(&mut self, event: &TaskEvent) {
+        let data = &event.data;
+
+        if event.event_type == TASK_DELETED {
+            self.deleted = true;
+            self.updated_at = Some(event.created_at);
+            return;
+        }
+
+        if event.event_type == TASK_TRANSFERRED {
+            if let Some(created_by) = data.get("created_by").and_then(|v| v.as_i64()) {
+                self.created_by = UserId(created_by as i32);
+            }
+            self.updated_at = Some(event.created_at);
+            return;
+        }
+
+        if event.event_type == TASK_CREATED {
+            self.created_at = Some(event.created_at);
+            if let Some(created_by) = data.get("created_by").and_then(|v| v.as_i64()) {
+                self.created_by = UserId(created_by as i32);
+            }
+            self.client_id = data.get("client_id")
+                .and_then(|v| v.as_str())
+                .and_then(|v| Uuid::parse_str(v).ok());
+        }
+
+        if let Some(name) = data.get("name").and_then(|v| v.as_str()) {
+            self.name = name.to_string();
+        }
+        if let Some(description) = data.get("description") {
+            self.description = description.as_str().map(|s| s.to_string());
+        }
+        if let Some(status) = data.get("status").and_then(|v| v.as_str()) {
+            self.status = status.to_string();
+        }
+        if let Some(external_link) = data.get("external_link") {
+            self.external_link = external_link.as_str().map(|s| s.to_string());
+        }
+        if let Some(teams) = data.get("teams").and_then(|v| v.as_array()) {
+            self.teams = teams.iter()
+                .filter_map(|t| t.as_str().map(|s| s.to_string()))
+                .collect();
+        }
+
+        self.updated_at = Some(event.created_at);
+    }
+}
+
+/// Append an event for a task inside the caller's transaction. Versions are
+/// per task and strictly increasing; the table rejects updates and deletes.
+pub async fn append<T: Serialize>(
+    conn: &mut PgConnection,
+    task_id: TaskId,
+    event_type: &str,
+    actor_id: UserId,
+    data: &T,
+) -> Result<(), ServiceError> {
+    let mut data = serde_json::to_value(data).map_err(|e| {
+        log::error!("Failed to serialize task event: {}", e);
+        ServiceError::InternalError("Failed to serialize event".to_string())
+    })?;
+    // Descriptions are sealed in the log just like in the projection
+    if let Some(description) = data.get_mut("description") {
+        if let Some(sealed) = encryption::seal_text(description.as_str())? {
+            *description = serde_json::Value::String(sealed);
+        }
+    }
+
+    // Writers of the same task queue up here until the first commits, so the
+    // next version is read after the previous one is visible. The lock is
+    // taken on the task id rather than its row, which the first event of a
+    // task may precede.
+    sqlx::query("SELECT pg_advisory_xact_lock($1, $2)")
+        .bind(TASK_EVENTS_LOCK)
+        .bind(task_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            log::error!("Database error locking task {} events: {}", task_id, e);
+            ServiceError::DatabaseError("Failed to record task event".to_string())
+        })?;
+
+    sqlx::query(
+        "INSERT INTO task_events (task_id, version, event_type, actor_id, data)
+         VALUES ($1, COALESCE((SELECT MAX(version) FROM task_events WHERE task_id = $1), 0) + 1, $2, $3, $4)"
+    )
+    .bind(task_id)
+    .bind(event_type)
+    .bind(actor_id)
+    .bind(data)
+    .execute(conn)
+    .await
+    .map_err(|e| match e {
+        // Only possible if something appended without the lock; the caller's
+        // transaction is rolled back and the client can simply retry
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            log::warn!("Concurrent modification of task {}: {}", task_id, e);
+            ServiceError::Conflict("Task was modified concurrently, please retry".to_string())
+                .with_code("TASK_VERSION_CONFLICT")
+        }
+        _ => {
+            log::error!("Database error appending task event: {}", e);
+            ServiceError::DatabaseError("Failed to record task event".to_string())
+        }
+    })?;
+
+    Ok(())
+}
+
+/// Load the full event stream for a task, oldest first
+pub async fn load_events(db: &Database, task_id: TaskId) -> Result<Vec<TaskEvent>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT id, task_id, version, event_type, actor_id, data, created_at
+         FROM task_events WHERE task_id = $1 ORDER BY version"
+    )
+    .bind(task_id)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading task events: {}", e);
+        ServiceError::DatabaseError("Failed to load task events".to_string())
+    })?;
+
+    Ok(rows.iter().map(|row| TaskEvent {
+        id: row.get("id"),
+        task_id: row.get("task_id"),
+        version: row.get("version"),
+        event_type: row.get("event_type"),
+        actor_id: row.get("actor_id"),
+        data: open_description(row.get("data")),
+        created_at: row.get("created_at"),
+    }).collect())
+}
+
+fn open_description(mut data: serde_json::Value) -> serde_json::Value {
+    if let Some(description) = data.get_mut("description") {
+        if let Some(opened) = encryption::open_text(description.as_str().map(|s| s.to_string())) {
+            *description = serde_json::Value::String(opened);
+        }
+    }
+    data
+}
+
+/// Rebuild the tasks/task_teams projection for one task from its events.
+/// Returns the folded state, or None when the task has no events at all.
+pub async fn rebuild_projection(db: &Database, task_id: TaskId) -> Result<Option<TaskState>, ServiceError> {
+    let events = load_events(db, task_id).await?;
+    if events.is_empty() {
+        return Ok(None);
+    }
+
+    let mut state = TaskState::default();
+    for event in &events {
+        state.apply(event);
+    }
+
+    let mut tx = db.begin().await
+        .map_err(|e| {
+            log::error!("Failed to begin transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    if state.deleted {
+        sqlx::query("DELETE FROM tasks WHERE id = $1")
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                log::error!("Database error removing projected task: {}", e);
+                ServiceError::DatabaseError("Failed to rebuild task".to_string())
+            })?;
+    } else {
+        sqlx::query(
+            "INSERT INTO tasks (id, name, description, status, external_link, created_by, created_at, updated_at, client_id)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                description = EXCLUDED.description,
+                status = EXCLUDED.status,
+                external_link = EXCLUDED.external_link,
+                created_by = EXCLUDED.created_by,
+                client_id = EXCLUDED.client_id,
+                created_at = EXCLUDED.created_at,
+                updated_at = EXCLUDED.updated_at"
+        )
+        .bind(task_id)
+        .bind(&state.name)
+        .bind(encryption::seal_text(state.description.as_deref())?)
+        .bind(&state.status)
+        .bind(&state.external_link)
+        .bind(state.created_by)
+        .bind(state.created_at)
+        .bind(state.updated_at)
+        .bind(state.client_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error writing projected task: {}", e);
+            ServiceError::DatabaseError("Failed to rebuild task".to_string())
+        })?;
+
+        sqlx::query("DELETE FROM task_teams WHERE task_id = $1")
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                log::error!("Database error clearing projected teams: {}", e);
+                ServiceError::DatabaseError("Failed to rebuild task".to_string())
+            })?;
+
+        sqlx::query(
+            "INSERT INTO task_teams (task_id, team_id)
+             SELECT $1, id FROM teams WHERE name = ANY($2)"
+        )
+        .bind(task_id)
+        .bind(&state.teams)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            log::error!("Database error writing projected teams: {}", e);
+            ServiceError::DatabaseError("Failed to rebuild task".to_string())
+        })?;
+    }
+
+    tx.commit().await
+        .map_err(|e| {
+            log::error!("Failed to commit transaction: {}", e);
+            ServiceError::DatabaseError("Transaction failed".to_string())
+        })?;
+
+    Ok(Some(state))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::test_db::TestDb;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn rebuilt_tasks_keep_the_time_of_their_last_event() {
+        let Some(test) = TestDb::create().await else { return };
+        let user_id = test.insert_user("alice", "member").await;
+        let task_id = test.insert_task("Draft", user_id).await;
+        let last_event: DateTime<Utc> = "2024-01-02T03:04:05Z".parse().expect("timestamp");
+        sqlx::query(
+            "INSERT INTO task_events (task_id, version, event_type, actor_id, data, created_at)
+             VALUES ($1, 1, $2, $3, jsonb_build_object('name', 'Draft', 'status', 'TO_DO', 'created_by', $3), $4 - INTERVAL '1 day'),
+                    ($1, 2, $5, $3, jsonb_build_object('status', 'DOING'), $4)"
+        )
+        .bind(task_id)
+        .bind(TASK_CREATED)
+        .bind(user_id)
+        .bind(last_event)
+        .bind(TASK_UPDATED)
+        .execute(&test.db.pool)
+        .await
+        .expect("insert events");
+
+        rebuild_projection(&test.db, task_id).await.expect("rebuild").expect("task has events");
+
+        let (status, updated_at): (String, DateTime<Utc>) = sqlx::query_as("SELECT status, updated_at FROM tasks WHERE id = $1")
+            .bind(task_id)
+            .fetch_one(&test.db.pool)
+            .await
+            .expect("projected task");
+        assert_eq!(status, "DOING");
+        assert_eq!(updated_at, last_event);
+
+        test.drop().await;
+    }
+}
@@ -0,0 +1,90 @@
+use sqlx::{PgConnection, Row};
+
+use crate::models::ids::TaskId;
+use crate::models::task::{task_key, TaskReference, TASK_KEY_PREFIX};
+use crate::utils::errors::ServiceError;
+
+// Mentions past this many in one description are ignored
+const MAX_LINKS: usize = 100;
+
+/// Task ids mentioned by key in `text`, e.g. 12 for `KAN-12` or `kan-12`,
+/// in order of first mention. Keys must stand alone: `XKAN-12` and
+/// `KAN-12b` are not mentions.
+pub fn find_keys(text: &str) -> Vec<TaskId> {
+    // ASCII lowercasing leaves every other character, and so every byte
+    // offset, unchanged
+    let text = text.to_ascii_lowercase();
+    let prefix = format!("{}-", TASK_KEY_PREFIX).to_ascii_lowercase();
+    let bytes = text.as_bytes();
+    let mut ids = Vec::new();
+
+    for (at, _) in text.match_indices(&prefix) {
+        if at > 0 && bytes[at - 1].is_ascii_alphanumeric() {
+            continue;
+        }
+        let digits_start = at + prefix.len();
+        let digits_end = digits_start + bytes[digits_start..].iter().take_while(|b| b.is_ascii_digit()).count();
+        if bytes.get(digits_end).is_some_and(|b| b.is_ascii_alphanumeric()) {
+            continue;
+        }
+
+        if let Ok(id) = text[digits_start..digits_end].parse::<i32>() {
+            let id = TaskId(id);
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+            if ids.len() == MAX_LINKS {
+                break;
+            }
+        }
+    }
+    ids
+}
+
+/// Point a task's links at the tasks its description mentions, inside the
+/// caller's transaction, and return them. Mentions of unknown tasks and of
+/// the task itself are dropped.
+pub async fn replace(
+    conn: &mut PgConnection,
+    task_id: TaskId,
+    description: Option<&str>,
+) -> Result<Vec<TaskReference>, ServiceError> {
+    sqlx::query("DELETE FROM task_links WHERE source_task_id = $1")
+        .bind(task_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| {
+            log::error!("Database error removing task links: {}", e);
+            ServiceError::DatabaseError("Failed to update task links".to_string())
+        })?;
+
+    let mentioned = description.map(find_keys).unwrap_or_default();
+    if mentioned.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let rows = sqlx::query(
+        "WITH linked AS (
+             INSERT INTO task_links (source_task_id, target_task_id)
+             SELECT $1, id FROM tasks WHERE id = ANY($2) AND id <> $1
+             RETURNING target_task_id
+         )
+         SELECT t.id, t.name, t.status FROM tasks t JOIN linked l ON l.target_task_id = t.id
+         ORDER BY t.id"
+    )
+    .bind(task_id)
+    .bind(&mentioned)
+    .fetch_all(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error storing task links: {}", e);
+        ServiceError::DatabaseError("Failed to update task links".to_string())
+    })?;
+
+    Ok(rows.iter()
+        .map(|row| {
+            let id: TaskId = row.get("id");
+            TaskReference { id, key: task_key(id), name: row.get("name"), status: row.get("status") }
+        })
+        .collect())
+}
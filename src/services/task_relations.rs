@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::file::TaskAttachmentSimple;
+use crate::models::ids::{TaskId, UserId};
+use crate::models::task::{task_key, TaskReference};
+use crate::utils::errors::ServiceError;
+
+/// Load team names for many tasks with a single query, keyed by task id
+pub async fn get_teams_for_tasks(db: &Database, task_ids: &[TaskId]) -> Result<HashMap<TaskId, Vec<String>>, ServiceError> {
+    let mut teams: HashMap<TaskId, Vec<String>> = HashMap::new();
+    if task_ids.is_empty() {
+        return Ok(teams);
+    }
+
+    let team_rows = sqlx::query(
+        "SELECT tt.task_id, t.name FROM teams t
+         JOIN task_teams tt ON t.id = tt.team_id
+         WHERE tt.task_id = ANY($1)"
+    )
+    .bind(task_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error getting teams for tasks: {}", e);
+        ServiceError::DatabaseError("Failed to query task teams".to_string())
+    })?;
+
+    for row in team_rows {
+        teams.entry(row.get("task_id")).or_default().push(row.get("name"));
+    }
+
+    Ok(teams)
+}
+
+/// Load attachments for many tasks with a single query, keyed by task id
+pub async fn get_attachments_for_tasks(db: &Database, task_ids: &[TaskId]) -> Result<HashMap<TaskId, Vec<TaskAttachmentSimple>>, ServiceError> {
+    let mut attachments: HashMap<TaskId, Vec<TaskAttachmentSimple>> = HashMap::new();
+    if task_ids.is_empty() {
+        return Ok(attachments);
+    }
+
+    let attachment_rows = sqlx::query(
+        "SELECT task_id, file_name, cloudinary_secure_url FROM task_attachments
+         WHERE task_id = ANY($1) AND processing_status NOT IN ('flagged', 'rejected')"
+    )
+    .bind(task_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error getting attachments for tasks: {}", e);
+        ServiceError::DatabaseError("Failed to query task attachments".to_string())
+    })?;
+
+    for row in attachment_rows {
+        attachments.entry(row.get("task_id")).or_default().push(TaskAttachmentSimple {
+            name: row.get("file_name"),
+            url: row.get("cloudinary_secure_url"),
+        });
+    }
+
+    Ok(attachments)
+}
+
+type LinkMap = HashMap<TaskId, Vec<TaskReference>>;
+
+/// Load the tasks each task mentions and the tasks it is mentioned in with
+/// a single query, keyed by task id
+pub async fn get_links_for_tasks(db: &Database, task_ids: &[TaskId]) -> Result<(LinkMap, LinkMap), ServiceError> {
+    let mut references = LinkMap::new();
+    let mut mentioned_in = LinkMap::new();
+    if task_ids.is_empty() {
+        return Ok((references, mentioned_in));
+    }
+
+    let link_rows = sqlx::query(
+        "SELECT TRUE AS outgoing, l.source_task_id AS task_id, t.id, t.name, t.status
+         FROM task_links l JOIN tasks t ON t.id = l.target_task_id
+         WHERE l.source_task_id = ANY($1)
+         UNION ALL
+         SELECT FALSE, l.target_task_id, t.id, t.name, t.status
+         FROM task_links l JOIN tasks t ON t.id = l.source_task_id
+         WHERE l.target_task_id = ANY($1)
+         ORDER BY id"
+    )
+    .bind(task_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error getting links for tasks: {}", e);
+        ServiceError::DatabaseError("Failed to query task links".to_string())
+    })?;
+
+    for row in link_rows {
+        let id: TaskId = row.get("id");
+        let reference = TaskReference { id, key: task_key(id), name: row.get("name"), status: row.get("status") };
+        let links = if row.get("outgoing") { &mut references } else { &mut mentioned_in };
+        links.entry(row.get("task_id")).or_default().push(reference);
+    }
+
+    Ok((references, mentioned_in))
+}
+
+/// Count votes for many tasks with a single query, keyed by task id; tasks
+/// without votes are left out
+pub async fn get_vote_counts_for_tasks(db: &Database, task_ids: &[TaskId]) -> Result<HashMap<TaskId, i64>, ServiceError> {
+    if task_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let vote_rows = sqlx::query(
+        "SELECT task_id, COUNT(*) AS votes FROM task_votes WHERE task_id = ANY($1) GROUP BY task_id"
+    )
+    .bind(task_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error counting votes for tasks: {}", e);
+        ServiceError::DatabaseError("Failed to query task votes".to_string())
+    })?;
+
+    Ok(vote_rows.iter().map(|row| (row.get("task_id"), row.get("votes"))).collect())
+}
+
+/// Which of the given tasks a user voted for, with a single query
+pub async fn get_voted_tasks(db: &Database, task_ids: &[TaskId], user_id: UserId) -> Result<HashSet<TaskId>, ServiceError> {
+    if task_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let voted_rows = sqlx::query("SELECT task_id FROM task_votes WHERE task_id = ANY($1) AND user_id = $2")
+        .bind(task_ids)
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error getting user votes: {}", e);
+            ServiceError::DatabaseError("Failed to query task votes".to_string())
+        })?;
+
+    Ok(voted_rows.iter().map(|row| row.get("task_id")).collect())
+}
+
+/// Which of the given tasks have an open SLA breach, with a single query
+pub async fn get_sla_breached_tasks(db: &Database, task_ids: &[TaskId]) -> Result<HashSet<TaskId>, ServiceError> {
+    if task_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let breach_rows = sqlx::query(
+        "SELECT DISTINCT task_id FROM sla_breaches WHERE task_id = ANY($1) AND resolved_at IS NULL"
+    )
+    .bind(task_ids)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error getting SLA breaches for tasks: {}", e);
+        ServiceError::DatabaseError("Failed to query SLA breaches".to_string())
+    })?;
+
+    Ok(breach_rows.iter().map(|row| row.get("task_id")).collect())
+}
@@ -0,0 +1,343 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use sqlx::Row;
+use sqlx::postgres::PgRow;
+
+use crate::Database;
+use crate::models::availability::Availability;
+use crate::models::file::TaskAttachmentSimple;
+use crate::models::ids::{TaskId, UserId};
+use crate::models::task::{task_key, TaskReference, TaskResponse};
+use crate::services::task_events::TaskState;
+use crate::services::{availability, encryption, task_relations};
+use crate::utils::errors::ServiceError;
+
+/// Builds `TaskResponse` values from task rows. Teams, attachments, away
+/// owners, task links, vote counts and SLA breaches come from maps keyed by
+/// task id that are loaded up front, so assembling a whole page costs six
+/// queries; a task missing from a map gets an empty list (or no absence, no
+/// votes, no breach). Rows read from `board_cards` carry all but the away
+/// owners and the viewer's votes themselves (see `for_cards`).
+#[derive(Debug, Default)]
+pub struct TaskResponseAssembler {
+    teams: HashMap<TaskId, Vec<String>>,
+    attachments: HashMap<TaskId, Vec<TaskAttachmentSimple>>,
+    owners_away: HashMap<TaskId, Availability>,
+    references: HashMap<TaskId, Vec<TaskReference>>,
+    mentioned_in: HashMap<TaskId, Vec<TaskReference>>,
+    votes: HashMap<TaskId, i64>,
+    sla_breached: HashSet<TaskId>,
+    /// Tasks the caller voted for, once loaded with `with_votes_of`
+    voted: Option<HashSet<TaskId>>,
+}
+
+impl TaskResponseAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load teams, attachments, away owners, links, vote counts and SLA
+    /// breaches for the given tasks in one query each
+    pub async fn preload(db: &Database, task_ids: &[TaskId]) -> Result<Self, ServiceError> {
+        let (references, mentioned_in) = task_relations::get_links_for_tasks(db, task_ids).await?;
+        let mut assembler = Self::new()
+            .with_teams(task_relations::get_teams_for_tasks(db, task_ids).await?)
+            .with_attachments(task_relations::get_attachments_for_tasks(db, task_ids).await?)
+            .with_owners_away(availability::away_owners_for_tasks(db, task_ids).await?);
+        assembler.references = references;
+        assembler.mentioned_in = mentioned_in;
+        assembler.votes = task_relations::get_vote_counts_for_tasks(db, task_ids).await?;
+        assembler.sla_breached = task_relations::get_sla_breached_tasks(db, task_ids).await?;
+        Ok(assembler)
+    }
+
+    /// Also load which of the tasks `viewer` voted for, so responses carry
+    /// `voted_by_me`
+    pub async fn with_votes_of(mut self, db: &Database, task_ids: &[TaskId], viewer: UserId) -> Result<Self, ServiceError> {
+        self.voted = Some(task_relations::get_voted_tasks(db, task_ids, viewer).await?);
+        Ok(self)
+    }
+
+    pub fn with_teams(mut self, teams: HashMap<TaskId, Vec<String>>) -> Self {
+        self.teams.extend(teams);
+        self
+    }
+
+    pub fn with_attachments(mut self, attachments: HashMap<TaskId, Vec<TaskAttachmentSimple>>) -> Self {
+        self.attachments.extend(attachments);
+        self
+    }
+
+    pub fn with_owners_away(mut self, owners_away: HashMap<TaskId, Availability>) -> Self {
+        self.owners_away.extend(owners_away);
+        self
+    }
+
+    /// Set the teams for one task, replacing anything preloaded for it
+    pub fn with_task_teams(mut self, task_id: TaskId, teams: Vec<String>) -> Self {
+        self.teams.insert(task_id, teams);
+        self
+    }
+
+    /// Build the response for a row selecting the standard task columns
+    pub fn assemble(&mut self, row: &PgRow) -> TaskResponse {
+        let task_id: TaskId = row.get("id");
+        TaskResponse {
+            id: task_id,
+            key: task_key(task_id),
+            name: row.get("name"),
+            description: encryption::open_text(row.get("description")),
+            status: row.get("status"),
+            external_link: row.get("external_link"),
+            created_by: row.get("created_by"),
+            client_id: row.get("client_id"),
+            teams: self.teams.remove(&task_id).unwrap_or_default(),
+            attachments: self.attachments.remove(&task_id).unwrap_or_default(),
+            owner_away: self.owners_away.remove(&task_id),
+            references: self.references.remove(&task_id).unwrap_or_default(),
+            mentioned_in: self.mentioned_in.remove(&task_id).unwrap_or_default(),
+            votes: self.votes.remove(&task_id).unwrap_or_default(),
+            voted_by_me: self.voted.as_ref().map(|voted| voted.contains(&task_id)),
+            sla_breached: self.sla_breached.contains(&task_id),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+
+    pub fn assemble_all(mut self, rows: &[PgRow]) -> Vec<TaskResponse> {
+        rows.iter().map(|row| self.assemble(row)).collect()
+    }
+
+    /// Build the response for a task folded from its event stream
+    pub fn assemble_state(&mut self, task_id: TaskId, state: TaskState) -> TaskResponse {
+        TaskResponse {
+            id: task_id,
+            key: task_key(task_id),
+            name: state.name,
+            description: state.description,
+            status: state.status,
+            external_link: state.external_link,
+            created_by: state.created_by,
+            client_id: state.client_id,
+            teams: self.teams.remove(&task_id).unwrap_or_default(),
+            attachments: self.attachments.remove(&task_id).unwrap_or_default(),
+            owner_away: self.owners_away.remove(&task_id),
+            references: self.references.remove(&task_id).unwrap_or_default(),
+            mentioned_in: self.mentioned_in.remove(&task_id).unwrap_or_default(),
+            votes: self.votes.remove(&task_id).unwrap_or_default(),
+            voted_by_me: self.voted.as_ref().map(|voted| voted.contains(&task_id)),
+            sla_breached: self.sla_breached.contains(&task_id),
+            created_at: state.created_at.unwrap_or_default(),
+            updated_at: state.updated_at.unwrap_or_default(),
+        }
+    }
+
+    /// Load what a board card does not carry for the given tasks: away
+    /// owners, which depend on the time of the request, and the tasks
+    /// `viewer` voted for. Rows are then built with `assemble_card`.
+    pub async fn for_cards(db: &Database, task_ids: &[TaskId], viewer: UserId) -> Result<Self, ServiceError> {
+        Self::new()
+            .with_owners_away(availability::away_owners_for_tasks(db, task_ids).await?)
+            .with_votes_of(db, task_ids, viewer).await
+    }
+
+    /// Build the response for a row that carries its own relations, as a
+    /// `teams` text array, an `attachments` JSON array of {name, url},
+    /// `links_to` / `linked_from` JSON arrays of {id, name, status}, a
+    /// `votes` count and an `sla_breached` flag. Fails when a JSON column
+    /// does not hold what it should, rather than leaving its entries out.
+    pub fn assemble_aggregated(row: &PgRow) -> Result<TaskResponse, ServiceError> {
+        Self::new().assemble_card(row)
+    }
+
+    /// Like `assemble_aggregated`, adding the away owner and the viewer's
+    /// vote when they were loaded with `for_cards`
+    pub fn assemble_card(&mut self, row: &PgRow) -> Result<TaskResponse, ServiceError> {
+        let task_id: TaskId = row.get("id");
+        let attachments: Vec<TaskAttachmentSimple> = aggregated(row, task_id, "attachments")?;
+
+        self.teams.insert(task_id, row.get("teams"));
+        self.attachments.insert(task_id, attachments);
+        self.references.insert(task_id, aggregated_links(row, task_id, "links_to")?);
+        self.mentioned_in.insert(task_id, aggregated_links(row, task_id, "linked_from")?);
+        self.votes.insert(task_id, row.get("votes"));
+        if row.get::<bool, _>("sla_breached") {
+            self.sla_breached.insert(task_id);
+        }
+        Ok(self.assemble(row))
+    }
+}
+
+#[derive(Deserialize)]
+struct AggregatedLink {
+    id: TaskId,
+    name: String,
+    status: String,
+}
+
+fn aggregated<T: DeserializeOwned>(row: &PgRow, task_id: TaskId, column: &str) -> Result<T, ServiceError> {
+    serde_json::from_value(row.get(column)).map_err(|e| {
+        log::error!("Malformed {} of task {}: {}", column, task_id, e);
+        ServiceError::InternalError("Failed to read task relations".to_string())
+    })
+}
+
+fn aggregated_links(row: &PgRow, task_id: TaskId, column: &str) -> Result<Vec<TaskReference>, ServiceError> {
+    let links: Vec<AggregatedLink> = aggregated(row, task_id, column)?;
+    Ok(links.into_iter()
+        .map(|link| TaskReference { id: link.id, key: task_key(link.id), name: link.name, status: link.status })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::task_writes;
+    use crate::utils::test_db::TestDb;
+
+    const COLUMNS: &str = "tk.id, tk.name, tk.description, tk.status, tk.external_link, tk.client_id, tk.created_by,
+                           tk.created_at, tk.updated_at";
+
+    async fn seal_description(test: &TestDb, task_id: TaskId, text: &str) {
+        sqlx::query("UPDATE tasks SET description = $2 WHERE id = $1")
+            .bind(task_id)
+            .bind(encryption::seal_text(Some(text)).expect("seal"))
+            .execute(&test.db.pool)
+            .await
+            .expect("set description");
+    }
+
+    async fn vote(test: &TestDb, task_id: TaskId, user_id: UserId) {
+        sqlx::query("INSERT INTO task_votes (task_id, user_id) VALUES ($1, $2)")
+            .bind(task_id)
+            .bind(user_id)
+            .execute(&test.db.pool)
+            .await
+            .expect("vote");
+    }
+
+    async fn plain_row(test: &TestDb, task_id: TaskId) -> PgRow {
+        sqlx::query(&format!("SELECT {} FROM tasks tk WHERE tk.id = $1", COLUMNS))
+            .bind(task_id)
+            .fetch_one(&test.db.pool)
+            .await
+            .expect("task row")
+    }
+
+    async fn card_row(test: &TestDb, task_id: TaskId) -> PgRow {
+        sqlx::query(&format!(
+            "SELECT {}, c.teams, c.attachments, c.links_to, c.linked_from, c.votes, c.sla_breached
+             FROM tasks tk JOIN board_cards c ON c.task_id = tk.id WHERE tk.id = $1",
+            COLUMNS
+        ))
+        .bind(task_id)
+        .fetch_one(&test.db.pool)
+        .await
+        .expect("card row")
+    }
+
+    fn json(task: &TaskResponse) -> serde_json::Value {
+        serde_json::to_value(task).expect("serialize task")
+    }
+
+    #[actix_web::test]
+    async fn aggregated_row_assembles_like_preloaded_row() {
+        let Some(test) = TestDb::create().await else { return };
+        encryption::init_for_tests();
+        let user = test.insert_user("owner", "member").await;
+        let task = test.insert_task("task", user).await;
+        let other = test.insert_task("other", user).await;
+        test.relate(task, other, user).await;
+        seal_description(&test, task, "secret").await;
+
+        let mut plain = TaskResponseAssembler::preload(&test.db, &[task]).await.expect("preload");
+        let plain = plain.assemble(&plain_row(&test, task).await);
+        let mut conn = test.db.pool.acquire().await.expect("connection");
+        let aggregated = task_writes::fetch(&mut conn, task).await.expect("fetch");
+        drop(conn);
+
+        assert_eq!(json(&aggregated), json(&plain));
+        assert_eq!(plain.description.as_deref(), Some("secret"));
+        assert_eq!(plain.teams.len(), 1);
+        assert_eq!(plain.attachments.len(), 1);
+        assert_eq!(plain.references.len(), 1);
+        assert_eq!(plain.votes, 1);
+        assert!(plain.sla_breached);
+        assert_eq!(plain.voted_by_me, None);
+        test.drop().await;
+    }
+
+    #[actix_web::test]
+    async fn card_row_assembles_like_preloaded_row_with_votes() {
+        let Some(test) = TestDb::create().await else { return };
+        let owner = test.insert_user("owner", "member").await;
+        let viewer = test.insert_user("viewer", "member").await;
+        let task = test.insert_task("task", owner).await;
+        let other = test.insert_task("other", owner).await;
+        test.relate(task, other, viewer).await;
+        sqlx::query("UPDATE users SET availability_status = 'out_of_office' WHERE id = $1")
+            .bind(owner)
+            .execute(&test.db.pool)
+            .await
+            .expect("set owner away");
+
+        let mut plain = TaskResponseAssembler::preload(&test.db, &[task]).await.expect("preload")
+            .with_votes_of(&test.db, &[task], viewer).await.expect("votes");
+        let plain = plain.assemble(&plain_row(&test, task).await);
+        let mut cards = TaskResponseAssembler::for_cards(&test.db, &[task], viewer).await.expect("for cards");
+        let card = cards.assemble_card(&card_row(&test, task).await).expect("card");
+
+        assert_eq!(json(&card), json(&plain));
+        assert_eq!(card.voted_by_me, Some(true));
+        assert_eq!(card.owner_away.map(|away| away.user_id), Some(owner));
+        test.drop().await;
+    }
+
+    #[actix_web::test]
+    async fn with_votes_of_marks_only_the_viewers_votes() {
+        let Some(test) = TestDb::create().await else { return };
+        let viewer = test.insert_user("viewer", "member").await;
+        let someone = test.insert_user("someone", "member").await;
+        let voted = test.insert_task("voted", viewer).await;
+        let not_voted = test.insert_task("not voted", viewer).await;
+        vote(&test, voted, viewer).await;
+        vote(&test, not_voted, someone).await;
+        let ids = [voted, not_voted];
+
+        let mut assembler = TaskResponseAssembler::preload(&test.db, &ids).await.expect("preload")
+            .with_votes_of(&test.db, &ids, viewer).await.expect("votes");
+        let voted = assembler.assemble(&plain_row(&test, voted).await);
+        let not_voted = assembler.assemble(&plain_row(&test, not_voted).await);
+        assert_eq!((voted.votes, voted.voted_by_me), (1, Some(true)));
+        assert_eq!((not_voted.votes, not_voted.voted_by_me), (1, Some(false)));
+
+        let mut without = TaskResponseAssembler::preload(&test.db, &ids).await.expect("preload");
+        assert_eq!(without.assemble(&plain_row(&test, ids[0]).await).voted_by_me, None);
+        test.drop().await;
+    }
+
+    #[test]
+    fn assemble_state_never_decrypts() {
+        encryption::init_for_tests();
+        let sealed = encryption::seal_text(Some("secret")).expect("seal");
+        assert_ne!(sealed.as_deref(), Some("secret"));
+        let state = TaskState {
+            name: "task".to_string(),
+            description: sealed.clone(),
+            status: "TO_DO".to_string(),
+            created_by: UserId(1),
+            ..TaskState::default()
+        };
+
+        let task = TaskResponseAssembler::new()
+            .with_task_teams(TaskId(1), vec!["Backend".to_string()])
+            .assemble_state(TaskId(1), state);
+        assert_eq!(task.description, sealed);
+        assert_eq!(task.teams, vec!["Backend".to_string()]);
+        assert!(task.attachments.is_empty() && task.references.is_empty() && task.mentioned_in.is_empty());
+        assert_eq!((task.votes, task.voted_by_me, task.sla_breached), (0, None, false));
+        assert!(task.owner_away.is_none());
+    }
+}
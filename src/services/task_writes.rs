@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use sqlx::{PgConnection, Row};
+
+use crate::models::ids::{TaskId, TeamId, UserId};
+use crate::models::task::{CreateTaskRequest, TaskResponse, UpdateTaskRequest};
+use crate::services::{column_policies, encryption, outbox, scripts, task_events, task_links};
+use crate::services::task_response::TaskResponseAssembler;
+use crate::utils::errors::ServiceError;
+use crate::utils::sql::Patch;
+
+/// Outbox event for an update that moved a task to another status; the
+/// payload has the status `from` and `to`, and `script` when an automation
+/// script made the change
+pub const TASK_STATUS_CHANGED: &str = "task.status_changed";
+
+/// Lock a task and return its status if the update is going to set one, for
+/// `enqueue_status_change` to compare against afterwards
+pub async fn status_before_update(
+    conn: &mut PgConnection,
+    task_id: TaskId,
+    update: &UpdateTaskRequest,
+) -> Result<Option<String>, ServiceError> {
+    if update.status.is_none() {
+        return Ok(None);
+    }
+    sqlx::query_scalar("SELECT status FROM tasks WHERE id = $1 FOR UPDATE")
+        .bind(task_id)
+        .fetch_optional(conn)
+        .await
+        .map_err(|e| {
+            log::error!("Database error reading task status: {}", e);
+            ServiceError::DatabaseError("Failed to update task".to_string())
+        })
+}
+
+/// Enqueue `TASK_STATUS_CHANGED` if the update moved the task away from the
+/// status returned by `status_before_update`. `script` names the automation
+/// script behind the update, if any.
+pub async fn enqueue_status_change(
+    conn: &mut PgConnection,
+    task_id: TaskId,
+    before: Option<String>,
+    update: &UpdateTaskRequest,
+    script: Option<&str>,
+) -> Result<(), ServiceError> {
+    match (before, &update.status) {
+        (Some(from), Some(to)) if &from != to => {
+            let mut payload = serde_json::json!({
+                "id": task_id,
+                "from": from,
+                "to": to,
+            });
+            if let Some(script) = script {
+                payload["script"] = serde_json::json!(script);
+            }
+            outbox::enqueue(conn, "task", task_id.0, TASK_STATUS_CHANGED, &payload).await
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Read a task with its teams, attachments and links through the caller's
+/// transaction, seeing its own uncommitted writes
+pub async fn fetch(conn: &mut PgConnection, task_id: TaskId) -> Result<TaskResponse, ServiceError> {
+    let row = sqlx::query(
+        "SELECT tk.id, tk.name, tk.description, tk.status, tk.external_link, tk.client_id, tk.created_by,
+                tk.created_at, tk.updated_at,
+                ARRAY(SELECT t.name FROM teams t JOIN task_teams tt ON t.id = tt.team_id
+                      WHERE tt.task_id = tk.id) AS teams,
+                COALESCE((SELECT json_agg(json_build_object('name', a.file_name, 'url', a.cloudinary_secure_url))
+                          FROM task_attachments a
+                          WHERE a.task_id = tk.id AND a.processing_status NOT IN ('flagged', 'rejected')), '[]'::json) AS attachments,
+                COALESCE((SELECT json_agg(json_build_object('id', t.id, 'name', t.name, 'status', t.status) ORDER BY t.id)
+                          FROM task_links l JOIN tasks t ON t.id = l.target_task_id
+                          WHERE l.source_task_id = tk.id), '[]'::json) AS links_to,
+                COALESCE((SELECT json_agg(json_build_object('id', t.id, 'name', t.name, 'status', t.status) ORDER BY t.id)
+                          FROM task_links l JOIN tasks t ON t.id = l.source_task_id
+                          WHERE l.target_task_id = tk.id), '[]'::json) AS linked_from,
+                (SELECT COUNT(*) FROM task_votes v WHERE v.task_id = tk.id) AS votes,
+                EXISTS(SELECT 1 FROM sla_breaches b WHERE b.task_id = tk.id AND b.resolved_at IS NULL) AS sla_breached
+         FROM tasks tk WHERE tk.id = $1"
+    )
+    .bind(task_id)
+    .fetch_one(conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error fetching updated task: {}", e);
+        ServiceError::DatabaseError("Failed to fetch task".to_string())
+    })?;
+
+    TaskResponseAssembler::assemble_aggregated(&row)
+}
+
+/// Look up the ids of teams by name, in the order given, through the
+/// caller's transaction; an unknown name is a validation error
+pub async fn team_ids(conn: &mut PgConnection, team_names: &[String]) -> Result<Vec<TeamId>, ServiceError> {
+    if team_names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rows = sqlx::query("SELECT id, name FROM teams WHERE name = ANY($1)")
+        .bind(team_names)
+        .fetch_all(conn)
+        .await
+        .map_err(|e| {
+            log::error!("Database error getting teams: {}", e);
+            ServiceError::DatabaseError("Failed to query team".to_string())
+        })?;
+    let ids: HashMap<String, TeamId> = rows.iter().map(|row| (row.get("name"), row.get("id"))).collect();
+
+    team_names.iter()
+        .map(|name| ids.get(name).copied().ok_or_else(|| {
+            ServiceError::ValidationError(format!("Team '{}' not found", name)).with_code("TEAM_NOT_FOUND")
+        }))
+        .collect()
+}
+
+/// Insert a task owned by `owner` inside the caller's transaction, assign
+/// its teams and record the creation event. Input must already be
+/// validated, team names resolved to `team_ids`. A `client_id` that is
+/// already taken is a conflict.
+pub async fn insert(
+    conn: &mut PgConnection,
+    task: &CreateTaskRequest,
+    owner: UserId,
+    actor_id: UserId,
+    team_ids: &[TeamId],
+) -> Result<TaskResponse, ServiceError> {
+    insert_once(conn, task, owner, actor_id, team_ids).await?
+        .ok_or_else(|| ServiceError::Conflict("A task with this client id already exists".to_string()).with_code("CLIENT_ID_TAKEN"))
+}
+
+/// Like `insert`, except that when a task with the same `client_id` exists
+/// nothing is written and None is returned: the client is retrying a create
+/// that already went through
+pub async fn insert_once(
+    conn: &mut PgConnection,
+    task: &CreateTaskRequest,
+    owner: UserId,
+    actor_id: UserId,
+    team_ids: &[TeamId],
+) -> Result<Option<TaskResponse>, ServiceError> {
+    let stored_description = encryption::seal_text(task.description.as_deref())?;
+    let task_row = sqlx::query(
+        "INSERT INTO tasks (name, description, status, external_link, client_id, created_by)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (client_id) DO NOTHING
+         RETURNING id, name, description, status, external_link, client_id, created_by, created_at, updated_at"
+    )
+    .bind(&task.name)
+    .bind(&stored_description)
+    .bind(&task.status)
+    .bind(&task.external_link)
+    .bind(task.client_id)
+    .bind(owner)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| {
+        log::error!("Database error creating task: {}", e);
+        ServiceError::DatabaseError("Failed to create task".to_string())
+    })?;
+    let Some(task_row) = task_row else {
+        return Ok(None);
+    };
+
+    let task_id: TaskId = task_row.get("id");
+    if !team_ids.is_empty() {
+        sqlx::query("INSERT INTO task_teams (task_id, team_id) SELECT $1, UNNEST($2::int[])")
+            .bind(task_id)
+            .bind(team_ids)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| {
+                log::error!("Database error assigning teams: {}", e);
+                ServiceError::DatabaseError("Failed to assign team".to_string())
+            })?;
+    }
+
+    let mut task_response = TaskResponseAssembler::new()
+        .with_task_teams(task_id, task.teams.clone().unwrap_or_default())
+        .assemble(&task_row);
+    task_response.references = task_links::replace(&mut *conn, task_id, task_response.description.as_deref()).await?;
+    scripts::validate(&mut *conn, task_events::TASK_CREATED, &task_response, None).await?;
+
+    task_events::append(conn, task_id, task_events::TASK_CREATED, actor_id, &serde_json::json!({
+        "name": task_response.name,
+        "description": task_response.description,
+        "status": task_response.status,
+        "external_link": task_response.external_link,
+        "created_by": task_response.created_by,
+        "client_id": task_response.client_id,
+        "teams": task_response.teams,
+    })).await?;
+    outbox::enqueue(conn, "task", task_id.0, "task.created", &task_response).await?;
+
+    Ok(Some(task_response))
+}
+
+/// The task columns an update request writes, bumping `updated_at`; an empty
+/// external link clears it
+pub fn patch(update: &UpdateTaskRequest, stored_description: Option<&String>) -> Patch {
+    let patch = Patch::new("tasks")
+        .set_raw("updated_at", "NOW()")
+        .set_opt("name", update.name.as_ref())
+        .set_opt("description", stored_description)
+        .set_opt("status", update.status.as_ref());
+    match update.external_link.as_deref() {
+        Some("") => patch.set_null("external_link"),
+        link => patch.set_opt("external_link", link),
+    }
+}
+
+/// Apply a partial update to a task inside the caller's transaction, append
+/// the matching task event and run the policy of the column it moved into.
+/// Team names must already be validated; `script` is passed on to
+/// `enqueue_status_change`.
+pub async fn apply_update(
+    conn: &mut PgConnection,
+    task_id: TaskId,
+    actor_id: UserId,
+    update: &UpdateTaskRequest,
+    team_ids: Option<&[TeamId]>,
+    script: Option<&str>,
+) -> Result<(), ServiceError> {
+    let status_before = status_before_update(&mut *conn, task_id, update).await?;
+
+    let stored_description = encryption::seal_text(update.description.as_deref())?;
+    if let Some(mut query) = patch(update, stored_description.as_ref()).build("id", task_id, None) {
+        query.build()
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| {
+                log::error!("Database error updating task: {}", e);
+                ServiceError::DatabaseError("Failed to update task".to_string())
+            })?;
+    }
+
+    if update.description.is_some() {
+        task_links::replace(&mut *conn, task_id, update.description.as_deref()).await?;
+    }
+
+    if let Some(team_ids) = team_ids {
+        sqlx::query("DELETE FROM task_teams WHERE task_id = $1")
+            .bind(task_id)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| {
+                log::error!("Database error removing team assignments: {}", e);
+                ServiceError::DatabaseError("Failed to remove team assignments".to_string())
+            })?;
+
+        sqlx::query("INSERT INTO task_teams (task_id, team_id) SELECT $1, UNNEST($2::int[])")
+            .bind(task_id)
+            .bind(team_ids)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| {
+                log::error!("Database error assigning teams: {}", e);
+                ServiceError::DatabaseError("Failed to assign team".to_string())
+            })?;
+    }
+
+    task_events::append(&mut *conn, task_id, task_events::TASK_UPDATED, actor_id, &changed_fields(update)).await?;
+    column_policies::apply(&mut *conn, task_id, actor_id, status_before.as_deref(), update).await?;
+    enqueue_status_change(conn, task_id, status_before, update, script).await
+}
+
+/// The subset of fields an update request touches, as recorded in task events
+pub fn changed_fields(update: &UpdateTaskRequest) -> serde_json::Map<String, serde_json::Value> {
+    let mut changes = serde_json::Map::new();
+    if let Some(ref name) = update.name {
+        changes.insert("name".to_string(), serde_json::json!(name));
+    }
+    if let Some(ref description) = update.description {
+        changes.insert("description".to_string(), serde_json::json!(description));
+    }
+    if let Some(ref status) = update.status {
+        changes.insert("status".to_string(), serde_json::json!(status));
+    }
+    if let Some(ref external_link) = update.external_link {
+        let external_link = Some(external_link).filter(|link| !link.is_empty());
+        changes.insert("external_link".to_string(), serde_json::json!(external_link));
+    }
+    if let Some(ref teams) = update.teams {
+        changes.insert("teams".to_string(), serde_json::json!(teams));
+    }
+    changes
+}
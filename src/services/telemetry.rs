@@ -0,0 +1,91 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use sqlx::Row;
+
+use crate::config::AppConfig;
+use crate::Database;
+use crate::utils::boot_report;
+use crate::utils::errors::ServiceError;
+
+/// What an opted-in instance reports. Counts are rounded down to a power of
+/// ten so the report says how big an installation is, not who it belongs to.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub version: &'static str,
+    pub features: Vec<&'static str>,
+    pub users: &'static str,
+    pub teams: &'static str,
+    pub tasks: &'static str,
+    pub attachments: &'static str,
+}
+
+/// Periodically send a `Report` to the configured endpoint. Does nothing
+/// unless telemetry is enabled.
+pub fn spawn_reporter(db: Arc<Database>, config: &AppConfig) {
+    let Some(endpoint) = config.telemetry_endpoint.clone() else {
+        return;
+    };
+    let features = boot_report::enabled_features(config);
+    let interval = Duration::from_secs(config.telemetry_interval_secs);
+
+    tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .user_agent("kanban-be")
+            .build()
+            .expect("HTTP client configuration is valid");
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let report = match collect(&db, features.clone()).await {
+                Ok(report) => report,
+                Err(e) => {
+                    log::warn!("Telemetry skipped: {}", e);
+                    continue;
+                }
+            };
+            // Failures are only worth a debug line; nobody depends on this
+            match client.post(&endpoint).json(&report).send().await.and_then(|res| res.error_for_status()) {
+                Ok(_) => log::debug!("Telemetry sent to {}", endpoint),
+                Err(e) => log::debug!("Telemetry to {} failed: {}", endpoint, e),
+            }
+        }
+    });
+}
+
+async fn collect(db: &Database, features: Vec<&'static str>) -> Result<Report, ServiceError> {
+    let row = sqlx::query(
+        "SELECT (SELECT COUNT(*) FROM users WHERE is_active) AS users,
+                (SELECT COUNT(*) FROM teams) AS teams,
+                (SELECT COUNT(*) FROM tasks) AS tasks,
+                (SELECT COUNT(*) FROM task_attachments) AS attachments"
+    )
+    .fetch_one(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error collecting telemetry: {}", e);
+        ServiceError::DatabaseError("Failed to collect telemetry".to_string())
+    })?;
+
+    Ok(Report {
+        version: env!("CARGO_PKG_VERSION"),
+        features,
+        users: bucket(row.get("users")),
+        teams: bucket(row.get("teams")),
+        tasks: bucket(row.get("tasks")),
+        attachments: bucket(row.get("attachments")),
+    })
+}
+
+fn bucket(count: i64) -> &'static str {
+    match count {
+        i64::MIN..=0 => "0",
+        1..=9 => "1-9",
+        10..=99 => "10-99",
+        100..=999 => "100-999",
+        1_000..=9_999 => "1000-9999",
+        _ => "10000+",
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::http::StatusCode;
+use sqlx::Row;
+
+use crate::Database;
+use crate::models::ids::UserId;
+use crate::models::usage::UsageEntry;
+use crate::utils::errors::ServiceError;
+
+// Who made a request: the user, and the API key when one was used
+type Caller = (UserId, Option<i32>);
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Counters {
+    requests: i64,
+    client_errors: i64,
+    server_errors: i64,
+}
+
+impl Counters {
+    fn add(&mut self, other: Counters) {
+        self.requests += other.requests;
+        self.client_errors += other.client_errors;
+        self.server_errors += other.server_errors;
+    }
+}
+
+// Counts since the last flush. Requests only touch this map; the flusher
+// moves it into today's rows of `api_usage`.
+static PENDING: Mutex<BTreeMap<Caller, Counters>> = Mutex::new(BTreeMap::new());
+
+/// Count one authenticated request by its response status
+pub fn record(user_id: UserId, api_key_id: Option<i32>, status: StatusCode) {
+    let counters = Counters {
+        requests: 1,
+        client_errors: status.is_client_error() as i64,
+        server_errors: status.is_server_error() as i64,
+    };
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    pending.entry((user_id, api_key_id)).or_default().add(counters);
+}
+
+/// Periodically add the pending counts to the daily usage table
+pub fn spawn_flusher(db: Arc<Database>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = flush(&db).await {
+                log::error!("Usage flush failed: {}", e);
+            }
+        }
+    });
+}
+
+async fn flush(db: &Database) -> Result<(), ServiceError> {
+    let batch = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let mut user_ids = Vec::with_capacity(batch.len());
+    let mut api_key_ids = Vec::with_capacity(batch.len());
+    let mut requests = Vec::with_capacity(batch.len());
+    let mut client_errors = Vec::with_capacity(batch.len());
+    let mut server_errors = Vec::with_capacity(batch.len());
+    for ((user_id, api_key_id), counters) in &batch {
+        user_ids.push(user_id.0);
+        api_key_ids.push(*api_key_id);
+        requests.push(counters.requests);
+        client_errors.push(counters.client_errors);
+        server_errors.push(counters.server_errors);
+    }
+
+    // Users and keys deleted since the requests are skipped by the joins
+    let result = sqlx::query(
+        "INSERT INTO api_usage (day, user_id, api_key_id, requests, client_errors, server_errors)
+         SELECT (NOW() AT TIME ZONE 'UTC')::date, b.user_id, b.api_key_id, b.requests, b.client_errors, b.server_errors
+         FROM UNNEST($1::int[], $2::int[], $3::bigint[], $4::bigint[], $5::bigint[])
+              AS b(user_id, api_key_id, requests, client_errors, server_errors)
+         JOIN users u ON u.id = b.user_id
+         LEFT JOIN api_keys k ON k.id = b.api_key_id
+         WHERE b.api_key_id IS NULL OR k.id IS NOT NULL
+         ON CONFLICT (day, user_id, COALESCE(api_key_id, 0)) DO UPDATE SET
+            requests = api_usage.requests + EXCLUDED.requests,
+            client_errors = api_usage.client_errors + EXCLUDED.client_errors,
+            server_errors = api_usage.server_errors + EXCLUDED.server_errors"
+    )
+    .bind(&user_ids)
+    .bind(&api_key_ids)
+    .bind(&requests)
+    .bind(&client_errors)
+    .bind(&server_errors)
+    .execute(&db.pool)
+    .await;
+
+    if let Err(e) = result {
+        // Keep the counts for the next attempt
+        let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+        for (caller, counters) in batch {
+            pending.entry(caller).or_default().add(counters);
+        }
+        log::error!("Database error recording usage: {}", e);
+        return Err(ServiceError::DatabaseError("Failed to record usage".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Usage per user and API key over the last `days` days (today included),
+/// busiest first; only one user's when `user_id` is given
+pub async fn summary(db: &Database, days: i64, user_id: Option<UserId>) -> Result<Vec<UsageEntry>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT a.user_id, u.username, a.api_key_id, k.name AS api_key_name,
+                SUM(a.requests)::bigint AS requests,
+                SUM(a.client_errors)::bigint AS client_errors,
+                SUM(a.server_errors)::bigint AS server_errors
+         FROM api_usage a
+         JOIN users u ON u.id = a.user_id
+         LEFT JOIN api_keys k ON k.id = a.api_key_id
+         WHERE a.day > (NOW() AT TIME ZONE 'UTC')::date - $1::int
+           AND ($2::int IS NULL OR a.user_id = $2)
+         GROUP BY a.user_id, u.username, a.api_key_id, k.name
+         ORDER BY requests DESC, a.user_id, a.api_key_id NULLS FIRST"
+    )
+    .bind(days as i32)
+    .bind(user_id)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading usage: {}", e);
+        ServiceError::DatabaseError("Failed to load usage".to_string())
+    })?;
+
+    Ok(rows.iter().map(|row| {
+        let requests: i64 = row.get("requests");
+        let client_errors: i64 = row.get("client_errors");
+        let server_errors: i64 = row.get("server_errors");
+        UsageEntry {
+            user_id: row.get("user_id"),
+            username: row.get("username"),
+            api_key_id: row.get("api_key_id"),
+            api_key_name: row.get("api_key_name"),
+            requests,
+            client_errors,
+            server_errors,
+            error_rate: if requests > 0 { (client_errors + server_errors) as f64 / requests as f64 } else { 0.0 },
+        }
+    }).collect())
+}
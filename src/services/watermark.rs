@@ -0,0 +1,55 @@
+use uuid::Uuid;
+
+use crate::utils::errors::ServiceError;
+
+/// Whether downloads of this type can be watermarked
+pub fn supports(mime_type: &str) -> bool {
+    mime_type == "application/pdf" || mime_type.starts_with("image/")
+}
+
+/// Stamp `data` with `text` by running the configured watermark command as
+/// `<command> <input> <output> <text>`. Any failure is an error: on a
+/// confidential board an unstamped copy must never be served instead.
+pub async fn apply(command: &str, data: &[u8], extension: &str, text: &str) -> Result<Vec<u8>, ServiceError> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next()
+        .ok_or_else(|| ServiceError::InternalError("WATERMARK_COMMAND is empty".to_string()))?;
+
+    let work_id = Uuid::new_v4();
+    let input = std::env::temp_dir().join(format!("watermark_{}_in.{}", work_id, extension));
+    let output = std::env::temp_dir().join(format!("watermark_{}_out.{}", work_id, extension));
+
+    let result = async {
+        tokio::fs::write(&input, data).await.map_err(|e| {
+            log::error!("Failed to write watermark input: {}", e);
+            ServiceError::InternalError("Failed to watermark file".to_string())
+        })?;
+
+        let status = tokio::process::Command::new(program)
+            .args(parts)
+            .arg(&input)
+            .arg(&output)
+            .arg(text)
+            .status()
+            .await
+            .map_err(|e| {
+                log::error!("Failed to run watermark command: {}", e);
+                ServiceError::InternalError("Failed to watermark file".to_string())
+            })?;
+
+        if !status.success() {
+            log::error!("Watermark command exited with {}", status);
+            return Err(ServiceError::InternalError("Failed to watermark file".to_string()));
+        }
+
+        tokio::fs::read(&output).await.map_err(|e| {
+            log::error!("Failed to read watermark output: {}", e);
+            ServiceError::InternalError("Failed to watermark file".to_string())
+        })
+    }.await;
+
+    let _ = tokio::fs::remove_file(&input).await;
+    let _ = tokio::fs::remove_file(&output).await;
+
+    result
+}
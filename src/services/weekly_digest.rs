@@ -0,0 +1,207 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use chrono::{DateTime, Datelike, Duration, NaiveTime, Utc, Weekday};
+use serde_json::json;
+use sqlx::Row;
+use tera::{Context, Tera};
+
+use crate::Database;
+use crate::models::email_template::RenderedEmail;
+use crate::models::ids::UserId;
+use crate::models::report::{Contributor, WeeklyDigest};
+use crate::models::task::{SlaBreachQuery, TaskReference};
+use crate::services::email_templates::{self, EmailTemplates};
+use crate::services::mailer::{Email, Mailer};
+use crate::services::{board_diff, sla};
+use crate::utils::errors::ServiceError;
+use crate::utils::locale;
+
+const TOP_CONTRIBUTORS: i64 = 5;
+const OVERDUE_MAX: i64 = 50;
+const CHECK_INTERVAL: StdDuration = StdDuration::from_secs(15 * 60);
+// A send missed by more than this, e.g. while the server was down, is
+// skipped rather than sent late
+const SEND_WINDOW: Duration = Duration::hours(24);
+
+const HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Weekly digest</title></head>
+<body>
+<h1>Weekly digest</h1>
+<p>{{ from }} to {{ to }}</p>
+<h2>Completed ({{ completed | length }})</h2>
+<ul>{% for task in completed %}<li>{{ task.key }} {{ task.name }}</li>{% else %}<li>None</li>{% endfor %}</ul>
+<h2>New ({{ created | length }})</h2>
+<ul>{% for task in created %}<li>{{ task.key }} {{ task.name }} ({{ task.status }})</li>{% else %}<li>None</li>{% endfor %}</ul>
+<h2>Overdue ({{ overdue | length }})</h2>
+<ul>{% for task in overdue %}<li>{{ task.key }} {{ task.name }}, in {{ task.status }} past "{{ task.rule_name }}"</li>{% else %}<li>None</li>{% endfor %}</ul>
+<h2>Top contributors</h2>
+<ol>{% for person in contributors %}<li>{{ person.name }}: {{ person.updates }} updates</li>{% else %}<li>None</li>{% endfor %}</ol>
+</body>
+</html>
+"#;
+
+async fn top_contributors(db: &Database, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Contributor>, ServiceError> {
+    let rows = sqlx::query(
+        "SELECT e.actor_id, u.name, COUNT(*) AS updates
+         FROM task_events e JOIN users u ON u.id = e.actor_id
+         WHERE e.created_at >= $1 AND e.created_at < $2
+         GROUP BY e.actor_id, u.name
+         ORDER BY updates DESC, u.name
+         LIMIT $3"
+    )
+    .bind(from)
+    .bind(to)
+    .bind(TOP_CONTRIBUTORS)
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading top contributors: {}", e);
+        ServiceError::DatabaseError("Failed to build weekly digest".to_string())
+    })?;
+
+    Ok(rows.iter().map(|row| Contributor {
+        user_id: row.get("actor_id"),
+        name: row.get("name"),
+        updates: row.get("updates"),
+    }).collect())
+}
+
+/// The week ending at `to`
+pub async fn build(db: &Database, to: DateTime<Utc>) -> Result<WeeklyDigest, ServiceError> {
+    let from = to - Duration::days(7);
+    let diff = board_diff::compute(db, from, to).await?;
+    let (overdue, _) = sla::list_breaches(db, &SlaBreachQuery { open: Some(true), rule_id: None }, OVERDUE_MAX, 0).await?;
+    let top_contributors = top_contributors(db, from, to).await?;
+
+    Ok(WeeklyDigest {
+        from,
+        to,
+        completed: diff.closed,
+        created: diff.created,
+        overdue,
+        top_contributors,
+    })
+}
+
+/// Template variables shared by the email and the HTML preview, with dates
+/// formatted for `locale`
+fn context(digest: &WeeklyDigest, locale: &str) -> serde_json::Value {
+    let task = |task: &TaskReference| json!({ "key": task.key, "name": task.name, "status": task.status });
+    json!({
+        "from": locale::format_datetime(digest.from, locale),
+        "to": locale::format_datetime(digest.to, locale),
+        "completed": digest.completed.iter().map(task).collect::<Vec<_>>(),
+        "created": digest.created.iter().map(task).collect::<Vec<_>>(),
+        "overdue": digest.overdue.iter()
+            .map(|breach| json!({ "key": breach.task_key, "name": breach.task_name, "status": breach.status, "rule_name": breach.rule_name }))
+            .collect::<Vec<_>>(),
+        "contributors": digest.top_contributors.iter()
+            .map(|person| json!({ "name": person.name, "updates": person.updates }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// The digest as a standalone HTML page, for previews
+pub fn render_html(digest: &WeeklyDigest) -> Result<String, ServiceError> {
+    let context = Context::from_value(context(digest, locale::ENGLISH))
+        .map_err(|e| ServiceError::InternalError(format!("Failed to render weekly digest: {}", e)))?;
+    Tera::one_off(HTML, &context, true)
+        .map_err(|e| ServiceError::InternalError(format!("Failed to render weekly digest: {}", e)))
+}
+
+/// The digest email as it would be sent to a recipient using `locale`
+pub async fn render_email(
+    db: &Database,
+    templates: &EmailTemplates,
+    digest: &WeeklyDigest,
+    locale: &str,
+) -> Result<RenderedEmail, ServiceError> {
+    templates.render(db, email_templates::WEEKLY_DIGEST, locale, &context(digest, locale)).await
+}
+
+/// The latest scheduled send at or before `now`
+fn last_slot(now: DateTime<Utc>, weekday: Weekday, at: NaiveTime) -> DateTime<Utc> {
+    let days_back = (7 + now.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    let slot = (now.date_naive() - Duration::days(days_back as i64)).and_time(at).and_utc();
+    if slot > now { slot - Duration::days(7) } else { slot }
+}
+
+// Claim a scheduled send so that only one instance, once, sends it
+async fn claim(db: &Database, scheduled_at: DateTime<Utc>) -> Result<bool, ServiceError> {
+    let result = sqlx::query("INSERT INTO weekly_digests (scheduled_at) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(scheduled_at)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error claiming weekly digest: {}", e);
+            ServiceError::DatabaseError("Failed to send weekly digest".to_string())
+        })?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Email the digest to every active admin with a verified email address
+async fn send(db: &Database, templates: &EmailTemplates, mailer: &dyn Mailer, scheduled_at: DateTime<Utc>) -> Result<(), ServiceError> {
+    let digest = build(db, scheduled_at).await?;
+    let rows = sqlx::query(
+        "SELECT id, email, locale FROM users
+         WHERE role = 'admin' AND is_active AND email IS NOT NULL AND email_verified_at IS NOT NULL"
+    )
+    .fetch_all(&db.pool)
+    .await
+    .map_err(|e| {
+        log::error!("Database error loading weekly digest recipients: {}", e);
+        ServiceError::DatabaseError("Failed to send weekly digest".to_string())
+    })?;
+
+    let mut sent = 0;
+    for row in rows {
+        let user_id: UserId = row.get("id");
+        let recipient_locale: String = row.get("locale");
+        let result = match render_email(db, templates, &digest, &recipient_locale).await {
+            Ok(rendered) => mailer.send(&Email { to: row.get("email"), subject: rendered.subject, body: rendered.body }).await,
+            Err(e) => Err(e.to_string()),
+        };
+        match result {
+            Ok(()) => sent += 1,
+            Err(e) => log::error!("Failed to send weekly digest to user {}: {}", user_id, e),
+        }
+    }
+
+    sqlx::query("UPDATE weekly_digests SET recipients = $2 WHERE scheduled_at = $1")
+        .bind(scheduled_at)
+        .bind(sent)
+        .execute(&db.pool)
+        .await
+        .map_err(|e| {
+            log::error!("Database error recording weekly digest: {}", e);
+            ServiceError::DatabaseError("Failed to send weekly digest".to_string())
+        })?;
+    log::info!("Weekly digest for the week to {} sent to {} admins", scheduled_at, sent);
+    Ok(())
+}
+
+/// Send the weekly digest every `weekday` at `at` UTC
+pub fn spawn_sender(db: Arc<Database>, templates: Arc<EmailTemplates>, mailer: Arc<dyn Mailer>, weekday: Weekday, at: NaiveTime) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let now = Utc::now();
+            let scheduled_at = last_slot(now, weekday, at);
+            if now - scheduled_at > SEND_WINDOW {
+                continue;
+            }
+            match claim(&db, scheduled_at).await {
+                Ok(true) => {
+                    if let Err(e) = send(&db, &templates, mailer.as_ref(), scheduled_at).await {
+                        log::error!("Weekly digest failed: {}", e);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => log::error!("Weekly digest failed: {}", e),
+            }
+        }
+    });
+}
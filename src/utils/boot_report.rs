@@ -0,0 +1,180 @@
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::config::{ApiDocsAccess, AppConfig, AuthTransport};
+
+/// Summary of what the server started with, emitted once it is listening.
+/// Secrets are never included; the database URL is reduced to host and name.
+#[derive(Debug, Serialize)]
+pub struct BootReport {
+    pub service: &'static str,
+    pub version: &'static str,
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub environment: String,
+    pub listen_addresses: Vec<String>,
+    pub config: ConfigSummary,
+    pub schema: SchemaStatus,
+    pub features: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigSummary {
+    pub database: String,
+    pub frontend_urls: Vec<String>,
+    pub worker_threads: Option<usize>,
+    pub request_timeout_secs: u64,
+    pub long_request_timeout_secs: u64,
+    pub load_shed_max_in_flight: usize,
+    pub load_shed_max_pool_wait_ms: u64,
+    pub outbox_poll_interval_secs: u64,
+    pub outbox_max_attempts: i32,
+    pub usage_flush_interval_secs: u64,
+    pub realtime_queue_capacity: usize,
+    pub plugins: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaStatus {
+    pub up_to_date: bool,
+    pub missing_tables: Vec<String>,
+}
+
+impl BootReport {
+    pub fn new(config: &AppConfig, missing_tables: Vec<String>, addrs: &[SocketAddr]) -> Self {
+        BootReport {
+            service: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            pid: std::process::id(),
+            started_at: Utc::now(),
+            environment: config.environment.clone(),
+            listen_addresses: addrs.iter().map(|addr| addr.to_string()).collect(),
+            config: ConfigSummary {
+                database: redact_database_url(&config.database_url),
+                frontend_urls: config.frontend_urls.clone(),
+                worker_threads: config.worker_threads,
+                request_timeout_secs: config.request_timeout_secs,
+                long_request_timeout_secs: config.long_request_timeout_secs,
+                load_shed_max_in_flight: config.load_shed_max_in_flight,
+                load_shed_max_pool_wait_ms: config.load_shed_max_pool_wait_ms,
+                outbox_poll_interval_secs: config.outbox_poll_interval_secs,
+                outbox_max_attempts: config.outbox_max_attempts,
+                usage_flush_interval_secs: config.usage_flush_interval_secs,
+                realtime_queue_capacity: config.realtime_queue_capacity,
+                plugins: config.plugins.clone(),
+            },
+            schema: SchemaStatus {
+                up_to_date: missing_tables.is_empty(),
+                missing_tables,
+            },
+            features: enabled_features(config),
+        }
+    }
+
+    /// Print the report to stdout: a single JSON line in production so log
+    /// shippers can parse it, a readable banner everywhere else
+    pub fn print(&self, config: &AppConfig) {
+        if config.is_production() {
+            println!("{}", self.to_json());
+            return;
+        }
+
+        println!("🚀 Starting {} v{} ({})", self.service, self.version, self.environment);
+        for addr in &self.listen_addresses {
+            println!("🌐 Listening on http://{}", addr);
+        }
+        println!("🗄️  Database: {}", self.config.database);
+        if self.schema.up_to_date {
+            println!("✅ Schema up to date");
+        } else {
+            println!("⚠️  Schema missing tables: {}", self.schema.missing_tables.join(", "));
+        }
+        println!("🧩 Features: {}", if self.features.is_empty() { "none".to_string() } else { self.features.join(", ") });
+        if config.api_docs != ApiDocsAccess::Disabled {
+            println!("📖 Swagger UI available at: http://localhost:{}/swagger-ui/", config.port);
+        }
+    }
+
+    /// Write the report to the readiness file. The file is written under a
+    /// temporary name and renamed so a watcher never sees a partial report.
+    pub fn write_readiness_file(&self, path: &str) -> std::io::Result<()> {
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, self.to_json())?;
+        std::fs::rename(&tmp_path, path)
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
+/// Optional features switched on by the configuration, by name
+pub fn enabled_features(config: &AppConfig) -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if config.api_docs != ApiDocsAccess::Disabled {
+        features.push("swagger_ui");
+    }
+    if config.cdn_base_url.is_some() {
+        features.push("cdn");
+    }
+    if config.cdn_signing_key.is_some() {
+        features.push("cdn_signed_urls");
+    }
+    if config.virus_scan_command.is_some() {
+        features.push("virus_scan");
+    }
+    if config.confidential_board {
+        features.push("confidential_board");
+    }
+    if config.sensitive_board {
+        features.push("sensitive_board");
+    }
+    if config.row_level_security {
+        features.push("row_level_security");
+    }
+    if config.admin_bind_address.is_some() {
+        features.push("admin_listener");
+    }
+    if config.weekly_digest_at.is_some() {
+        features.push("weekly_digest");
+    }
+    if config.watermark_command.is_some() {
+        features.push("watermark");
+    }
+    if config.preview_command.is_some() {
+        features.push("attachment_previews");
+    }
+    if config.pdf_text_command.is_some() {
+        features.push("pdf_text_search");
+    }
+    if config.moderation.is_some() {
+        features.push("content_moderation");
+    }
+    if config.auth_transport == AuthTransport::Cookie {
+        features.push("cookie_auth");
+    }
+    if config.jwt_algorithm != "HS256" {
+        features.push("jwks");
+    }
+    if config.telemetry_endpoint.is_some() {
+        features.push("telemetry");
+    }
+    if config.magic_link_url.is_some() {
+        features.push("magic_link");
+    }
+    features
+}
+
+// Keep only host, port and database name, e.g. `postgres://db.example.com/kanban`
+fn redact_database_url(url: &str) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let rest = rest.rsplit_once('@').map(|(_, host)| host).unwrap_or(rest);
+    let rest = rest.split('?').next().unwrap_or(rest);
+    if scheme.is_empty() {
+        rest.to_string()
+    } else {
+        format!("{}://{}", scheme, rest)
+    }
+}
@@ -0,0 +1,88 @@
+use std::net::IpAddr;
+
+use actix_web::http::header::X_FORWARDED_FOR;
+use actix_web::HttpRequest;
+
+/// Address of the client a request came from, for rate limits, captcha
+/// checks and audit logs. The TCP peer is taken as is unless it is one of
+/// `trusted_proxies`; then X-Forwarded-For is read from the right, each hop
+/// a trusted proxy appended standing in for the peer, until an address not
+/// in the list. Entries further left were written by the client and are
+/// never believed. None when the peer address is unknown.
+pub fn client_ip(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let mut hop = req.peer_addr()?.ip();
+    let forwarded: Vec<&str> = req.headers()
+        .get_all(X_FORWARDED_FOR)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    for entry in forwarded.iter().rev() {
+        if !trusted_proxies.contains(&hop) {
+            break;
+        }
+        match entry.trim().parse::<IpAddr>() {
+            Ok(ip) => hop = ip,
+            // A proxy we trust would not have written this
+            Err(_) => break,
+        }
+    }
+    Some(hop)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    const PROXY: &str = "127.0.0.1";
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().expect("IP address")
+    }
+
+    fn request(peer: &str, forwarded_for: Option<&str>) -> HttpRequest {
+        let mut req = TestRequest::default().peer_addr(format!("{}:4000", peer).parse().expect("socket address"));
+        if let Some(value) = forwarded_for {
+            req = req.insert_header((X_FORWARDED_FOR, value));
+        }
+        req.to_http_request()
+    }
+
+    #[test]
+    fn headers_from_untrusted_peers_are_ignored() {
+        let req = request("203.0.113.7", Some("198.51.100.1"));
+        assert_eq!(client_ip(&req, &[ip(PROXY)]), Some(ip("203.0.113.7")));
+        assert_eq!(client_ip(&req, &[]), Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn the_right_most_untrusted_hop_is_the_client() {
+        // The client claimed 198.51.100.1; the proxy appended what it saw
+        let req = request(PROXY, Some("198.51.100.1, 203.0.113.7"));
+        assert_eq!(client_ip(&req, &[ip(PROXY)]), Some(ip("203.0.113.7")));
+
+        // Two proxies in a row
+        let req = request(PROXY, Some("198.51.100.1, 203.0.113.7, 10.0.0.2"));
+        assert_eq!(client_ip(&req, &[ip(PROXY), ip("10.0.0.2")]), Some(ip("203.0.113.7")));
+    }
+
+    #[test]
+    fn garbage_stops_the_walk() {
+        let req = request(PROXY, Some("203.0.113.7, not-an-ip"));
+        assert_eq!(client_ip(&req, &[ip(PROXY)]), Some(ip(PROXY)));
+    }
+
+    #[test]
+    fn a_trusted_peer_without_the_header_is_the_client() {
+        let req = request(PROXY, None);
+        assert_eq!(client_ip(&req, &[ip(PROXY)]), Some(ip(PROXY)));
+    }
+
+    #[test]
+    fn unknown_peers_have_no_address() {
+        let req = TestRequest::default().insert_header((X_FORWARDED_FOR, "203.0.113.7")).to_http_request();
+        assert_eq!(client_ip(&req, &[ip(PROXY)]), None);
+    }
+}
@@ -0,0 +1,77 @@
+// Minimal RFC 4180 reader: comma separated, fields optionally wrapped in
+// double quotes, `""` for a literal quote inside a quoted field, CRLF or LF
+// line endings. Quoted fields may span lines.
+
+/// Split CSV text into records of fields. A leading UTF-8 byte order mark and
+/// blank lines are skipped. Fails on an unterminated quoted field or stray
+/// characters after a closing quote, naming the line it started on.
+pub fn parse(input: &str) -> Result<Vec<Vec<String>>, String> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+
+    let mut records = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+    let mut quote_line = 0;
+    let mut in_quotes = false;
+    let mut after_quote = false;
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => {
+                    in_quotes = false;
+                    after_quote = true;
+                }
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            ',' => {
+                record.push(std::mem::take(&mut field));
+                after_quote = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                if !(record.len() == 1 && record[0].is_empty() && !after_quote) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+                after_quote = false;
+                line += 1;
+            }
+            '"' if field.is_empty() && !after_quote => {
+                in_quotes = true;
+                quote_line = line;
+            }
+            _ if after_quote => {
+                return Err(format!("Unexpected character after closing quote on line {}", line));
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!("Quoted field starting on line {} is not terminated", quote_line));
+    }
+
+    // Last record without a trailing newline
+    if !field.is_empty() || !record.is_empty() || after_quote {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
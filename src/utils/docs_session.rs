@@ -0,0 +1,52 @@
+use actix_web::cookie::{time, Cookie, SameSite};
+use actix_web::http::header::{HeaderMap, COOKIE};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Cookie that lets a browser open the API docs when they are admin-only
+pub const COOKIE_NAME: &str = "kanban_docs";
+
+const SESSION_MINUTES: i64 = 60;
+
+/// A signed, expiring docs session cookie. The value is
+/// `<expires>.<hmac>`, so verifying it needs no server-side state.
+pub fn issue(secret: &str) -> Cookie<'static> {
+    let expires = Utc::now().timestamp() + SESSION_MINUTES * 60;
+    Cookie::build(COOKIE_NAME, format!("{}.{}", expires, hex::encode(sign(secret, expires).finalize().into_bytes())))
+        .path("/")
+        .secure(true)
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(time::Duration::minutes(SESSION_MINUTES))
+        .finish()
+}
+
+/// Whether the request carries an unexpired docs session cookie signed with
+/// this secret
+pub fn is_valid(secret: &str, headers: &HeaderMap) -> bool {
+    headers.get_all(COOKIE)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().strip_prefix(COOKIE_NAME)?.strip_prefix('='))
+        .any(|value| verify(secret, value))
+}
+
+fn verify(secret: &str, value: &str) -> bool {
+    let Some((expires, signature)) = value.split_once('.') else {
+        return false;
+    };
+    let (Ok(expires), Ok(signature)) = (expires.parse::<i64>(), hex::decode(signature)) else {
+        return false;
+    };
+    expires > Utc::now().timestamp() && sign(secret, expires).verify_slice(&signature).is_ok()
+}
+
+fn sign(secret: &str, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(format!("api-docs:{}", expires).as_bytes());
+    mac
+}
@@ -1,4 +1,4 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{http::{header::RETRY_AFTER, StatusCode}, HttpResponse, ResponseError};
 use serde::Serialize;
 use std::fmt;
 use utoipa::ToSchema;
@@ -12,6 +12,114 @@ pub enum ServiceError {
     DatabaseError(String),
     ValidationError(String),
     AuthenticationError(String),
+    Conflict(String),
+    Forbidden(String),
+    PayloadTooLarge(String),
+    RateLimited(String),
+    PreconditionFailed(String),
+    ServiceUnavailable(String),
+    GatewayTimeout(String),
+    /// Any of the above with a more specific error code than its default
+    Coded {
+        code: &'static str,
+        #[schema(no_recursion)]
+        error: Box<ServiceError>,
+    },
+    /// Any of the above with a list of individual problems for the client,
+    /// such as every password rule that was not met
+    Detailed {
+        details: Vec<String>,
+        #[schema(no_recursion)]
+        error: Box<ServiceError>,
+    },
+    /// Any of the above with a Retry-After delay, in seconds, for the client
+    RetryAfter {
+        secs: u64,
+        #[schema(no_recursion)]
+        error: Box<ServiceError>,
+    },
+}
+
+impl ServiceError {
+    /// Attach a specific machine-readable code (e.g. `TASK_NOT_FOUND`)
+    pub fn with_code(self, code: &'static str) -> Self {
+        match self {
+            ServiceError::Coded { error, .. } => ServiceError::Coded { code, error },
+            error => ServiceError::Coded { code, error: Box::new(error) },
+        }
+    }
+
+    /// Attach the individual problems behind this error
+    pub fn with_details(self, details: Vec<String>) -> Self {
+        ServiceError::Detailed { details, error: Box::new(self) }
+    }
+
+    /// Tell the client how many seconds to wait before trying again
+    pub fn with_retry_after(self, secs: u64) -> Self {
+        ServiceError::RetryAfter { secs, error: Box::new(self) }
+    }
+
+    /// Delay attached with `with_retry_after`, if any
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            ServiceError::RetryAfter { secs, .. } => Some(*secs),
+            ServiceError::Coded { error, .. } | ServiceError::Detailed { error, .. } => error.retry_after(),
+            _ => None,
+        }
+    }
+
+    /// Individual problems attached with `with_details`, if any
+    pub fn details(&self) -> &[String] {
+        match self {
+            ServiceError::Detailed { details, .. } => details,
+            ServiceError::Coded { error, .. } | ServiceError::RetryAfter { error, .. } => error.details(),
+            _ => &[],
+        }
+    }
+
+    /// Stable error code clients can branch on. Codes are part of the API
+    /// contract: add new ones freely, but never rename an existing code.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ServiceError::Unauthorized(_) => "UNAUTHORIZED",
+            ServiceError::NotFound(_) => "NOT_FOUND",
+            ServiceError::InternalError(_) => "INTERNAL_ERROR",
+            ServiceError::DatabaseError(_) => "DATABASE_ERROR",
+            ServiceError::ValidationError(_) => "VALIDATION_ERROR",
+            ServiceError::AuthenticationError(_) => "AUTHENTICATION_FAILED",
+            ServiceError::Conflict(_) => "CONFLICT",
+            ServiceError::Forbidden(_) => "FORBIDDEN",
+            ServiceError::PayloadTooLarge(_) => "PAYLOAD_TOO_LARGE",
+            ServiceError::RateLimited(_) => "RATE_LIMITED",
+            ServiceError::PreconditionFailed(_) => "PRECONDITION_FAILED",
+            ServiceError::ServiceUnavailable(_) => "SERVICE_UNAVAILABLE",
+            ServiceError::GatewayTimeout(_) => "REQUEST_TIMEOUT",
+            ServiceError::Coded { code, .. } => code,
+            ServiceError::Detailed { error, .. } | ServiceError::RetryAfter { error, .. } => error.error_code(),
+        }
+    }
+
+    // Message safe to return to the client; internal details stay in the logs
+    pub fn public_message(&self) -> String {
+        match self {
+            ServiceError::InternalError(_) => "Something went wrong".to_string(), // Don't expose internal details
+            ServiceError::DatabaseError(_) => "Database operation failed".to_string(), // Don't expose database details
+            ServiceError::Unauthorized(msg)
+            | ServiceError::NotFound(msg)
+            | ServiceError::ValidationError(msg)
+            | ServiceError::AuthenticationError(msg)
+            | ServiceError::Conflict(msg)
+            | ServiceError::Forbidden(msg)
+            | ServiceError::PayloadTooLarge(msg)
+            | ServiceError::RateLimited(msg)
+            | ServiceError::PreconditionFailed(msg)
+            | ServiceError::ServiceUnavailable(msg)
+            | ServiceError::GatewayTimeout(msg) => msg.clone(),
+            ServiceError::Coded { error, .. }
+            | ServiceError::Detailed { error, .. }
+            | ServiceError::RetryAfter { error, .. } => error.public_message(),
+        }
+    }
 }
 
 impl fmt::Display for ServiceError {
@@ -23,57 +131,56 @@ impl fmt::Display for ServiceError {
             ServiceError::DatabaseError(msg) => write!(f, "Database Error: {}", msg),
             ServiceError::ValidationError(msg) => write!(f, "Validation Error: {}", msg),
             ServiceError::AuthenticationError(msg) => write!(f, "Authentication Error: {}", msg),
+            ServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            ServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            ServiceError::PayloadTooLarge(msg) => write!(f, "Payload Too Large: {}", msg),
+            ServiceError::RateLimited(msg) => write!(f, "Rate Limited: {}", msg),
+            ServiceError::PreconditionFailed(msg) => write!(f, "Precondition Failed: {}", msg),
+            ServiceError::ServiceUnavailable(msg) => write!(f, "Service Unavailable: {}", msg),
+            ServiceError::GatewayTimeout(msg) => write!(f, "Gateway Timeout: {}", msg),
+            ServiceError::Coded { code, error } => write!(f, "{} [{}]", error, code),
+            ServiceError::Detailed { details, error } => write!(f, "{} ({})", error, details.join("; ")),
+            ServiceError::RetryAfter { secs, error } => write!(f, "{} (retry after {}s)", error, secs),
         }
     }
 }
 
+impl std::error::Error for ServiceError {}
+
 impl ResponseError for ServiceError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self {
-            ServiceError::Unauthorized(msg) => {
-                log::error!("Unauthorized: {}", msg);
-                HttpResponse::Unauthorized().json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: msg.clone(),
-                })
-            }
-            ServiceError::NotFound(msg) => {
-                log::error!("Not Found: {}", msg);
-                HttpResponse::NotFound().json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: msg.clone(),
-                })
-            }
-            ServiceError::InternalError(msg) => {
-                log::error!("Internal Error: {}", msg);
-                HttpResponse::InternalServerError().json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: "Something went wrong".to_string(), // Don't expose internal details
-                })
-            }
-            ServiceError::DatabaseError(msg) => {
-                log::error!("Database Error: {}", msg);
-                HttpResponse::InternalServerError().json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: "Database operation failed".to_string(), // Don't expose database details
-                })
-            }
-            ServiceError::ValidationError(msg) => {
-                log::error!("Validation Error: {}", msg);
-                HttpResponse::BadRequest().json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: msg.clone(),
-                })
-            }
-            ServiceError::AuthenticationError(msg) => {
-                log::error!("Authentication Error: {}", msg);
-                HttpResponse::Unauthorized().json(ErrorResponse {
-                    status: "error".to_string(),
-                    message: msg.clone(),
-                })
-            }
+            ServiceError::Unauthorized(_) | ServiceError::AuthenticationError(_) => StatusCode::UNAUTHORIZED,
+            ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::InternalError(_) | ServiceError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ServiceError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            ServiceError::Conflict(_) => StatusCode::CONFLICT,
+            ServiceError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ServiceError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ServiceError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ServiceError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ServiceError::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ServiceError::GatewayTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            ServiceError::Coded { error, .. }
+            | ServiceError::Detailed { error, .. }
+            | ServiceError::RetryAfter { error, .. } => error.status_code(),
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        log::error!("{}", self);
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(secs) = self.retry_after() {
+            response.insert_header((RETRY_AFTER, secs));
+        }
+        response.json(ErrorResponse {
+            status: "error".to_string(),
+            message: self.public_message(),
+            error_code: self.error_code().to_string(),
+            details: self.details().to_vec(),
+            correlation_id: None,
+        })
+    }
 }
 
 // Convert sqlx errors to ServiceError
@@ -81,6 +188,9 @@ impl From<sqlx::Error> for ServiceError {
     fn from(err: sqlx::Error) -> Self {
         match err {
             sqlx::Error::RowNotFound => ServiceError::NotFound("Record not found".to_string()),
+            sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+                ServiceError::Conflict("Record already exists".to_string())
+            }
             _ => ServiceError::DatabaseError(err.to_string()),
         }
     }
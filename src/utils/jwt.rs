@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters, EllipticCurveKeyType, Jwk,
+    JwkSet, KeyAlgorithm, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::spki::SubjectPublicKeyInfoRef;
+use rsa::pkcs8::{DecodePublicKey, ObjectIdentifier};
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::AppConfig;
+
+// Algorithm and curve of the EC keys ES256 verifies with
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const P256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+
+/// Signing key for new tokens plus every key tokens are still accepted
+/// under. With HS256 the shared secret does both; with RS256/ES256 tokens
+/// carry the `kid` of the key that signed them, and the public keys are
+/// published as a JWKS so other services can verify tokens on their own.
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    key_id: Option<String>,
+    encoding: EncodingKey,
+    decoding: HashMap<Option<String>, DecodingKey>,
+    jwks: JwkSet,
+}
+
+impl fmt::Debug for JwtKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKeys")
+            .field("algorithm", &self.algorithm)
+            .field("key_id", &self.key_id)
+            .field("verification_keys", &self.decoding.len())
+            .finish()
+    }
+}
+
+impl JwtKeys {
+    /// Load the keys described by the configuration, reading PEM files for
+    /// the asymmetric algorithms
+    pub fn from_config(config: &AppConfig) -> Result<Self, String> {
+        let algorithm = match config.jwt_algorithm.as_str() {
+            "HS256" => Algorithm::HS256,
+            "RS256" => Algorithm::RS256,
+            "ES256" => Algorithm::ES256,
+            other => return Err(format!("Unsupported JWT_ALGORITHM '{}'", other)),
+        };
+
+        if algorithm == Algorithm::HS256 {
+            let mut decoding = HashMap::new();
+            decoding.insert(None, DecodingKey::from_secret(config.jwt_secret.as_bytes()));
+            return Ok(JwtKeys {
+                algorithm,
+                key_id: None,
+                encoding: EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+                decoding,
+                jwks: JwkSet { keys: Vec::new() },
+            });
+        }
+
+        let private_path = config.jwt_private_key_file.as_deref()
+            .ok_or("JWT_PRIVATE_KEY_FILE is required for asymmetric JWT algorithms")?;
+        let public_path = config.jwt_public_key_file.as_deref()
+            .ok_or("JWT_PUBLIC_KEY_FILE is required for asymmetric JWT algorithms")?;
+
+        let private_pem = read_file(private_path)?;
+        let encoding = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(&private_pem),
+            _ => EncodingKey::from_ec_pem(&private_pem),
+        }
+        .map_err(|e| format!("Invalid private key in {}: {}", private_path, e))?;
+
+        let mut keys = JwtKeys {
+            algorithm,
+            key_id: None,
+            encoding,
+            decoding: HashMap::new(),
+            jwks: JwkSet { keys: Vec::new() },
+        };
+
+        let current_id = keys.add_public_key(config.jwt_key_id.clone(), public_path)?;
+        keys.key_id = Some(current_id);
+        for (key_id, path) in &config.jwt_previous_public_keys {
+            keys.add_public_key(Some(key_id.clone()), path)?;
+        }
+
+        Ok(keys)
+    }
+
+    // Register a verification key and its JWK; without an explicit id the
+    // key is named after a hash of its contents. Returns the key id.
+    fn add_public_key(&mut self, key_id: Option<String>, path: &str) -> Result<String, String> {
+        let public_pem = read_file(path)?;
+        let der = pem::parse(&public_pem)
+            .map_err(|e| format!("Invalid public key in {}: {}", path, e))?
+            .into_contents();
+        let key_id = key_id.unwrap_or_else(|| hex::encode(&Sha256::digest(&der)[..8]));
+
+        let (decoding, parameters) = match self.algorithm {
+            Algorithm::RS256 => {
+                let (n, e) = rsa_components(&der).ok_or_else(|| format!("{} is not an RSA public key", path))?;
+                let (n, e) = (URL_SAFE_NO_PAD.encode(n), URL_SAFE_NO_PAD.encode(e));
+                let decoding = DecodingKey::from_rsa_components(&n, &e)
+                    .map_err(|e| format!("Invalid public key in {}: {}", path, e))?;
+                (decoding, AlgorithmParameters::RSA(RSAKeyParameters { key_type: RSAKeyType::RSA, n, e }))
+            }
+            _ => {
+                let (x, y) = ec_p256_components(&der).ok_or_else(|| format!("{} is not a P-256 public key", path))?;
+                let (x, y) = (URL_SAFE_NO_PAD.encode(x), URL_SAFE_NO_PAD.encode(y));
+                let decoding = DecodingKey::from_ec_components(&x, &y)
+                    .map_err(|e| format!("Invalid public key in {}: {}", path, e))?;
+                let parameters = EllipticCurveKeyParameters {
+                    key_type: EllipticCurveKeyType::EC,
+                    curve: EllipticCurve::P256,
+                    x,
+                    y,
+                };
+                (decoding, AlgorithmParameters::EllipticCurve(parameters))
+            }
+        };
+
+        if self.decoding.insert(Some(key_id.clone()), decoding).is_some() {
+            return Err(format!("Duplicate JWT key id '{}'", key_id));
+        }
+        self.jwks.keys.push(Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match self.algorithm {
+                    Algorithm::RS256 => KeyAlgorithm::RS256,
+                    _ => KeyAlgorithm::ES256,
+                }),
+                key_id: Some(key_id.clone()),
+                ..Default::default()
+            },
+            algorithm: parameters,
+        });
+
+        Ok(key_id)
+    }
+
+    /// Sign claims with the current key
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.key_id.clone();
+        encode(&header, claims, &self.encoding)
+    }
+
+    /// Verify a token against the key named by its `kid` and decode its
+    /// claims. Tokens without a `kid` are checked against the current key.
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let key_id = header.kid.or_else(|| self.key_id.clone());
+        let key = self.decoding.get(&key_id)
+            .ok_or(jsonwebtoken::errors::ErrorKind::InvalidKeyFormat)?;
+        Ok(decode::<T>(token, key, &Validation::new(self.algorithm))?.claims)
+    }
+
+    /// Public keys for the JWKS endpoint; empty with HS256
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+// Modulus and exponent of an RSA key, as a SubjectPublicKeyInfo or a
+// PKCS#1 RSAPublicKey, in the unsigned big-endian bytes JWK wants
+fn rsa_components(der: &[u8]) -> Option<(Vec<u8>, Vec<u8>)> {
+    let key = RsaPublicKey::from_public_key_der(der)
+        .or_else(|_| RsaPublicKey::from_pkcs1_der(der))
+        .ok()?;
+    Some((key.n().to_bytes_be(), key.e().to_bytes_be()))
+}
+
+// Coordinates of a P-256 SubjectPublicKeyInfo, whose key is the
+// uncompressed point 0x04 || x || y
+fn ec_p256_components(der: &[u8]) -> Option<(&[u8], &[u8])> {
+    let info = SubjectPublicKeyInfoRef::try_from(der).ok()?;
+    if info.algorithm.oid != EC_PUBLIC_KEY || info.algorithm.parameters_oid().ok()? != P256 {
+        return None;
+    }
+    let point = info.subject_public_key.as_bytes()?;
+    if point.len() != 65 || point[0] != 0x04 {
+        return None;
+    }
+    Some((&point[1..33], &point[33..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use rsa::pkcs1::EncodeRsaPublicKey;
+
+    use super::*;
+
+    const RSA_2048: &str = "-----BEGIN PUBLIC KEY-----
+MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAlzMWMjgrxv4LHBEPJNA9
+LmCUAwtO5eYbwuf6dM25qNG1XnnEjAS/lxPwlRHcp2DR3kz5rGMzktUSV1chjC58
+Dz93hgmrPWLQjXimhtM8rQsdsKpTezF8Q+bXwr6dTHSK4LXWE9eQStYGHt+ABijq
+3tCYFOeCIlJDQIUwuOZvOOH86qEDeVd50JZSvtElr/zrKVW4AopJHW/WZxBAnLl9
+kdmEaueFpfz5EJvYb+7uZ+qfoNUFTQKU5CASa3Hc4JE1dR6eYdwYgL6RYXFmk3Mc
+2b37BCdMnRr/vE5VKc07LdE7gy/saVZ/Dzeexe37xlpatbtVfrnvnQsnxtsCq/Il
+xQIDAQAB
+-----END PUBLIC KEY-----";
+    const P_256: &str = "-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEV2OcWJzbI5k/cfofgnfhlqXXIJB7
+vIZvBFMz5osqoVlnWUyYOTTxB2O8aDiMq74GurQuCOL1VqMIUt/eM0tEAg==
+-----END PUBLIC KEY-----";
+    const P_384: &str = "-----BEGIN PUBLIC KEY-----
+MHYwEAYHKoZIzj0CAQYFK4EEACIDYgAEZ8AE528V+t3tQuqCMIflFBSgSzYDA3sO
+a+T23hhiTfoMNKxhAmsupNOVgZl2NF1wVxGN75CoYouG2DnIeLm+uWNu8K8imONZ
+S4EdW4/WpJ8M44QUN6CWlGgczRRhaeBp
+-----END PUBLIC KEY-----";
+
+    fn der(pem: &str) -> Vec<u8> {
+        pem::parse(pem).expect("test key").into_contents()
+    }
+
+    #[test]
+    fn reads_rsa_keys_as_spki_and_pkcs1() {
+        let spki = der(RSA_2048);
+        let (n, e) = rsa_components(&spki).expect("RSA key");
+        assert_eq!((n.len(), e.as_slice()), (256, &[1, 0, 1][..]));
+
+        let pkcs1 = RsaPublicKey::from_public_key_der(&spki).expect("RSA key").to_pkcs1_der().expect("encode");
+        assert_eq!(rsa_components(pkcs1.as_bytes()), Some((n, e)));
+    }
+
+    #[test]
+    fn reads_p256_keys_only() {
+        let p256 = der(P_256);
+        let (x, y) = ec_p256_components(&p256).expect("P-256 key");
+        assert_eq!((x.len(), y.len()), (32, 32));
+
+        // Right algorithm, wrong curve
+        assert_eq!(ec_p256_components(&der(P_384)), None);
+        // EC and RSA keys are not mistaken for each other
+        assert_eq!(ec_p256_components(&der(RSA_2048)), None);
+        assert_eq!(rsa_components(&der(P_256)), None);
+    }
+
+    #[test]
+    fn rejects_wrong_algorithm_oids() {
+        // Turn rsaEncryption (1.2.840.113549.1.1.1) into 1.2.840.113549.1.1.2
+        let mut rsa = der(RSA_2048);
+        let oid = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+        let at = rsa.windows(oid.len()).position(|window| window == oid).expect("rsaEncryption OID");
+        rsa[at + oid.len() - 1] = 0x02;
+        assert_eq!(rsa_components(&rsa), None);
+
+        // Turn id-ecPublicKey (1.2.840.10045.2.1) into 1.2.840.10045.2.2
+        let mut ec = der(P_256);
+        let oid = [0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+        let at = ec.windows(oid.len()).position(|window| window == oid).expect("id-ecPublicKey OID");
+        ec[at + oid.len() - 1] = 0x02;
+        assert_eq!(ec_p256_components(&ec), None);
+    }
+
+    #[test]
+    fn truncated_keys_are_rejected_without_panicking() {
+        for key in [der(RSA_2048), der(P_256)] {
+            for length in 0..key.len() {
+                assert_eq!(rsa_components(&key[..length]), None);
+                assert_eq!(ec_p256_components(&key[..length]), None);
+            }
+        }
+    }
+
+    #[test]
+    fn bad_long_form_lengths_are_rejected() {
+        let rsa = der(RSA_2048);
+        // The outer SEQUENCE of a 2048-bit key uses a two-byte long-form length
+        assert_eq!(&rsa[..2], &[0x30, 0x82]);
+
+        let inputs: [&[u8]; 5] = [
+            &[0x30, 0x84, 0xff, 0xff, 0xff, 0xff],
+            &[0x30, 0x89, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            &[0x30, 0x80, 0x00, 0x00],
+            &[0x30, 0x81],
+            // Long form for a length that fits in one byte is not DER
+            &[0x30, 0x81, 0x01, 0x00],
+        ];
+        for input in inputs {
+            assert_eq!(rsa_components(input), None);
+            assert_eq!(ec_p256_components(input), None);
+        }
+
+        let mut overlong = rsa.clone();
+        overlong[2] = 0xff;
+        assert_eq!(rsa_components(&overlong), None);
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+use crate::utils::errors::ServiceError;
+
+pub const ENGLISH: &str = "en";
+pub const INDONESIAN: &str = "id";
+
+/// Locales emails can be sent in; English is the fallback
+pub const SUPPORTED: &[&str] = &[ENGLISH, INDONESIAN];
+
+const MONTHS_EN: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+const MONTHS_ID: [&str; 12] = [
+    "Januari", "Februari", "Maret", "April", "Mei", "Juni",
+    "Juli", "Agustus", "September", "Oktober", "November", "Desember",
+];
+
+/// Match a language tag such as `id-ID` or `EN` to a supported locale
+pub fn resolve(tag: &str) -> Option<&'static str> {
+    let language = tag.trim().split(['-', '_']).next().unwrap_or_default().to_lowercase();
+    SUPPORTED.iter().copied().find(|locale| *locale == language)
+}
+
+/// Like `resolve`, rejecting unsupported tags with 400
+pub fn parse(tag: &str) -> Result<&'static str, ServiceError> {
+    resolve(tag).ok_or_else(|| {
+        ServiceError::ValidationError(format!("Locale must be one of: {}", SUPPORTED.join(", ")))
+            .with_code("UNSUPPORTED_LOCALE")
+    })
+}
+
+/// Like `resolve`, falling back to English for missing or unknown tags
+pub fn resolve_or_default(tag: Option<&str>) -> &'static str {
+    tag.and_then(resolve).unwrap_or(ENGLISH)
+}
+
+/// Format an instant for people reading `locale`, e.g. `16 October 2026,
+/// 14:05 UTC` or `16 Oktober 2026 pukul 14.05 UTC`
+pub fn format_datetime(at: DateTime<Utc>, locale: &str) -> String {
+    let month = at.month0() as usize;
+    match resolve_or_default(Some(locale)) {
+        INDONESIAN => format!(
+            "{} {} {} pukul {:02}.{:02} UTC",
+            at.day(), MONTHS_ID[month], at.year(), at.hour(), at.minute()
+        ),
+        _ => format!(
+            "{} {} {}, {:02}:{:02} UTC",
+            at.day(), MONTHS_EN[month], at.year(), at.hour(), at.minute()
+        ),
+    }
+}
@@ -0,0 +1,61 @@
+use std::io::Write;
+
+use chrono::{SecondsFormat, Utc};
+use log::kv::{self, Key, Value, VisitSource};
+use serde_json::{Map, Value as Json};
+
+use crate::config::LogFormat;
+use crate::middleware::request_context;
+
+/// Install the global logger. Lines logged while handling a request carry
+/// its request id and, once authenticated, the user id.
+pub fn init(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+    match format {
+        LogFormat::Text => builder.format(|buf, record| {
+            let timestamp = buf.timestamp();
+            match request_context::current() {
+                Some(ctx) => writeln!(buf, "[{} {:<5} {}] [{}] {}", timestamp, record.level(), record.target(), ctx, record.args()),
+                None => writeln!(buf, "[{} {:<5} {}] {}", timestamp, record.level(), record.target(), record.args()),
+            }
+        }),
+        LogFormat::Json => builder.format(|buf, record| {
+            let mut line = Map::new();
+            line.insert("timestamp".to_string(), Json::from(Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)));
+            line.insert("level".to_string(), Json::from(record.level().as_str()));
+            line.insert("target".to_string(), Json::from(record.target()));
+            line.insert("message".to_string(), Json::from(record.args().to_string()));
+            if let Some(ctx) = request_context::current() {
+                line.insert("request_id".to_string(), Json::from(ctx.request_id.as_str()));
+                if let Some(user_id) = ctx.user_id() {
+                    line.insert("user_id".to_string(), Json::from(user_id.0));
+                }
+            }
+            let _ = record.key_values().visit(&mut Fields(&mut line));
+            writeln!(buf, "{}", Json::Object(line))
+        }),
+    };
+    builder.init();
+}
+
+/// Copies the structured key-values of a record, such as the route and
+/// latency of access log lines, into its JSON object
+struct Fields<'a>(&'a mut Map<String, Json>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let value = if let Some(n) = value.to_i64() {
+            Json::from(n)
+        } else if let Some(n) = value.to_u64() {
+            Json::from(n)
+        } else if let Some(n) = value.to_f64() {
+            Json::from(n)
+        } else if let Some(b) = value.to_bool() {
+            Json::from(b)
+        } else {
+            Json::from(value.to_string())
+        };
+        self.0.insert(key.as_str().to_string(), value);
+        Ok(())
+    }
+}
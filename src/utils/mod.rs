@@ -1 +1,14 @@
+pub mod cdn;
+pub mod client_ip;
+pub mod csv;
+pub mod docs_session;
 pub mod errors;
+pub mod jwt;
+pub mod locale;
+pub mod logging;
+pub mod boot_report;
+pub mod password;
+pub mod sql;
+pub mod text;
+#[cfg(test)]
+pub mod test_db;
@@ -0,0 +1,111 @@
+use crate::utils::errors::ServiceError;
+
+// bcrypt ignores everything past 72 bytes
+const MAX_BYTES: usize = 72;
+
+// Frequently leaked passwords, compared case-insensitively
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456", "12345678", "123456789", "1234567890", "password", "password1",
+    "password123", "passw0rd", "qwerty", "qwerty123", "qwertyuiop", "abc123",
+    "abcd1234", "111111", "000000", "123123", "1q2w3e4r", "1qaz2wsx",
+    "iloveyou", "welcome", "welcome1", "welcome123", "letmein", "letmein1",
+    "admin", "admin123", "administrator", "monkey", "dragon", "football",
+    "baseball", "sunshine", "princess", "master", "shadow", "superman",
+    "trustno1", "changeme", "secret", "secret123", "login", "starwars",
+    "whatever", "zaq12wsx", "asdfghjkl", "computer", "internet", "kanban123",
+];
+
+/// A kind of character a password can be required to contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CharacterClass {
+    Letter,
+    Uppercase,
+    Lowercase,
+    Digit,
+    Symbol,
+}
+
+impl CharacterClass {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "letter" => Some(CharacterClass::Letter),
+            "upper" | "uppercase" => Some(CharacterClass::Uppercase),
+            "lower" | "lowercase" => Some(CharacterClass::Lowercase),
+            "digit" => Some(CharacterClass::Digit),
+            "symbol" => Some(CharacterClass::Symbol),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CharacterClass::Letter => c.is_alphabetic(),
+            CharacterClass::Uppercase => c.is_uppercase(),
+            CharacterClass::Lowercase => c.is_lowercase(),
+            CharacterClass::Digit => c.is_ascii_digit(),
+            CharacterClass::Symbol => !c.is_alphanumeric() && !c.is_whitespace(),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            CharacterClass::Letter => "a letter",
+            CharacterClass::Uppercase => "an uppercase letter",
+            CharacterClass::Lowercase => "a lowercase letter",
+            CharacterClass::Digit => "a digit",
+            CharacterClass::Symbol => "a symbol",
+        }
+    }
+}
+
+/// Rules new passwords must meet, configured through `AppConfig`. Passwords
+/// are also capped at 72 bytes and may never contain the username.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub required_classes: Vec<CharacterClass>,
+    pub deny_common: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            required_classes: vec![CharacterClass::Letter, CharacterClass::Digit],
+            deny_common: true,
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Check a new password against every rule. The error lists each rule
+    /// that was not met in its details.
+    pub fn validate(&self, password: &str, username: &str) -> Result<(), ServiceError> {
+        let mut problems = Vec::new();
+
+        if password.chars().count() < self.min_length {
+            problems.push(format!("Password must be at least {} characters long", self.min_length));
+        }
+        if password.len() > MAX_BYTES {
+            problems.push(format!("Password must be at most {} bytes long", MAX_BYTES));
+        }
+        for class in &self.required_classes {
+            if !password.chars().any(|c| class.matches(c)) {
+                problems.push(format!("Password must contain {}", class.description()));
+            }
+        }
+        if !username.is_empty() && password.to_lowercase().contains(&username.to_lowercase()) {
+            problems.push("Password must not contain the username".to_string());
+        }
+        if self.deny_common && COMMON_PASSWORDS.contains(&password.to_lowercase().as_str()) {
+            problems.push("Password is too common".to_string());
+        }
+
+        match problems.first() {
+            None => Ok(()),
+            Some(first) => Err(ServiceError::ValidationError(first.clone())
+                .with_details(problems)
+                .with_code("WEAK_PASSWORD")),
+        }
+    }
+}
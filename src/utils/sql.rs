@@ -0,0 +1,402 @@
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::models::ids::{AttachmentId, TaskId, TeamId, UserId};
+
+// Dynamic SQL helpers. Table and column names are `&'static str`, so only
+// identifiers written in the source can reach the query text; every value
+// goes through a bind parameter, numbered in the order it is pushed.
+
+/// A value to bind into a dynamically built query
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+    Int(i32),
+    BigInt(i64),
+    Text(String),
+    Bool(bool),
+}
+
+impl From<i32> for SqlValue {
+    fn from(value: i32) -> Self {
+        SqlValue::Int(value)
+    }
+}
+
+impl From<i64> for SqlValue {
+    fn from(value: i64) -> Self {
+        SqlValue::BigInt(value)
+    }
+}
+
+impl From<String> for SqlValue {
+    fn from(value: String) -> Self {
+        SqlValue::Text(value)
+    }
+}
+
+impl From<&str> for SqlValue {
+    fn from(value: &str) -> Self {
+        SqlValue::Text(value.to_string())
+    }
+}
+
+impl From<&String> for SqlValue {
+    fn from(value: &String) -> Self {
+        SqlValue::Text(value.clone())
+    }
+}
+
+impl From<bool> for SqlValue {
+    fn from(value: bool) -> Self {
+        SqlValue::Bool(value)
+    }
+}
+
+macro_rules! sql_value_from_id {
+    ($($id:ty),*) => {
+        $(impl From<$id> for SqlValue {
+            fn from(id: $id) -> Self {
+                SqlValue::Int(id.0)
+            }
+        })*
+    };
+}
+
+sql_value_from_id!(TaskId, UserId, TeamId, AttachmentId);
+
+fn push_value(qb: &mut QueryBuilder<'static, Postgres>, value: &SqlValue) {
+    match value {
+        SqlValue::Int(v) => qb.push_bind(*v),
+        SqlValue::BigInt(v) => qb.push_bind(*v),
+        SqlValue::Text(v) => qb.push_bind(v.clone()),
+        SqlValue::Bool(v) => qb.push_bind(*v),
+    };
+}
+
+/// Sort order for a whitelisted column
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub column: &'static str,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Eq(&'static str, SqlValue),
+    Ne(&'static str, SqlValue),
+    ILike(&'static str, String),
+    ILikeOrMatches(&'static str, String, &'static str, String),
+}
+
+/// SELECT with optional filters, sort and paging. The same filters can be
+/// rendered as a COUNT query for the unpaged total.
+#[derive(Debug, Clone)]
+pub struct Select {
+    table: &'static str,
+    columns: &'static str,
+    conditions: Vec<Condition>,
+    sort: Vec<Sort>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+impl Select {
+    pub fn new(table: &'static str, columns: &'static str) -> Self {
+        Select {
+            table,
+            columns,
+            conditions: Vec::new(),
+            sort: Vec::new(),
+            limit: None,
+            offset: None,
+        }
+    }
+
+    /// `column = value`
+    pub fn eq(mut self, column: &'static str, value: impl Into<SqlValue>) -> Self {
+        self.conditions.push(Condition::Eq(column, value.into()));
+        self
+    }
+
+    /// `column <> value`
+    pub fn ne(mut self, column: &'static str, value: impl Into<SqlValue>) -> Self {
+        self.conditions.push(Condition::Ne(column, value.into()));
+        self
+    }
+
+    /// `column = value` when a value is given, no filter otherwise
+    pub fn eq_opt<T: Into<SqlValue>>(self, column: &'static str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.eq(column, value),
+            None => self,
+        }
+    }
+
+    /// `column ILIKE pattern` when a pattern is given; the pattern is used
+    /// as is, so callers escape any wildcards they do not intend
+    pub fn ilike(mut self, column: &'static str, pattern: Option<String>) -> Self {
+        if let Some(pattern) = pattern {
+            self.conditions.push(Condition::ILike(column, pattern));
+        }
+        self
+    }
+
+    /// `column ILIKE pattern OR document @@ plainto_tsquery(query)` when a
+    /// search is given, so rows match on a name or on indexed content
+    pub fn ilike_or_matches(
+        mut self,
+        column: &'static str,
+        pattern: Option<String>,
+        document: &'static str,
+        query: Option<String>,
+    ) -> Self {
+        if let (Some(pattern), Some(query)) = (pattern, query) {
+            self.conditions.push(Condition::ILikeOrMatches(column, pattern, document, query));
+        }
+        self
+    }
+
+    /// Append a sort key; later keys break ties of earlier ones
+    pub fn order_by(mut self, sort: Sort) -> Self {
+        self.sort.push(sort);
+        self
+    }
+
+    pub fn paginate(mut self, limit: i64, offset: i64) -> Self {
+        self.limit = Some(limit);
+        self.offset = Some(offset);
+        self
+    }
+
+    fn push_where(&self, qb: &mut QueryBuilder<'static, Postgres>) {
+        for (i, condition) in self.conditions.iter().enumerate() {
+            qb.push(if i == 0 { " WHERE " } else { " AND " });
+            match condition {
+                Condition::Eq(column, value) => {
+                    qb.push(*column).push(" = ");
+                    push_value(qb, value);
+                }
+                Condition::Ne(column, value) => {
+                    qb.push(*column).push(" <> ");
+                    push_value(qb, value);
+                }
+                Condition::ILike(column, pattern) => {
+                    qb.push(*column).push(" ILIKE ").push_bind(pattern.clone());
+                }
+                Condition::ILikeOrMatches(column, pattern, document, query) => {
+                    qb.push("(").push(*column).push(" ILIKE ").push_bind(pattern.clone())
+                        .push(" OR ").push(*document).push(" @@ plainto_tsquery('simple', ")
+                        .push_bind(query.clone()).push("))");
+                }
+            }
+        }
+    }
+
+    pub fn build(&self) -> QueryBuilder<'static, Postgres> {
+        let mut qb = QueryBuilder::new(format!("SELECT {} FROM {}", self.columns, self.table));
+        self.push_where(&mut qb);
+
+        for (i, sort) in self.sort.iter().enumerate() {
+            qb.push(if i == 0 { " ORDER BY " } else { ", " })
+                .push(sort.column)
+                .push(if sort.descending { " DESC" } else { " ASC" });
+        }
+        if let Some(limit) = self.limit {
+            qb.push(" LIMIT ").push_bind(limit);
+        }
+        if let Some(offset) = self.offset {
+            qb.push(" OFFSET ").push_bind(offset);
+        }
+        qb
+    }
+
+    pub fn build_count(&self) -> QueryBuilder<'static, Postgres> {
+        let mut qb = QueryBuilder::new(format!("SELECT COUNT(*) FROM {}", self.table));
+        self.push_where(&mut qb);
+        qb
+    }
+}
+
+/// UPDATE of the columns that were actually provided
+#[derive(Debug, Clone)]
+pub struct Patch {
+    table: &'static str,
+    raw: Vec<(&'static str, &'static str)>,
+    /// Provided columns; `None` sets the column to NULL
+    values: Vec<(&'static str, Option<SqlValue>)>,
+}
+
+impl Patch {
+    pub fn new(table: &'static str) -> Self {
+        Patch { table, raw: Vec::new(), values: Vec::new() }
+    }
+
+    /// Set a column to a fixed SQL expression such as `NOW()`
+    pub fn set_raw(mut self, column: &'static str, expression: &'static str) -> Self {
+        self.raw.push((column, expression));
+        self
+    }
+
+    pub fn set(mut self, column: &'static str, value: impl Into<SqlValue>) -> Self {
+        self.values.push((column, Some(value.into())));
+        self
+    }
+
+    /// Set a column only when the request provided a value for it; `None`
+    /// leaves the column unchanged, use `set_null` to clear it
+    pub fn set_opt<T: Into<SqlValue>>(self, column: &'static str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.set(column, value),
+            None => self,
+        }
+    }
+
+    /// Set a column to NULL
+    pub fn set_null(mut self, column: &'static str) -> Self {
+        self.values.push((column, None));
+        self
+    }
+
+    /// Whether any provided (non-raw) column is being changed
+    pub fn has_changes(&self) -> bool {
+        !self.values.is_empty()
+    }
+
+    /// Render `UPDATE table SET ... WHERE key = id`, optionally with a
+    /// RETURNING list, or None when the patch sets no column at all
+    pub fn build(&self, key: &'static str, id: impl Into<SqlValue>, returning: Option<&'static str>) -> Option<QueryBuilder<'static, Postgres>> {
+        if self.raw.is_empty() && self.values.is_empty() {
+            return None;
+        }
+        let mut qb = QueryBuilder::new(format!("UPDATE {} SET ", self.table));
+
+        let mut first = true;
+        for (column, expression) in &self.raw {
+            if !first {
+                qb.push(", ");
+            }
+            qb.push(*column).push(" = ").push(*expression);
+            first = false;
+        }
+        for (column, value) in &self.values {
+            if !first {
+                qb.push(", ");
+            }
+            qb.push(*column).push(" = ");
+            match value {
+                Some(value) => push_value(&mut qb, value),
+                None => {
+                    qb.push("NULL");
+                }
+            }
+            first = false;
+        }
+
+        qb.push(" WHERE ").push(key).push(" = ");
+        push_value(&mut qb, &id.into());
+        if let Some(returning) = returning {
+            qb.push(" RETURNING ").push(returning);
+        }
+        Some(qb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Row;
+
+    use super::*;
+    use crate::utils::test_db::TestDb;
+
+    const HOSTILE: &str = "x'; DROP TABLE tasks; --";
+
+    fn filtered() -> Select {
+        Select::new("tasks", "id, name")
+            .eq("created_by", UserId(1))
+            .ne("status", "DONE")
+            .eq_opt::<&str>("external_link", None)
+            .ilike_or_matches("name", Some(format!("%{}%", HOSTILE)), "name_tsv", Some(HOSTILE.to_string()))
+    }
+
+    #[test]
+    fn select_binds_every_value_in_order() {
+        let select = filtered()
+            .order_by(Sort { column: "created_at", descending: true })
+            .order_by(Sort { column: "id", descending: false })
+            .paginate(10, 20);
+
+        assert_eq!(
+            select.build().sql(),
+            "SELECT id, name FROM tasks WHERE created_by = $1 AND status <> $2 \
+             AND (name ILIKE $3 OR name_tsv @@ plainto_tsquery('simple', $4)) \
+             ORDER BY created_at DESC, id ASC LIMIT $5 OFFSET $6"
+        );
+        assert_eq!(
+            select.build_count().sql(),
+            "SELECT COUNT(*) FROM tasks WHERE created_by = $1 AND status <> $2 \
+             AND (name ILIKE $3 OR name_tsv @@ plainto_tsquery('simple', $4))"
+        );
+    }
+
+    #[test]
+    fn patch_binds_values_after_raw_expressions() {
+        let patch = Patch::new("tasks")
+            .set_raw("updated_at", "NOW()")
+            .set("name", HOSTILE)
+            .set_opt::<&str>("status", None)
+            .set_null("external_link")
+            .set("status", "DONE");
+
+        assert!(patch.has_changes());
+        assert_eq!(
+            patch.build("id", TaskId(7), Some("id")).expect("patch sets columns").sql(),
+            "UPDATE tasks SET updated_at = NOW(), name = $1, external_link = NULL, status = $2 WHERE id = $3 RETURNING id"
+        );
+    }
+
+    #[test]
+    fn empty_patch_builds_nothing() {
+        let patch = Patch::new("tasks").set_opt::<&str>("name", None);
+        assert!(!patch.has_changes());
+        assert!(patch.build("id", TaskId(1), None).is_none());
+        assert!(Patch::new("tasks").set_raw("updated_at", "NOW()").build("id", TaskId(1), None).is_some());
+    }
+
+    #[actix_web::test]
+    async fn bound_values_reach_the_right_placeholders() {
+        let Some(test) = TestDb::create().await else { return };
+        let user = test.insert_user("owner", "member").await;
+        let task = test.insert_task("task", user).await;
+        sqlx::query("UPDATE tasks SET external_link = 'https://example.com' WHERE id = $1")
+            .bind(task)
+            .execute(&test.db.pool)
+            .await
+            .expect("set link");
+
+        let row = Patch::new("tasks")
+            .set_raw("updated_at", "NOW()")
+            .set("name", HOSTILE)
+            .set_null("external_link")
+            .set("status", "DOING")
+            .build("id", task, Some("name, status, external_link"))
+            .expect("patch sets columns")
+            .build()
+            .fetch_one(&test.db.pool)
+            .await
+            .expect("update task");
+        assert_eq!(row.get::<String, _>("name"), HOSTILE);
+        assert_eq!(row.get::<String, _>("status"), "DOING");
+        assert_eq!(row.get::<Option<String>, _>("external_link"), None);
+
+        let select = Select::new("tasks", "id, name")
+            .eq("created_by", user)
+            .ne("status", "DONE")
+            .ilike("name", Some(format!("{}%", HOSTILE)))
+            .order_by(Sort { column: "id", descending: false })
+            .paginate(1, 0);
+        let rows = select.build().build().fetch_all(&test.db.pool).await.expect("select");
+        assert_eq!(rows.iter().map(|row| row.get("id")).collect::<Vec<TaskId>>(), vec![task]);
+        let total: i64 = select.build_count().build_query_scalar().fetch_one(&test.db.pool).await.expect("count");
+        assert_eq!(total, 1);
+        test.drop().await;
+    }
+}
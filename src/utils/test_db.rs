@@ -0,0 +1,203 @@
+//! Throwaway databases for tests that need Postgres. They are skipped unless
+//! TEST_DATABASE_URL points at a server where the tests may create
+//! databases and roles, e.g. `postgres://postgres@localhost:5432/postgres`.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use actix_web::web;
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{ConnectOptions, Connection};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+use uuid::Uuid;
+
+use crate::Database;
+use crate::middleware::auth::{AuthenticatedUser, Claims};
+use crate::models::ids::{TaskId, UserId};
+
+const SCHEMA: &str = include_str!("../../kanban_db.sql");
+
+// The service's own role: not a superuser, so row-level security applies
+const APP_ROLE: &str = "kanban_test_app";
+
+/// A fresh database loaded with kanban_db.sql, reached as an unprivileged
+/// role. Call `drop` at the end of the test to remove it.
+pub struct TestDb {
+    pub db: web::Data<Database>,
+    admin: PgConnectOptions,
+    /// Where the pool connects, for connections made outside it
+    pub app_url: String,
+    name: String,
+}
+
+impl TestDb {
+    pub async fn create() -> Option<TestDb> {
+        Self::create_with(false).await
+    }
+
+    /// Like `create`, with the pool setting up connections for the
+    /// row-level security policies
+    pub async fn create_with(row_level_security: bool) -> Option<TestDb> {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL is not set; skipping database test");
+            return None;
+        };
+        let admin = PgConnectOptions::from_str(&url).expect("TEST_DATABASE_URL is a Postgres URL");
+        let name = format!("kanban_test_{}", Uuid::new_v4().simple());
+
+        let mut conn = admin.connect().await.expect("connect to TEST_DATABASE_URL");
+        sqlx::raw_sql(&format!(
+            "DO $$ BEGIN
+                 CREATE ROLE {role} LOGIN PASSWORD '{role}';
+             EXCEPTION WHEN duplicate_object OR unique_violation THEN NULL;
+             END $$",
+            role = APP_ROLE
+        ))
+        .execute(&mut conn)
+        .await
+        .expect("create the test role");
+        sqlx::raw_sql(&format!("CREATE DATABASE {}", name))
+            .execute(&mut conn)
+            .await
+            .expect("create the test database");
+        conn.close().await.ok();
+
+        let mut conn = admin.clone().database(&name).connect().await.expect("connect to the test database");
+        sqlx::raw_sql(SCHEMA).execute(&mut conn).await.expect("load kanban_db.sql");
+        sqlx::raw_sql(&format!(
+            "GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA public TO {role};
+             GRANT USAGE, SELECT ON ALL SEQUENCES IN SCHEMA public TO {role}",
+            role = APP_ROLE
+        ))
+        .execute(&mut conn)
+        .await
+        .expect("grant the test role access");
+        conn.close().await.ok();
+
+        let app_url = format!(
+            "postgres://{role}:{role}@{}:{}/{}",
+            admin.get_host(), admin.get_port(), name, role = APP_ROLE
+        );
+        let db = Database::new(&app_url, row_level_security).await.expect("connect as the test role");
+        Some(TestDb { db: web::Data::new(db), admin, app_url, name })
+    }
+
+    /// Close the pool and remove the database
+    pub async fn drop(self) {
+        self.db.pool.close().await;
+        let mut conn = self.admin.connect().await.expect("connect to TEST_DATABASE_URL");
+        sqlx::raw_sql(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+            .execute(&mut conn)
+            .await
+            .expect("drop the test database");
+    }
+
+    pub async fn insert_user(&self, username: &str, role: &str) -> UserId {
+        sqlx::query_scalar("INSERT INTO users (username, password, name, role) VALUES ($1, 'x', $1, $2) RETURNING id")
+            .bind(username)
+            .bind(role)
+            .fetch_one(&self.db.pool)
+            .await
+            .expect("insert user")
+    }
+
+    pub async fn insert_task(&self, name: &str, created_by: UserId) -> TaskId {
+        sqlx::query_scalar("INSERT INTO tasks (name, status, created_by) VALUES ($1, 'TO_DO', $2) RETURNING id")
+            .bind(name)
+            .bind(created_by)
+            .fetch_one(&self.db.pool)
+            .await
+            .expect("insert task")
+    }
+
+    /// Give `task` a team, an attachment, a link to `other`, a vote from
+    /// `voter` and an open SLA breach, so every relation the list loads has rows
+    pub async fn relate(&self, task: TaskId, other: TaskId, voter: UserId) {
+        sqlx::query(
+            "WITH rule AS (
+                 INSERT INTO sla_rules (name, status, max_hours) VALUES ('rule', 'TO_DO', 1) RETURNING id
+             ), team AS (
+                 INSERT INTO task_teams (task_id, team_id) SELECT $1, id FROM teams ORDER BY id LIMIT 1
+             ), attachment AS (
+                 INSERT INTO task_attachments (task_id, file_name, file_size, mime_type, cloudinary_public_id,
+                                               cloudinary_url, cloudinary_secure_url, uploaded_by, processing_status)
+                 VALUES ($1, 'a.txt', 1, 'text/plain', 'a', 'http://a', 'https://a', $3, 'ready')
+             ), link AS (
+                 INSERT INTO task_links (source_task_id, target_task_id) VALUES ($1, $2)
+             ), vote AS (
+                 INSERT INTO task_votes (task_id, user_id) VALUES ($1, $3)
+             )
+             INSERT INTO sla_breaches (rule_id, task_id, entered_at, breached_at)
+             SELECT id, $1, NOW(), NOW() FROM rule"
+        )
+        .bind(task)
+        .bind(other)
+        .bind(voter)
+        .execute(&self.db.pool)
+        .await
+        .expect("relate task");
+    }
+}
+
+/// The caller a handler sees for a signed-in user with a login token
+pub fn signed_in(id: UserId, role: &str) -> AuthenticatedUser {
+    AuthenticatedUser {
+        id,
+        claims: Claims {
+            sub: id.to_string(),
+            username: format!("user{}", id),
+            name: format!("User {}", id),
+            exp: usize::MAX,
+            iat: 0,
+            jti: Uuid::new_v4().to_string(),
+            role: role.to_string(),
+            team_ids: Vec::new(),
+            ver: 1,
+            email_unverified: false,
+        },
+        role: role.to_string(),
+        scopes: None,
+        api_key_id: None,
+    }
+}
+
+/// Counts the statements sqlx runs on the current thread while `count` is
+/// running, from the events sqlx logs for each of them
+pub struct QueryCounter(Arc<AtomicUsize>);
+
+impl Subscriber for QueryCounter {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "sqlx::query"
+    }
+
+    fn new_span(&self, _: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        if event.metadata().target() == "sqlx::query" {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+impl QueryCounter {
+    /// Run `future` to completion and return its output with the number of
+    /// statements it ran
+    pub async fn count<F: std::future::Future>(future: F) -> (F::Output, usize) {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let _guard = tracing::subscriber::set_default(QueryCounter(counter.clone()));
+        let output = future.await;
+        (output, counter.load(Ordering::SeqCst))
+    }
+}
@@ -0,0 +1,89 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::utils::errors::ServiceError;
+
+// Invisible formatting characters that only confuse search and exports:
+// soft hyphen, zero-width space, directional marks and overrides, word
+// joiners and the byte order mark. Zero-width (non-)joiners are kept since
+// emoji sequences and some scripts depend on them.
+fn is_invisible(c: char) -> bool {
+    matches!(c,
+        '\u{00AD}'
+        | '\u{200B}'
+        | '\u{200E}' | '\u{200F}'
+        | '\u{2028}' | '\u{2029}'
+        | '\u{202A}'..='\u{202E}'
+        | '\u{2060}'..='\u{2064}'
+        | '\u{2066}'..='\u{2069}'
+        | '\u{FEFF}'
+    )
+}
+
+// NFC-normalize and drop control and invisible characters. Tabs become
+// spaces; line breaks are kept as `\n` only when `multiline` is set.
+fn clean(value: &str, multiline: bool) -> String {
+    value.replace("\r\n", "\n")
+        .nfc()
+        .filter_map(|c| match c {
+            '\n' if multiline => Some('\n'),
+            '\t' | '\n' | '\r' => Some(' '),
+            c if c.is_control() || is_invisible(c) => None,
+            c => Some(c),
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn check_length(field: &str, value: &str, max_graphemes: usize, max_chars: usize) -> Result<(), ServiceError> {
+    // Graphemes are what the user sees as characters; the char limit is what
+    // the database column holds
+    if value.graphemes(true).count() > max_graphemes || value.chars().count() > max_chars {
+        return Err(ServiceError::ValidationError(format!("{} must be at most {} characters long", field, max_graphemes))
+            .with_code("TEXT_TOO_LONG"));
+    }
+    Ok(())
+}
+
+/// Normalize a single-line value such as a name and check its length
+pub fn single_line(field: &str, value: &str, max_graphemes: usize, max_chars: usize) -> Result<String, ServiceError> {
+    let value = clean(value, false);
+    check_length(field, &value, max_graphemes, max_chars)?;
+    Ok(value)
+}
+
+/// Normalize free text that may span lines and check its length
+pub fn multi_line(field: &str, value: &str, max_graphemes: usize) -> Result<String, ServiceError> {
+    let value = clean(value, true);
+    check_length(field, &value, max_graphemes, usize::MAX)?;
+    Ok(value)
+}
+
+/// Trim an email address and check that it plausibly is one; whether it
+/// receives mail is only known once a link sent there is opened
+pub fn email(value: &str) -> Result<String, ServiceError> {
+    const EMAIL_MAX: usize = 255;
+    let email = value.trim();
+    let plausible = email.len() <= EMAIL_MAX
+        && !email.chars().any(char::is_whitespace)
+        && email.split_once('@').is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+    if !plausible {
+        return Err(ServiceError::ValidationError("A valid email address is required".to_string())
+            .with_code("INVALID_EMAIL"));
+    }
+    Ok(email.to_string())
+}
+
+/// A byte count for messages, e.g. "10MB" or "1500 bytes"
+pub fn byte_size(bytes: usize) -> String {
+    const MB: usize = 1024 * 1024;
+    const KB: usize = 1024;
+    if bytes >= MB && bytes.is_multiple_of(MB) {
+        format!("{}MB", bytes / MB)
+    } else if bytes >= KB && bytes.is_multiple_of(KB) {
+        format!("{}KB", bytes / KB)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}